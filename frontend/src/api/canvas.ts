@@ -1,5 +1,7 @@
 import { invoke } from '@tauri-apps/api/tauri';
-import type { CommandResult, CanvasState } from './types';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import type { CommandResult, CommandError, CanvasEdge, CanvasNode, CanvasState } from './types';
+import { commandError } from './errors';
 
 export async function getCanvasState(
   projectId: number
@@ -8,20 +10,109 @@ export async function getCanvasState(
     projectId,
   });
   if (!result.success) {
-    throw new Error(result.error || 'Failed to get canvas state');
+    throw commandError(result.error, 'Failed to get canvas state');
   }
   return result.data || null;
 }
 
+/** Save the whole canvas; cycles are rejected unless `allowCycles` is set */
 export async function saveCanvasState(
   projectId: number,
-  state: CanvasState
+  state: CanvasState,
+  allowCycles = false
 ): Promise<void> {
   const result = await invoke<CommandResult<void>>('save_canvas_state', {
     projectId,
     state,
+    allowCycles,
   });
   if (!result.success) {
-    throw new Error(result.error || 'Failed to save canvas state');
+    throw commandError(result.error, 'Failed to save canvas state');
   }
 }
+
+/** Add or replace one node, e.g. after a drag; cheaper than saving the whole canvas */
+export async function updateCanvasNode(projectId: number, node: CanvasNode): Promise<void> {
+  const result = await invoke<CommandResult<void>>('update_canvas_node', {
+    projectId,
+    node,
+  });
+  if (!result.success) {
+    throw commandError(result.error, 'Failed to update canvas node');
+  }
+}
+
+/** Add or replace one edge; it must not create a cycle unless `allowCycles` is set */
+export async function updateCanvasEdge(
+  projectId: number,
+  edge: CanvasEdge,
+  allowCycles = false
+): Promise<void> {
+  const result = await invoke<CommandResult<void>>('update_canvas_edge', {
+    projectId,
+    edge,
+    allowCycles,
+  });
+  if (!result.success) {
+    throw commandError(result.error, 'Failed to update canvas edge');
+  }
+}
+
+export interface CanvasVersion {
+  id: number;
+  project_id: number;
+  created_at: string;
+}
+
+/** Saved canvas versions of a project, newest first */
+export async function listCanvasVersions(projectId: number): Promise<CanvasVersion[]> {
+  const result = await invoke<CommandResult<CanvasVersion[]>>('list_canvas_versions', {
+    projectId,
+  });
+  if (!result.success || !result.data) {
+    throw commandError(result.error, 'Failed to list canvas versions');
+  }
+  return result.data;
+}
+
+/** Restore a canvas version; the canvas it replaces is kept as a version */
+export async function restoreCanvasVersion(versionId: number): Promise<CanvasState> {
+  const result = await invoke<CommandResult<CanvasState>>('restore_canvas_version', {
+    versionId,
+  });
+  if (!result.success || !result.data) {
+    throw commandError(result.error, 'Failed to restore canvas version');
+  }
+  return result.data;
+}
+
+export interface CanvasNodeEvent {
+  project_id: number;
+  node_id: string;
+  status: 'running' | 'done' | 'failed';
+  output?: string;
+  error?: CommandError;
+}
+
+export interface CanvasRunResult {
+  /** Output of every node, by node id */
+  outputs: Record<string, string>;
+  /** Ids of the `output` nodes, in execution order */
+  output_node_ids: string[];
+}
+
+/** Run the project's saved canvas as a pipeline of prompt, llm, rag_search and output nodes */
+export async function runCanvas(projectId: number): Promise<CanvasRunResult> {
+  const result = await invoke<CommandResult<CanvasRunResult>>('run_canvas', {
+    projectId,
+  });
+  if (!result.success || !result.data) {
+    throw commandError(result.error, 'Failed to run canvas');
+  }
+  return result.data;
+}
+
+/** Subscribe to per-node progress of `runCanvas` */
+export function onCanvasNode(callback: (event: CanvasNodeEvent) => void): Promise<UnlistenFn> {
+  return listen<CanvasNodeEvent>('canvas-node', (event) => callback(event.payload));
+}
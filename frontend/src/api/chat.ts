@@ -1,29 +1,90 @@
 import { invoke } from '@tauri-apps/api/tauri';
 import { listen } from '@tauri-apps/api/event';
-import type { CommandResult, ChatMessage, ChatResponse } from './types';
+import type { CommandResult, CommandError, ChatMessage, ChatResponse } from './types';
+import { commandError, type CommandFailure } from './errors';
+
+export type ResponseFormat =
+  | { type: 'text' }
+  | { type: 'json_object' }
+  | { type: 'json_schema'; schema: Record<string, unknown> };
 
 export interface SendChatRequest {
   provider_id: string;
-  model: string;
+  /** Empty or omitted uses the provider's default model */
+  model?: string;
   messages: ChatMessage[];
   temperature?: number;
   max_tokens?: number;
   top_p?: number;
+  frequency_penalty?: number;
+  presence_penalty?: number;
+  seed?: number;
+  response_format?: ResponseFormat;
   stream: boolean;
+  /** Chunks buffered between the provider and the UI when streaming (default 100) */
+  stream_capacity?: number;
+  /** Providers tried in order if the first is rate limited or down (non-streaming only) */
+  fallback_provider_ids?: string[];
+  /** Ask for the rest of a reply cut off by max_tokens and join the parts (non-streaming only) */
+  auto_continue?: boolean;
+  /** Most follow-up requests made by auto_continue (1-10, default 3) */
+  max_continuations?: number;
+  /** JSON Schema the reply must match; a bad reply is sent back once to be fixed (non-streaming only) */
+  json_schema?: Record<string, unknown>;
+}
+
+export interface SendChatResponse extends ChatResponse {
+  /** The provider that answered, which differs from `provider_id` after a fallback */
+  provider_id: string;
+  /** Time to the whole reply, including fallbacks and continuations */
+  latency_ms: number;
 }
 
 export interface ChatChunk {
   request_id: string;
   delta: string;
+  /** Reasoning ("thinking") text from reasoner models, separate from the answer */
+  reasoning_delta?: string;
   finish_reason?: string;
 }
 
-export async function sendChatMessage(request: SendChatRequest): Promise<ChatResponse> {
-  const result = await invoke<CommandResult<ChatResponse>>('send_chat_message', {
+/** Payload of 'chat-complete', with the stream's latencies */
+export interface ChatStreamComplete {
+  request_id: string;
+  /** Time to the first chunk; absent if the stream produced none */
+  first_token_ms?: number | null;
+  total_ms: number;
+}
+
+export interface ChatStreamError {
+  request_id: string;
+  error: CommandError;
+}
+
+/** `requestId` lets the request be aborted with `cancelRequest` */
+export async function sendChatMessage(
+  request: SendChatRequest,
+  requestId?: string
+): Promise<SendChatResponse> {
+  const result = await invoke<CommandResult<SendChatResponse>>('send_chat_message', {
     request,
+    requestId,
   });
   if (!result.success || !result.data) {
-    throw new Error(result.error || 'Failed to send chat message');
+    throw commandError(result.error, 'Failed to send chat message');
+  }
+  return result.data;
+}
+
+/**
+ * Abort an in-flight chat request, streaming or not. A cancelled stream completes with
+ * what it received so far; other requests fail with a 'cancelled' error.
+ * Resolves to false if no request with this id is running.
+ */
+export async function cancelRequest(requestId: string): Promise<boolean> {
+  const result = await invoke<CommandResult<boolean>>('cancel_request', { requestId });
+  if (!result.success || result.data === undefined) {
+    throw commandError(result.error, 'Failed to cancel request');
   }
   return result.data;
 }
@@ -32,10 +93,12 @@ export async function sendChatMessageStream(
   request: SendChatRequest,
   requestId: string,
   onChunk: (chunk: ChatChunk) => void,
-  onComplete: () => void
+  onComplete: (complete: ChatStreamComplete) => void,
+  onError?: (error: CommandFailure) => void
 ): Promise<() => void> {
   let unlisten1: (() => void) | null = null;
   let unlisten2: (() => void) | null = null;
+  let unlisten3: (() => void) | null = null;
 
   const cleanup = () => {
     if (unlisten1) {
@@ -46,6 +109,10 @@ export async function sendChatMessageStream(
       unlisten2();
       unlisten2 = null;
     }
+    if (unlisten3) {
+      unlisten3();
+      unlisten3 = null;
+    }
   };
 
   try {
@@ -57,9 +124,17 @@ export async function sendChatMessageStream(
     });
 
     // Listen for completion
-    unlisten2 = await listen<string>('chat-complete', (event) => {
-      if (event.payload === requestId) {
-        onComplete();
+    unlisten2 = await listen<ChatStreamComplete>('chat-complete', (event) => {
+      if (event.payload.request_id === requestId) {
+        onComplete(event.payload);
+        cleanup();
+      }
+    });
+
+    // Listen for failure; a stream ends with either 'chat-complete' or 'chat-error'
+    unlisten3 = await listen<ChatStreamError>('chat-error', (event) => {
+      if (event.payload.request_id === requestId) {
+        onError?.(commandError(event.payload.error, 'Streaming failed'));
         cleanup();
       }
     });
@@ -72,7 +147,7 @@ export async function sendChatMessageStream(
 
     if (!result.success) {
       cleanup();
-      throw new Error(result.error || 'Failed to start streaming');
+      throw commandError(result.error, 'Failed to start streaming');
     }
 
     // Return cleanup function for caller to use if component unmounts
@@ -82,3 +157,26 @@ export async function sendChatMessageStream(
     throw error;
   }
 }
+
+export interface EstimateTokensRequest {
+  provider_id: string;
+  /** Empty or omitted uses the provider's default model */
+  model?: string;
+  messages: ChatMessage[];
+  /** Completion tokens to include in the cost estimate */
+  max_tokens?: number;
+}
+
+export interface TokenEstimate {
+  prompt_tokens: number;
+  /** Absent if the model's price is unknown */
+  estimated_cost_usd?: number;
+}
+
+export async function estimateTokens(request: EstimateTokensRequest): Promise<TokenEstimate> {
+  const result = await invoke<CommandResult<TokenEstimate>>('estimate_tokens', { request });
+  if (!result.success || !result.data) {
+    throw commandError(result.error, 'Failed to estimate tokens');
+  }
+  return result.data;
+}
@@ -1,18 +1,34 @@
 import { invoke } from '@tauri-apps/api/tauri';
-import type { CommandResult, MaskedProviderConfig } from './types';
+import type { AuthScheme, CommandResult, MaskedProviderConfig, ModelInfo } from './types';
+import { commandError } from './errors';
 
 export interface UpdateProviderRequest {
   provider_id: string;
   api_key?: string;
   base_url?: string;
   default_model?: string;
+  embedding_model?: string;
   enabled?: boolean;
+  auth_scheme?: AuthScheme;
+  fallback_provider_ids?: string[];
+  /** 0 removes the limit */
+  requests_per_minute?: number;
+  /** Log request and response bodies at debug level, with the API key redacted */
+  log_requests?: boolean;
 }
 
 export async function getProviders(): Promise<MaskedProviderConfig[]> {
   const result = await invoke<CommandResult<MaskedProviderConfig[]>>('get_providers');
   if (!result.success || !result.data) {
-    throw new Error(result.error || 'Failed to get providers');
+    throw commandError(result.error, 'Failed to get providers');
+  }
+  return result.data;
+}
+
+export async function listEnabledProviders(): Promise<MaskedProviderConfig[]> {
+  const result = await invoke<CommandResult<MaskedProviderConfig[]>>('list_enabled_providers');
+  if (!result.success || !result.data) {
+    throw commandError(result.error, 'Failed to list enabled providers');
   }
   return result.data;
 }
@@ -20,7 +36,7 @@ export async function getProviders(): Promise<MaskedProviderConfig[]> {
 export async function updateProvider(request: UpdateProviderRequest): Promise<void> {
   const result = await invoke<CommandResult<void>>('update_provider', { request });
   if (!result.success) {
-    throw new Error(result.error || 'Failed to update provider');
+    throw commandError(result.error, 'Failed to update provider');
   }
 }
 
@@ -29,16 +45,159 @@ export async function deleteProvider(providerId: string): Promise<void> {
     providerId,
   });
   if (!result.success) {
-    throw new Error(result.error || 'Failed to delete provider');
+    throw commandError(result.error, 'Failed to delete provider');
+  }
+}
+
+export interface ConnectionTestResult {
+  /**
+   * `credentials`: a free key check such as listing models; `completion`: a minimal chat;
+   * `simulated`: nothing was sent because offline mode is on;
+   * `not_applicable`: nothing was sent because the provider takes no API key
+   */
+  method: 'credentials' | 'completion' | 'simulated' | 'not_applicable';
+  message: string;
+}
+
+export async function testProviderConnection(providerId: string): Promise<ConnectionTestResult> {
+  const result = await invoke<CommandResult<ConnectionTestResult>>('test_provider_connection', {
+    providerId,
+  });
+  if (!result.success || !result.data) {
+    throw commandError(result.error, 'Failed to test connection');
   }
+  return result.data;
 }
 
-export async function testProviderConnection(providerId: string): Promise<string> {
-  const result = await invoke<CommandResult<string>>('test_provider_connection', {
+export async function listProviderModels(providerId: string): Promise<ModelInfo[]> {
+  const result = await invoke<CommandResult<ModelInfo[]>>('list_provider_models', {
     providerId,
   });
   if (!result.success || !result.data) {
-    throw new Error(result.error || 'Failed to test connection');
+    throw commandError(result.error, 'Failed to list models');
+  }
+  return result.data;
+}
+
+export interface GeneralConfig {
+  theme: string;
+  default_provider?: string | null;
+  validation_limits: ValidationLimits;
+  offline_mode: boolean;
+}
+
+/** Input size limits; defaults are a 10MB document, 10000-char query, 1MB message, top_k 1000 */
+export interface ValidationLimits {
+  max_document_bytes: number;
+  max_query_chars: number;
+  max_message_bytes: number;
+  max_top_k: number;
+}
+
+export async function getValidationLimits(): Promise<ValidationLimits> {
+  const result = await invoke<CommandResult<ValidationLimits>>('get_validation_limits');
+  if (!result.success || !result.data) {
+    throw commandError(result.error, 'Failed to get validation limits');
+  }
+  return result.data;
+}
+
+/** Replace the limits; they apply immediately and are kept across restarts */
+export async function setValidationLimits(newLimits: ValidationLimits): Promise<ValidationLimits> {
+  const result = await invoke<CommandResult<ValidationLimits>>('set_validation_limits', {
+    newLimits,
+  });
+  if (!result.success || !result.data) {
+    throw commandError(result.error, 'Failed to set validation limits');
+  }
+  return result.data;
+}
+
+export async function getOfflineMode(): Promise<boolean> {
+  const result = await invoke<CommandResult<boolean>>('get_offline_mode');
+  if (!result.success || result.data === undefined) {
+    throw commandError(result.error, 'Failed to get offline mode');
+  }
+  return result.data;
+}
+
+/** While on, providers are never contacted: replies are canned and embeddings hash-based */
+export async function setOfflineMode(enabled: boolean): Promise<void> {
+  const result = await invoke<CommandResult<void>>('set_offline_mode', { enabled });
+  if (!result.success) {
+    throw commandError(result.error, 'Failed to set offline mode');
+  }
+}
+
+export interface ConfigExport {
+  format_version: number;
+  providers: MaskedProviderConfig[];
+  /** API keys by provider id, in plaintext; only in exports that include secrets */
+  api_keys?: Record<string, string[]>;
+  general: GeneralConfig;
+}
+
+export interface ExportConfigResponse {
+  config: ConfigExport;
+  /** Set when the export holds API keys */
+  warning?: string;
+}
+
+export interface ConfigImportSummary {
+  added: string[];
+  updated: string[];
+  keys_imported: string[];
+}
+
+/** Export the provider setup; API keys are only included when `includeSecrets` */
+export async function exportConfig(includeSecrets = false): Promise<ExportConfigResponse> {
+  const result = await invoke<CommandResult<ExportConfigResponse>>('export_config', {
+    includeSecrets,
+  });
+  if (!result.success || !result.data) {
+    throw commandError(result.error, 'Failed to export config');
+  }
+  return result.data;
+}
+
+/**
+ * Merge an exported config; existing API keys are kept unless `overwriteKeys`, and the
+ * local theme and default provider unless `importGeneral`
+ */
+export async function importConfig(
+  config: ConfigExport,
+  overwriteKeys = false,
+  importGeneral = false
+): Promise<ConfigImportSummary> {
+  const result = await invoke<CommandResult<ConfigImportSummary>>('import_config', {
+    config,
+    overwriteKeys,
+    importGeneral,
+  });
+  if (!result.success || !result.data) {
+    throw commandError(result.error, 'Failed to import config');
+  }
+  return result.data;
+}
+
+/** Text `factoryReset` must be given to confirm */
+export const FACTORY_RESET_CONFIRMATION = 'DELETE ALL DATA';
+
+export interface FactoryResetResult {
+  removed_paths: string[];
+  master_key_deleted: boolean;
+}
+
+/**
+ * Delete the RAG database, the encrypted config and the keychain master key.
+ * The app must be restarted afterwards.
+ */
+export async function factoryReset(confirmation: string): Promise<FactoryResetResult> {
+  const result = await invoke<CommandResult<FactoryResetResult>>('factory_reset', {
+    confirmation,
+  });
+  if (!result.success || !result.data) {
+    throw commandError(result.error, 'Failed to reset');
   }
   return result.data;
 }
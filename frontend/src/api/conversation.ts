@@ -2,14 +2,31 @@ import { invoke } from '@tauri-apps/api/tauri';
 import type {
   CommandResult,
   Conversation,
+  ConversationSettings,
   Message,
   ConversationWithMessages,
+  Page,
 } from './types';
+import { commandError } from './errors';
 
 export interface CreateConversationRequest {
   title: string;
   provider_id: string;
   model: string;
+  /** Default generation parameters and system prompt */
+  settings?: ConversationSettings;
+}
+
+export interface StartConversationRequest {
+  /** Empty or omitted uses the start of `first_message` */
+  title?: string;
+  provider_id: string;
+  model: string;
+  project_id?: number;
+  /** Default generation parameters and system prompt */
+  settings?: ConversationSettings;
+  /** The user's opening message */
+  first_message: string;
 }
 
 export interface AddMessageRequest {
@@ -18,6 +35,15 @@ export interface AddMessageRequest {
   content: string;
 }
 
+export interface ChatInConversationRequest {
+  conversation_id: number;
+  content: string;
+  temperature?: number;
+  max_tokens?: number;
+  /** Token budget for history; older turns beyond it are summarized */
+  max_history_tokens?: number;
+}
+
 export async function createConversation(
   request: CreateConversationRequest
 ): Promise<Conversation> {
@@ -25,15 +51,34 @@ export async function createConversation(
     request,
   });
   if (!result.success || !result.data) {
-    throw new Error(result.error || 'Failed to create conversation');
+    throw commandError(result.error, 'Failed to create conversation');
   }
   return result.data;
 }
 
-export async function listConversations(): Promise<Conversation[]> {
-  const result = await invoke<CommandResult<Conversation[]>>('list_conversations');
+/** Create a conversation and its first user message in one step */
+export async function startConversation(
+  request: StartConversationRequest
+): Promise<ConversationWithMessages> {
+  const result = await invoke<CommandResult<ConversationWithMessages>>('start_conversation', {
+    request,
+  });
   if (!result.success || !result.data) {
-    throw new Error(result.error || 'Failed to list conversations');
+    throw commandError(result.error, 'Failed to start conversation');
+  }
+  return result.data;
+}
+
+export async function listConversations(
+  limit?: number,
+  offset?: number
+): Promise<Page<Conversation>> {
+  const result = await invoke<CommandResult<Page<Conversation>>>('list_conversations', {
+    limit,
+    offset,
+  });
+  if (!result.success || !result.data) {
+    throw commandError(result.error, 'Failed to list conversations');
   }
   return result.data;
 }
@@ -48,7 +93,7 @@ export async function getConversationWithMessages(
     }
   );
   if (!result.success || !result.data) {
-    throw new Error(result.error || 'Failed to get conversation');
+    throw commandError(result.error, 'Failed to get conversation');
   }
   return result.data;
 }
@@ -62,16 +107,79 @@ export async function updateConversationTitle(
     title,
   });
   if (!result.success) {
-    throw new Error(result.error || 'Failed to update conversation title');
+    throw commandError(result.error, 'Failed to update conversation title');
+  }
+}
+
+export async function updateConversationSettings(
+  conversationId: number,
+  settings: ConversationSettings
+): Promise<Conversation> {
+  const result = await invoke<CommandResult<Conversation>>('update_conversation_settings', {
+    conversationId,
+    settings,
+  });
+  if (!result.success || !result.data) {
+    throw commandError(result.error, 'Failed to update conversation settings');
   }
+  return result.data;
 }
 
+/** Copy a conversation up to and including `upToMessageId` into a new conversation */
+export async function forkConversation(
+  conversationId: number,
+  upToMessageId: number
+): Promise<Conversation> {
+  const result = await invoke<CommandResult<Conversation>>('fork_conversation', {
+    conversationId,
+    upToMessageId,
+  });
+  if (!result.success || !result.data) {
+    throw commandError(result.error, 'Failed to fork conversation');
+  }
+  return result.data;
+}
+
+/** Move a conversation to the trash; see `purgeConversation` for permanent deletion */
 export async function deleteConversation(conversationId: number): Promise<void> {
   const result = await invoke<CommandResult<void>>('delete_conversation', {
     conversationId,
   });
   if (!result.success) {
-    throw new Error(result.error || 'Failed to delete conversation');
+    throw commandError(result.error, 'Failed to delete conversation');
+  }
+}
+
+export async function listTrashedConversations(
+  limit?: number,
+  offset?: number
+): Promise<Page<Conversation>> {
+  const result = await invoke<CommandResult<Page<Conversation>>>('list_trashed_conversations', {
+    limit,
+    offset,
+  });
+  if (!result.success || !result.data) {
+    throw commandError(result.error, 'Failed to list trashed conversations');
+  }
+  return result.data;
+}
+
+export async function restoreConversation(conversationId: number): Promise<Conversation> {
+  const result = await invoke<CommandResult<Conversation>>('restore_conversation', {
+    conversationId,
+  });
+  if (!result.success || !result.data) {
+    throw commandError(result.error, 'Failed to restore conversation');
+  }
+  return result.data;
+}
+
+export async function purgeConversation(conversationId: number): Promise<void> {
+  const result = await invoke<CommandResult<void>>('purge_conversation', {
+    conversationId,
+  });
+  if (!result.success) {
+    throw commandError(result.error, 'Failed to permanently delete conversation');
   }
 }
 
@@ -80,7 +188,36 @@ export async function addMessage(request: AddMessageRequest): Promise<Message> {
     request,
   });
   if (!result.success || !result.data) {
-    throw new Error(result.error || 'Failed to add message');
+    throw commandError(result.error, 'Failed to add message');
+  }
+  return result.data;
+}
+
+/**
+ * Send a message in a conversation using its stored history, provider and model.
+ * Both the message and the reply are saved; returns the assistant's message.
+ */
+export async function chatInConversation(request: ChatInConversationRequest): Promise<Message> {
+  const result = await invoke<CommandResult<Message>>('chat_in_conversation', {
+    request,
+  });
+  if (!result.success || !result.data) {
+    throw commandError(result.error, 'Failed to send message');
+  }
+  return result.data;
+}
+
+/** Answer the last user message again, replacing the last assistant reply if there is one */
+export async function regenerateLastResponse(
+  conversationId: number,
+  temperature?: number
+): Promise<Message> {
+  const result = await invoke<CommandResult<Message>>('regenerate_last_response', {
+    conversationId,
+    temperature,
+  });
+  if (!result.success || !result.data) {
+    throw commandError(result.error, 'Failed to regenerate response');
   }
   return result.data;
 }
@@ -95,7 +232,19 @@ export async function getConversationMessages(
     }
   );
   if (!result.success || !result.data) {
-    throw new Error(result.error || 'Failed to get messages');
+    throw commandError(result.error, 'Failed to get messages');
+  }
+  return result.data;
+}
+
+/** Edit a message; every later message in its conversation is deleted */
+export async function editMessage(messageId: number, newContent: string): Promise<Message> {
+  const result = await invoke<CommandResult<Message>>('edit_message', {
+    messageId,
+    newContent,
+  });
+  if (!result.success || !result.data) {
+    throw commandError(result.error, 'Failed to edit message');
   }
   return result.data;
 }
@@ -105,6 +254,6 @@ export async function deleteMessage(messageId: number): Promise<void> {
     messageId,
   });
   if (!result.success) {
-    throw new Error(result.error || 'Failed to delete message');
+    throw commandError(result.error, 'Failed to delete message');
   }
 }
@@ -0,0 +1,23 @@
+import type { CommandError, CommandErrorCode } from './types';
+
+/** Error thrown by the API wrappers when a command fails */
+export class CommandFailure extends Error {
+  readonly code: CommandErrorCode;
+  readonly retryAfterSecs?: number;
+
+  constructor(code: CommandErrorCode, message: string, retryAfterSecs?: number) {
+    super(message);
+    this.name = 'CommandFailure';
+    this.code = code;
+    this.retryAfterSecs = retryAfterSecs;
+  }
+}
+
+/** Build the error for a failed command, using `fallback` when the backend sent no details */
+export function commandError(error: CommandError | undefined, fallback: string): CommandFailure {
+  return new CommandFailure(
+    error?.code ?? 'internal',
+    error?.message || fallback,
+    error?.retry_after_secs
+  );
+}
@@ -0,0 +1,35 @@
+import { invoke } from '@tauri-apps/api/tauri';
+import type { CommandError, CommandResult } from './types';
+import { commandError } from './errors';
+
+export interface SubsystemHealth {
+  ok: boolean;
+  error?: CommandError;
+}
+
+export interface ProviderHealth {
+  id: string;
+  ok: boolean;
+  latency_ms: number;
+  /** False if the provider has no cheap credential check, so it wasn't contacted */
+  checked: boolean;
+  error?: CommandError;
+}
+
+export interface HealthReport {
+  db: SubsystemHealth;
+  /** Always ok with a passphrase-derived key */
+  keychain: SubsystemHealth;
+  config: SubsystemHealth;
+  /** Enabled providers; empty if the config couldn't be loaded */
+  providers: ProviderHealth[];
+}
+
+/** Check the database, master key, config and enabled providers in one call */
+export async function healthCheck(): Promise<HealthReport> {
+  const result = await invoke<CommandResult<HealthReport>>('health_check');
+  if (!result.success || !result.data) {
+    throw commandError(result.error, 'Failed to run health check');
+  }
+  return result.data;
+}
@@ -0,0 +1,24 @@
+import { invoke } from '@tauri-apps/api/tauri';
+import type { CommandResult } from './types';
+import { commandError } from './errors';
+
+export interface McpServerInfo {
+  /** Endpoint MCP clients POST JSON-RPC messages to */
+  url: string;
+}
+
+/** Serve RAG search and chat to MCP clients on 127.0.0.1 (requires a build with the "mcp" feature) */
+export async function startMcpServer(port: number): Promise<McpServerInfo> {
+  const result = await invoke<CommandResult<McpServerInfo>>('start_mcp_server', { port });
+  if (!result.success || !result.data) {
+    throw commandError(result.error, 'Failed to start MCP server');
+  }
+  return result.data;
+}
+
+export async function stopMcpServer(): Promise<void> {
+  const result = await invoke<CommandResult<void>>('stop_mcp_server');
+  if (!result.success) {
+    throw commandError(result.error, 'Failed to stop MCP server');
+  }
+}
@@ -1,45 +1,166 @@
 import { invoke } from '@tauri-apps/api/tauri';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
 import type {
   CommandResult,
+  Page,
   Project,
   Document,
   ChunkMatch,
   RagChatResponse,
 } from './types';
+import { commandError } from './errors';
 
 export interface AddDocumentRequest {
   project_id: number;
   name: string;
   content: string;
   provider_id: string;
+  /** Strip control characters other than tab/newline (default); false rejects such content */
+  strip_control_chars?: boolean;
+  /** Add the document even if the project already has one with the same content */
+  force?: boolean;
 }
 
 export interface AddDocumentResponse {
   document_id: number;
   chunks_created: number;
+  /** The content was already in the project; `document_id` is the existing document */
+  was_duplicate: boolean;
 }
 
+export interface AddDocumentFromFileRequest {
+  project_id: number;
+  /** PDFs have their text extracted; other files are read as UTF-8 text */
+  path: string;
+  provider_id: string;
+  /** Defaults to the file name */
+  name?: string;
+  /** Same as AddDocumentRequest.force */
+  force?: boolean;
+}
+
+export interface AddDocumentsRequest {
+  project_id: number;
+  provider_id: string;
+  documents: { name: string; content: string }[];
+  /** Same as AddDocumentRequest.strip_control_chars, for every document */
+  strip_control_chars?: boolean;
+}
+
+/** Outcome of one document of a batch; error is set if it wasn't added */
+export interface AddDocumentResult {
+  name: string;
+  document_id: number | null;
+  chunks_created: number;
+  /** The content was already in the project; `document_id` is the existing document */
+  was_duplicate: boolean;
+  error: string | null;
+}
+
+export interface BatchEmbeddingProgress {
+  /** Chunks embedded so far, across all documents of the batch */
+  done: number;
+  total: number;
+}
+
+export interface DocumentIngested {
+  /** Documents handled so far, this one included */
+  done: number;
+  total: number;
+  result: AddDocumentResult;
+}
+
+export interface EmbeddingProgress {
+  document_id: number;
+  done: number;
+  total: number;
+}
+
+export interface EmbeddingComplete {
+  document_id: number;
+  chunks_created: number;
+}
+
+export type DistanceMetric = 'cosine' | 'dot_product' | 'euclidean';
+
 export interface RagSearchRequest {
   project_id: number;
   query: string;
   provider_id: string;
+  /** Results to return (1 to `max_top_k` of the validation limits, 1000 by default) */
   top_k: number;
+  metric?: DistanceMetric;
+  min_similarity?: number;
+  context_window?: number;
+  /** Candidate list size for large, indexed projects (10-4096, default 64); higher trades speed for recall */
+  ef_search?: number;
+  /** Attach ranking diagnostics (`explanation`) to each result */
+  explain?: boolean;
+  /** Only search chunks of these documents */
+  document_ids?: number[];
+  /** Only search documents whose name matches this SQL LIKE pattern, e.g. '%v1%' */
+  name_like?: string;
+}
+
+export interface RagSearchRerankRequest {
+  project_id: number;
+  query: string;
+  provider_id: string;
+  top_k: number;
+  /** Candidates per result before re-ranking (2-10); `top_k * candidate_multiplier` may be at most 2000 */
+  candidate_multiplier: number;
+  metric?: DistanceMetric;
+  /** Provider whose rerank model orders the candidates (e.g. 'cohere'); omitted uses diversity re-ranking */
+  rerank_provider_id?: string;
+}
+
+export interface RagSearchExpandedRequest {
+  project_id: number;
+  query: string;
+  /** Provider that embeds the query and its paraphrases */
+  provider_id: string;
+  top_k: number;
+  metric?: DistanceMetric;
+  /** Provider that writes paraphrases of the query; omitted searches with the query alone */
+  expansion_provider_id?: string;
+  /** Empty or omitted uses the expansion provider's default model */
+  expansion_model?: string;
+  /** Paraphrases to ask for (1-10, default 3) */
+  expansions?: number;
+}
+
+export interface RagSearchExpandedResponse {
+  /** The queries searched: the original one, then the paraphrases */
+  queries: string[];
+  results: ChunkMatch[];
 }
 
 export interface RagChatRequest {
   project_id: number;
   query: string;
   provider_id: string;
-  model: string;
+  /** Empty or omitted uses the provider's default model */
+  model?: string;
   top_k: number;
   temperature?: number;
   max_tokens?: number;
+  metric?: DistanceMetric;
+  min_similarity?: number;
+  context_window?: number;
+  /** Chunks buffered between the provider and the UI when streaming (default 100) */
+  stream_capacity?: number;
+  /** JSON Schema the answer must match; a bad answer is sent back once to be fixed (non-streaming only) */
+  json_schema?: Record<string, unknown>;
+  /** Only use chunks of these documents as sources */
+  document_ids?: number[];
+  /** Only use documents whose name matches this SQL LIKE pattern as sources */
+  name_like?: string;
 }
 
 export async function createProject(name: string): Promise<Project> {
   const result = await invoke<CommandResult<Project>>('create_project', { name });
   if (!result.success || !result.data) {
-    throw new Error(result.error || 'Failed to create project');
+    throw commandError(result.error, 'Failed to create project');
   }
   return result.data;
 }
@@ -47,7 +168,7 @@ export async function createProject(name: string): Promise<Project> {
 export async function listProjects(): Promise<Project[]> {
   const result = await invoke<CommandResult<Project[]>>('list_projects');
   if (!result.success || !result.data) {
-    throw new Error(result.error || 'Failed to list projects');
+    throw commandError(result.error, 'Failed to list projects');
   }
   return result.data;
 }
@@ -57,16 +178,66 @@ export async function deleteProject(projectId: number): Promise<void> {
     projectId,
   });
   if (!result.success) {
-    throw new Error(result.error || 'Failed to delete project');
+    throw commandError(result.error, 'Failed to delete project');
+  }
+}
+
+/** Write a project with its documents and embeddings to a JSON file */
+export async function exportProject(projectId: number, path: string): Promise<void> {
+  const result = await invoke<CommandResult<void>>('export_project', { projectId, path });
+  if (!result.success) {
+    throw commandError(result.error, 'Failed to export project');
+  }
+}
+
+/** Recreate a project from a file written by exportProject, without re-embedding */
+export async function importProject(path: string): Promise<Project> {
+  const result = await invoke<CommandResult<Project>>('import_project', { path });
+  if (!result.success || !result.data) {
+    throw commandError(result.error, 'Failed to import project');
+  }
+  return result.data;
+}
+
+export interface ProjectEmbeddingInfo {
+  project_id: number;
+  /** Absent until the project has chunks */
+  dimension?: number;
+  /** "provider/model"; absent if unknown */
+  model?: string;
+}
+
+/** Rebuild a project's approximate search index, returning the number of chunks indexed */
+export async function rebuildIndex(projectId: number): Promise<number> {
+  const result = await invoke<CommandResult<number>>('rebuild_index', { projectId });
+  if (!result.success || result.data === undefined) {
+    throw commandError(result.error, 'Failed to rebuild search index');
   }
+  return result.data;
 }
 
-export async function listDocuments(projectId: number): Promise<Document[]> {
-  const result = await invoke<CommandResult<Document[]>>('list_documents', {
+export async function getProjectEmbeddingInfo(projectId: number): Promise<ProjectEmbeddingInfo> {
+  const result = await invoke<CommandResult<ProjectEmbeddingInfo>>('get_project_embedding_info', {
     projectId,
   });
   if (!result.success || !result.data) {
-    throw new Error(result.error || 'Failed to list documents');
+    throw commandError(result.error, 'Failed to get project embedding info');
+  }
+  return result.data;
+}
+
+export async function listDocuments(
+  projectId: number,
+  limit?: number,
+  offset?: number
+): Promise<Page<Document>> {
+  const result = await invoke<CommandResult<Page<Document>>>('list_documents', {
+    projectId,
+    limit,
+    offset,
+  });
+  if (!result.success || !result.data) {
+    throw commandError(result.error, 'Failed to list documents');
   }
   return result.data;
 }
@@ -76,7 +247,7 @@ export async function deleteDocument(documentId: number): Promise<void> {
     documentId,
   });
   if (!result.success) {
-    throw new Error(result.error || 'Failed to delete document');
+    throw commandError(result.error, 'Failed to delete document');
   }
 }
 
@@ -87,7 +258,39 @@ export async function addDocument(
     request,
   });
   if (!result.success || !result.data) {
-    throw new Error(result.error || 'Failed to add document');
+    throw commandError(result.error, 'Failed to add document');
+  }
+  return result.data;
+}
+
+export async function addDocumentFromFile(
+  request: AddDocumentFromFileRequest
+): Promise<AddDocumentResponse> {
+  const result = await invoke<CommandResult<AddDocumentResponse>>('add_document_from_file', {
+    request,
+  });
+  if (!result.success || !result.data) {
+    throw commandError(result.error, 'Failed to add document from file');
+  }
+  return result.data;
+}
+
+export async function addDocuments(
+  request: AddDocumentsRequest
+): Promise<AddDocumentResult[]> {
+  const result = await invoke<CommandResult<AddDocumentResult[]>>('add_documents', {
+    request,
+  });
+  if (!result.success || !result.data) {
+    throw commandError(result.error, 'Failed to add documents');
+  }
+  return result.data;
+}
+
+export async function clearEmbeddingCache(): Promise<number> {
+  const result = await invoke<CommandResult<number>>('clear_embedding_cache');
+  if (!result.success || result.data === undefined) {
+    throw commandError(result.error, 'Failed to clear embedding cache');
   }
   return result.data;
 }
@@ -97,17 +300,72 @@ export async function ragSearch(request: RagSearchRequest): Promise<ChunkMatch[]
     request,
   });
   if (!result.success || !result.data) {
-    throw new Error(result.error || 'Failed to search');
+    throw commandError(result.error, 'Failed to search');
   }
   return result.data;
 }
 
-export async function ragChat(request: RagChatRequest): Promise<RagChatResponse> {
+export async function ragSearchRerank(request: RagSearchRerankRequest): Promise<ChunkMatch[]> {
+  const result = await invoke<CommandResult<ChunkMatch[]>>('rag_search_rerank', {
+    request,
+  });
+  if (!result.success || !result.data) {
+    throw commandError(result.error, 'Failed to search');
+  }
+  return result.data;
+}
+
+/** Search with the query and model-written paraphrases of it, fusing the rankings */
+export async function ragSearchExpanded(
+  request: RagSearchExpandedRequest
+): Promise<RagSearchExpandedResponse> {
+  const result = await invoke<CommandResult<RagSearchExpandedResponse>>('rag_search_expanded', {
+    request,
+  });
+  if (!result.success || !result.data) {
+    throw commandError(result.error, 'Failed to search');
+  }
+  return result.data;
+}
+
+/** `requestId` lets the request be aborted with `cancelRequest` */
+export async function ragChat(request: RagChatRequest, requestId?: string): Promise<RagChatResponse> {
   const result = await invoke<CommandResult<RagChatResponse>>('rag_chat', {
     request,
+    requestId,
   });
   if (!result.success || !result.data) {
-    throw new Error(result.error || 'Failed to RAG chat');
+    throw commandError(result.error, 'Failed to RAG chat');
   }
   return result.data;
 }
+
+/** Subscribe to per-batch embedding progress of add/update document commands */
+export function onEmbeddingProgress(
+  callback: (progress: EmbeddingProgress) => void
+): Promise<UnlistenFn> {
+  return listen<EmbeddingProgress>('embedding-progress', (event) => callback(event.payload));
+}
+
+/** Subscribe to embedding progress of add_documents */
+export function onBatchEmbeddingProgress(
+  callback: (progress: BatchEmbeddingProgress) => void
+): Promise<UnlistenFn> {
+  return listen<BatchEmbeddingProgress>('batch-embedding-progress', (event) =>
+    callback(event.payload)
+  );
+}
+
+/** Subscribe to each document of add_documents being stored or rejected */
+export function onDocumentIngested(
+  callback: (ingested: DocumentIngested) => void
+): Promise<UnlistenFn> {
+  return listen<DocumentIngested>('document-ingested', (event) => callback(event.payload));
+}
+
+/** Subscribe to completion of document embedding */
+export function onEmbeddingComplete(
+  callback: (complete: EmbeddingComplete) => void
+): Promise<UnlistenFn> {
+  return listen<EmbeddingComplete>('embedding-complete', (event) => callback(event.payload));
+}
@@ -1,9 +1,35 @@
 // Common types used across API calls
 
+export type CommandErrorCode =
+  | 'validation'
+  | 'not_found'
+  | 'rate_limited'
+  | 'unauthorized'
+  | 'timeout'
+  | 'network'
+  | 'unsupported'
+  | 'provider'
+  | 'database'
+  | 'config'
+  | 'cancelled'
+  | 'internal';
+
+export interface CommandError {
+  code: CommandErrorCode;
+  message: string;
+  /** Delay the provider asked for before retrying, when rate limited */
+  retry_after_secs?: number;
+}
+
 export interface CommandResult<T> {
   success: boolean;
   data?: T;
-  error?: string;
+  error?: CommandError;
+}
+
+export interface Page<T> {
+  items: T[];
+  total: number;
 }
 
 export interface ChatMessage {
@@ -20,6 +46,8 @@ export interface ChatResponse {
     completion_tokens: number;
     total_tokens: number;
   };
+  system_fingerprint?: string;
+  reasoning_content?: string;
 }
 
 export interface MaskedProviderConfig {
@@ -27,7 +55,25 @@ export interface MaskedProviderConfig {
   has_api_key: boolean;
   base_url?: string;
   default_model?: string;
+  embedding_model?: string;
   enabled: boolean;
+  /** How the key is sent; only used by the openai-compatible provider */
+  auth_scheme: AuthScheme;
+  fallback_provider_ids: string[];
+  /** Absent means unlimited */
+  requests_per_minute?: number;
+  /** Request and response bodies are logged at debug level (target `provider_requests`) */
+  log_requests: boolean;
+  /** The key comes from `LLMWB_<PROVIDER_ID>_API_KEY` and overrides any stored one */
+  api_key_from_env: boolean;
+}
+
+export type AuthScheme = 'bearer' | 'x_api_key';
+
+export interface ModelInfo {
+  id: string;
+  display_name: string;
+  context_window?: number;
 }
 
 export interface Project {
@@ -35,7 +81,6 @@ export interface Project {
   name: string;
   created_at: string;
   updated_at: string;
-  canvas_state?: string;
 }
 
 export interface Document {
@@ -52,18 +97,38 @@ export interface Chunk {
   project_id: number;
   content: string;
   chunk_index: number;
+  /** Source page (1-based) for chunks of PDFs */
+  page_number?: number | null;
+  /** Character range of the chunk in the document text, to highlight it in the source */
+  start_offset?: number | null;
+  end_offset?: number | null;
 }
 
 export interface ChunkMatch {
   chunk: Chunk;
   similarity: number;
   document_name: string;
+  expanded_content?: string;
+  /** Present when the search was asked to explain its results */
+  explanation?: MatchExplanation;
+  /** About 200 chars of the chunk around the query's words, marked as `**word**` */
+  snippet?: string;
+}
+
+export interface MatchExplanation {
+  /** Position in the results (1-based) */
+  rank: number;
+  /** Score under `metric`; higher is better (Euclidean distance is negated) */
+  similarity: number;
+  metric: 'cosine' | 'dot_product' | 'euclidean';
+  chunk_chars: number;
 }
 
 export interface RagChatResponse {
   response: string;
   sources: ChunkMatch[];
   model: string;
+  no_relevant_sources: boolean;
 }
 
 export interface CanvasState {
@@ -99,6 +164,24 @@ export interface Conversation {
   model: string;
   created_at: string;
   updated_at: string;
+  /** Conversation this one was forked from */
+  parent_conversation_id?: number | null;
+  /** When the conversation was moved to the trash */
+  deleted_at?: string | null;
+  /** Summary sent in place of older messages */
+  history_summary?: string | null;
+  /** Last message covered by history_summary */
+  summary_through_message_id?: number | null;
+  /** Default generation parameters and system prompt */
+  settings: ConversationSettings;
+}
+
+export interface ConversationSettings {
+  temperature?: number | null;
+  max_tokens?: number | null;
+  top_p?: number | null;
+  /** Sent as the first message of every request */
+  system_prompt?: string | null;
 }
 
 export interface Message {
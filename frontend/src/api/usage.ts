@@ -0,0 +1,34 @@
+import { invoke } from '@tauri-apps/api/tauri';
+import type { CommandResult } from './types';
+import { commandError } from './errors';
+
+export type UsageGroupBy = 'provider' | 'model' | 'conversation' | 'day';
+
+export interface UsageSummaryEntry {
+  /** Provider id, model, conversation id or day (YYYY-MM-DD); null for usage outside a conversation */
+  group_key: string | null;
+  requests: number;
+  prompt_tokens: number;
+  completion_tokens: number;
+  /** Cost at list prices of the models with a known price */
+  estimated_cost_usd: number;
+  /** Requests to models with no known price, which the cost leaves out */
+  unpriced_requests: number;
+}
+
+/** Usage between `from` (inclusive) and `to` (exclusive), as ISO-8601 timestamps */
+export async function getUsageSummary(
+  from?: string,
+  to?: string,
+  groupBy?: UsageGroupBy
+): Promise<UsageSummaryEntry[]> {
+  const result = await invoke<CommandResult<UsageSummaryEntry[]>>('get_usage_summary', {
+    from,
+    to,
+    groupBy,
+  });
+  if (!result.success || !result.data) {
+    throw commandError(result.error, 'Failed to get usage summary');
+  }
+  return result.data;
+}
@@ -116,6 +116,18 @@ const Chat: React.FC = () => {
           },
           () => {
             setIsStreaming(false);
+          },
+          (error) => {
+            logError('Streaming error:', error);
+            setMessages((prev) => {
+              const newMessages = [...prev];
+              const lastMessage = newMessages[newMessages.length - 1];
+              if (lastMessage && lastMessage.role === 'assistant') {
+                lastMessage.content += `\n\nError: ${error.message}`;
+              }
+              return newMessages;
+            });
+            setIsStreaming(false);
           }
         );
       }
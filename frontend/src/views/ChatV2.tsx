@@ -88,7 +88,7 @@ const ChatV2: React.FC = () => {
 
   const loadConversations = async () => {
     try {
-      const convos = await listConversations();
+      const { items: convos } = await listConversations();
       setConversations(convos);
 
       // Select first conversation if none selected
@@ -302,6 +302,11 @@ const ChatV2: React.FC = () => {
               logError('Failed to save assistant message:', error);
             }
           }
+        },
+        (error) => {
+          logError('Streaming error:', error);
+          showError(`Chat error: ${error.message}`);
+          setIsStreaming(false);
         }
       );
 
@@ -570,7 +575,7 @@ const ChatV2: React.FC = () => {
           onClose={() => setDeleteModal(null)}
           onConfirm={confirmDeleteConversation}
           title="Delete Conversation"
-          message={`Are you sure you want to delete "${deleteModal.title}"? This action cannot be undone.`}
+          message={`Are you sure you want to delete "${deleteModal.title}"? It will be moved to the trash.`}
           confirmText="Delete"
           cancelText="Cancel"
         />
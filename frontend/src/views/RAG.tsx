@@ -51,7 +51,7 @@ const RAG: React.FC = () => {
     if (!selectedProject) return;
     setIsLoadingDocuments(true);
     try {
-      const docs = await listDocuments(selectedProject.id);
+      const { items: docs } = await listDocuments(selectedProject.id);
       setDocuments(docs);
     } catch (error) {
       logError('Failed to load documents:', error);
@@ -39,6 +39,31 @@ const AVAILABLE_PROVIDERS = [
       'embedding-001',
     ],
   },
+  {
+    id: 'voyage',
+    name: 'Voyage AI (embeddings only)',
+    defaultBaseUrl: 'https://api.voyageai.com/v1',
+    defaultModel: 'voyage-3',
+    models: [
+      'voyage-3',
+      'voyage-3-large',
+      'voyage-3-lite',
+      'voyage-code-3',
+    ],
+  },
+  // Search queries are embedded like documents (input_type "search_document"), since
+  // embedding requests don't say whether they carry a query or chunks; recall can be
+  // somewhat lower than with Cohere's "search_query" input type
+  {
+    id: 'cohere',
+    name: 'Cohere (embeddings and reranking)',
+    defaultBaseUrl: 'https://api.cohere.com',
+    defaultModel: 'rerank-english-v3.0',
+    models: [
+      'rerank-english-v3.0',
+      'rerank-multilingual-v3.0',
+    ],
+  },
 ];
 
 const Settings: React.FC = () => {
@@ -116,7 +141,7 @@ const Settings: React.FC = () => {
 
     try {
       const result = await testProviderConnection(activeProvider);
-      setTestResult({ success: true, message: result });
+      setTestResult({ success: true, message: result.message });
     } catch (error) {
       setTestResult({
         success: false,
@@ -1,35 +1,122 @@
-use crate::rag::RagDatabase;
+use crate::config::ConfigStore;
+use crate::llm_providers::{create_provider, ChatMessage, ChatRequest, ChatRole};
+use crate::rag::context::build_context;
+use crate::rag::{CanvasEdge, CanvasNode, CanvasState, CanvasVersion, RagDatabase};
+use crate::validation::{self, LimitsState, ValidationError, ValidationLimits};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::Arc;
+use tauri::{AppHandle, Manager};
 use tokio::sync::Mutex;
 
 use super::config_commands::CommandResult;
+use super::rag_commands::{search_chunks, RagSearchRequest};
+use super::usage_commands::record_usage;
+use super::CommandError;
 
-#[derive(Debug, Deserialize, Serialize)]
-pub struct CanvasState {
-    pub nodes: Vec<CanvasNode>,
-    pub edges: Vec<CanvasEdge>,
-}
+impl CanvasState {
+    /// Check that node and edge ids are unique, every edge connects existing nodes,
+    /// and, unless `allow_cycles` is set, that the graph has no cycles
+    /// A canvas with cycles can be saved as a draft, but not run.
+    pub fn validate(&self, allow_cycles: bool) -> Result<(), ValidationError> {
+        if allow_cycles {
+            self.nodes_by_id().map(|_| ())
+        } else {
+            self.topological_order().map(|_| ())
+        }
+    }
 
-#[derive(Debug, Deserialize, Serialize)]
-pub struct CanvasNode {
-    pub id: String,
-    pub node_type: String,
-    pub position: Position,
-    pub data: serde_json::Value,
-}
+    /// Replace the edge with the same id, or add it
+    pub fn upsert_edge(&mut self, edge: CanvasEdge) {
+        match self.edges.iter_mut().find(|e| e.id == edge.id) {
+            Some(existing) => *existing = edge,
+            None => self.edges.push(edge),
+        }
+    }
 
-#[derive(Debug, Deserialize, Serialize)]
-pub struct Position {
-    pub x: f64,
-    pub y: f64,
-}
+    /// Nodes by id, checking that node and edge ids are unique and every edge
+    /// connects existing nodes
+    fn nodes_by_id(&self) -> Result<HashMap<&str, &CanvasNode>, ValidationError> {
+        let mut nodes: HashMap<&str, &CanvasNode> = HashMap::new();
+        for node in &self.nodes {
+            if nodes.insert(node.id.as_str(), node).is_some() {
+                return Err(ValidationError::InvalidCanvas(format!(
+                    "duplicate node id '{}'",
+                    node.id
+                )));
+            }
+        }
+
+        let mut edge_ids = HashSet::new();
+        for edge in &self.edges {
+            if !edge_ids.insert(edge.id.as_str()) {
+                return Err(ValidationError::InvalidCanvas(format!(
+                    "duplicate edge id '{}'",
+                    edge.id
+                )));
+            }
+            for end in [&edge.source, &edge.target] {
+                if !nodes.contains_key(end.as_str()) {
+                    return Err(ValidationError::InvalidCanvas(format!(
+                        "edge '{}' references missing node '{}'",
+                        edge.id, end
+                    )));
+                }
+            }
+        }
+
+        Ok(nodes)
+    }
+
+    /// Nodes ordered so that every edge's source comes before its target
+    /// Fails if the graph is invalid or has a cycle (see `validate`).
+    pub fn topological_order(&self) -> Result<Vec<&CanvasNode>, ValidationError> {
+        let nodes = self.nodes_by_id()?;
+
+        let mut in_degree: HashMap<&str, usize> = nodes.keys().map(|id| (*id, 0)).collect();
+        let mut targets: HashMap<&str, Vec<&str>> = HashMap::new();
+        for edge in &self.edges {
+            *in_degree.entry(edge.target.as_str()).or_default() += 1;
+            targets
+                .entry(edge.source.as_str())
+                .or_default()
+                .push(edge.target.as_str());
+        }
+
+        // Kahn's algorithm, seeded in node order so the result is deterministic
+        let mut ready: VecDeque<&str> = self
+            .nodes
+            .iter()
+            .map(|n| n.id.as_str())
+            .filter(|id| in_degree[id] == 0)
+            .collect();
+        let mut order = Vec::with_capacity(self.nodes.len());
+        while let Some(id) = ready.pop_front() {
+            order.push(nodes[id]);
+            for target in targets.get(id).into_iter().flatten() {
+                let degree = in_degree.get_mut(target).unwrap();
+                *degree -= 1;
+                if *degree == 0 {
+                    ready.push_back(target);
+                }
+            }
+        }
+
+        if order.len() < self.nodes.len() {
+            let in_cycle = self
+                .nodes
+                .iter()
+                .find(|n| in_degree[n.id.as_str()] > 0)
+                .map_or("", |n| n.id.as_str());
+            return Err(ValidationError::InvalidCanvas(format!(
+                "cycle through node '{}'",
+                in_cycle
+            )));
+        }
 
-#[derive(Debug, Deserialize, Serialize)]
-pub struct CanvasEdge {
-    pub id: String,
-    pub source: String,
-    pub target: String,
+        Ok(order)
+    }
 }
 
 /// Get canvas state for a project
@@ -40,40 +127,611 @@ pub async fn get_canvas_state(
 ) -> Result<CommandResult<Option<CanvasState>>, String> {
     let db = rag_db.lock().await;
 
-    match db.get_project(project_id).await {
-        Ok(project) => {
-            if let Some(state_json) = project.canvas_state {
-                match serde_json::from_str::<CanvasState>(&state_json) {
-                    Ok(state) => Ok(CommandResult::ok(Some(state))),
-                    Err(e) => Ok(CommandResult::err(format!(
-                        "Failed to parse canvas state: {}",
-                        e
-                    ))),
-                }
-            } else {
-                Ok(CommandResult::ok(None))
-            }
-        }
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+    match db.get_canvas_state(project_id).await {
+        Ok(state) => Ok(CommandResult::ok(state)),
+        Err(e) => Ok(CommandResult::err(e)),
     }
 }
 
 /// Save canvas state for a project
+/// Only the nodes and edges that changed since the last save are written.
+/// Cycles are rejected unless `allow_cycles` is set.
 #[tauri::command]
 pub async fn save_canvas_state(
     rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
     project_id: i64,
     state: CanvasState,
+    allow_cycles: Option<bool>,
 ) -> Result<CommandResult<()>, String> {
-    let state_json = match serde_json::to_string(&state) {
-        Ok(json) => json,
-        Err(e) => return Ok(CommandResult::err(format!("Serialization error: {}", e))),
-    };
+    if let Err(e) = state.validate(allow_cycles.unwrap_or(false)) {
+        return Ok(CommandResult::err(e));
+    }
 
     let db = rag_db.lock().await;
 
-    match db.update_canvas_state(project_id, state_json).await {
+    match db.save_canvas_state(project_id, &state).await {
         Ok(_) => Ok(CommandResult::ok(())),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// Add or replace one edge of a project's canvas, if the graph stays acyclic
+/// (or `allow_cycles` is set)
+/// Only the edge's row is written, and no version is kept.
+async fn put_canvas_edge(
+    db: &RagDatabase,
+    project_id: i64,
+    edge: CanvasEdge,
+    allow_cycles: bool,
+) -> Result<(), CommandError> {
+    let mut state = db.get_canvas_state(project_id).await?.unwrap_or_default();
+    state.upsert_edge(edge.clone());
+    state.validate(allow_cycles)?;
+
+    db.upsert_canvas_edge(project_id, &edge).await?;
+    Ok(())
+}
+
+/// Add or replace a single node of a project's canvas, e.g. after it was moved or edited
+/// Cheaper than `save_canvas_state` for frequent changes: only the node's row is written,
+/// and no version is kept.
+#[tauri::command]
+pub async fn update_canvas_node(
+    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    project_id: i64,
+    node: CanvasNode,
+) -> Result<CommandResult<()>, String> {
+    let db = rag_db.lock().await;
+
+    match db.upsert_canvas_node(project_id, &node).await {
+        Ok(()) => Ok(CommandResult::ok(())),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// Add or replace a single edge of a project's canvas
+/// The edge must connect existing nodes, and not create a cycle unless `allow_cycles` is set.
+#[tauri::command]
+pub async fn update_canvas_edge(
+    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    project_id: i64,
+    edge: CanvasEdge,
+    allow_cycles: Option<bool>,
+) -> Result<CommandResult<()>, String> {
+    let db = rag_db.lock().await;
+
+    match put_canvas_edge(&db, project_id, edge, allow_cycles.unwrap_or(false)).await {
+        Ok(()) => Ok(CommandResult::ok(())),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// List saved canvas versions of a project, newest first
+#[tauri::command]
+pub async fn list_canvas_versions(
+    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    project_id: i64,
+) -> Result<CommandResult<Vec<CanvasVersion>>, String> {
+    let db = rag_db.lock().await;
+
+    match db.list_canvas_versions(project_id).await {
+        Ok(versions) => Ok(CommandResult::ok(versions)),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// Restore a saved canvas version, returning the restored state
+/// The canvas it replaces is kept as a version, so a restore can be undone.
+#[tauri::command]
+pub async fn restore_canvas_version(
+    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    version_id: i64,
+) -> Result<CommandResult<CanvasState>, String> {
+    let db = rag_db.lock().await;
+
+    match db.restore_canvas_version(version_id).await {
+        Ok((_, state)) => Ok(CommandResult::ok(state)),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// Placeholder in node templates that is replaced by the node's input
+const INPUT_PLACEHOLDER: &str = "{{input}}";
+
+/// Sources retrieved by a `rag_search` node that doesn't set `top_k`
+const DEFAULT_SEARCH_TOP_K: usize = 5;
+
+/// Config of a `prompt` node, which outputs its template with the input filled in
+#[derive(Debug, Deserialize)]
+struct PromptNodeData {
+    template: String,
+}
+
+/// Config of an `llm` node, which sends its input to a provider as the user message
+#[derive(Debug, Deserialize)]
+struct LlmNodeData {
+    provider_id: String,
+    /// Empty uses the provider's default model
+    #[serde(default)]
+    model: String,
+    system_prompt: Option<String>,
+    temperature: Option<f32>,
+    max_tokens: Option<u32>,
+}
+
+/// Config of a `rag_search` node, which outputs the project sources matching a query
+#[derive(Debug, Deserialize)]
+struct RagSearchNodeData {
+    /// Provider used to embed the query
+    provider_id: String,
+    /// Query template; the node's input when unset
+    query: Option<String>,
+    top_k: Option<usize>,
+    min_similarity: Option<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum NodeStatus {
+    Running,
+    Done,
+    Failed,
+}
+
+/// Progress of one node during `run_canvas`, emitted as a 'canvas-node' event
+#[derive(Debug, Clone, Serialize)]
+struct CanvasNodeEvent {
+    project_id: i64,
+    node_id: String,
+    status: NodeStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<CommandError>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CanvasRunResult {
+    /// Output of every node, by node id
+    pub outputs: HashMap<String, String>,
+    /// Ids of the `output` nodes, in execution order
+    pub output_node_ids: Vec<String>,
+}
+
+/// Parse a node's `data` as the config of its type
+fn node_data<T: DeserializeOwned>(node: &CanvasNode) -> Result<T, CommandError> {
+    serde_json::from_value(node.data.clone()).map_err(|e| {
+        CommandError::Validation(format!(
+            "Node '{}' has an invalid {} config: {}",
+            node.id, node.node_type, e
+        ))
+    })
+}
+
+/// Fill a node template with the node's input
+fn render(template: &str, input: &str) -> String {
+    template.replace(INPUT_PLACEHOLDER, input)
+}
+
+/// Run a single node on its input, returning its output
+async fn run_node(
+    rag_db: &Arc<Mutex<RagDatabase>>,
+    config_store: &Arc<Mutex<ConfigStore>>,
+    limits: &ValidationLimits,
+    project_id: i64,
+    node: &CanvasNode,
+    input: String,
+) -> Result<String, CommandError> {
+    match node.node_type.as_str() {
+        "prompt" => {
+            let data: PromptNodeData = node_data(node)?;
+            Ok(render(&data.template, &input))
+        }
+        "llm" => {
+            let data: LlmNodeData = node_data(node)?;
+            if let Some(temp) = data.temperature {
+                validation::validate_temperature(temp)?;
+            }
+            if let Some(max_tokens) = data.max_tokens {
+                validation::validate_max_tokens(max_tokens)?;
+            }
+
+            let store = config_store.lock().await;
+            let provider_config = store.get_enabled_provider(&data.provider_id)?;
+            drop(store);
+
+            let model = provider_config.resolve_model(&data.model)?;
+            let provider = create_provider(&provider_config)?;
+
+            let mut messages = Vec::with_capacity(2);
+            if let Some(system_prompt) = data.system_prompt {
+                messages.push(ChatMessage {
+                    role: ChatRole::System,
+                    content: system_prompt,
+                });
+            }
+            messages.push(ChatMessage {
+                role: ChatRole::User,
+                content: input,
+            });
+
+            let response = provider
+                .chat(ChatRequest {
+                    model,
+                    messages,
+                    temperature: data.temperature,
+                    max_tokens: data.max_tokens,
+                    top_p: None,
+                    frequency_penalty: None,
+                    presence_penalty: None,
+                    seed: None,
+                    response_format: None,
+                    stream: false,
+                })
+                .await?;
+
+            if let Some(usage) = &response.usage {
+                record_usage(
+                    rag_db,
+                    &data.provider_id,
+                    &response.model,
+                    usage.prompt_tokens,
+                    usage.completion_tokens,
+                    None,
+                )
+                .await;
+            }
+
+            Ok(response.content)
+        }
+        "rag_search" => {
+            let data: RagSearchNodeData = node_data(node)?;
+            let query = match &data.query {
+                Some(template) => render(template, &input),
+                None => input,
+            };
+            let top_k = data.top_k.unwrap_or(DEFAULT_SEARCH_TOP_K);
+            validation::validate_query(&query, limits)?;
+            validation::validate_top_k(top_k, limits)?;
+
+            let sources = search_chunks(
+                rag_db,
+                config_store,
+                RagSearchRequest {
+                    project_id,
+                    query,
+                    provider_id: data.provider_id,
+                    top_k,
+                    metric: None,
+                    min_similarity: data.min_similarity,
+                    context_window: None,
+                    ef_search: None,
+                    explain: false,
+                    document_ids: None,
+                    name_like: None,
+                },
+            )
+            .await?;
+
+            Ok(build_context(&sources))
+        }
+        // `output` nodes and untyped (note) nodes pass their input through
+        _ => Ok(input),
+    }
+}
+
+/// Run the canvas graph in topological order
+/// Each node's input is the outputs of the nodes with edges into it, joined by blank
+/// lines in edge order. `on_progress` is called as each node starts, finishes or fails;
+/// the first failure stops the run.
+async fn execute_canvas(
+    rag_db: &Arc<Mutex<RagDatabase>>,
+    config_store: &Arc<Mutex<ConfigStore>>,
+    limits: &ValidationLimits,
+    project_id: i64,
+    state: &CanvasState,
+    on_progress: impl Fn(CanvasNodeEvent),
+) -> Result<CanvasRunResult, CommandError> {
+    let order = state.topological_order()?;
+
+    let mut outputs: HashMap<String, String> = HashMap::new();
+    let mut output_node_ids = Vec::new();
+
+    for node in order {
+        let input = state
+            .edges
+            .iter()
+            .filter(|edge| edge.target == node.id)
+            .filter_map(|edge| outputs.get(&edge.source))
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join("\n\n");
+
+        let event = |status, output, error| CanvasNodeEvent {
+            project_id,
+            node_id: node.id.clone(),
+            status,
+            output,
+            error,
+        };
+
+        on_progress(event(NodeStatus::Running, None, None));
+
+        match run_node(rag_db, config_store, limits, project_id, node, input).await {
+            Ok(output) => {
+                on_progress(event(NodeStatus::Done, Some(output.clone()), None));
+                if node.node_type == "output" {
+                    output_node_ids.push(node.id.clone());
+                }
+                outputs.insert(node.id.clone(), output);
+            }
+            Err(e) => {
+                on_progress(event(NodeStatus::Failed, None, Some(e.clone())));
+                return Err(e);
+            }
+        }
+    }
+
+    Ok(CanvasRunResult {
+        outputs,
+        output_node_ids,
+    })
+}
+
+/// Run a project's saved canvas as a pipeline
+/// Node types: `prompt` (template), `llm` (provider call), `rag_search` (project search)
+/// and `output`; the config of each comes from its `data`, and `{{input}}` in templates is
+/// replaced by the node's input. Progress is emitted per node via 'canvas-node' events.
+#[tauri::command]
+pub async fn run_canvas(
+    app_handle: AppHandle,
+    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    config_store: tauri::State<'_, Arc<Mutex<ConfigStore>>>,
+    limits: tauri::State<'_, Arc<LimitsState>>,
+    project_id: i64,
+) -> Result<CommandResult<CanvasRunResult>, String> {
+    let db = rag_db.lock().await;
+    let state = match db.get_canvas_state(project_id).await {
+        Ok(Some(state)) => state,
+        Ok(None) => {
+            return Ok(CommandResult::err(CommandError::Validation(
+                "Project has no saved canvas".to_string(),
+            )))
+        }
+        Err(e) => return Ok(CommandResult::err(e)),
+    };
+    drop(db);
+
+    let limits = limits.get();
+    match execute_canvas(
+        &rag_db,
+        &config_store,
+        &limits,
+        project_id,
+        &state,
+        |event| {
+            let _ = app_handle.emit_all("canvas-node", event);
+        },
+    )
+    .await
+    {
+        Ok(result) => Ok(CommandResult::ok(result)),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_support;
+    use crate::rag::Position;
+    use tempfile::TempDir;
+
+    fn node(id: &str) -> CanvasNode {
+        CanvasNode {
+            id: id.to_string(),
+            node_type: "default".to_string(),
+            position: Position { x: 0.0, y: 0.0 },
+            data: serde_json::Value::Null,
+        }
+    }
+
+    fn edge(id: &str, source: &str, target: &str) -> CanvasEdge {
+        CanvasEdge {
+            id: id.to_string(),
+            source: source.to_string(),
+            target: target.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_topological_order() {
+        let state = CanvasState {
+            nodes: vec![node("c"), node("a"), node("b")],
+            edges: vec![edge("e1", "a", "b"), edge("e2", "b", "c")],
+        };
+        let order: Vec<&str> = state
+            .topological_order()
+            .unwrap()
+            .iter()
+            .map(|n| n.id.as_str())
+            .collect();
+        assert_eq!(order, ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_validate_rejects_corrupt_graphs() {
+        let duplicate_node = CanvasState {
+            nodes: vec![node("a"), node("a")],
+            edges: vec![],
+        };
+        assert!(duplicate_node.validate(true).is_err());
+
+        let dangling_edge = CanvasState {
+            nodes: vec![node("a")],
+            edges: vec![edge("e1", "a", "missing")],
+        };
+        let err = dangling_edge.validate(true).unwrap_err().to_string();
+        assert!(err.contains("missing"));
+
+        let cycle = CanvasState {
+            nodes: vec![node("a"), node("b")],
+            edges: vec![edge("e1", "a", "b"), edge("e2", "b", "a")],
+        };
+        assert!(cycle
+            .validate(false)
+            .unwrap_err()
+            .to_string()
+            .contains("cycle"));
+        assert!(cycle.validate(true).is_ok());
+        assert!(cycle.topological_order().is_err());
+
+        let self_loop = CanvasState {
+            nodes: vec![node("a")],
+            edges: vec![edge("e1", "a", "a")],
+        };
+        assert!(self_loop.validate(false).is_err());
+    }
+
+    async fn stores(dir: &TempDir) -> (Arc<Mutex<RagDatabase>>, Arc<Mutex<ConfigStore>>, i64) {
+        let (rag_db, config_store) = test_support::stores(dir).await;
+        let project = rag_db
+            .lock()
+            .await
+            .create_project("Project".to_string())
+            .await
+            .unwrap();
+        (rag_db, config_store, project.id)
+    }
+
+    #[tokio::test]
+    async fn test_single_element_updates_without_versions() {
+        let dir = TempDir::new().unwrap();
+        let (rag_db, _, project_id) = stores(&dir).await;
+        let db = rag_db.lock().await;
+
+        db.upsert_canvas_node(project_id, &node("a")).await.unwrap();
+        db.upsert_canvas_node(project_id, &node("b")).await.unwrap();
+        put_canvas_edge(&db, project_id, edge("e1", "a", "b"), false)
+            .await
+            .unwrap();
+        let moved = CanvasNode {
+            position: Position { x: 5.0, y: 7.0 },
+            ..node("a")
+        };
+        db.upsert_canvas_node(project_id, &moved).await.unwrap();
+
+        // An edge creating a cycle is rejected and nothing is saved
+        let cycle = put_canvas_edge(&db, project_id, edge("e2", "b", "a"), false).await;
+        assert!(matches!(cycle, Err(CommandError::Validation(_))));
+
+        let state = db.get_canvas_state(project_id).await.unwrap().unwrap();
+        assert_eq!(state.nodes.len(), 2);
+        assert_eq!(state.nodes[0].position.x, 5.0);
+        assert_eq!(state.edges.len(), 1);
+        assert!(db
+            .list_canvas_versions(project_id)
+            .await
+            .unwrap()
+            .is_empty());
+
+        // Unless cycles are allowed, e.g. for a draft
+        put_canvas_edge(&db, project_id, edge("e2", "b", "a"), true)
+            .await
+            .unwrap();
+        let state = db.get_canvas_state(project_id).await.unwrap().unwrap();
+        assert_eq!(state.edges.len(), 2);
+    }
+
+    fn typed_node(id: &str, node_type: &str, data: serde_json::Value) -> CanvasNode {
+        CanvasNode {
+            node_type: node_type.to_string(),
+            data,
+            ..node(id)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_execute_canvas_pipeline() {
+        let dir = TempDir::new().unwrap();
+        let (rag_db, config_store, project_id) = stores(&dir).await;
+
+        let state = CanvasState {
+            nodes: vec![
+                typed_node("out", "output", serde_json::Value::Null),
+                typed_node(
+                    "llm",
+                    "llm",
+                    serde_json::json!({"provider_id": "mock", "model": "mock-model"}),
+                ),
+                typed_node(
+                    "topic",
+                    "prompt",
+                    serde_json::json!({"template": "borrowing"}),
+                ),
+                typed_node(
+                    "question",
+                    "prompt",
+                    serde_json::json!({"template": "Explain {{input}}"}),
+                ),
+            ],
+            edges: vec![
+                edge("e1", "topic", "question"),
+                edge("e2", "question", "llm"),
+                edge("e3", "llm", "out"),
+            ],
+        };
+
+        let events = std::sync::Mutex::new(Vec::new());
+        let result = execute_canvas(
+            &rag_db,
+            &config_store,
+            &ValidationLimits::default(),
+            project_id,
+            &state,
+            |event| events.lock().unwrap().push((event.node_id, event.status)),
+        )
+        .await
+        .unwrap();
+
+        // The mock provider echoes the user message
+        assert_eq!(result.outputs["out"], "Explain borrowing");
+        assert_eq!(result.output_node_ids, ["out"]);
+
+        let events = events.into_inner().unwrap();
+        assert_eq!(events.len(), 8);
+        assert_eq!(events[0], ("topic".to_string(), NodeStatus::Running));
+        assert_eq!(events[7], ("out".to_string(), NodeStatus::Done));
+    }
+
+    #[tokio::test]
+    async fn test_execute_canvas_stops_at_invalid_node() {
+        let dir = TempDir::new().unwrap();
+        let (rag_db, config_store, project_id) = stores(&dir).await;
+
+        let state = CanvasState {
+            nodes: vec![
+                typed_node("llm", "llm", serde_json::json!({"model": "no-provider"})),
+                typed_node("out", "output", serde_json::Value::Null),
+            ],
+            edges: vec![edge("e1", "llm", "out")],
+        };
+
+        let events = std::sync::Mutex::new(Vec::new());
+        let result = execute_canvas(
+            &rag_db,
+            &config_store,
+            &ValidationLimits::default(),
+            project_id,
+            &state,
+            |event| events.lock().unwrap().push(event.status),
+        )
+        .await;
+
+        assert!(matches!(result, Err(CommandError::Validation(_))));
+        assert_eq!(
+            events.into_inner().unwrap(),
+            [NodeStatus::Running, NodeStatus::Failed]
+        );
     }
 }
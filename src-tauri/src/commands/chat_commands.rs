@@ -1,176 +1,1306 @@
-use crate::config::ConfigStore;
-use crate::llm_providers::{create_provider, ChatChunk, ChatMessage, ChatRequest, ChatResponse};
+use crate::config::{ConfigStore, ProviderConfig};
+use crate::llm_providers::{
+    create_provider, ChatChunk, ChatMessage, ChatRequest, ChatResponse, ChatRole, LlmProvider,
+    ProviderError, ResponseFormat, Usage,
+};
+use crate::pricing::estimate_cost;
+use crate::rag::RagDatabase;
+use crate::tokenizer::{count_prompt_tokens, count_provider_tokens};
 use crate::validation;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tauri::{AppHandle, Manager};
 use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
 
 use super::config_commands::CommandResult;
+use super::rag_commands::RagSourcesEvent;
+use super::request_commands::{register_optional, run_cancellable, RequestGuard, RequestRegistry};
+use super::usage_commands::record_usage;
+use super::CommandError;
 
 #[derive(Debug, Deserialize)]
 pub struct SendChatRequest {
     pub provider_id: String,
+    /// Empty uses the provider's default model
+    #[serde(default)]
     pub model: String,
     pub messages: Vec<ChatMessage>,
     pub temperature: Option<f32>,
     pub max_tokens: Option<u32>,
     pub top_p: Option<f32>,
+    pub frequency_penalty: Option<f32>,
+    pub presence_penalty: Option<f32>,
+    /// Sampling seed for reproducible outputs, where the provider supports it
+    pub seed: Option<u64>,
+    /// Text, any JSON object, or JSON matching a schema
+    pub response_format: Option<ResponseFormat>,
     pub stream: bool,
+    /// When set, the last message, if it is the user's, and the reply are saved to this
+    /// conversation; a request that fails saves no reply
+    pub conversation_id: Option<i64>,
+    /// Chunks buffered between the provider and the UI before the provider is paused
+    pub stream_capacity: Option<usize>,
+    /// Providers to try in order if `provider_id` is rate limited or down (non-streaming only);
+    /// None uses the provider's configured fallbacks. Fallbacks use their default model.
+    pub fallback_provider_ids: Option<Vec<String>>,
+    /// When the reply is cut off by `max_tokens`, ask for the rest and join the parts
+    /// (non-streaming only)
+    #[serde(default)]
+    pub auto_continue: bool,
+    /// Most follow-up requests made by `auto_continue` (1 to 10, default 3)
+    pub max_continuations: Option<u32>,
+    /// JSON Schema the reply must match; a non-matching reply is sent back once to be
+    /// fixed before the request fails (non-streaming only)
+    #[serde(default)]
+    pub json_schema: Option<Value>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SendChatResponse {
+    #[serde(flatten)]
+    pub response: ChatResponse,
+    /// The provider that answered, which differs from the requested one after a fallback
+    pub provider_id: String,
+    /// Time from sending the request to having the whole reply, including fallbacks
+    /// and continuations
+    pub latency_ms: u64,
+}
+
+/// Chunks buffered between a provider stream and the UI when the request doesn't say
+pub(crate) const DEFAULT_STREAM_CAPACITY: usize = 100;
+
+/// Follow-up requests `auto_continue` makes when the request doesn't limit them
+const DEFAULT_MAX_CONTINUATIONS: u32 = 3;
+
+/// Asks the model to correct a reply that doesn't match the requested JSON schema
+const FIX_JSON_PROMPT: &str =
+    "Your reply is not valid JSON matching the required schema. Fix it and reply with only \
+     the corrected JSON, without any other text or code fences.";
+
+/// Longest a received delta waits before being emitted
+const FLUSH_INTERVAL: Duration = Duration::from_millis(16);
+
+/// Buffered bytes of text that are emitted without waiting for the flush interval
+const FLUSH_BYTES: usize = 512;
+
+#[derive(Clone, Serialize)]
+pub(crate) struct ChunkEvent {
+    request_id: String,
+    delta: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reasoning_delta: Option<String>,
+    finish_reason: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+pub(crate) struct StreamCompleteEvent {
+    request_id: String,
+    /// Time from starting the stream to its first chunk; None if nothing arrived
+    first_token_ms: Option<u64>,
+    /// Time from starting the stream to its end
+    total_ms: u64,
+}
+
+/// How long a stream took to start answering and to finish
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct StreamTiming {
+    started: Instant,
+    first_chunk: Option<Duration>,
+    total: Option<Duration>,
+}
+
+impl StreamTiming {
+    /// Start timing a stream that is about to be sent
+    pub(crate) fn start() -> Self {
+        Self {
+            started: Instant::now(),
+            first_chunk: None,
+            total: None,
+        }
+    }
+
+    fn complete_event(&self, request_id: &str) -> StreamCompleteEvent {
+        StreamCompleteEvent {
+            request_id: request_id.to_string(),
+            first_token_ms: self.first_chunk.map(|d| d.as_millis() as u64),
+            total_ms: self
+                .total
+                .unwrap_or_else(|| self.started.elapsed())
+                .as_millis() as u64,
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub(crate) struct StreamErrorEvent {
+    request_id: String,
+    error: CommandError,
+}
+
+/// An event of a streamed reply, sent to the UI under its event name
+#[derive(Clone, Serialize)]
+#[serde(untagged)]
+pub(crate) enum StreamEvent {
+    Sources(RagSourcesEvent),
+    Chunk(ChunkEvent),
+    Complete(StreamCompleteEvent),
+    Error(StreamErrorEvent),
+}
+
+impl StreamEvent {
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            StreamEvent::Sources(_) => "rag-sources",
+            StreamEvent::Chunk(_) => "chat-chunk",
+            StreamEvent::Complete(_) => "chat-complete",
+            StreamEvent::Error(_) => "chat-error",
+        }
+    }
+
+    /// Emit the event to every window
+    pub(crate) fn emit(self, app_handle: &AppHandle) {
+        let _ = app_handle.emit_all(self.name(), self);
+    }
+}
+
+/// Chunks received since the last emit, merged into a single event
+#[derive(Default)]
+struct ChunkBatch {
+    delta: String,
+    reasoning_delta: Option<String>,
+    finish_reason: Option<String>,
+}
+
+impl ChunkBatch {
+    fn push(&mut self, chunk: ChatChunk) {
+        self.delta.push_str(&chunk.delta);
+        if let Some(reasoning) = chunk.reasoning_delta {
+            self.reasoning_delta
+                .get_or_insert_with(String::new)
+                .push_str(&reasoning);
+        }
+        if chunk.finish_reason.is_some() {
+            self.finish_reason = chunk.finish_reason;
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.delta.is_empty() && self.reasoning_delta.is_none() && self.finish_reason.is_none()
+    }
+
+    /// Whether to emit now rather than at the next flush interval
+    fn should_flush(&self) -> bool {
+        let bytes = self.delta.len() + self.reasoning_delta.as_ref().map_or(0, String::len);
+        bytes >= FLUSH_BYTES || self.finish_reason.is_some()
+    }
+
+    fn take(&mut self, request_id: &str) -> ChunkEvent {
+        let batch = std::mem::take(self);
+        ChunkEvent {
+            request_id: request_id.to_string(),
+            delta: batch.delta,
+            reasoning_delta: batch.reasoning_delta,
+            finish_reason: batch.finish_reason,
+        }
+    }
+}
+
+/// Pass received chunks to `on_event` as 'chat-chunk' events until the channel closes
+/// Small deltas are coalesced and emitted every `FLUSH_INTERVAL` (or once `FLUSH_BYTES`
+/// accumulate), so fast providers don't flood the UI with one event per token.
+/// Records when the first chunk arrived and when the stream ended in `timing`.
+/// Returns the assembled response text
+pub(crate) async fn forward_chunks(
+    on_event: &impl Fn(StreamEvent),
+    request_id: &str,
+    mut rx: tokio::sync::mpsc::Receiver<ChatChunk>,
+    timing: &mut StreamTiming,
+) -> String {
+    let mut full_text = String::new();
+    let mut batch = ChunkBatch::default();
+    let mut flush = tokio::time::interval(FLUSH_INTERVAL);
+    flush.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            chunk = rx.recv() => {
+                let Some(chunk) = chunk else { break };
+                if timing.first_chunk.is_none() {
+                    timing.first_chunk = Some(timing.started.elapsed());
+                }
+                full_text.push_str(&chunk.delta);
+                batch.push(chunk);
+
+                if batch.should_flush() {
+                    on_event(StreamEvent::Chunk(batch.take(request_id)));
+                }
+            }
+            _ = flush.tick(), if !batch.is_empty() => {
+                on_event(StreamEvent::Chunk(batch.take(request_id)));
+            }
+        }
+    }
+
+    timing.total = Some(timing.started.elapsed());
+
+    // Final flush of whatever arrived since the last emit
+    if !batch.is_empty() {
+        on_event(StreamEvent::Chunk(batch.take(request_id)));
+    }
+
+    full_text
+}
+
+/// Stream `request` from the provider into `tx` on a new task
+/// Cancelling the request stops the stream without an error, so what was already
+/// received is kept as the reply.
+pub(crate) fn spawn_cancellable_stream(
+    provider: Arc<dyn LlmProvider>,
+    request: ChatRequest,
+    tx: tokio::sync::mpsc::Sender<ChatChunk>,
+    guard: &RequestGuard,
+) -> JoinHandle<Result<(), ProviderError>> {
+    let cancelled = guard.token();
+    tokio::spawn(async move {
+        tokio::select! {
+            result = provider.stream_chat(request, tx) => result,
+            _ = cancelled.cancelled() => Ok(()),
+        }
+    })
+}
+
+/// Wait for the provider's streaming task and return how it ended
+pub(crate) async fn stream_result(
+    stream: JoinHandle<Result<(), ProviderError>>,
+) -> Result<(), CommandError> {
+    match stream.await {
+        Ok(result) => Ok(result?),
+        Err(e) => Err(CommandError::Internal(format!(
+            "Streaming task failed: {}",
+            e
+        ))),
+    }
+}
+
+/// Emit the stream's terminal event: 'chat-complete' with the stream's latencies if it
+/// succeeded, otherwise 'chat-error' with the failure. Exactly one of the two is emitted
+/// per request, so the UI can always leave its loading state.
+pub(crate) fn emit_stream_end(
+    on_event: &impl Fn(StreamEvent),
+    request_id: &str,
+    result: Result<(), CommandError>,
+    timing: StreamTiming,
+) {
+    match result {
+        Ok(()) => on_event(StreamEvent::Complete(timing.complete_event(request_id))),
+        Err(error) => {
+            tracing::error!("Streaming error: {}", error);
+            on_event(StreamEvent::Error(StreamErrorEvent {
+                request_id: request_id.to_string(),
+                error,
+            }));
+        }
+    }
 }
 
 /// Send a chat message (non-streaming)
+/// With a `request_id`, the request can be aborted by `cancel_request`.
 #[tauri::command]
 pub async fn send_chat_message(
     config_store: tauri::State<'_, Arc<Mutex<ConfigStore>>>,
+    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    requests: tauri::State<'_, Arc<RequestRegistry>>,
     request: SendChatRequest,
-) -> Result<CommandResult<ChatResponse>, String> {
+    request_id: Option<String>,
+) -> Result<CommandResult<SendChatResponse>, String> {
     // Validate inputs
     if let Err(e) = validation::validate_not_empty("provider_id", &request.provider_id) {
-        return Ok(CommandResult::err(e.to_string()));
+        return Ok(CommandResult::err(e));
     }
-    if let Err(e) = validation::validate_not_empty("model", &request.model) {
-        return Ok(CommandResult::err(e.to_string()));
+    if let Some(request_id) = &request_id {
+        if let Err(e) = validation::validate_not_empty("request_id", request_id) {
+            return Ok(CommandResult::err(e));
+        }
     }
     if request.messages.is_empty() {
-        return Ok(CommandResult::err("Messages cannot be empty".to_string()));
+        return Ok(CommandResult::err(CommandError::Validation(
+            "Messages cannot be empty".to_string(),
+        )));
     }
     if let Some(temp) = request.temperature {
         if let Err(e) = validation::validate_temperature(temp) {
-            return Ok(CommandResult::err(e.to_string()));
+            return Ok(CommandResult::err(e));
         }
     }
     if let Some(max_tokens) = request.max_tokens {
         if let Err(e) = validation::validate_max_tokens(max_tokens) {
-            return Ok(CommandResult::err(e.to_string()));
+            return Ok(CommandResult::err(e));
+        }
+    }
+    if let Some(penalty) = request.frequency_penalty {
+        if let Err(e) = validation::validate_penalty("frequency_penalty", penalty) {
+            return Ok(CommandResult::err(e));
+        }
+    }
+    if let Some(penalty) = request.presence_penalty {
+        if let Err(e) = validation::validate_penalty("presence_penalty", penalty) {
+            return Ok(CommandResult::err(e));
+        }
+    }
+    if let Some(ResponseFormat::JsonSchema(schema)) = &request.response_format {
+        if let Err(e) = validation::validate_json_schema("response_format", schema) {
+            return Ok(CommandResult::err(e));
+        }
+    }
+    if let Some(max_continuations) = request.max_continuations {
+        if let Err(e) = validation::validate_max_continuations(max_continuations) {
+            return Ok(CommandResult::err(e));
+        }
+    }
+    if let Some(schema) = &request.json_schema {
+        if let Err(e) = validation::validate_json_schema("json_schema", schema) {
+            return Ok(CommandResult::err(e));
         }
     }
 
     let store = config_store.lock().await;
 
     // Get provider config
-    let provider_config = match store.get_provider(&request.provider_id) {
+    let provider_config = match store.get_enabled_provider(&request.provider_id) {
         Ok(config) => config,
-        Err(e) => return Ok(CommandResult::err(e.to_string())),
+        Err(e) => return Ok(CommandResult::err(e)),
     };
 
+    // Fallbacks that can't be used (missing, disabled, no model) are skipped
+    let fallback_ids = request
+        .fallback_provider_ids
+        .clone()
+        .unwrap_or_else(|| provider_config.fallback_provider_ids.clone());
+    let fallbacks: Vec<(ProviderConfig, String)> = fallback_ids
+        .iter()
+        .filter(|id| **id != request.provider_id)
+        .filter_map(|id| {
+            let fallback = store
+                .get_enabled_provider(id)
+                .and_then(|config| config.resolve_model("").map(|model| (config, model)));
+            if let Err(e) = &fallback {
+                tracing::warn!("Skipping fallback provider {}: {}", id, e);
+            }
+            fallback.ok()
+        })
+        .collect();
+
     drop(store);
 
-    // Create provider instance
-    let provider = match create_provider(&provider_config) {
-        Ok(p) => p,
-        Err(e) => return Ok(CommandResult::err(e.to_string())),
+    let model = match provider_config.resolve_model(&request.model) {
+        Ok(model) => model,
+        Err(e) => return Ok(CommandResult::err(e)),
     };
 
     // Send chat request
     let chat_request = ChatRequest {
-        model: request.model,
+        model: model.clone(),
         messages: request.messages,
         temperature: request.temperature,
         max_tokens: request.max_tokens,
         top_p: request.top_p,
+        frequency_penalty: request.frequency_penalty,
+        presence_penalty: request.presence_penalty,
+        seed: request.seed,
+        response_format: request.response_format,
         stream: false,
     };
 
-    match provider.chat(chat_request).await {
+    let mut attempts = vec![(provider_config, model)];
+    attempts.extend(fallbacks);
+
+    let max_continuations = if request.auto_continue {
+        request
+            .max_continuations
+            .unwrap_or(DEFAULT_MAX_CONTINUATIONS)
+    } else {
+        0
+    };
+
+    // Don't pay for a reply that can't be saved
+    if let Some(conversation_id) = request.conversation_id {
+        if let Err(e) = rag_db.lock().await.get_conversation(conversation_id).await {
+            return Ok(CommandResult::err(e));
+        }
+    }
+
+    let guard = match register_optional(&requests, request_id.as_deref()) {
+        Ok(guard) => guard,
+        Err(e) => return Ok(CommandResult::err(e)),
+    };
+    let response = run_cancellable(
+        guard.as_ref(),
+        chat_with_fallback(
+            &rag_db,
+            request.conversation_id,
+            attempts,
+            chat_request,
+            max_continuations,
+            request.json_schema.as_ref(),
+        ),
+    )
+    .await;
+
+    match response {
         Ok(response) => Ok(CommandResult::ok(response)),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// Send a chat request to each (provider, model) in turn until one answers
+/// Only provider outages (rate limits, 5xx, unreachable) move on to the next provider;
+/// any other error, or the last provider's error, is returned.
+/// A truncated answer is continued up to `max_continuations` times by the provider that gave it,
+/// then checked against `json_schema` if one is given. The usage of every request to the
+/// answering provider is recorded, even when the answer then fails the schema.
+/// An accepted answer is saved to the conversation, after the user message it answers.
+async fn chat_with_fallback(
+    rag_db: &Arc<Mutex<RagDatabase>>,
+    conversation_id: Option<i64>,
+    attempts: Vec<(ProviderConfig, String)>,
+    request: ChatRequest,
+    max_continuations: u32,
+    json_schema: Option<&Value>,
+) -> Result<SendChatResponse, CommandError> {
+    let validator = json_schema.map(json_schema_validator).transpose()?;
+    let started = Instant::now();
+    let count = attempts.len();
+
+    for (i, (provider_config, model)) in attempts.into_iter().enumerate() {
+        let provider = create_provider(&provider_config)?;
+        let chat_request = ChatRequest {
+            model,
+            ..request.clone()
+        };
+
+        match provider.chat(chat_request.clone()).await {
+            Ok(response) => {
+                let response = continue_truncated(
+                    provider.as_ref(),
+                    &chat_request,
+                    response,
+                    max_continuations,
+                )
+                .await;
+                let (response, schema_errors) = match (json_schema, &validator) {
+                    (Some(schema), Some(validator)) => {
+                        check_json_schema(
+                            provider.as_ref(),
+                            &chat_request,
+                            response,
+                            schema,
+                            validator,
+                        )
+                        .await
+                    }
+                    _ => (response, Vec::new()),
+                };
+
+                if let Some(usage) = &response.usage {
+                    record_usage(
+                        rag_db,
+                        &provider_config.provider_id,
+                        &response.model,
+                        usage.prompt_tokens,
+                        usage.completion_tokens,
+                        conversation_id,
+                    )
+                    .await;
+                }
+                check_schema_errors(&schema_errors)?;
+
+                if let Some(conversation_id) = conversation_id {
+                    save_exchange(
+                        rag_db,
+                        conversation_id,
+                        &request.messages,
+                        &response.content,
+                    )
+                    .await;
+                }
+
+                return Ok(SendChatResponse {
+                    response,
+                    provider_id: provider_config.provider_id.clone(),
+                    latency_ms: started.elapsed().as_millis() as u64,
+                });
+            }
+            Err(e) if e.is_provider_outage() && i + 1 < count => {
+                tracing::warn!(
+                    "Provider {} failed ({}), falling back",
+                    provider_config.provider_id,
+                    e
+                );
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Err(CommandError::Internal(
+        "No provider to send the chat to".to_string(),
+    ))
+}
+
+/// Save the reply to `messages` in a conversation, together with the last message if it
+/// is the user's. The reply has already been paid for, so a failure is only logged.
+async fn save_exchange(
+    rag_db: &Arc<Mutex<RagDatabase>>,
+    conversation_id: i64,
+    messages: &[ChatMessage],
+    reply: &str,
+) {
+    let db = rag_db.lock().await;
+    let result = match messages.last().filter(|m| matches!(m.role, ChatRole::User)) {
+        Some(message) => db
+            .add_exchange(conversation_id, message.content.clone(), reply.to_string())
+            .await
+            .map(|_| ()),
+        None => db
+            .add_message(
+                conversation_id,
+                ChatRole::Assistant.as_str().to_string(),
+                reply.to_string(),
+            )
+            .await
+            .map(|_| ()),
+    };
+    if let Err(e) = result {
+        tracing::error!("Failed to persist chat response: {}", e);
+    }
+}
+
+/// Ask for the rest of a reply cut off by max_tokens, re-sending the request with the
+/// partial reply appended as an assistant turn and joining the parts, at most
+/// `max_continuations` times. Stops early if a continuation adds nothing or fails,
+/// returning what was generated so far.
+async fn continue_truncated(
+    provider: &dyn LlmProvider,
+    request: &ChatRequest,
+    mut response: ChatResponse,
+    max_continuations: u32,
+) -> ChatResponse {
+    for _ in 0..max_continuations {
+        if !response.is_truncated() {
+            break;
+        }
+
+        let mut messages = request.messages.clone();
+        messages.push(ChatMessage {
+            role: ChatRole::Assistant,
+            content: response.content.clone(),
+        });
+        let continuation = match provider
+            .chat(ChatRequest {
+                messages,
+                ..request.clone()
+            })
+            .await
+        {
+            Ok(continuation) => continuation,
+            Err(e) => {
+                tracing::warn!("Failed to continue truncated response: {}", e);
+                break;
+            }
+        };
+        if continuation.content.is_empty() {
+            break;
+        }
+
+        response.content.push_str(&continuation.content);
+        response.finish_reason = continuation.finish_reason;
+        response.usage = add_usage(response.usage, continuation.usage);
+    }
+
+    response
+}
+
+/// Combined usage of two requests; None only if neither reported any
+fn add_usage(total: Option<Usage>, more: Option<Usage>) -> Option<Usage> {
+    match (total, more) {
+        (Some(total), Some(more)) => Some(Usage {
+            prompt_tokens: total.prompt_tokens + more.prompt_tokens,
+            completion_tokens: total.completion_tokens + more.completion_tokens,
+            total_tokens: total.total_tokens + more.total_tokens,
+        }),
+        (total, more) => total.or(more),
+    }
+}
+
+/// Why `content` doesn't match the schema; empty if it is JSON that matches
+fn json_schema_errors(validator: &jsonschema::Validator, content: &str) -> Vec<String> {
+    match serde_json::from_str::<Value>(content.trim()) {
+        Ok(value) => validator
+            .iter_errors(&value)
+            .map(|e| e.to_string())
+            .collect(),
+        Err(e) => vec![format!("not valid JSON: {}", e)],
+    }
+}
+
+/// Compile a request's `json_schema`, before anything is sent to the provider
+pub(crate) fn json_schema_validator(schema: &Value) -> Result<jsonschema::Validator, CommandError> {
+    jsonschema::validator_for(schema)
+        .map_err(|e| CommandError::Validation(format!("Invalid json_schema: {}", e)))
+}
+
+/// Fail with what is wrong with a reply that still doesn't match the `json_schema`
+pub(crate) fn check_schema_errors(errors: &[String]) -> Result<(), CommandError> {
+    if errors.is_empty() {
+        return Ok(());
     }
+    Err(CommandError::Provider(format!(
+        "Response doesn't match json_schema after a retry: {}",
+        errors.join("; ")
+    )))
+}
+
+/// Check a reply against `schema`. A reply that doesn't match is sent back once as an
+/// assistant turn with the errors and the schema, asking for it to be fixed.
+/// Returns the final reply, with usage covering both requests, and why it still doesn't
+/// match (empty if it does). If the fix request fails, the first reply is returned with
+/// its errors.
+pub(crate) async fn check_json_schema(
+    provider: &dyn LlmProvider,
+    request: &ChatRequest,
+    response: ChatResponse,
+    schema: &Value,
+    validator: &jsonschema::Validator,
+) -> (ChatResponse, Vec<String>) {
+    let errors = json_schema_errors(validator, &response.content);
+    if errors.is_empty() {
+        return (response, errors);
+    }
+    tracing::info!(
+        "Response doesn't match json_schema, asking for a fix: {}",
+        errors.join("; ")
+    );
+
+    let mut messages = request.messages.clone();
+    messages.push(ChatMessage {
+        role: ChatRole::Assistant,
+        content: response.content.clone(),
+    });
+    messages.push(ChatMessage {
+        role: ChatRole::User,
+        content: format!(
+            "{}\n\nErrors:\n- {}\n\nSchema:\n{}",
+            FIX_JSON_PROMPT,
+            errors.join("\n- "),
+            schema
+        ),
+    });
+    let fixed = match provider
+        .chat(ChatRequest {
+            messages,
+            ..request.clone()
+        })
+        .await
+    {
+        Ok(fixed) => fixed,
+        Err(e) => {
+            tracing::warn!("Request to fix the JSON reply failed: {}", e);
+            return (response, errors);
+        }
+    };
+
+    let errors = json_schema_errors(validator, &fixed.content);
+    (
+        ChatResponse {
+            usage: add_usage(response.usage, fixed.usage),
+            ..fixed
+        },
+        errors,
+    )
 }
 
 /// Send a streaming chat message
-/// Chunks are emitted via the 'chat-chunk' event
+/// Chunks are emitted via the 'chat-chunk' event, then 'chat-complete' or 'chat-error'
 #[tauri::command]
 pub async fn send_chat_message_stream(
     app_handle: AppHandle,
     config_store: tauri::State<'_, Arc<Mutex<ConfigStore>>>,
+    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    requests: tauri::State<'_, Arc<RequestRegistry>>,
     request: SendChatRequest,
     request_id: String, // Unique ID for this request
 ) -> Result<CommandResult<()>, String> {
-    // Validate inputs
-    if let Err(e) = validation::validate_not_empty("provider_id", &request.provider_id) {
-        return Ok(CommandResult::err(e.to_string()));
-    }
-    if let Err(e) = validation::validate_not_empty("model", &request.model) {
-        return Ok(CommandResult::err(e.to_string()));
+    match start_chat_stream(
+        &config_store,
+        &rag_db,
+        &requests,
+        request,
+        request_id,
+        move |event| event.emit(&app_handle),
+    )
+    .await
+    {
+        Ok(_) => Ok(CommandResult::ok(())),
+        Err(e) => Ok(CommandResult::err(e)),
     }
-    if let Err(e) = validation::validate_not_empty("request_id", &request_id) {
-        return Ok(CommandResult::err(e.to_string()));
+}
+
+/// Validate a streaming chat request and start streaming the reply through `on_event`
+/// With a `conversation_id`, the user message is saved before streaming and the reply
+/// once the stream succeeds; a stream that fails midway leaves its partial reply unsaved.
+/// Returns the task forwarding the stream, which ends after the terminal event.
+pub(crate) async fn start_chat_stream(
+    config_store: &Arc<Mutex<ConfigStore>>,
+    rag_db: &Arc<Mutex<RagDatabase>>,
+    requests: &Arc<RequestRegistry>,
+    request: SendChatRequest,
+    request_id: String,
+    on_event: impl Fn(StreamEvent) + Send + Sync + 'static,
+) -> Result<JoinHandle<()>, CommandError> {
+    // Validate inputs
+    validation::validate_not_empty("provider_id", &request.provider_id)?;
+    validation::validate_not_empty("request_id", &request_id)?;
+    if let Some(capacity) = request.stream_capacity {
+        validation::validate_stream_capacity(capacity)?;
     }
     if request.messages.is_empty() {
-        return Ok(CommandResult::err("Messages cannot be empty".to_string()));
+        return Err(CommandError::Validation(
+            "Messages cannot be empty".to_string(),
+        ));
     }
     if let Some(temp) = request.temperature {
-        if let Err(e) = validation::validate_temperature(temp) {
-            return Ok(CommandResult::err(e.to_string()));
-        }
+        validation::validate_temperature(temp)?;
     }
     if let Some(max_tokens) = request.max_tokens {
-        if let Err(e) = validation::validate_max_tokens(max_tokens) {
-            return Ok(CommandResult::err(e.to_string()));
-        }
+        validation::validate_max_tokens(max_tokens)?;
+    }
+    if let Some(penalty) = request.frequency_penalty {
+        validation::validate_penalty("frequency_penalty", penalty)?;
+    }
+    if let Some(penalty) = request.presence_penalty {
+        validation::validate_penalty("presence_penalty", penalty)?;
+    }
+    if let Some(ResponseFormat::JsonSchema(schema)) = &request.response_format {
+        validation::validate_json_schema("response_format", schema)?;
+    }
+    if request.json_schema.is_some() {
+        return Err(CommandError::Validation(
+            "json_schema is not supported when streaming".to_string(),
+        ));
     }
-
-    let store = config_store.lock().await;
 
     // Get provider config
-    let provider_config = match store.get_provider(&request.provider_id) {
-        Ok(config) => config,
-        Err(e) => return Ok(CommandResult::err(e.to_string())),
-    };
-
-    drop(store);
+    let provider_config = config_store
+        .lock()
+        .await
+        .get_enabled_provider(&request.provider_id)?;
+    let model = provider_config.resolve_model(&request.model)?;
 
     // Create provider instance
-    let provider = match create_provider(&provider_config) {
-        Ok(p) => p,
-        Err(e) => return Ok(CommandResult::err(e.to_string())),
-    };
+    let provider = create_provider(&provider_config)?;
 
-    // Create channel for streaming
-    let (tx, mut rx) = tokio::sync::mpsc::channel::<ChatChunk>(100);
+    let guard = requests.register(&request_id)?;
 
-    // Spawn task to receive chunks and emit events
-    let app_handle_clone = app_handle.clone();
-    let request_id_clone = request_id.clone();
-    tokio::spawn(async move {
-        while let Some(chunk) = rx.recv().await {
-            #[derive(Clone, Serialize)]
-            struct ChunkEvent {
-                request_id: String,
-                delta: String,
-                finish_reason: Option<String>,
-            }
-
-            let _ = app_handle_clone.emit_all(
-                "chat-chunk",
-                ChunkEvent {
-                    request_id: request_id_clone.clone(),
-                    delta: chunk.delta,
-                    finish_reason: chunk.finish_reason,
-                },
-            );
+    // Persist the user message before streaming so it survives a UI disconnect
+    if let Some(conversation_id) = request.conversation_id {
+        let db = rag_db.lock().await;
+        db.get_conversation(conversation_id).await?;
+        if let Some(user_message) = request
+            .messages
+            .last()
+            .filter(|m| matches!(m.role, ChatRole::User))
+        {
+            db.add_message(
+                conversation_id,
+                ChatRole::User.as_str().to_string(),
+                user_message.content.clone(),
+            )
+            .await?;
         }
+    }
 
-        // Emit completion event
-        let _ = app_handle_clone.emit_all("chat-complete", request_id_clone);
-    });
+    // Create channel for streaming
+    let (tx, rx) = tokio::sync::mpsc::channel::<ChatChunk>(
+        request.stream_capacity.unwrap_or(DEFAULT_STREAM_CAPACITY),
+    );
+
+    // Streams don't report usage, so it is recorded from token estimates
+    let provider_id = provider_config.provider_id.clone();
+    let usage_model = model.clone();
+    let prompt_tokens = count_prompt_tokens(&provider_id, &request.messages);
+    let conversation_id = request.conversation_id;
 
     // Send streaming request
     let chat_request = ChatRequest {
-        model: request.model,
+        model,
         messages: request.messages,
         temperature: request.temperature,
         max_tokens: request.max_tokens,
         top_p: request.top_p,
+        frequency_penalty: request.frequency_penalty,
+        presence_penalty: request.presence_penalty,
+        seed: request.seed,
+        response_format: request.response_format,
         stream: true,
     };
 
-    tokio::spawn(async move {
-        if let Err(e) = provider.stream_chat(chat_request, tx).await {
-            tracing::error!("Streaming error: {}", e);
+    let mut timing = StreamTiming::start();
+    let stream = spawn_cancellable_stream(provider, chat_request, tx, &guard);
+
+    // Spawn task to receive chunks and emit events
+    let rag_db = rag_db.clone();
+    Ok(tokio::spawn(async move {
+        // Keep the request cancellable until its stream has ended
+        let _guard = guard;
+        let full_text = forward_chunks(&on_event, &request_id, rx, &mut timing).await;
+        let result = stream_result(stream).await;
+
+        if !full_text.is_empty() {
+            record_usage(
+                &rag_db,
+                &provider_id,
+                &usage_model,
+                prompt_tokens as u32,
+                count_provider_tokens(&provider_id, &full_text) as u32,
+                conversation_id,
+            )
+            .await;
+        }
+
+        // Persist the assembled assistant reply, unless the stream broke off
+        if let Some(conversation_id) = conversation_id {
+            if result.is_ok() && !full_text.is_empty() {
+                let db = rag_db.lock().await;
+                if let Err(e) = db
+                    .add_message(
+                        conversation_id,
+                        ChatRole::Assistant.as_str().to_string(),
+                        full_text,
+                    )
+                    .await
+                {
+                    tracing::error!("Failed to persist streamed response: {}", e);
+                }
+            }
         }
-    });
 
-    Ok(CommandResult::ok(()))
+        emit_stream_end(&on_event, &request_id, result, timing);
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EstimateTokensRequest {
+    pub provider_id: String,
+    /// Empty uses the provider's default model
+    #[serde(default)]
+    pub model: String,
+    pub messages: Vec<ChatMessage>,
+    /// Completion tokens to include in the cost, e.g. the request's max_tokens
+    pub max_tokens: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TokenEstimate {
+    pub prompt_tokens: usize,
+    /// None if the model's price is unknown
+    pub estimated_cost_usd: Option<f64>,
+}
+
+/// Estimate the prompt tokens and cost of a chat request before sending it
+#[tauri::command]
+pub async fn estimate_tokens(
+    config_store: tauri::State<'_, Arc<Mutex<ConfigStore>>>,
+    request: EstimateTokensRequest,
+) -> Result<CommandResult<TokenEstimate>, String> {
+    if let Some(max_tokens) = request.max_tokens {
+        if let Err(e) = validation::validate_max_tokens(max_tokens) {
+            return Ok(CommandResult::err(e));
+        }
+    }
+
+    let store = config_store.lock().await;
+    let model = match store
+        .get_provider(&request.provider_id)
+        .and_then(|config| config.resolve_model(&request.model))
+    {
+        Ok(model) => model,
+        Err(e) => return Ok(CommandResult::err(e)),
+    };
+    drop(store);
+
+    let prompt_tokens = count_prompt_tokens(&request.provider_id, &request.messages);
+    let completion_tokens = request.max_tokens.unwrap_or(0) as usize;
+
+    Ok(CommandResult::ok(TokenEstimate {
+        prompt_tokens,
+        estimated_cost_usd: estimate_cost(&model, prompt_tokens, completion_tokens),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_support;
+    use crate::llm_providers::mock::{MockProvider, MOCK_STREAM_ERROR};
+    use crate::rag::UsageGroupBy;
+    use crate::tokenizer::count_tokens;
+
+    fn chunk(delta: &str, finish_reason: Option<&str>) -> ChatChunk {
+        ChatChunk {
+            delta: delta.to_string(),
+            reasoning_delta: None,
+            finish_reason: finish_reason.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_chunk_batch_coalesces_deltas() {
+        let mut batch = ChunkBatch::default();
+        assert!(batch.is_empty());
+
+        batch.push(chunk("Hel", None));
+        batch.push(ChatChunk {
+            reasoning_delta: Some("think".to_string()),
+            ..chunk("lo", None)
+        });
+        assert!(!batch.should_flush());
+
+        batch.push(chunk("", Some("stop")));
+        assert!(batch.should_flush());
+
+        let event = batch.take("req");
+        assert_eq!(event.delta, "Hello");
+        assert_eq!(event.reasoning_delta.as_deref(), Some("think"));
+        assert_eq!(event.finish_reason.as_deref(), Some("stop"));
+        assert!(batch.is_empty());
+    }
+
+    /// Chat with a mock replying "part<n> " to the n-th request, truncated until the
+    /// `complete_at`-th, returning the joined reply and the requests sent
+    async fn chat_with_continuations(
+        complete_at: usize,
+        max_continuations: u32,
+    ) -> (ChatResponse, Vec<ChatRequest>) {
+        let provider = MockProvider::new()
+            .with_replies((1..=5).map(|n| format!("part{} ", n)))
+            .with_truncated_replies(complete_at - 1);
+        let request = ChatRequest {
+            max_tokens: Some(2),
+            ..test_support::chat_request("Write a long story")
+        };
+        let first = provider.chat(request.clone()).await.unwrap();
+        let response = continue_truncated(&provider, &request, first, max_continuations).await;
+        (response, provider.requests())
+    }
+
+    #[tokio::test]
+    async fn test_continue_truncated_joins_parts() {
+        let (response, requests) = chat_with_continuations(3, 5).await;
+        assert_eq!(response.content, "part1 part2 part3 ");
+        assert_eq!(response.finish_reason.as_deref(), Some("stop"));
+        let completion_tokens: usize = (1..=3).map(|n| count_tokens(&format!("part{} ", n))).sum();
+        assert_eq!(
+            response.usage.unwrap().completion_tokens as usize,
+            completion_tokens
+        );
+
+        // Each continuation carries the partial reply so far
+        assert_eq!(requests.len(), 3);
+        for (request, partial) in requests[1..].iter().zip(["part1 ", "part1 part2 "]) {
+            let last = request.messages.last().unwrap();
+            assert!(matches!(last.role, ChatRole::Assistant));
+            assert_eq!(last.content, partial);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_continue_truncated_stops_at_limit() {
+        let (response, requests) = chat_with_continuations(usize::MAX, 2).await;
+        assert_eq!(response.content, "part1 part2 part3 ");
+        assert!(response.is_truncated());
+        assert_eq!(requests.len(), 3);
+
+        let (response, requests) = chat_with_continuations(usize::MAX, 0).await;
+        assert_eq!(response.content, "part1 ");
+        assert_eq!(requests.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_chat_falls_back_when_primary_is_unreachable() {
+        // A port nothing listens on, so the primary fails to connect
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let mut primary = ProviderConfig::empty("openai-compatible".to_string());
+        primary.enabled = true;
+        primary.base_url = Some(format!("http://127.0.0.1:{}/v1", port));
+        let mut fallback = ProviderConfig::empty("mock".to_string());
+        fallback.enabled = true;
+        let request = test_support::chat_request("Hello");
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let (rag_db, _) = test_support::stores(&dir).await;
+        let response = chat_with_fallback(
+            &rag_db,
+            None,
+            vec![
+                (primary, "local-model".to_string()),
+                (fallback, "mock-model".to_string()),
+            ],
+            request,
+            0,
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.provider_id, "mock");
+        assert_eq!(response.response.model, "mock-model");
+        assert_eq!(response.response.content, "Hello");
+    }
+
+    #[tokio::test]
+    async fn test_chat_saves_exchange_to_conversation() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let (rag_db, _) = test_support::stores(&dir).await;
+        let conversation = rag_db
+            .lock()
+            .await
+            .create_conversation(
+                "Chat".to_string(),
+                "mock".to_string(),
+                "mock-model".to_string(),
+                None,
+            )
+            .await
+            .unwrap();
+        let mut provider = ProviderConfig::empty("mock".to_string());
+        provider.enabled = true;
+        let request = test_support::chat_request("Hello");
+
+        chat_with_fallback(
+            &rag_db,
+            Some(conversation.id),
+            vec![(provider, "mock-model".to_string())],
+            request,
+            0,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let messages: Vec<(String, String)> = rag_db
+            .lock()
+            .await
+            .get_conversation_messages(conversation.id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|m| (m.role, m.content))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                ("user".to_string(), "Hello".to_string()),
+                ("assistant".to_string(), "Hello".to_string()),
+            ]
+        );
+    }
+
+    fn schema() -> Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": { "name": { "type": "string" } },
+            "required": ["name"]
+        })
+    }
+
+    /// Tokens the mock counts for `requests` answered with `replies`
+    fn mock_tokens(requests: &[ChatRequest], replies: &[&str]) -> u32 {
+        let prompt: usize = requests
+            .iter()
+            .flat_map(|r| &r.messages)
+            .map(|m| count_tokens(&m.content))
+            .sum();
+        let completion: usize = replies.iter().map(|r| count_tokens(r)).sum();
+        (prompt + completion) as u32
+    }
+
+    /// Ask the mock for JSON matching `schema()`, returning the final reply,
+    /// why it still doesn't match and the requests sent
+    async fn chat_with_schema(replies: &[&str]) -> (ChatResponse, Vec<String>, Vec<ChatRequest>) {
+        let provider = MockProvider::new().with_replies(replies.iter().copied());
+        let request = test_support::chat_request("Who are you?");
+        let schema = schema();
+        let validator = json_schema_validator(&schema).unwrap();
+        let first = provider.chat(request.clone()).await.unwrap();
+        let (response, errors) =
+            check_json_schema(&provider, &request, first, &schema, &validator).await;
+        (response, errors, provider.requests())
+    }
+
+    #[tokio::test]
+    async fn test_json_schema_accepts_matching_reply() {
+        let reply = " {\"name\": \"bot\"}\n";
+        let (response, errors, requests) = chat_with_schema(&[reply]).await;
+        assert!(errors.is_empty());
+        assert_eq!(response.content.trim(), "{\"name\": \"bot\"}");
+        assert_eq!(requests.len(), 1);
+        assert_eq!(
+            response.usage.unwrap().total_tokens,
+            mock_tokens(&requests, &[reply])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_json_schema_retries_once_with_errors() {
+        let replies = ["{\"age\": 3}", "{\"name\": \"bot\"}"];
+        let (response, errors, requests) = chat_with_schema(&replies).await;
+        assert!(errors.is_empty());
+        assert_eq!(response.content, "{\"name\": \"bot\"}");
+        assert_eq!(
+            response.usage.unwrap().total_tokens,
+            mock_tokens(&requests, &replies)
+        );
+
+        // The fix request carries the bad reply and what is wrong with it
+        let fix = &requests[1].messages;
+        assert_eq!(fix.len(), 3);
+        assert!(matches!(fix[1].role, ChatRole::Assistant));
+        assert_eq!(fix[1].content, "{\"age\": 3}");
+        assert!(fix[2].content.contains("\"name\" is a required property"));
+    }
+
+    #[tokio::test]
+    async fn test_json_schema_fails_after_one_retry() {
+        let replies = ["Sure! Here it is", "```json\n{}\n```"];
+        let (response, errors, requests) = chat_with_schema(&replies).await;
+        assert_eq!(requests.len(), 2);
+        assert_eq!(
+            response.usage.unwrap().total_tokens,
+            mock_tokens(&requests, &replies)
+        );
+
+        let err = check_schema_errors(&errors).unwrap_err();
+        assert!(matches!(err, CommandError::Provider(_)));
+        assert!(err.to_string().contains("not valid JSON"));
+    }
+
+    #[tokio::test]
+    async fn test_chat_records_usage_when_json_fix_fails() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let (rag_db, _) = test_support::stores(&dir).await;
+        let mut provider = ProviderConfig::empty("mock".to_string());
+        provider.enabled = true;
+        let request = test_support::chat_request("Hello");
+
+        // The mock echoes the prompt, so neither reply is JSON
+        let err = chat_with_fallback(
+            &rag_db,
+            None,
+            vec![(provider, "mock-model".to_string())],
+            request,
+            0,
+            Some(&schema()),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, CommandError::Provider(_)));
+
+        let usage = rag_db
+            .lock()
+            .await
+            .get_usage_summary(None, None, UsageGroupBy::Provider)
+            .await
+            .unwrap();
+        assert_eq!(usage.len(), 1);
+        assert_eq!(usage[0].group_key.as_deref(), Some("mock"));
+        assert_eq!(usage[0].requests, 1);
+        assert!(usage[0].prompt_tokens > count_tokens("Hello") as i64);
+    }
+
+    /// Stream `content` into a new conversation, returning the stored messages
+    /// and the names of the events emitted
+    async fn stream_into_conversation(content: &str) -> (Vec<(String, String)>, Vec<&'static str>) {
+        let dir = tempfile::TempDir::new().unwrap();
+        let (rag_db, config_store) = test_support::stores(&dir).await;
+        let conversation = rag_db
+            .lock()
+            .await
+            .create_conversation(
+                "Chat".to_string(),
+                "mock".to_string(),
+                "mock-model".to_string(),
+                None,
+            )
+            .await
+            .unwrap();
+        let request: SendChatRequest = serde_json::from_value(serde_json::json!({
+            "provider_id": "mock",
+            "model": "mock-model",
+            "messages": [{ "role": "user", "content": content }],
+            "stream": true,
+            "conversation_id": conversation.id,
+        }))
+        .unwrap();
+
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = events.clone();
+        start_chat_stream(
+            &config_store,
+            &rag_db,
+            &Arc::new(RequestRegistry::default()),
+            request,
+            "stream".to_string(),
+            move |event| recorded.lock().unwrap().push(event.name()),
+        )
+        .await
+        .unwrap()
+        .await
+        .unwrap();
+
+        let messages = rag_db
+            .lock()
+            .await
+            .get_conversation_messages(conversation.id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|m| (m.role, m.content))
+            .collect();
+        let events = events.lock().unwrap().clone();
+        (messages, events)
+    }
+
+    #[tokio::test]
+    async fn test_stream_saves_reply_to_conversation() {
+        let (messages, events) = stream_into_conversation("hello there").await;
+        assert_eq!(
+            messages,
+            vec![
+                ("user".to_string(), "hello there".to_string()),
+                ("assistant".to_string(), "hello there".to_string()),
+            ]
+        );
+        assert_eq!(events.last(), Some(&"chat-complete"));
+    }
+
+    #[tokio::test]
+    async fn test_failed_stream_leaves_partial_reply_unsaved() {
+        let content = format!("hello {} there", MOCK_STREAM_ERROR);
+        let (messages, events) = stream_into_conversation(&content).await;
+        assert_eq!(messages, vec![("user".to_string(), content)]);
+        assert_eq!(events, vec!["chat-chunk", "chat-error"]);
+    }
+
+    #[test]
+    fn test_chunk_batch_flushes_large_text() {
+        let mut batch = ChunkBatch::default();
+        batch.push(chunk(&"x".repeat(FLUSH_BYTES), None));
+        assert!(batch.should_flush());
+    }
 }
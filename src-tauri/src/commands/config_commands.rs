@@ -1,4 +1,10 @@
-use crate::config::{ConfigStore, MaskedProviderConfig};
+use super::CommandError;
+use crate::config::{
+    ConfigExport, ConfigImportSummary, ConfigStore, MaskedProviderConfig, ProviderUpdate,
+};
+use crate::llm_providers::{create_provider, ModelInfo, ProviderError};
+use crate::rag::embeddings::invalidate_query_embeddings;
+use crate::validation::{self, LimitsState, ValidationLimits};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tokio::sync::Mutex;
@@ -9,7 +15,7 @@ pub struct CommandResult<T> {
     #[serde(skip_serializing_if = "Option::is_none")]
     data: Option<T>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    error: Option<String>,
+    error: Option<CommandError>,
 }
 
 impl<T> CommandResult<T> {
@@ -21,11 +27,11 @@ impl<T> CommandResult<T> {
         }
     }
 
-    pub fn err(error: String) -> Self {
+    pub fn err(error: impl Into<CommandError>) -> Self {
         Self {
             success: false,
             data: None,
-            error: Some(error),
+            error: Some(error.into()),
         }
     }
 }
@@ -33,10 +39,8 @@ impl<T> CommandResult<T> {
 #[derive(Debug, Deserialize)]
 pub struct UpdateProviderRequest {
     pub provider_id: String,
-    pub api_key: Option<String>,
-    pub base_url: Option<String>,
-    pub default_model: Option<String>,
-    pub enabled: Option<bool>,
+    #[serde(flatten)]
+    pub update: ProviderUpdate,
 }
 
 /// Get all providers (masked, without API keys)
@@ -48,7 +52,20 @@ pub async fn get_providers(
 
     match store.get_all_providers_masked() {
         Ok(providers) => Ok(CommandResult::ok(providers)),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// Get the providers that can be used for requests: enabled and with an API key (masked)
+#[tauri::command]
+pub async fn list_enabled_providers(
+    config_store: tauri::State<'_, Arc<Mutex<ConfigStore>>>,
+) -> Result<CommandResult<Vec<MaskedProviderConfig>>, String> {
+    let store = config_store.lock().await;
+
+    match store.get_enabled_providers_masked() {
+        Ok(providers) => Ok(CommandResult::ok(providers)),
+        Err(e) => Ok(CommandResult::err(e)),
     }
 }
 
@@ -58,17 +75,26 @@ pub async fn update_provider(
     config_store: tauri::State<'_, Arc<Mutex<ConfigStore>>>,
     request: UpdateProviderRequest,
 ) -> Result<CommandResult<()>, String> {
+    if let Some(base_url) = &request.update.base_url {
+        if let Err(e) = validation::validate_url("base_url", base_url) {
+            return Ok(CommandResult::err(e));
+        }
+    }
+    if let Some(rate) = request.update.requests_per_minute {
+        if let Err(e) = validation::validate_requests_per_minute(rate) {
+            return Ok(CommandResult::err(e));
+        }
+    }
+
     let store = config_store.lock().await;
 
-    match store.update_provider(
-        request.provider_id,
-        request.api_key,
-        request.base_url,
-        request.default_model,
-        request.enabled,
-    ) {
-        Ok(_) => Ok(CommandResult::ok(())),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+    match store.update_provider(request.provider_id.clone(), request.update) {
+        Ok(_) => {
+            // The provider's model or endpoint may change what a query embeds to
+            invalidate_query_embeddings(&request.provider_id);
+            Ok(CommandResult::ok(()))
+        }
+        Err(e) => Ok(CommandResult::err(e)),
     }
 }
 
@@ -81,41 +107,216 @@ pub async fn delete_provider(
     let store = config_store.lock().await;
 
     match store.delete_provider(&provider_id) {
-        Ok(_) => Ok(CommandResult::ok(())),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Ok(_) => {
+            invalidate_query_embeddings(&provider_id);
+            Ok(CommandResult::ok(()))
+        }
+        Err(e) => Ok(CommandResult::err(e)),
     }
 }
 
+#[derive(Debug, Serialize)]
+pub struct ExportConfigResponse {
+    pub config: ConfigExport,
+    /// Set when the export holds API keys in plaintext
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+}
+
+/// Export the provider setup and general settings to share or move to another machine
+/// API keys are left out unless `include_secrets`, in which case they are in plaintext.
+#[tauri::command]
+pub async fn export_config(
+    config_store: tauri::State<'_, Arc<Mutex<ConfigStore>>>,
+    include_secrets: bool,
+) -> Result<CommandResult<ExportConfigResponse>, String> {
+    let store = config_store.lock().await;
+
+    match store.export_config(include_secrets) {
+        Ok(config) => Ok(CommandResult::ok(ExportConfigResponse {
+            config,
+            warning: include_secrets.then(|| {
+                "This export contains your API keys in plaintext; keep it private".to_string()
+            }),
+        })),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// Merge a config written by `export_config` into this one
+/// Existing API keys are kept unless `overwrite_keys`; the local theme and default
+/// provider are kept unless `import_general`.
+#[tauri::command]
+pub async fn import_config(
+    config_store: tauri::State<'_, Arc<Mutex<ConfigStore>>>,
+    config: ConfigExport,
+    overwrite_keys: bool,
+    import_general: Option<bool>,
+) -> Result<CommandResult<ConfigImportSummary>, String> {
+    for provider in &config.providers {
+        if let Some(base_url) = &provider.base_url {
+            if let Err(e) = validation::validate_url("base_url", base_url) {
+                return Ok(CommandResult::err(e));
+            }
+        }
+    }
+
+    let store = config_store.lock().await;
+
+    match store.import_config(&config, overwrite_keys, import_general.unwrap_or(false)) {
+        Ok(summary) => {
+            for provider in &config.providers {
+                invalidate_query_embeddings(&provider.provider_id);
+            }
+            Ok(CommandResult::ok(summary))
+        }
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// Get the input size limits in effect
+#[tauri::command]
+pub async fn get_validation_limits(
+    limits: tauri::State<'_, Arc<LimitsState>>,
+) -> Result<CommandResult<ValidationLimits>, String> {
+    Ok(CommandResult::ok(limits.get()))
+}
+
+/// Replace the input size limits; they apply at once and are saved for later runs
+#[tauri::command]
+pub async fn set_validation_limits(
+    config_store: tauri::State<'_, Arc<Mutex<ConfigStore>>>,
+    limits: tauri::State<'_, Arc<LimitsState>>,
+    new_limits: ValidationLimits,
+) -> Result<CommandResult<ValidationLimits>, String> {
+    if let Err(e) = validation::validate_limits(&new_limits) {
+        return Ok(CommandResult::err(e));
+    }
+
+    let store = config_store.lock().await;
+
+    match store.set_validation_limits(new_limits) {
+        Ok(()) => {
+            limits.set(new_limits);
+            Ok(CommandResult::ok(new_limits))
+        }
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// Whether offline mode is on
+#[tauri::command]
+pub async fn get_offline_mode(
+    config_store: tauri::State<'_, Arc<Mutex<ConfigStore>>>,
+) -> Result<CommandResult<bool>, String> {
+    let store = config_store.lock().await;
+
+    match store.offline_mode() {
+        Ok(enabled) => Ok(CommandResult::ok(enabled)),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// Turn offline mode on or off
+/// While on, every provider request is answered locally with canned replies and
+/// hash-based embeddings, and connection tests succeed without contacting the provider.
+#[tauri::command]
+pub async fn set_offline_mode(
+    config_store: tauri::State<'_, Arc<Mutex<ConfigStore>>>,
+    enabled: bool,
+) -> Result<CommandResult<()>, String> {
+    let store = config_store.lock().await;
+
+    match store.set_offline_mode(enabled) {
+        Ok(()) => Ok(CommandResult::ok(())),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// How a connection test reached the provider
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionTestMethod {
+    /// The provider's credential check (e.g. listing models), which costs nothing
+    Credentials,
+    /// A minimal chat completion with the default model
+    Completion,
+    /// Nothing was sent because offline mode is on
+    Simulated,
+    /// Nothing was sent because the provider takes no API key
+    NotApplicable,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConnectionTestResult {
+    pub method: ConnectionTestMethod,
+    pub message: String,
+}
+
 /// Test provider connection
+/// Uses the provider's free credential check where there is one, and otherwise a
+/// minimal completion with the default model.
 #[tauri::command]
 pub async fn test_provider_connection(
     config_store: tauri::State<'_, Arc<Mutex<ConfigStore>>>,
     provider_id: String,
-) -> Result<CommandResult<String>, String> {
-    use crate::llm_providers::{create_provider, ChatMessage, ChatRequest, ChatRole};
+) -> Result<CommandResult<ConnectionTestResult>, String> {
+    match connection_test(&config_store, &provider_id).await {
+        Ok(result) => Ok(CommandResult::ok(result)),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
 
-    let store = config_store.lock().await;
+async fn connection_test(
+    config_store: &Arc<Mutex<ConfigStore>>,
+    provider_id: &str,
+) -> Result<ConnectionTestResult, CommandError> {
+    use crate::llm_providers::{ChatMessage, ChatRequest, ChatRole};
 
-    // Get provider config
-    let provider_config = match store.get_provider(&provider_id) {
-        Ok(config) => config,
-        Err(e) => return Ok(CommandResult::err(e.to_string())),
-    };
+    let provider_config = config_store.lock().await.get_provider(provider_id)?;
 
-    drop(store); // Release lock
+    if provider_config.offline {
+        return Ok(ConnectionTestResult {
+            method: ConnectionTestMethod::Simulated,
+            message: "Offline mode is on; the connection was simulated.".to_string(),
+        });
+    }
 
-    // Create provider instance
-    let provider = match create_provider(&provider_config) {
-        Ok(p) => p,
-        Err(e) => return Ok(CommandResult::err(e.to_string())),
-    };
+    let provider = create_provider(&provider_config)?;
+    let has_key = !provider_config.api_keys.is_empty();
+
+    match provider.verify_credentials().await {
+        Ok(()) if has_key => {
+            return Ok(ConnectionTestResult {
+                method: ConnectionTestMethod::Credentials,
+                message: "Connection successful. API key accepted.".to_string(),
+            })
+        }
+        Ok(()) => {
+            return Ok(ConnectionTestResult {
+                method: ConnectionTestMethod::Credentials,
+                message: "Connection successful.".to_string(),
+            })
+        }
+        // Local and simulated providers take no API key, so there is nothing to check
+        Err(ProviderError::UnsupportedFeature(_)) if !has_key => {
+            return Ok(ConnectionTestResult {
+                method: ConnectionTestMethod::NotApplicable,
+                message: format!(
+                    "{} uses no API key; there is nothing to test.",
+                    provider.name()
+                ),
+            })
+        }
+        Err(ProviderError::UnsupportedFeature(_)) => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    let model = provider_config.resolve_model("")?;
 
     // Send a simple test request
     let test_request = ChatRequest {
-        model: provider_config
-            .default_model
-            .clone()
-            .unwrap_or_else(|| "default".to_string()),
+        model,
         messages: vec![ChatMessage {
             role: ChatRole::User,
             content: "Hello, this is a test. Please respond with 'OK'.".to_string(),
@@ -123,14 +324,81 @@ pub async fn test_provider_connection(
         temperature: Some(0.7),
         max_tokens: Some(50),
         top_p: None,
+        frequency_penalty: None,
+        presence_penalty: None,
+        seed: None,
+        response_format: None,
         stream: false,
     };
 
-    match provider.chat(test_request).await {
-        Ok(response) => Ok(CommandResult::ok(format!(
-            "Connection successful. Response: {}",
-            response.content
-        ))),
-        Err(e) => Ok(CommandResult::err(format!("Connection failed: {}", e))),
+    let response = provider.chat(test_request).await?;
+    Ok(ConnectionTestResult {
+        method: ConnectionTestMethod::Completion,
+        message: format!("Connection successful. Response: {}", response.content),
+    })
+}
+
+/// List the models a provider offers, for populating model pickers
+#[tauri::command]
+pub async fn list_provider_models(
+    config_store: tauri::State<'_, Arc<Mutex<ConfigStore>>>,
+    provider_id: String,
+) -> Result<CommandResult<Vec<ModelInfo>>, String> {
+    let store = config_store.lock().await;
+
+    let provider_config = match store.get_provider(&provider_id) {
+        Ok(config) => config,
+        Err(e) => return Ok(CommandResult::err(e)),
+    };
+
+    drop(store); // Release lock
+
+    let provider = match create_provider(&provider_config) {
+        Ok(p) => p,
+        Err(e) => return Ok(CommandResult::err(e)),
+    };
+
+    match provider.list_models().await {
+        Ok(models) => Ok(CommandResult::ok(models)),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_support;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_connection_test_of_keyless_provider_is_not_applicable() {
+        let dir = TempDir::new().unwrap();
+        let (_, config_store) = test_support::stores(&dir).await;
+
+        let result = connection_test(&config_store, "mock").await.unwrap();
+        assert!(matches!(result.method, ConnectionTestMethod::NotApplicable));
+        assert!(!result.message.contains("API key accepted"));
+    }
+
+    #[tokio::test]
+    async fn test_connection_test_in_offline_mode_is_simulated() {
+        let dir = TempDir::new().unwrap();
+        let (_, config_store) = test_support::stores(&dir).await;
+        config_store.lock().await.set_offline_mode(true).unwrap();
+
+        // Nothing is sent, so even a provider that was never set up passes
+        for provider_id in ["mock", "claude"] {
+            let result = connection_test(&config_store, provider_id).await.unwrap();
+            assert!(matches!(result.method, ConnectionTestMethod::Simulated));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connection_test_of_unknown_provider_fails() {
+        let dir = TempDir::new().unwrap();
+        let (_, config_store) = test_support::stores(&dir).await;
+
+        let result = connection_test(&config_store, "nonexistent").await;
+        assert!(result.is_err());
     }
 }
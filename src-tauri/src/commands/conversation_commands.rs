@@ -1,23 +1,66 @@
-use crate::rag::{Conversation, Message, RagDatabase};
-use crate::validation;
+use crate::config::ConfigStore;
+use crate::llm_providers::{
+    create_provider, ChatMessage, ChatRequest, ChatResponse, ChatRole, LlmProvider, ProviderError,
+};
+use crate::rag::database::DEFAULT_PAGE_SIZE;
+use crate::rag::{Conversation, ConversationSettings, Message, Page, RagDatabase};
+use crate::tokenizer::count_prompt_tokens;
+use crate::validation::{self, LimitsState, ValidationError, ValidationLimits};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
 use super::config_commands::CommandResult;
+use super::usage_commands::record_usage;
+use super::CommandError;
 
 #[derive(Debug, Deserialize)]
 pub struct CreateConversationRequest {
     pub title: String,
     pub provider_id: String,
     pub model: String,
+    /// RAG project the conversation belongs to, if any
+    pub project_id: Option<i64>,
+    /// Default generation parameters and system prompt
+    #[serde(default)]
+    pub settings: ConversationSettings,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StartConversationRequest {
+    /// Empty uses the start of `first_message`
+    #[serde(default)]
+    pub title: String,
+    pub provider_id: String,
+    pub model: String,
+    /// RAG project the conversation belongs to, if any
+    pub project_id: Option<i64>,
+    /// Default generation parameters and system prompt
+    #[serde(default)]
+    pub settings: ConversationSettings,
+    /// The user's opening message
+    pub first_message: String,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct AddMessageRequest {
     pub conversation_id: i64,
-    pub role: String,
+    pub role: String, // "system", "user" or "assistant"
+    pub content: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChatInConversationRequest {
+    pub conversation_id: i64,
+    /// The user's new message
     pub content: String,
+    /// Override the conversation's default temperature
+    pub temperature: Option<f32>,
+    /// Override the conversation's default max_tokens
+    pub max_tokens: Option<u32>,
+    /// Token budget for the history sent with the message; older turns beyond it are
+    /// summarized. None sends the whole history.
+    pub max_history_tokens: Option<usize>,
 }
 
 #[derive(Debug, Serialize)]
@@ -30,40 +73,130 @@ pub struct ConversationWithMessages {
 #[tauri::command]
 pub async fn create_conversation(
     rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    limits: tauri::State<'_, Arc<LimitsState>>,
     request: CreateConversationRequest,
 ) -> Result<CommandResult<Conversation>, String> {
     // Validate inputs
     if let Err(e) = validation::validate_name("conversation title", &request.title) {
-        return Ok(CommandResult::err(e.to_string()));
+        return Ok(CommandResult::err(e));
     }
     if let Err(e) = validation::validate_not_empty("provider_id", &request.provider_id) {
-        return Ok(CommandResult::err(e.to_string()));
+        return Ok(CommandResult::err(e));
     }
     if let Err(e) = validation::validate_not_empty("model", &request.model) {
-        return Ok(CommandResult::err(e.to_string()));
+        return Ok(CommandResult::err(e));
+    }
+    if let Err(e) = validate_conversation_settings(&request.settings, &limits.get()) {
+        return Ok(CommandResult::err(e));
     }
 
     let db = rag_db.lock().await;
 
     match db
-        .create_conversation(request.title, request.provider_id, request.model)
+        .create_conversation_with_settings(
+            request.title,
+            request.provider_id,
+            request.model,
+            request.project_id,
+            &request.settings,
+        )
         .await
     {
         Ok(conversation) => Ok(CommandResult::ok(conversation)),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// Create a conversation with its first user message, atomically
+/// Without a title, the conversation is named after the start of the message;
+/// `generate_conversation_title` can replace it once there is a reply.
+#[tauri::command]
+pub async fn start_conversation(
+    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    limits: tauri::State<'_, Arc<LimitsState>>,
+    request: StartConversationRequest,
+) -> Result<CommandResult<ConversationWithMessages>, String> {
+    // Validate inputs
+    let limits = limits.get();
+    if let Err(e) = validation::validate_not_empty("first_message", &request.first_message) {
+        return Ok(CommandResult::err(e));
+    }
+    if let Err(e) =
+        validation::validate_message_size("first_message", &request.first_message, &limits)
+    {
+        return Ok(CommandResult::err(e));
+    }
+    let title = if request.title.trim().is_empty() {
+        fallback_title(&request.first_message)
+    } else {
+        request.title
+    };
+    if let Err(e) = validation::validate_name("conversation title", &title) {
+        return Ok(CommandResult::err(e));
+    }
+    if let Err(e) = validation::validate_not_empty("provider_id", &request.provider_id) {
+        return Ok(CommandResult::err(e));
+    }
+    if let Err(e) = validation::validate_not_empty("model", &request.model) {
+        return Ok(CommandResult::err(e));
+    }
+    if let Err(e) = validate_conversation_settings(&request.settings, &limits) {
+        return Ok(CommandResult::err(e));
+    }
+
+    let db = rag_db.lock().await;
+
+    match db
+        .start_conversation(
+            title,
+            request.provider_id,
+            request.model,
+            request.project_id,
+            &request.settings,
+            request.first_message,
+        )
+        .await
+    {
+        Ok((conversation, message)) => Ok(CommandResult::ok(ConversationWithMessages {
+            conversation,
+            messages: vec![message],
+        })),
+        Err(e) => Ok(CommandResult::err(e)),
     }
 }
 
-/// List all conversations
+/// List conversations, one page at a time
 #[tauri::command]
 pub async fn list_conversations(
     rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+) -> Result<CommandResult<Page<Conversation>>, String> {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    let offset = offset.unwrap_or(0);
+    if let Err(e) = validation::validate_pagination(limit, offset) {
+        return Ok(CommandResult::err(e));
+    }
+
+    let db = rag_db.lock().await;
+
+    match db.list_conversations(limit, offset).await {
+        Ok(conversations) => Ok(CommandResult::ok(conversations)),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// List conversations belonging to a project
+#[tauri::command]
+pub async fn list_conversations_for_project(
+    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    project_id: i64,
 ) -> Result<CommandResult<Vec<Conversation>>, String> {
     let db = rag_db.lock().await;
 
-    match db.list_conversations().await {
+    match db.list_conversations_for_project(project_id).await {
         Ok(conversations) => Ok(CommandResult::ok(conversations)),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::err(e)),
     }
 }
 
@@ -77,12 +210,12 @@ pub async fn get_conversation_with_messages(
 
     let conversation = match db.get_conversation(conversation_id).await {
         Ok(c) => c,
-        Err(e) => return Ok(CommandResult::err(e.to_string())),
+        Err(e) => return Ok(CommandResult::err(e)),
     };
 
     let messages = match db.get_conversation_messages(conversation_id).await {
         Ok(m) => m,
-        Err(e) => return Ok(CommandResult::err(e.to_string())),
+        Err(e) => return Ok(CommandResult::err(e)),
     };
 
     Ok(CommandResult::ok(ConversationWithMessages {
@@ -100,18 +233,217 @@ pub async fn update_conversation_title(
 ) -> Result<CommandResult<()>, String> {
     // Validate title
     if let Err(e) = validation::validate_name("conversation title", &title) {
-        return Ok(CommandResult::err(e.to_string()));
+        return Ok(CommandResult::err(e));
     }
 
     let db = rag_db.lock().await;
 
     match db.update_conversation_title(conversation_id, title).await {
         Ok(_) => Ok(CommandResult::ok(())),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::err(e)),
     }
 }
 
-/// Delete a conversation
+/// Validate a conversation's generation defaults
+fn validate_conversation_settings(
+    settings: &ConversationSettings,
+    limits: &ValidationLimits,
+) -> Result<(), ValidationError> {
+    if let Some(temperature) = settings.temperature {
+        validation::validate_temperature(temperature)?;
+    }
+    if let Some(max_tokens) = settings.max_tokens {
+        validation::validate_max_tokens(max_tokens)?;
+    }
+    if let Some(top_p) = settings.top_p {
+        validation::validate_range("top_p", top_p, 0.0, 1.0)?;
+    }
+    if let Some(system_prompt) = &settings.system_prompt {
+        validation::validate_message_size("system_prompt", system_prompt, limits)?;
+    }
+    Ok(())
+}
+
+/// Replace a conversation's default generation parameters and system prompt
+#[tauri::command]
+pub async fn update_conversation_settings(
+    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    limits: tauri::State<'_, Arc<LimitsState>>,
+    conversation_id: i64,
+    settings: ConversationSettings,
+) -> Result<CommandResult<Conversation>, String> {
+    if let Err(e) = validate_conversation_settings(&settings, &limits.get()) {
+        return Ok(CommandResult::err(e));
+    }
+
+    let db = rag_db.lock().await;
+
+    match db
+        .update_conversation_settings(conversation_id, &settings)
+        .await
+    {
+        Ok(conversation) => Ok(CommandResult::ok(conversation)),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// Prompt used to summarize the first exchange into a title
+const TITLE_PROMPT: &str =
+    "Write a short title (at most 6 words) for a conversation that starts with \
+     the exchange below. Reply with the title only, without quotes or punctuation at the end.";
+
+/// Number of characters of the first user message used when no title can be generated
+const FALLBACK_TITLE_CHARS: usize = 40;
+
+/// Tidy a model-generated title: first line only, without surrounding quotes or a final period
+fn clean_title(raw: &str) -> String {
+    raw.lines()
+        .find(|line| !line.trim().is_empty())
+        .unwrap_or("")
+        .trim()
+        .trim_start_matches(['"', '\'', '*'])
+        .trim_end_matches(['"', '\'', '*', '.'])
+        .trim()
+        .to_string()
+}
+
+/// Title from the first characters of the user's message
+fn fallback_title(user_message: &str) -> String {
+    let text = user_message
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    if text.chars().count() <= FALLBACK_TITLE_CHARS {
+        return text;
+    }
+    let truncated: String = text.chars().take(FALLBACK_TITLE_CHARS).collect();
+    format!("{}...", truncated.trim_end())
+}
+
+/// Generate a conversation title from its first user + assistant exchange
+/// Falls back to the start of the first user message if the provider fails
+/// An empty `model` uses the provider's default model.
+#[tauri::command]
+pub async fn generate_conversation_title(
+    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    config_store: tauri::State<'_, Arc<Mutex<ConfigStore>>>,
+    conversation_id: i64,
+    provider_id: String,
+    model: String,
+) -> Result<CommandResult<String>, String> {
+    // Validate inputs
+    if let Err(e) = validation::validate_not_empty("provider_id", &provider_id) {
+        return Ok(CommandResult::err(e));
+    }
+
+    let db = rag_db.lock().await;
+    let messages = match db.get_conversation_messages(conversation_id).await {
+        Ok(m) => m,
+        Err(e) => return Ok(CommandResult::err(e)),
+    };
+    drop(db);
+
+    let first_user = match messages.iter().find(|m| m.role == "user") {
+        Some(m) => m.content.clone(),
+        None => {
+            return Ok(CommandResult::err(CommandError::Validation(
+                "Conversation has no user message yet".to_string(),
+            )))
+        }
+    };
+    let first_assistant = messages
+        .iter()
+        .find(|m| m.role == "assistant")
+        .map(|m| m.content.clone())
+        .unwrap_or_default();
+
+    // Ask the provider for a title
+    let store = config_store.lock().await;
+    let provider = store
+        .get_enabled_provider(&provider_id)
+        .and_then(|config| config.resolve_model(&model).map(|model| (config, model)))
+        .map_err(|e| e.to_string())
+        .and_then(|(config, model)| {
+            create_provider(&config)
+                .map(|provider| (provider, model))
+                .map_err(|e| e.to_string())
+        });
+    drop(store);
+
+    let generated = match provider {
+        Ok((provider, model)) => provider
+            .chat(ChatRequest {
+                model,
+                messages: vec![
+                    ChatMessage {
+                        role: ChatRole::System,
+                        content: TITLE_PROMPT.to_string(),
+                    },
+                    ChatMessage {
+                        role: ChatRole::User,
+                        content: format!("User: {}\n\nAssistant: {}", first_user, first_assistant),
+                    },
+                ],
+                temperature: Some(0.3),
+                max_tokens: Some(30),
+                top_p: None,
+                frequency_penalty: None,
+                presence_penalty: None,
+                seed: None,
+                response_format: None,
+                stream: false,
+            })
+            .await
+            .map_err(|e| e.to_string()),
+        Err(e) => Err(e),
+    };
+
+    let generated = match generated {
+        Ok(response) => Some(clean_title(&response.content)),
+        Err(e) => {
+            tracing::warn!("Title generation failed, using fallback: {}", e);
+            None
+        }
+    };
+
+    let title = generated
+        .filter(|t| validation::validate_name("conversation title", t).is_ok())
+        .unwrap_or_else(|| fallback_title(&first_user));
+
+    if let Err(e) = validation::validate_name("conversation title", &title) {
+        return Ok(CommandResult::err(e));
+    }
+
+    let db = rag_db.lock().await;
+    match db
+        .update_conversation_title(conversation_id, title.clone())
+        .await
+    {
+        Ok(_) => Ok(CommandResult::ok(title)),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// Copy a conversation up to and including one of its messages into a new conversation
+#[tauri::command]
+pub async fn fork_conversation(
+    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    conversation_id: i64,
+    up_to_message_id: i64,
+) -> Result<CommandResult<Conversation>, String> {
+    let db = rag_db.lock().await;
+
+    match db
+        .fork_conversation(conversation_id, up_to_message_id)
+        .await
+    {
+        Ok(conversation) => Ok(CommandResult::ok(conversation)),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// Move a conversation to the trash
+/// Use `purge_conversation` to delete it permanently
 #[tauri::command]
 pub async fn delete_conversation(
     rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
@@ -119,9 +451,58 @@ pub async fn delete_conversation(
 ) -> Result<CommandResult<()>, String> {
     let db = rag_db.lock().await;
 
-    match db.delete_conversation(conversation_id).await {
+    match db.soft_delete_conversation(conversation_id).await {
+        Ok(_) => Ok(CommandResult::ok(())),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// List conversations in the trash, one page at a time
+#[tauri::command]
+pub async fn list_trashed_conversations(
+    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+) -> Result<CommandResult<Page<Conversation>>, String> {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    let offset = offset.unwrap_or(0);
+    if let Err(e) = validation::validate_pagination(limit, offset) {
+        return Ok(CommandResult::err(e));
+    }
+
+    let db = rag_db.lock().await;
+
+    match db.list_trashed_conversations(limit, offset).await {
+        Ok(conversations) => Ok(CommandResult::ok(conversations)),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// Take a conversation out of the trash
+#[tauri::command]
+pub async fn restore_conversation(
+    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    conversation_id: i64,
+) -> Result<CommandResult<Conversation>, String> {
+    let db = rag_db.lock().await;
+
+    match db.restore_conversation(conversation_id).await {
+        Ok(conversation) => Ok(CommandResult::ok(conversation)),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// Permanently delete a conversation in the trash and its messages
+#[tauri::command]
+pub async fn purge_conversation(
+    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    conversation_id: i64,
+) -> Result<CommandResult<()>, String> {
+    let db = rag_db.lock().await;
+
+    match db.purge_conversation(conversation_id).await {
         Ok(_) => Ok(CommandResult::ok(())),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::err(e)),
     }
 }
 
@@ -129,18 +510,18 @@ pub async fn delete_conversation(
 #[tauri::command]
 pub async fn add_message(
     rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    limits: tauri::State<'_, Arc<LimitsState>>,
     request: AddMessageRequest,
 ) -> Result<CommandResult<Message>, String> {
     // Validate inputs
-    if let Err(e) = validation::validate_not_empty("role", &request.role) {
-        return Ok(CommandResult::err(e.to_string()));
+    if let Err(e) = validation::validate_role(&request.role) {
+        return Ok(CommandResult::err(e));
     }
     if let Err(e) = validation::validate_not_empty("content", &request.content) {
-        return Ok(CommandResult::err(e.to_string()));
+        return Ok(CommandResult::err(e));
     }
-    // Limit message content to reasonable size (1MB)
-    if let Err(e) = validation::validate_length("content", &request.content, None, Some(1_048_576)) {
-        return Ok(CommandResult::err(e.to_string()));
+    if let Err(e) = validation::validate_message_size("content", &request.content, &limits.get()) {
+        return Ok(CommandResult::err(e));
     }
 
     let db = rag_db.lock().await;
@@ -150,7 +531,389 @@ pub async fn add_message(
         .await
     {
         Ok(message) => Ok(CommandResult::ok(message)),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// Validate a message sent with `chat_in_conversation`
+fn validate_chat_in_conversation(
+    request: &ChatInConversationRequest,
+    limits: &ValidationLimits,
+) -> Result<(), ValidationError> {
+    validation::validate_not_empty("content", &request.content)?;
+    validation::validate_message_size("content", &request.content, limits)?;
+    if let Some(temperature) = request.temperature {
+        validation::validate_temperature(temperature)?;
+    }
+    if let Some(max_tokens) = request.max_tokens {
+        validation::validate_max_tokens(max_tokens)?;
+    }
+    if let Some(max_history_tokens) = request.max_history_tokens {
+        validation::validate_range("max_history_tokens", max_history_tokens, 1, 2_000_000)?;
+    }
+    Ok(())
+}
+
+/// Prompt used to condense older conversation turns
+const SUMMARY_PROMPT: &str =
+    "Summarize the conversation below so it can stand in for the original messages. \
+     Keep facts, decisions, names and open questions; leave out pleasantries. \
+     Reply with the summary only.";
+
+/// Most recent messages always sent verbatim when older history is summarized
+const SUMMARY_KEEP_RECENT_MESSAGES: usize = 6;
+
+/// Maximum length of a generated summary
+const SUMMARY_MAX_TOKENS: u32 = 1024;
+
+/// System note carrying the summary of older messages
+fn summary_message(summary: &str) -> ChatMessage {
+    ChatMessage {
+        role: ChatRole::System,
+        content: format!("Summary of the earlier conversation:\n{}", summary),
+    }
+}
+
+/// Ask the provider to fold `messages` into the previous summary, if any
+async fn summarize_history(
+    provider: &dyn LlmProvider,
+    model: &str,
+    previous_summary: Option<&str>,
+    messages: &[Message],
+) -> Result<ChatResponse, ProviderError> {
+    let mut transcript = String::new();
+    if let Some(summary) = previous_summary {
+        transcript.push_str(&format!("Summary so far: {}\n\n", summary));
+    }
+    for message in messages {
+        let speaker = match ChatRole::parse(&message.role) {
+            Some(ChatRole::User) => "User",
+            Some(ChatRole::Assistant) => "Assistant",
+            Some(ChatRole::System) => "System",
+            None => continue,
+        };
+        transcript.push_str(&format!("{}: {}\n\n", speaker, message.content));
+    }
+
+    provider
+        .chat(ChatRequest {
+            model: model.to_string(),
+            messages: vec![
+                ChatMessage {
+                    role: ChatRole::System,
+                    content: SUMMARY_PROMPT.to_string(),
+                },
+                ChatMessage {
+                    role: ChatRole::User,
+                    content: transcript.trim_end().to_string(),
+                },
+            ],
+            temperature: Some(0.2),
+            max_tokens: Some(SUMMARY_MAX_TOKENS),
+            top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            seed: None,
+            response_format: None,
+            stream: false,
+        })
+        .await
+}
+
+/// Messages to send for a conversation: its system prompt, the stored summary in place of
+/// the messages it covers, then the rest of the history and the new user message. When that exceeds
+/// `max_history_tokens`, everything but the most recent messages is summarized (and
+/// the summary stored, so it isn't recomputed on the next message).
+async fn assemble_history(
+    rag_db: &Arc<Mutex<RagDatabase>>,
+    provider: &dyn LlmProvider,
+    model: &str,
+    conversation: &Conversation,
+    stored: Vec<Message>,
+    user_message: ChatMessage,
+    max_history_tokens: Option<usize>,
+) -> Result<Vec<ChatMessage>, CommandError> {
+    let to_chat = |messages: &[Message]| -> Vec<ChatMessage> {
+        messages
+            .iter()
+            .filter_map(|m| {
+                ChatRole::parse(&m.role).map(|role| ChatMessage {
+                    role,
+                    content: m.content.clone(),
+                })
+            })
+            .collect()
+    };
+
+    let system_prompt = conversation
+        .settings
+        .system_prompt
+        .as_ref()
+        .filter(|prompt| !prompt.trim().is_empty())
+        .map(|prompt| ChatMessage {
+            role: ChatRole::System,
+            content: prompt.clone(),
+        });
+
+    let Some(budget) = max_history_tokens else {
+        let mut messages: Vec<ChatMessage> = system_prompt.into_iter().collect();
+        messages.extend(to_chat(&stored));
+        messages.push(user_message);
+        return Ok(messages);
+    };
+
+    let mut summary = conversation.history_summary.clone();
+    let mut remaining = match (&summary, conversation.summary_through_message_id) {
+        (Some(_), Some(through)) => stored.into_iter().filter(|m| m.id > through).collect(),
+        _ => {
+            summary = None;
+            stored
+        }
+    };
+
+    let assemble = |summary: &Option<String>, remaining: &[Message]| {
+        let mut messages: Vec<ChatMessage> = system_prompt.iter().cloned().collect();
+        messages.extend(summary.iter().map(|s| summary_message(s)));
+        messages.extend(to_chat(remaining));
+        messages.push(user_message.clone());
+        messages
+    };
+
+    let messages = assemble(&summary, &remaining);
+    if count_prompt_tokens(&conversation.provider_id, &messages) <= budget
+        || remaining.len() <= SUMMARY_KEEP_RECENT_MESSAGES
+    {
+        return Ok(messages);
+    }
+
+    let recent = remaining.split_off(remaining.len() - SUMMARY_KEEP_RECENT_MESSAGES);
+    let through_message_id = remaining.last().map(|m| m.id).unwrap_or_default();
+    let response = summarize_history(provider, model, summary.as_deref(), &remaining).await?;
+    if let Some(usage) = &response.usage {
+        record_usage(
+            rag_db,
+            &conversation.provider_id,
+            &response.model,
+            usage.prompt_tokens,
+            usage.completion_tokens,
+            Some(conversation.id),
+        )
+        .await;
+    }
+
+    let new_summary = response.content.trim().to_string();
+    if new_summary.is_empty() {
+        return Err(CommandError::Provider(
+            "Provider returned an empty history summary".to_string(),
+        ));
+    }
+
+    rag_db
+        .lock()
+        .await
+        .set_conversation_summary(conversation.id, &new_summary, through_message_id)
+        .await?;
+
+    Ok(assemble(&Some(new_summary), &recent))
+}
+
+/// A conversation that isn't in the trash, with its stored messages
+async fn open_conversation(
+    rag_db: &Arc<Mutex<RagDatabase>>,
+    conversation_id: i64,
+) -> Result<(Conversation, Vec<Message>), CommandError> {
+    let db = rag_db.lock().await;
+    let conversation = db.get_conversation(conversation_id).await?;
+    if conversation.deleted_at.is_some() {
+        return Err(CommandError::Validation(
+            "Conversation is in the trash; restore it to continue chatting".to_string(),
+        ));
+    }
+    let stored = db.get_conversation_messages(conversation.id).await?;
+    Ok((conversation, stored))
+}
+
+/// Settings of a single reply that override the conversation's defaults
+#[derive(Debug, Default)]
+struct ReplyOverrides {
+    temperature: Option<f32>,
+    max_tokens: Option<u32>,
+    max_history_tokens: Option<usize>,
+}
+
+/// Send `user_message` after the `stored` history to the conversation's provider and
+/// model, record the usage and return the reply's content. Nothing is saved.
+async fn generate_reply(
+    rag_db: &Arc<Mutex<RagDatabase>>,
+    config_store: &Arc<Mutex<ConfigStore>>,
+    conversation: &Conversation,
+    stored: Vec<Message>,
+    user_message: String,
+    overrides: ReplyOverrides,
+) -> Result<String, CommandError> {
+    let store = config_store.lock().await;
+    let provider_config = store.get_enabled_provider(&conversation.provider_id)?;
+    drop(store);
+
+    let model = provider_config.resolve_model(&conversation.model)?;
+    let provider = create_provider(&provider_config)?;
+
+    let messages = assemble_history(
+        rag_db,
+        provider.as_ref(),
+        &model,
+        conversation,
+        stored,
+        ChatMessage {
+            role: ChatRole::User,
+            content: user_message,
+        },
+        overrides.max_history_tokens,
+    )
+    .await?;
+
+    let response = provider
+        .chat(ChatRequest {
+            model,
+            messages,
+            temperature: overrides.temperature.or(conversation.settings.temperature),
+            max_tokens: overrides.max_tokens.or(conversation.settings.max_tokens),
+            top_p: conversation.settings.top_p,
+            frequency_penalty: None,
+            presence_penalty: None,
+            seed: None,
+            response_format: None,
+            stream: false,
+        })
+        .await?;
+
+    if let Some(usage) = &response.usage {
+        record_usage(
+            rag_db,
+            &conversation.provider_id,
+            &response.model,
+            usage.prompt_tokens,
+            usage.completion_tokens,
+            Some(conversation.id),
+        )
+        .await;
+    }
+
+    Ok(response.content)
+}
+
+/// Send the user's message with the conversation's stored history to its provider and
+/// model, then save both turns in one transaction. Nothing is saved if the provider
+/// fails, so the message can simply be sent again.
+pub(crate) async fn reply_in_conversation(
+    rag_db: &Arc<Mutex<RagDatabase>>,
+    config_store: &Arc<Mutex<ConfigStore>>,
+    limits: &ValidationLimits,
+    request: ChatInConversationRequest,
+) -> Result<Message, CommandError> {
+    validate_chat_in_conversation(&request, limits)?;
+
+    let (conversation, stored) = open_conversation(rag_db, request.conversation_id).await?;
+    let content = generate_reply(
+        rag_db,
+        config_store,
+        &conversation,
+        stored,
+        request.content.clone(),
+        ReplyOverrides {
+            temperature: request.temperature,
+            max_tokens: request.max_tokens,
+            max_history_tokens: request.max_history_tokens,
+        },
+    )
+    .await?;
+
+    let (_, reply) = rag_db
+        .lock()
+        .await
+        .add_exchange(conversation.id, request.content, content)
+        .await?;
+    Ok(reply)
+}
+
+/// Answer the conversation's last user message again: a trailing assistant reply is
+/// replaced by the new one, otherwise the reply is added. The old reply is only deleted
+/// once the provider has answered, in the same transaction that saves the new one, so
+/// a failure leaves the conversation unchanged.
+pub(crate) async fn regenerate_reply(
+    rag_db: &Arc<Mutex<RagDatabase>>,
+    config_store: &Arc<Mutex<ConfigStore>>,
+    conversation_id: i64,
+    temperature: Option<f32>,
+) -> Result<Message, CommandError> {
+    if let Some(temperature) = temperature {
+        validation::validate_temperature(temperature)?;
+    }
+
+    let (conversation, mut stored) = open_conversation(rag_db, conversation_id).await?;
+    let replaced = match stored.last() {
+        Some(last) if matches!(ChatRole::parse(&last.role), Some(ChatRole::Assistant)) => {
+            stored.pop()
+        }
+        _ => None,
+    };
+    let user_message = match stored.pop() {
+        Some(last) if matches!(ChatRole::parse(&last.role), Some(ChatRole::User)) => last,
+        _ => {
+            return Err(CommandError::Validation(
+                "Conversation has no user message to respond to".to_string(),
+            ))
+        }
+    };
+
+    let content = generate_reply(
+        rag_db,
+        config_store,
+        &conversation,
+        stored,
+        user_message.content,
+        ReplyOverrides {
+            temperature,
+            ..Default::default()
+        },
+    )
+    .await?;
+
+    Ok(rag_db
+        .lock()
+        .await
+        .replace_reply(conversation.id, replaced.map(|m| m.id), content)
+        .await?)
+}
+
+/// Regenerate the reply to the conversation's last user message, replacing the last
+/// assistant message if there is one
+#[tauri::command]
+pub async fn regenerate_last_response(
+    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    config_store: tauri::State<'_, Arc<Mutex<ConfigStore>>>,
+    conversation_id: i64,
+    temperature: Option<f32>,
+) -> Result<CommandResult<Message>, String> {
+    match regenerate_reply(&rag_db, &config_store, conversation_id, temperature).await {
+        Ok(message) => Ok(CommandResult::ok(message)),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// Send a message in a conversation and get the assistant's reply
+/// History, provider and model come from the conversation, and both the message and
+/// the reply are saved to it.
+#[tauri::command]
+pub async fn chat_in_conversation(
+    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    config_store: tauri::State<'_, Arc<Mutex<ConfigStore>>>,
+    limits: tauri::State<'_, Arc<LimitsState>>,
+    request: ChatInConversationRequest,
+) -> Result<CommandResult<Message>, String> {
+    match reply_in_conversation(&rag_db, &config_store, &limits.get(), request).await {
+        Ok(message) => Ok(CommandResult::ok(message)),
+        Err(e) => Ok(CommandResult::err(e)),
     }
 }
 
@@ -164,7 +927,31 @@ pub async fn get_conversation_messages(
 
     match db.get_conversation_messages(conversation_id).await {
         Ok(messages) => Ok(CommandResult::ok(messages)),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// Edit a message, deleting every later message in its conversation
+#[tauri::command]
+pub async fn edit_message(
+    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    limits: tauri::State<'_, Arc<LimitsState>>,
+    message_id: i64,
+    new_content: String,
+) -> Result<CommandResult<Message>, String> {
+    // Validate inputs
+    if let Err(e) = validation::validate_not_empty("content", &new_content) {
+        return Ok(CommandResult::err(e));
+    }
+    if let Err(e) = validation::validate_message_size("content", &new_content, &limits.get()) {
+        return Ok(CommandResult::err(e));
+    }
+
+    let db = rag_db.lock().await;
+
+    match db.edit_message(message_id, new_content).await {
+        Ok(message) => Ok(CommandResult::ok(message)),
+        Err(e) => Ok(CommandResult::err(e)),
     }
 }
 
@@ -178,6 +965,278 @@ pub async fn delete_message(
 
     match db.delete_message(message_id).await {
         Ok(_) => Ok(CommandResult::ok(())),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_support;
+    use tempfile::TempDir;
+
+    /// Stores with the mock provider configured and an empty conversation using it
+    async fn setup(dir: &TempDir) -> (Arc<Mutex<RagDatabase>>, Arc<Mutex<ConfigStore>>, i64) {
+        let (rag_db, config_store) = test_support::stores(dir).await;
+        let conversation = rag_db
+            .lock()
+            .await
+            .create_conversation(
+                "Chat".to_string(),
+                "mock".to_string(),
+                "mock-model".to_string(),
+                None,
+            )
+            .await
+            .unwrap();
+        (rag_db, config_store, conversation.id)
+    }
+
+    #[test]
+    fn test_clean_title() {
+        assert_eq!(
+            clean_title("\"Rust Ownership Basics.\""),
+            "Rust Ownership Basics"
+        );
+        assert_eq!(
+            clean_title("\n  **Trip Planning**  \nSecond line"),
+            "Trip Planning"
+        );
+        assert_eq!(clean_title("'Sourdough Tips'..."), "Sourdough Tips");
+        assert_eq!(clean_title("   \n"), "");
+    }
+
+    #[test]
+    fn test_fallback_title_truncates_on_characters() {
+        assert_eq!(fallback_title("  short\n question "), "short question");
+        let long = "é".repeat(FALLBACK_TITLE_CHARS + 5);
+        assert_eq!(
+            fallback_title(&long),
+            format!("{}...", "é".repeat(FALLBACK_TITLE_CHARS))
+        );
+    }
+
+    fn chat_request(conversation_id: i64, content: &str) -> ChatInConversationRequest {
+        ChatInConversationRequest {
+            conversation_id,
+            content: content.to_string(),
+            temperature: None,
+            max_tokens: None,
+            max_history_tokens: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reply_in_conversation_saves_both_turns() {
+        let dir = TempDir::new().unwrap();
+        let (rag_db, config_store, conversation_id) = setup(&dir).await;
+
+        let reply = reply_in_conversation(
+            &rag_db,
+            &config_store,
+            &ValidationLimits::default(),
+            chat_request(conversation_id, "hello"),
+        )
+        .await
+        .unwrap();
+        // The mock provider echoes the last message
+        assert_eq!(reply.role, "assistant");
+        assert_eq!(reply.content, "hello");
+
+        reply_in_conversation(
+            &rag_db,
+            &config_store,
+            &ValidationLimits::default(),
+            chat_request(conversation_id, "again"),
+        )
+        .await
+        .unwrap();
+
+        let messages = rag_db
+            .lock()
+            .await
+            .get_conversation_messages(conversation_id)
+            .await
+            .unwrap();
+        let turns: Vec<(&str, &str)> = messages
+            .iter()
+            .map(|m| (m.role.as_str(), m.content.as_str()))
+            .collect();
+        assert_eq!(
+            turns,
+            vec![
+                ("user", "hello"),
+                ("assistant", "hello"),
+                ("user", "again"),
+                ("assistant", "again"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reply_in_conversation_uses_settings_as_defaults() {
+        let dir = TempDir::new().unwrap();
+        let (rag_db, config_store, conversation_id) = setup(&dir).await;
+        let settings = ConversationSettings {
+            max_tokens: Some(3),
+            ..Default::default()
+        };
+        rag_db
+            .lock()
+            .await
+            .update_conversation_settings(conversation_id, &settings)
+            .await
+            .unwrap();
+        let message = "one two three four five six";
+
+        // The mock provider cuts its echo to the request's max_tokens
+        let reply = reply_in_conversation(
+            &rag_db,
+            &config_store,
+            &ValidationLimits::default(),
+            chat_request(conversation_id, message),
+        )
+        .await
+        .unwrap();
+        assert_eq!(reply.content, "one two three");
+
+        let reply = reply_in_conversation(
+            &rag_db,
+            &config_store,
+            &ValidationLimits::default(),
+            ChatInConversationRequest {
+                max_tokens: Some(100),
+                ..chat_request(conversation_id, message)
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(reply.content, message);
+    }
+
+    #[tokio::test]
+    async fn test_history_over_budget_is_summarized() {
+        let dir = TempDir::new().unwrap();
+        let (rag_db, config_store, conversation_id) = setup(&dir).await;
+        for content in ["one", "two", "three", "four"] {
+            reply_in_conversation(
+                &rag_db,
+                &config_store,
+                &ValidationLimits::default(),
+                chat_request(conversation_id, content),
+            )
+            .await
+            .unwrap();
+        }
+
+        let mut request = chat_request(conversation_id, "five");
+        request.max_history_tokens = Some(1);
+        let reply = reply_in_conversation(
+            &rag_db,
+            &config_store,
+            &ValidationLimits::default(),
+            request,
+        )
+        .await
+        .unwrap();
+        assert_eq!(reply.content, "five");
+
+        // The first exchange is summarized; the last six messages stay verbatim.
+        // The mock provider echoes the transcript it was asked to summarize.
+        let db = rag_db.lock().await;
+        let messages = db.get_conversation_messages(conversation_id).await.unwrap();
+        let conversation = db.get_conversation(conversation_id).await.unwrap();
+        assert_eq!(
+            conversation.history_summary.as_deref(),
+            Some("User: one\n\nAssistant: one")
+        );
+        assert_eq!(
+            conversation.summary_through_message_id,
+            Some(messages[1].id)
+        );
+
+        // Editing a summarized message drops the summary
+        db.edit_message(messages[0].id, "uno".to_string())
+            .await
+            .unwrap();
+        let conversation = db.get_conversation(conversation_id).await.unwrap();
+        assert_eq!(conversation.history_summary, None);
+        assert_eq!(conversation.summary_through_message_id, None);
+    }
+
+    #[tokio::test]
+    async fn test_regenerate_replaces_last_reply() {
+        let dir = TempDir::new().unwrap();
+        let (rag_db, config_store, conversation_id) = setup(&dir).await;
+
+        let result = regenerate_reply(&rag_db, &config_store, conversation_id, None).await;
+        assert!(matches!(result, Err(CommandError::Validation(_))));
+
+        let first = reply_in_conversation(
+            &rag_db,
+            &config_store,
+            &ValidationLimits::default(),
+            chat_request(conversation_id, "hello"),
+        )
+        .await
+        .unwrap();
+        let regenerated = regenerate_reply(&rag_db, &config_store, conversation_id, Some(0.5))
+            .await
+            .unwrap();
+        assert_ne!(regenerated.id, first.id);
+        assert_eq!(regenerated.content, "hello");
+
+        // A trailing user message is simply answered
+        rag_db
+            .lock()
+            .await
+            .add_message(conversation_id, "user".to_string(), "again".to_string())
+            .await
+            .unwrap();
+        regenerate_reply(&rag_db, &config_store, conversation_id, None)
+            .await
+            .unwrap();
+
+        let messages = rag_db
+            .lock()
+            .await
+            .get_conversation_messages(conversation_id)
+            .await
+            .unwrap();
+        let turns: Vec<(&str, &str)> = messages
+            .iter()
+            .map(|m| (m.role.as_str(), m.content.as_str()))
+            .collect();
+        assert_eq!(
+            turns,
+            vec![
+                ("user", "hello"),
+                ("assistant", "hello"),
+                ("user", "again"),
+                ("assistant", "again"),
+            ]
+        );
+        assert_eq!(messages[1].id, regenerated.id);
+    }
+
+    #[tokio::test]
+    async fn test_reply_in_trashed_conversation_is_rejected() {
+        let dir = TempDir::new().unwrap();
+        let (rag_db, config_store, conversation_id) = setup(&dir).await;
+        rag_db
+            .lock()
+            .await
+            .soft_delete_conversation(conversation_id)
+            .await
+            .unwrap();
+
+        let result = reply_in_conversation(
+            &rag_db,
+            &config_store,
+            &ValidationLimits::default(),
+            chat_request(conversation_id, "hi"),
+        )
+        .await;
+        assert!(matches!(result, Err(CommandError::Validation(_))));
     }
 }
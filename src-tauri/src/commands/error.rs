@@ -0,0 +1,242 @@
+use crate::config::ConfigError;
+use crate::llm_providers::ProviderError;
+use crate::rag::database::DatabaseError;
+use crate::rag::embeddings::EmbeddingError;
+use crate::rag::extract::ExtractError;
+use crate::rag::search::SearchError;
+use crate::validation::ValidationError;
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use std::time::Duration;
+use thiserror::Error;
+
+/// Error returned to the frontend inside `CommandResult`
+/// Serializes as `{code, message}` (plus `retry_after_secs` when rate limited); `code` is stable so the UI can react to the kind of
+/// failure (e.g. retry on "rate_limited") without parsing the message.
+#[derive(Error, Debug, Clone)]
+pub enum CommandError {
+    /// Invalid input from the caller
+    #[error("{0}")]
+    Validation(String),
+
+    /// The requested project, document, conversation, provider, etc. doesn't exist
+    #[error("{0}")]
+    NotFound(String),
+
+    /// The provider rejected the request for exceeding its rate limit
+    #[error("{message}")]
+    RateLimited {
+        message: String,
+        retry_after: Option<Duration>,
+    },
+
+    /// The provider rejected the API key
+    #[error("{0}")]
+    Unauthorized(String),
+
+    /// The provider request timed out
+    #[error("{0}")]
+    Timeout(String),
+
+    /// The provider could not be reached
+    #[error("{0}")]
+    Network(String),
+
+    /// The provider doesn't support the requested operation
+    #[error("{0}")]
+    Unsupported(String),
+
+    /// The provider returned an error
+    #[error("{0}")]
+    Provider(String),
+
+    /// Reading or writing the local database failed
+    #[error("{0}")]
+    Database(String),
+
+    /// Loading or saving configuration failed, or a provider is misconfigured
+    #[error("{0}")]
+    Config(String),
+
+    /// The caller cancelled the request before it finished
+    #[error("{0}")]
+    Cancelled(String),
+
+    /// Anything else
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl CommandError {
+    /// Machine-readable error code sent to the frontend
+    pub fn code(&self) -> &'static str {
+        match self {
+            CommandError::Validation(_) => "validation",
+            CommandError::NotFound(_) => "not_found",
+            CommandError::RateLimited { .. } => "rate_limited",
+            CommandError::Unauthorized(_) => "unauthorized",
+            CommandError::Timeout(_) => "timeout",
+            CommandError::Network(_) => "network",
+            CommandError::Unsupported(_) => "unsupported",
+            CommandError::Provider(_) => "provider",
+            CommandError::Database(_) => "database",
+            CommandError::Config(_) => "config",
+            CommandError::Cancelled(_) => "cancelled",
+            CommandError::Internal(_) => "internal",
+        }
+    }
+}
+
+impl Serialize for CommandError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("CommandError", 3)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", &self.to_string())?;
+        match self {
+            CommandError::RateLimited {
+                retry_after: Some(retry_after),
+                ..
+            } => state.serialize_field("retry_after_secs", &retry_after.as_secs_f64())?,
+            _ => state.skip_field("retry_after_secs")?,
+        }
+        state.end()
+    }
+}
+
+impl From<ValidationError> for CommandError {
+    fn from(e: ValidationError) -> Self {
+        CommandError::Validation(e.to_string())
+    }
+}
+
+impl From<ProviderError> for CommandError {
+    fn from(e: ProviderError) -> Self {
+        let message = e.to_string();
+        match e {
+            ProviderError::RateLimited { retry_after, .. } => CommandError::RateLimited {
+                message,
+                retry_after,
+            },
+            ProviderError::ApiError {
+                status: 401 | 403, ..
+            } => CommandError::Unauthorized(message),
+            ProviderError::ApiError { status: 408, .. } => CommandError::Timeout(message),
+            ProviderError::RequestError(e) if e.is_timeout() => CommandError::Timeout(message),
+            ProviderError::RequestError(e) if e.is_connect() => CommandError::Network(message),
+            ProviderError::UnsupportedFeature(_) => CommandError::Unsupported(message),
+            ProviderError::InvalidConfiguration(_) => CommandError::Config(message),
+            _ => CommandError::Provider(message),
+        }
+    }
+}
+
+impl From<DatabaseError> for CommandError {
+    fn from(e: DatabaseError) -> Self {
+        let message = e.to_string();
+        match e {
+            DatabaseError::ProjectNotFound(_)
+            | DatabaseError::DocumentNotFound(_)
+            | DatabaseError::ConversationNotFound(_)
+            | DatabaseError::MessageNotFound(_)
+            | DatabaseError::CanvasVersionNotFound(_)
+            | DatabaseError::SqlxError(sqlx::Error::RowNotFound) => CommandError::NotFound(message),
+            DatabaseError::DimensionMismatch { .. }
+            | DatabaseError::EmbeddingModelMismatch { .. }
+            | DatabaseError::InvalidExport(_) => CommandError::Validation(message),
+            _ => CommandError::Database(message),
+        }
+    }
+}
+
+impl From<ConfigError> for CommandError {
+    fn from(e: ConfigError) -> Self {
+        let message = e.to_string();
+        match e {
+            ConfigError::ProviderNotFound(_) => CommandError::NotFound(message),
+            ConfigError::InvalidExport(_) => CommandError::Validation(message),
+            _ => CommandError::Config(message),
+        }
+    }
+}
+
+impl From<EmbeddingError> for CommandError {
+    fn from(e: EmbeddingError) -> Self {
+        match e {
+            EmbeddingError::ProviderError(e) => e.into(),
+            EmbeddingError::NoProviderConfigured => CommandError::Config(e.to_string()),
+        }
+    }
+}
+
+impl From<ExtractError> for CommandError {
+    fn from(e: ExtractError) -> Self {
+        let message = e.to_string();
+        match e {
+            ExtractError::Io(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                CommandError::NotFound(message)
+            }
+            ExtractError::Io(_) => CommandError::Internal(message),
+            ExtractError::Pdf(_) | ExtractError::NotText | ExtractError::TooLarge { .. } => {
+                CommandError::Validation(message)
+            }
+        }
+    }
+}
+
+impl From<SearchError> for CommandError {
+    fn from(e: SearchError) -> Self {
+        match e {
+            SearchError::DatabaseError(e) => e.into(),
+            SearchError::RerankError(e) => e.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serializes_code_and_message() {
+        let error = CommandError::from(DatabaseError::ProjectNotFound(7));
+        let json = serde_json::to_value(&error).unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!({"code": "not_found", "message": "Project not found: 7"})
+        );
+    }
+
+    #[test]
+    fn test_provider_error_kinds() {
+        let rate_limited = CommandError::from(ProviderError::RateLimited {
+            retry_after: Some(Duration::from_secs(30)),
+            body: "slow down".to_string(),
+        });
+        assert_eq!(
+            serde_json::to_value(&rate_limited).unwrap(),
+            serde_json::json!({
+                "code": "rate_limited",
+                "message": "Rate limited: slow down",
+                "retry_after_secs": 30.0
+            })
+        );
+
+        let unauthorized = CommandError::from(ProviderError::ApiError {
+            status: 401,
+            body: "invalid key".to_string(),
+        });
+        assert_eq!(unauthorized.code(), "unauthorized");
+
+        let api = CommandError::from(ProviderError::ApiError {
+            status: 400,
+            body: "bad request".to_string(),
+        });
+        assert_eq!(api.code(), "provider");
+
+        let embedding = CommandError::from(EmbeddingError::ProviderError(
+            ProviderError::UnsupportedFeature("no embeddings".to_string()),
+        ));
+        assert_eq!(embedding.code(), "unsupported");
+    }
+}
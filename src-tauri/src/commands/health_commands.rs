@@ -0,0 +1,165 @@
+use crate::config::{ConfigStore, ProviderConfig};
+use crate::llm_providers::{create_provider, ProviderError};
+use crate::rag::RagDatabase;
+use futures::future::join_all;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+use super::config_commands::CommandResult;
+use super::CommandError;
+
+/// How long a provider gets to answer its credential check
+const PROVIDER_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Status of one subsystem; `error` says what's wrong when `ok` is false
+#[derive(Debug, Serialize)]
+pub struct SubsystemHealth {
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<CommandError>,
+}
+
+impl SubsystemHealth {
+    fn from_result<T, E: Into<CommandError>>(result: Result<T, E>) -> Self {
+        let error = result.err().map(Into::into);
+        Self {
+            ok: error.is_none(),
+            error,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProviderHealth {
+    pub id: String,
+    pub ok: bool,
+    pub latency_ms: u64,
+    /// False if the provider has no cheap credential check, so it wasn't contacted
+    pub checked: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<CommandError>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HealthReport {
+    pub db: SubsystemHealth,
+    /// Whether the master key can be read from the OS keychain;
+    /// always ok with a passphrase-derived key
+    pub keychain: SubsystemHealth,
+    pub config: SubsystemHealth,
+    /// Enabled providers; empty if the config couldn't be loaded
+    pub providers: Vec<ProviderHealth>,
+}
+
+/// Verify a provider's credentials, timing the round-trip
+async fn check_provider(config: &ProviderConfig) -> ProviderHealth {
+    let started = Instant::now();
+
+    let result = match create_provider(config) {
+        Ok(provider) => {
+            match tokio::time::timeout(PROVIDER_CHECK_TIMEOUT, provider.verify_credentials()).await
+            {
+                Ok(result) => result.map_err(CommandError::from),
+                Err(_) => Err(CommandError::Timeout(format!(
+                    "No answer within {} seconds",
+                    PROVIDER_CHECK_TIMEOUT.as_secs()
+                ))),
+            }
+        }
+        Err(e) => Err(e.into()),
+    };
+
+    let latency_ms = started.elapsed().as_millis() as u64;
+    let (ok, checked, error) = match result {
+        Ok(()) => (true, true, None),
+        Err(CommandError::Unsupported(_)) => (true, false, None),
+        Err(e) => (false, true, Some(e)),
+    };
+
+    ProviderHealth {
+        id: config.provider_id.clone(),
+        ok,
+        latency_ms,
+        checked,
+        error,
+    }
+}
+
+/// Report the status of the database, master key, config and every enabled provider
+/// Providers are checked concurrently with their free credential check, so this is
+/// cheap enough to call on startup.
+#[tauri::command]
+pub async fn health_check(
+    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    config_store: tauri::State<'_, Arc<Mutex<ConfigStore>>>,
+) -> Result<CommandResult<HealthReport>, String> {
+    let db = SubsystemHealth::from_result(rag_db.lock().await.ping().await);
+
+    let store = config_store.lock().await;
+    let keychain = SubsystemHealth::from_result(store.check_master_key());
+    let loaded = store.load();
+    drop(store); // Release lock before contacting providers
+
+    let enabled: Vec<ProviderConfig> = match &loaded {
+        Ok(config) => config
+            .providers
+            .values()
+            .filter(|p| p.enabled)
+            .cloned()
+            .map(ProviderConfig::with_env_key)
+            .map(|p| p.with_offline_mode(config.general.offline_mode))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    let config = SubsystemHealth::from_result(loaded);
+    let providers = join_all(enabled.iter().map(check_provider)).await;
+
+    Ok(CommandResult::ok(HealthReport {
+        db,
+        keychain,
+        config,
+        providers,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_support;
+    use crate::config::ProviderUpdate;
+
+    fn provider_config(provider_id: &str) -> ProviderConfig {
+        let dir = tempfile::TempDir::new().unwrap();
+        let store = test_support::config_store(&dir);
+        store
+            .update_provider(
+                provider_id.to_string(),
+                ProviderUpdate {
+                    enabled: Some(true),
+                    base_url: Some("http://localhost".to_string()),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        store.get_provider(provider_id).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_check_keyless_provider_is_ok_but_unchecked() {
+        // The mock provider takes no API key, so there is nothing to verify
+        let health = check_provider(&provider_config("mock")).await;
+        assert!(health.ok);
+        assert!(!health.checked);
+        assert!(health.error.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_check_provider_reports_errors() {
+        let health = check_provider(&provider_config("no-such-provider")).await;
+        assert!(!health.ok);
+        assert_eq!(health.id, "no-such-provider");
+        assert_eq!(health.error.map(|e| e.code()), Some("config"));
+    }
+}
@@ -0,0 +1,154 @@
+use crate::config::ConfigStore;
+use crate::rag::RagDatabase;
+use crate::validation::{self, LimitsState};
+use serde::Serialize;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use super::config_commands::CommandResult;
+use super::CommandError;
+
+#[derive(Debug, Serialize)]
+pub struct McpServerInfo {
+    /// Endpoint MCP clients POST JSON-RPC messages to
+    pub url: String,
+}
+
+/// The running MCP server, if any
+#[cfg(feature = "mcp")]
+struct RunningServer {
+    /// Distinguishes this server from ones started before or after it
+    generation: u64,
+    shutdown: tokio::sync::oneshot::Sender<()>,
+}
+
+#[cfg(feature = "mcp")]
+fn running_server() -> &'static Mutex<Option<RunningServer>> {
+    static RUNNING: std::sync::OnceLock<Mutex<Option<RunningServer>>> = std::sync::OnceLock::new();
+    RUNNING.get_or_init(|| Mutex::new(None))
+}
+
+/// Generation of the next MCP server started
+#[cfg(feature = "mcp")]
+static NEXT_GENERATION: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+#[cfg(feature = "mcp")]
+async fn start(
+    rag_db: Arc<Mutex<RagDatabase>>,
+    config_store: Arc<Mutex<ConfigStore>>,
+    limits: Arc<LimitsState>,
+    port: u16,
+) -> Result<McpServerInfo, CommandError> {
+    use crate::mcp::{serve, McpServer};
+
+    let mut running = running_server().lock().await;
+    if running.is_some() {
+        return Err(CommandError::Validation(
+            "The MCP server is already running".to_string(),
+        ));
+    }
+
+    // Only loopback is bound: the tools can spend the user's provider quota
+    let listener = tokio::net::TcpListener::bind(("127.0.0.1", port))
+        .await
+        .map_err(|e| CommandError::Internal(format!("Failed to listen on port {}: {}", port, e)))?;
+    let url = format!("http://127.0.0.1:{}/mcp", port);
+
+    let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel();
+    let generation = NEXT_GENERATION.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    let server = McpServer::new(rag_db, config_store, limits);
+    tokio::spawn(async move {
+        if let Err(e) = serve(server, listener, shutdown_rx).await {
+            tracing::error!("MCP server failed: {}", e);
+        }
+        // After a stop, the slot may already hold a server started since
+        let mut running = running_server().lock().await;
+        if running
+            .as_ref()
+            .is_some_and(|running| running.generation == generation)
+        {
+            *running = None;
+        }
+    });
+    *running = Some(RunningServer {
+        generation,
+        shutdown: shutdown_tx,
+    });
+
+    tracing::info!("MCP server listening on {}", url);
+    Ok(McpServerInfo { url })
+}
+
+#[cfg(not(feature = "mcp"))]
+async fn start(
+    _rag_db: Arc<Mutex<RagDatabase>>,
+    _config_store: Arc<Mutex<ConfigStore>>,
+    _limits: Arc<LimitsState>,
+    _port: u16,
+) -> Result<McpServerInfo, CommandError> {
+    Err(CommandError::Unsupported(
+        "This build has no MCP server; rebuild with the \"mcp\" feature".to_string(),
+    ))
+}
+
+/// Start serving RAG search and chat to MCP clients on 127.0.0.1:`port`
+#[tauri::command]
+pub async fn start_mcp_server(
+    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    config_store: tauri::State<'_, Arc<Mutex<ConfigStore>>>,
+    limits: tauri::State<'_, Arc<LimitsState>>,
+    port: u16,
+) -> Result<CommandResult<McpServerInfo>, String> {
+    if let Err(e) = validation::validate_port(port) {
+        return Ok(CommandResult::err(e));
+    }
+
+    match start(
+        rag_db.inner().clone(),
+        config_store.inner().clone(),
+        limits.inner().clone(),
+        port,
+    )
+    .await
+    {
+        Ok(info) => Ok(CommandResult::ok(info)),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// Stop the MCP server; does nothing if it isn't running
+#[tauri::command]
+pub async fn stop_mcp_server() -> Result<CommandResult<()>, String> {
+    #[cfg(feature = "mcp")]
+    if let Some(running) = running_server().lock().await.take() {
+        let _ = running.shutdown.send(());
+    }
+
+    Ok(CommandResult::ok(()))
+}
+
+#[cfg(all(test, feature = "mcp"))]
+mod tests {
+    use super::*;
+    use crate::commands::test_support;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_stopped_server_leaves_its_successor_running() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let (rag_db, config_store) = test_support::stores(&dir).await;
+        let limits = Arc::new(LimitsState::default());
+
+        start(rag_db.clone(), config_store.clone(), limits.clone(), 0)
+            .await
+            .unwrap();
+        stop_mcp_server().await.unwrap();
+        start(rag_db, config_store, limits, 0).await.unwrap();
+
+        // Give the first server time to finish shutting down
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(running_server().lock().await.is_some());
+
+        stop_mcp_server().await.unwrap();
+    }
+}
@@ -1,11 +1,25 @@
-pub mod config_commands;
-pub mod chat_commands;
-pub mod rag_commands;
 pub mod canvas_commands;
+pub mod chat_commands;
+pub mod config_commands;
 pub mod conversation_commands;
+pub mod error;
+pub mod health_commands;
+pub mod mcp_commands;
+pub mod rag_commands;
+pub mod request_commands;
+pub mod reset_commands;
+#[cfg(test)]
+pub(crate) mod test_support;
+pub mod usage_commands;
 
-pub use config_commands::*;
-pub use chat_commands::*;
-pub use rag_commands::*;
 pub use canvas_commands::*;
+pub use chat_commands::*;
+pub use config_commands::*;
 pub use conversation_commands::*;
+pub use error::CommandError;
+pub use health_commands::*;
+pub use mcp_commands::*;
+pub use rag_commands::*;
+pub use request_commands::*;
+pub use reset_commands::*;
+pub use usage_commands::*;
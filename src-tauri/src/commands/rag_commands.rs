@@ -1,12 +1,41 @@
 use crate::config::ConfigStore;
-use crate::llm_providers::{create_provider, ChatMessage, ChatRequest, ChatRole};
-use crate::rag::{chunk_text, search_similar, ChunkMatch, Document, EmbeddingService, Project, RagDatabase};
-use crate::validation;
+use crate::llm_providers::offline::is_offline_model_key;
+use crate::llm_providers::{
+    create_provider, ChatChunk, ChatMessage, ChatRequest, ChatRole, LlmProvider, Usage,
+};
+use crate::rag::chunking::{chunk_pages, ChunkConfig, ChunkPiece};
+use crate::rag::citations::extract_citations;
+use crate::rag::context::{build_context, fit_sources_to_budget, trim_history};
+use crate::rag::database::{DatabaseError, InsertOutcome, DEFAULT_PAGE_SIZE};
+use crate::rag::embeddings::{content_hash, DistanceMetric};
+use crate::rag::export::ProjectExport;
+use crate::rag::extract::extract_file_text;
+use crate::rag::hnsw;
+use crate::rag::search::{
+    expand_matches, explain_matches, rerank_candidates, search_multi_query, search_with_rerank,
+};
+use crate::rag::snippet::add_snippets;
+use crate::rag::{
+    chunk_text, search_similar, ChunkMatch, Document, DocumentStats, EmbeddingService, NewChunk,
+    Page, Project, ProjectEmbeddingInfo, ProjectStats, RagDatabase,
+};
+use crate::tokenizer::{count_prompt_tokens, count_provider_tokens, count_tokens};
+use crate::validation::{self, LimitsState, ValidationError, ValidationLimits};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::sync::Arc;
+use tauri::{AppHandle, Manager};
 use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
 
+use super::chat_commands::{
+    check_json_schema, check_schema_errors, emit_stream_end, forward_chunks, json_schema_validator,
+    spawn_cancellable_stream, stream_result, StreamEvent, StreamTiming, DEFAULT_STREAM_CAPACITY,
+};
 use super::config_commands::CommandResult;
+use super::request_commands::{register_optional, run_cancellable, RequestRegistry};
+use super::usage_commands::record_usage;
+use super::CommandError;
 
 /// Create a new RAG project
 #[tauri::command]
@@ -16,14 +45,14 @@ pub async fn create_project(
 ) -> Result<CommandResult<Project>, String> {
     // Validate project name
     if let Err(e) = validation::validate_name("project name", &name) {
-        return Ok(CommandResult::err(e.to_string()));
+        return Ok(CommandResult::err(e));
     }
 
     let db = rag_db.lock().await;
 
     match db.create_project(name).await {
         Ok(project) => Ok(CommandResult::ok(project)),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::err(e)),
     }
 }
 
@@ -36,7 +65,27 @@ pub async fn list_projects(
 
     match db.list_projects().await {
         Ok(projects) => Ok(CommandResult::ok(projects)),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// Rename a project
+#[tauri::command]
+pub async fn update_project(
+    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    project_id: i64,
+    name: String,
+) -> Result<CommandResult<Project>, String> {
+    // Validate project name
+    if let Err(e) = validation::validate_name("project name", &name) {
+        return Ok(CommandResult::err(e));
+    }
+
+    let db = rag_db.lock().await;
+
+    match db.update_project_name(project_id, name).await {
+        Ok(project) => Ok(CommandResult::ok(project)),
+        Err(e) => Ok(CommandResult::err(e)),
     }
 }
 
@@ -50,21 +99,186 @@ pub async fn delete_project(
 
     match db.delete_project(project_id).await {
         Ok(_) => Ok(CommandResult::ok(())),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::err(e)),
     }
 }
 
-/// List documents in a project
+/// List documents in a project, one page at a time
 #[tauri::command]
 pub async fn list_documents(
     rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
     project_id: i64,
-) -> Result<CommandResult<Vec<Document>>, String> {
+    limit: Option<i64>,
+    offset: Option<i64>,
+) -> Result<CommandResult<Page<Document>>, String> {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    let offset = offset.unwrap_or(0);
+    if let Err(e) = validation::validate_pagination(limit, offset) {
+        return Ok(CommandResult::err(e));
+    }
+
     let db = rag_db.lock().await;
 
-    match db.list_documents(project_id).await {
+    match db.list_documents(project_id, limit, offset).await {
         Ok(documents) => Ok(CommandResult::ok(documents)),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// Rename a document
+#[tauri::command]
+pub async fn rename_document(
+    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    document_id: i64,
+    name: String,
+) -> Result<CommandResult<Document>, String> {
+    // Validate document name
+    if let Err(e) = validation::validate_name("document name", &name) {
+        return Ok(CommandResult::err(e));
+    }
+
+    let db = rag_db.lock().await;
+
+    match db.rename_document(document_id, name).await {
+        Ok(document) => Ok(CommandResult::ok(document)),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// Get chunk count, character count, and embedding dimension for a document
+#[tauri::command]
+pub async fn get_document_stats(
+    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    document_id: i64,
+) -> Result<CommandResult<DocumentStats>, String> {
+    let db = rag_db.lock().await;
+
+    match db.get_document_stats(document_id).await {
+        Ok(stats) => Ok(CommandResult::ok(stats)),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// Get document count, chunk count, and approximate storage size for a project
+#[tauri::command]
+pub async fn get_project_stats(
+    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    project_id: i64,
+) -> Result<CommandResult<ProjectStats>, String> {
+    let db = rag_db.lock().await;
+
+    match db.get_project_stats(project_id).await {
+        Ok(stats) => Ok(CommandResult::ok(stats)),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// Write a project, its documents and their embedded chunks to a JSON file at `path`
+#[tauri::command]
+pub async fn export_project(
+    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    project_id: i64,
+    path: String,
+) -> Result<CommandResult<()>, String> {
+    if let Err(e) = validation::validate_not_empty("path", &path) {
+        return Ok(CommandResult::err(e));
+    }
+
+    let export = match rag_db.lock().await.export_project(project_id).await {
+        Ok(export) => export,
+        Err(e) => return Ok(CommandResult::err(e)),
+    };
+
+    let json = match serde_json::to_vec(&export) {
+        Ok(json) => json,
+        Err(e) => return Ok(CommandResult::err(CommandError::Internal(e.to_string()))),
+    };
+
+    match tokio::fs::write(&path, json).await {
+        Ok(_) => Ok(CommandResult::ok(())),
+        Err(e) => Ok(CommandResult::err(CommandError::Internal(format!(
+            "Failed to write {}: {}",
+            path, e
+        )))),
+    }
+}
+
+/// Recreate a project from a file written by `export_project`, under new ids
+/// The embeddings are imported as-is, so no provider is needed.
+#[tauri::command]
+pub async fn import_project(
+    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    path: String,
+) -> Result<CommandResult<Project>, String> {
+    if let Err(e) = validation::validate_not_empty("path", &path) {
+        return Ok(CommandResult::err(e));
+    }
+
+    let json = match tokio::fs::read(&path).await {
+        Ok(json) => json,
+        Err(e) => {
+            return Ok(CommandResult::err(CommandError::Validation(format!(
+                "Failed to read {}: {}",
+                path, e
+            ))))
+        }
+    };
+
+    let export: ProjectExport = match serde_json::from_slice(&json) {
+        Ok(export) => export,
+        Err(e) => {
+            return Ok(CommandResult::err(DatabaseError::InvalidExport(
+                e.to_string(),
+            )))
+        }
+    };
+
+    let db = rag_db.lock().await;
+
+    match db.import_project(&export).await {
+        Ok(project) => Ok(CommandResult::ok(project)),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// Get the embedding dimension and model a project's documents must be embedded with
+#[tauri::command]
+pub async fn get_project_embedding_info(
+    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    project_id: i64,
+) -> Result<CommandResult<ProjectEmbeddingInfo>, String> {
+    let db = rag_db.lock().await;
+
+    match db.get_project_embedding_info(project_id).await {
+        Ok(info) => Ok(CommandResult::ok(info)),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// Rebuild a project's approximate search index from its chunks, returning how many
+/// were indexed. Useful after many deletions, which leave the graph less efficient.
+#[tauri::command]
+pub async fn rebuild_index(
+    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    project_id: i64,
+) -> Result<CommandResult<usize>, String> {
+    // Build on a clone, so other commands can use the database meanwhile
+    let db = rag_db.lock().await.clone();
+
+    if let Err(e) = db.get_project(project_id).await {
+        return Ok(CommandResult::err(e));
+    }
+
+    match hnsw::rebuild_index(&db, project_id).await {
+        Ok(count) => Ok(CommandResult::ok(count)),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// Persist search indexes changed by new chunks; failures only cost a catch-up on next load
+async fn save_search_indexes(db: &RagDatabase) {
+    if let Err(e) = hnsw::save_indexes(db).await {
+        tracing::warn!("Failed to save search indexes: {}", e);
     }
 }
 
@@ -78,7 +292,7 @@ pub async fn delete_document(
 
     match db.delete_document(document_id).await {
         Ok(_) => Ok(CommandResult::ok(())),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::err(e)),
     }
 }
 
@@ -88,197 +302,1562 @@ pub struct AddDocumentRequest {
     pub name: String,
     pub content: String,
     pub provider_id: String, // Provider to use for embeddings
+    /// Strip control characters (other than tab and newline) from the content (default);
+    /// when false, content containing them is rejected
+    pub strip_control_chars: Option<bool>,
+    /// Add the document even if the project already has one with the same content
+    #[serde(default)]
+    pub force: bool,
 }
 
 #[derive(Debug, Serialize)]
 pub struct AddDocumentResponse {
     pub document_id: i64,
     pub chunks_created: usize,
+    /// The project already had this content; `document_id` is the existing document and
+    /// nothing was embedded
+    pub was_duplicate: bool,
+}
+
+#[derive(Clone, Serialize)]
+struct EmbeddingProgressEvent {
+    document_id: i64,
+    done: usize,
+    total: usize,
+}
+
+#[derive(Clone, Serialize)]
+struct EmbeddingCompleteEvent {
+    document_id: i64,
+    chunks_created: usize,
+}
+
+/// Embed a document's chunks, emitting an 'embedding-progress' event after each batch
+async fn embed_document_chunks(
+    app_handle: &AppHandle,
+    rag_db: &Arc<Mutex<RagDatabase>>,
+    embedding_service: &EmbeddingService,
+    document_id: i64,
+    chunks: &[String],
+) -> Result<Vec<Vec<f32>>, CommandError> {
+    embed_chunks(rag_db, embedding_service, chunks, |done, total| {
+        let _ = app_handle.emit_all(
+            "embedding-progress",
+            EmbeddingProgressEvent {
+                document_id,
+                done,
+                total,
+            },
+        );
+    })
+    .await
+}
+
+/// Embed chunks in batches, calling `on_progress(done, total)` after each batch
+/// Chunks whose text was embedded before by the same model are served from the
+/// embedding cache; only the rest are sent to the provider, and then cached.
+async fn embed_chunks(
+    rag_db: &Arc<Mutex<RagDatabase>>,
+    embedding_service: &EmbeddingService,
+    chunks: &[String],
+    on_progress: impl Fn(usize, usize) + Sync,
+) -> Result<Vec<Vec<f32>>, CommandError> {
+    let model = embedding_service.model_key();
+    let hashes: Vec<String> = chunks
+        .iter()
+        .map(|chunk| content_hash(&model, chunk))
+        .collect();
+
+    let mut embeddings = rag_db.lock().await.get_cached_embeddings(&hashes).await?;
+
+    // Each distinct uncached text is embedded once
+    let mut missing: Vec<(String, String)> = Vec::new();
+    for (hash, chunk) in hashes.iter().zip(chunks) {
+        if !embeddings.contains_key(hash) && !missing.iter().any(|(h, _)| h == hash) {
+            missing.push((hash.clone(), chunk.clone()));
+        }
+    }
+
+    let total = chunks.len();
+    let cached = total - missing.len();
+    tracing::debug!("{} of {} chunks found in embedding cache", cached, total);
+
+    let emit_progress = |done: usize| on_progress(done, total);
+
+    if missing.is_empty() {
+        emit_progress(total);
+    } else {
+        let texts = missing.iter().map(|(_, chunk)| chunk.clone()).collect();
+        let new_embeddings = embedding_service
+            .embed_texts_with_progress(texts, |done, _| emit_progress(cached + done))
+            .await?;
+
+        let entries: Vec<(String, Vec<f32>)> = missing
+            .into_iter()
+            .map(|(hash, _)| hash)
+            .zip(new_embeddings)
+            .collect();
+
+        rag_db
+            .lock()
+            .await
+            .cache_embeddings(&model, &entries)
+            .await?;
+
+        embeddings.extend(entries);
+    }
+
+    hashes
+        .iter()
+        .map(|hash| {
+            embeddings.get(hash).cloned().ok_or_else(|| {
+                CommandError::Provider("Provider returned fewer embeddings than chunks".to_string())
+            })
+        })
+        .collect()
 }
 
 /// Add a document to a project and generate embeddings
+/// Progress is reported via 'embedding-progress' events and completion via 'embedding-complete'
 #[tauri::command]
 pub async fn add_document(
+    app_handle: AppHandle,
     rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
     config_store: tauri::State<'_, Arc<Mutex<ConfigStore>>>,
+    limits: tauri::State<'_, Arc<LimitsState>>,
     request: AddDocumentRequest,
 ) -> Result<CommandResult<AddDocumentResponse>, String> {
     // Validate inputs
     if let Err(e) = validation::validate_name("document name", &request.name) {
-        return Ok(CommandResult::err(e.to_string()));
+        return Ok(CommandResult::err(e));
     }
-    if let Err(e) = validation::validate_document_content(&request.content) {
-        return Ok(CommandResult::err(e.to_string()));
+    let content = match sanitize_document_content(request.content, request.strip_control_chars) {
+        Ok(content) => content,
+        Err(e) => return Ok(CommandResult::err(e)),
+    };
+    if let Err(e) = validation::validate_document_content(&content, &limits.get()) {
+        return Ok(CommandResult::err(e));
     }
     if let Err(e) = validation::validate_not_empty("provider_id", &request.provider_id) {
-        return Ok(CommandResult::err(e.to_string()));
+        return Ok(CommandResult::err(e));
     }
 
     // Get provider for embeddings
     let store = config_store.lock().await;
-    let provider_config = match store.get_provider(&request.provider_id) {
+    let provider_config = match store.get_enabled_provider(&request.provider_id) {
         Ok(config) => config,
-        Err(e) => return Ok(CommandResult::err(e.to_string())),
+        Err(e) => return Ok(CommandResult::err(e)),
     };
     drop(store);
 
     let provider = match create_provider(&provider_config) {
         Ok(p) => p,
-        Err(e) => return Ok(CommandResult::err(e.to_string())),
+        Err(e) => return Ok(CommandResult::err(e)),
     };
 
     let embedding_service = EmbeddingService::new(provider);
 
-    // Create document
+    // Create document, first rejecting a provider whose vectors can't fit the project's
+    // when it reports its dimension up front (saves embedding the whole document)
     let db = rag_db.lock().await;
-    let document = match db
-        .create_document(request.project_id, request.name, None)
-        .await
+    if let Err(e) = check_embedding_model(&db, request.project_id, &embedding_service).await {
+        return Ok(CommandResult::err(e));
+    }
+    // Re-adding the same content returns the existing document instead of embedding it again
+    let document = match create_unless_duplicate(
+        &db,
+        request.project_id,
+        request.name,
+        None,
+        content.clone(),
+        request.force,
+    )
+    .await
     {
-        Ok(doc) => doc,
-        Err(e) => return Ok(CommandResult::err(e.to_string())),
+        Ok(InsertOutcome::Inserted(doc)) => doc,
+        Ok(InsertOutcome::Duplicate(existing)) => {
+            return Ok(CommandResult::ok(AddDocumentResponse {
+                document_id: existing.id,
+                chunks_created: 0,
+                was_duplicate: true,
+            }))
+        }
+        Err(e) => return Ok(CommandResult::err(e)),
     };
+    // Release the database while the provider embeds
+    drop(db);
 
     // Chunk the text
-    let chunks = chunk_text(&request.content, None);
+    let pieces = chunk_text(&content, None);
 
     // Generate embeddings for all chunks
-    let embeddings = match embedding_service.embed_texts(chunks.clone()).await {
+    let embeddings = match embed_document_chunks(
+        &app_handle,
+        &rag_db,
+        &embedding_service,
+        document.id,
+        &chunk_contents(&pieces),
+    )
+    .await
+    {
         Ok(emb) => emb,
-        Err(e) => return Ok(CommandResult::err(e.to_string())),
+        Err(e) => return Ok(CommandResult::err(e)),
     };
 
-    // Insert chunks with embeddings
-    let mut chunks_created = 0;
-    for (idx, (chunk_text, embedding)) in chunks.iter().zip(embeddings.iter()).enumerate() {
-        match db
-            .insert_chunk(
-                document.id,
-                request.project_id,
-                chunk_text.clone(),
-                embedding.clone(),
-                idx as i32,
-            )
-            .await
-        {
-            Ok(_) => chunks_created += 1,
-            Err(e) => {
-                tracing::error!("Failed to insert chunk {}: {}", idx, e);
+    // Insert chunks with embeddings, unless they don't fit the project's existing ones
+    let db = rag_db.lock().await;
+    let chunks_created = match db
+        .replace_document_chunks(
+            document.id,
+            None,
+            new_chunks(pieces, embeddings),
+            Some(&embedding_service.model_key()),
+        )
+        .await
+    {
+        Ok(chunks_created) => chunks_created,
+        Err(e) => {
+            if let Err(e) = db.delete_document(document.id).await {
+                tracing::error!("Failed to remove document {}: {}", document.id, e);
             }
+            return Ok(CommandResult::err(e));
         }
-    }
+    };
 
+    save_search_indexes(&db).await;
     drop(db);
 
+    let _ = app_handle.emit_all(
+        "embedding-complete",
+        EmbeddingCompleteEvent {
+            document_id: document.id,
+            chunks_created,
+        },
+    );
+
     Ok(CommandResult::ok(AddDocumentResponse {
         document_id: document.id,
         chunks_created,
+        was_duplicate: false,
     }))
 }
 
+/// Create a document to be embedded, or unless `force` find the project's document with
+/// the same content instead
+async fn create_unless_duplicate(
+    db: &RagDatabase,
+    project_id: i64,
+    name: String,
+    source_path: Option<String>,
+    content: String,
+    force: bool,
+) -> Result<InsertOutcome<Document>, DatabaseError> {
+    if force {
+        db.create_document(project_id, name, source_path, Some(content))
+            .await
+            .map(InsertOutcome::Inserted)
+    } else {
+        db.create_document_unless_duplicate(project_id, name, source_path, content)
+            .await
+    }
+}
+
+/// Reject a provider whose vectors can't go with the project's, before embedding anything:
+/// offline embeddings in a project indexed online or the reverse, or a dimension it
+/// reports up front that differs from the project's
+async fn check_embedding_model(
+    db: &RagDatabase,
+    project_id: i64,
+    embedding_service: &EmbeddingService,
+) -> Result<(), CommandError> {
+    let info = db.get_project_embedding_info(project_id).await?;
+    check_project_embedding(
+        &info,
+        &embedding_service.model_key(),
+        embedding_service.dimension(),
+    )
+}
+
+/// Check embeddings of `model_key`, with `dimension` if known, against a project's
+/// Offline hash embeddings never mix with a real model's, even at the same dimension:
+/// a project created in offline mode is only used offline, and any other only online.
+fn check_project_embedding(
+    info: &ProjectEmbeddingInfo,
+    model_key: &str,
+    dimension: Option<usize>,
+) -> Result<(), CommandError> {
+    let Some(expected) = info.dimension else {
+        return Ok(());
+    };
+    let project_offline = info.model.as_deref().is_some_and(is_offline_model_key);
+    if is_offline_model_key(model_key) && !project_offline {
+        return Err(CommandError::Validation(format!(
+            "Project {} is indexed with {}, so it can't be used in offline mode. Turn offline mode off to search it or add documents",
+            info.project_id,
+            info.model.as_deref().unwrap_or("an online embedding model")
+        )));
+    }
+    if project_offline && !is_offline_model_key(model_key) {
+        return Err(CommandError::Validation(format!(
+            "Project {} was indexed in offline mode, so it can only be used with offline mode on",
+            info.project_id
+        )));
+    }
+    match dimension {
+        Some(got) if got != expected => {
+            Err(DatabaseError::DimensionMismatch { expected, got }.into())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Maximum number of documents in one `add_documents` call
+const MAX_BATCH_DOCUMENTS: usize = 1000;
+
 #[derive(Debug, Deserialize)]
-pub struct RagSearchRequest {
+pub struct NewDocument {
+    pub name: String,
+    pub content: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AddDocumentsRequest {
     pub project_id: i64,
-    pub query: String,
-    pub provider_id: String,
-    pub top_k: usize,
+    pub provider_id: String, // Provider to use for embeddings
+    pub documents: Vec<NewDocument>,
+    /// Same as `AddDocumentRequest::strip_control_chars`, applied to every document
+    pub strip_control_chars: Option<bool>,
 }
 
-/// Search for relevant chunks
+/// Outcome of one document of a batch; `error` is set if it wasn't added
+#[derive(Debug, Clone, Serialize)]
+pub struct AddDocumentResult {
+    pub name: String,
+    pub document_id: Option<i64>,
+    pub chunks_created: usize,
+    /// The content was already in the project; `document_id` is the existing document
+    pub was_duplicate: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+struct BatchEmbeddingProgressEvent {
+    done: usize,
+    total: usize,
+}
+
+#[derive(Clone, Serialize)]
+struct DocumentIngestedEvent {
+    /// Documents of the batch handled so far, this one included
+    done: usize,
+    total: usize,
+    result: AddDocumentResult,
+}
+
+/// How each batch document is checked before it is chunked
+pub(crate) struct DocumentChecks {
+    /// Same as `AddDocumentRequest::strip_control_chars`
+    pub strip_control_chars: Option<bool>,
+    pub limits: ValidationLimits,
+}
+
+/// A batch document's sanitized content and chunks, or why it was rejected
+type PreparedDocument = Result<(String, Vec<ChunkPiece>), ValidationError>;
+
+/// Embed one batch document's chunks and store it with them in one transaction, unless
+/// the project already has the same content
+async fn ingest_document(
+    rag_db: &Arc<Mutex<RagDatabase>>,
+    embedding_service: &EmbeddingService,
+    project_id: i64,
+    name: String,
+    content: String,
+    pieces: Vec<ChunkPiece>,
+    on_embedding_progress: impl Fn(usize, usize) + Sync,
+) -> Result<InsertOutcome<(Document, usize)>, CommandError> {
+    let embeddings = embed_chunks(
+        rag_db,
+        embedding_service,
+        &chunk_contents(&pieces),
+        on_embedding_progress,
+    )
+    .await?;
+    let chunks = new_chunks(pieces, embeddings);
+    let model_key = embedding_service.model_key();
+
+    let db = rag_db.lock().await;
+    Ok(db
+        .create_document_with_chunks(
+            project_id,
+            name,
+            None,
+            Some(content),
+            chunks,
+            Some(&model_key),
+        )
+        .await?)
+}
+
+/// Chunk, embed and store a batch of documents
+/// Documents are embedded and stored one at a time, so only one document's vectors are
+/// held at once. A document whose content the project already has is not stored again.
+/// A document that is invalid, fails to embed or fails to store gets an error in its
+/// result without affecting the others. `on_embedding_progress(done, total)`
+/// counts chunks across the batch; `on_document(done, result)` is called as each
+/// document is handled.
+pub(crate) async fn ingest_documents(
+    rag_db: &Arc<Mutex<RagDatabase>>,
+    embedding_service: &EmbeddingService,
+    project_id: i64,
+    documents: Vec<NewDocument>,
+    checks: DocumentChecks,
+    on_embedding_progress: impl Fn(usize, usize) + Sync,
+    on_document: impl Fn(usize, &AddDocumentResult) + Sync,
+) -> Result<Vec<AddDocumentResult>, CommandError> {
+    check_embedding_model(&*rag_db.lock().await, project_id, embedding_service).await?;
+
+    // Validate and chunk each document; invalid ones are reported rather than embedded
+    let prepared: Vec<(String, PreparedDocument)> = documents
+        .into_iter()
+        .map(|document| {
+            let chunked = validation::validate_name("document name", &document.name)
+                .and_then(|_| {
+                    sanitize_document_content(document.content, checks.strip_control_chars)
+                })
+                .and_then(|content| {
+                    validation::validate_document_content(&content, &checks.limits)?;
+                    let chunks = chunk_text(&content, None);
+                    Ok((content, chunks))
+                });
+            (document.name, chunked)
+        })
+        .collect();
+
+    let total_chunks: usize = prepared
+        .iter()
+        .filter_map(|(_, chunked)| chunked.as_ref().ok())
+        .map(|(_, pieces)| pieces.len())
+        .sum();
+    let mut embedded = 0;
+
+    let mut results = Vec::with_capacity(prepared.len());
+    for (name, chunked) in prepared {
+        let ingested = match chunked {
+            Ok((content, pieces)) => {
+                let count = pieces.len();
+                let ingested = ingest_document(
+                    rag_db,
+                    embedding_service,
+                    project_id,
+                    name.clone(),
+                    content,
+                    pieces,
+                    |done, _| on_embedding_progress(embedded + done, total_chunks),
+                )
+                .await;
+                embedded += count;
+                // A failed document's chunks still count as handled
+                if ingested.is_err() {
+                    on_embedding_progress(embedded, total_chunks);
+                }
+                ingested
+            }
+            Err(e) => Err(e.into()),
+        };
+        let result = match ingested {
+            Ok(InsertOutcome::Inserted((document, chunks_created))) => AddDocumentResult {
+                name,
+                document_id: Some(document.id),
+                chunks_created,
+                was_duplicate: false,
+                error: None,
+            },
+            Ok(InsertOutcome::Duplicate(existing)) => AddDocumentResult {
+                name,
+                document_id: Some(existing.id),
+                chunks_created: 0,
+                was_duplicate: true,
+                error: None,
+            },
+            Err(e) => AddDocumentResult {
+                name,
+                document_id: None,
+                chunks_created: 0,
+                was_duplicate: false,
+                error: Some(e.to_string()),
+            },
+        };
+        results.push(result);
+        on_document(results.len(), results.last().expect("just pushed"));
+    }
+
+    save_search_indexes(&*rag_db.lock().await).await;
+
+    Ok(results)
+}
+
+/// Add several documents to a project, creating the provider once and embedding each
+/// document in turn. Progress is reported via 'batch-embedding-progress' events (chunks
+/// embedded) and a 'document-ingested' event per document.
 #[tauri::command]
-pub async fn rag_search(
+pub async fn add_documents(
+    app_handle: AppHandle,
     rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
     config_store: tauri::State<'_, Arc<Mutex<ConfigStore>>>,
-    request: RagSearchRequest,
-) -> Result<CommandResult<Vec<ChunkMatch>>, String> {
+    limits: tauri::State<'_, Arc<LimitsState>>,
+    request: AddDocumentsRequest,
+) -> Result<CommandResult<Vec<AddDocumentResult>>, String> {
     // Validate inputs
-    if let Err(e) = validation::validate_query(&request.query) {
-        return Ok(CommandResult::err(e.to_string()));
-    }
-    if let Err(e) = validation::validate_top_k(request.top_k) {
-        return Ok(CommandResult::err(e.to_string()));
-    }
     if let Err(e) = validation::validate_not_empty("provider_id", &request.provider_id) {
-        return Ok(CommandResult::err(e.to_string()));
+        return Ok(CommandResult::err(e));
+    }
+    if let Err(e) =
+        validation::validate_range("documents", request.documents.len(), 1, MAX_BATCH_DOCUMENTS)
+    {
+        return Ok(CommandResult::err(e));
     }
 
-    // Get provider for query embedding
+    // Get provider for embeddings
     let store = config_store.lock().await;
-    let provider_config = match store.get_provider(&request.provider_id) {
+    let provider_config = match store.get_enabled_provider(&request.provider_id) {
         Ok(config) => config,
-        Err(e) => return Ok(CommandResult::err(e.to_string())),
+        Err(e) => return Ok(CommandResult::err(e)),
     };
     drop(store);
 
     let provider = match create_provider(&provider_config) {
         Ok(p) => p,
-        Err(e) => return Ok(CommandResult::err(e.to_string())),
+        Err(e) => return Ok(CommandResult::err(e)),
     };
 
     let embedding_service = EmbeddingService::new(provider);
+    let total = request.documents.len();
 
-    // Generate query embedding
-    let query_embedding = match embedding_service.embed_text(request.query).await {
-        Ok(emb) => emb,
-        Err(e) => return Ok(CommandResult::err(e.to_string())),
-    };
-
-    // Search
-    let db = rag_db.lock().await;
-    match search_similar(&db, request.project_id, query_embedding, request.top_k).await {
+    match ingest_documents(
+        &rag_db,
+        &embedding_service,
+        request.project_id,
+        request.documents,
+        DocumentChecks {
+            strip_control_chars: request.strip_control_chars,
+            limits: limits.get(),
+        },
+        |done, total| {
+            let _ = app_handle.emit_all(
+                "batch-embedding-progress",
+                BatchEmbeddingProgressEvent { done, total },
+            );
+        },
+        |done, result| {
+            let _ = app_handle.emit_all(
+                "document-ingested",
+                DocumentIngestedEvent {
+                    done,
+                    total,
+                    result: result.clone(),
+                },
+            );
+        },
+    )
+    .await
+    {
         Ok(results) => Ok(CommandResult::ok(results)),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::err(e)),
     }
 }
 
+/// The text of each chunk, as sent for embedding
+fn chunk_contents(pieces: &[ChunkPiece]) -> Vec<String> {
+    pieces.iter().map(|piece| piece.content.clone()).collect()
+}
+
+/// Chunks to store, from chunk pieces and their embeddings
+fn new_chunks(pieces: Vec<ChunkPiece>, embeddings: Vec<Vec<f32>>) -> Vec<NewChunk> {
+    pieces
+        .into_iter()
+        .zip(embeddings)
+        .map(|(piece, embedding)| NewChunk {
+            content: piece.content,
+            embedding,
+            page_number: piece.page_number,
+            start_offset: Some(piece.start_offset as i64),
+            end_offset: Some(piece.end_offset as i64),
+        })
+        .collect()
+}
+
 #[derive(Debug, Deserialize)]
-pub struct RagChatRequest {
+pub struct AddDocumentFromFileRequest {
     pub project_id: i64,
-    pub query: String,
-    pub provider_id: String,
-    pub model: String,
-    pub top_k: usize,
-    pub temperature: Option<f32>,
-    pub max_tokens: Option<u32>,
+    /// File to ingest; PDFs have their text extracted, anything else is read as UTF-8 text
+    pub path: String,
+    pub provider_id: String, // Provider to use for embeddings
+    /// Document name; defaults to the file name
+    pub name: Option<String>,
+    /// Same as `AddDocumentRequest::force`
+    #[serde(default)]
+    pub force: bool,
 }
 
-#[derive(Debug, Serialize)]
-pub struct RagChatResponse {
-    pub response: String,
-    pub sources: Vec<ChunkMatch>,
-    pub model: String,
-}
+/// Extract, chunk, embed and store a file as a new document
+/// The document is removed again if embedding or storing its chunks fails.
+/// `on_embedding_progress(document_id, done, total)` is called after each batch.
+async fn add_file_document(
+    rag_db: &Arc<Mutex<RagDatabase>>,
+    config_store: &Arc<Mutex<ConfigStore>>,
+    limits: &ValidationLimits,
+    request: AddDocumentFromFileRequest,
+    on_embedding_progress: impl Fn(i64, usize, usize) + Sync,
+) -> Result<AddDocumentResponse, CommandError> {
+    validation::validate_not_empty("path", &request.path)?;
+    validation::validate_not_empty("provider_id", &request.provider_id)?;
+    let path = std::path::PathBuf::from(&request.path);
+    let name = match request.name {
+        Some(name) => name,
+        None => path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+    };
+    validation::validate_name("document name", &name)?;
 
-/// Chat with RAG context
-#[tauri::command]
-pub async fn rag_chat(
-    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
-    config_store: tauri::State<'_, Arc<Mutex<ConfigStore>>>,
-    request: RagChatRequest,
-) -> Result<CommandResult<RagChatResponse>, String> {
-    // Validate inputs
-    if let Err(e) = validation::validate_query(&request.query) {
-        return Ok(CommandResult::err(e.to_string()));
-    }
-    if let Err(e) = validation::validate_top_k(request.top_k) {
-        return Ok(CommandResult::err(e.to_string()));
-    }
-    if let Err(e) = validation::validate_not_empty("provider_id", &request.provider_id) {
-        return Ok(CommandResult::err(e.to_string()));
-    }
-    if let Err(e) = validation::validate_not_empty("model", &request.model) {
-        return Ok(CommandResult::err(e.to_string()));
-    }
-    if let Some(temp) = request.temperature {
-        if let Err(e) = validation::validate_temperature(temp) {
-            return Ok(CommandResult::err(e.to_string()));
-        }
-    }
-    if let Some(max_tokens) = request.max_tokens {
-        if let Err(e) = validation::validate_max_tokens(max_tokens) {
-            return Ok(CommandResult::err(e.to_string()));
-        }
-    }
+    // PDF parsing is CPU-bound; keep it off the async workers
+    let max_bytes = limits.max_document_bytes;
+    let text = tokio::task::spawn_blocking(move || extract_file_text(&path, max_bytes))
+        .await
+        .map_err(|e| CommandError::Internal(format!("Text extraction failed: {}", e)))??;
+    validation::validate_document_content(&text, limits)?;
+
+    // Get provider for embeddings
+    let provider_config = config_store
+        .lock()
+        .await
+        .get_enabled_provider(&request.provider_id)?;
+    let embedding_service = EmbeddingService::new(create_provider(&provider_config)?);
+
+    let db = rag_db.lock().await;
+    check_embedding_model(&db, request.project_id, &embedding_service).await?;
+    let document = match create_unless_duplicate(
+        &db,
+        request.project_id,
+        name,
+        Some(request.path),
+        text.clone(),
+        request.force,
+    )
+    .await?
+    {
+        InsertOutcome::Inserted(document) => document,
+        InsertOutcome::Duplicate(existing) => {
+            return Ok(AddDocumentResponse {
+                document_id: existing.id,
+                chunks_created: 0,
+                was_duplicate: true,
+            })
+        }
+    };
+    drop(db);
+
+    let pieces = chunk_pages(&text, None);
+    let chunks = chunk_contents(&pieces);
+    let stored = match embed_chunks(rag_db, &embedding_service, &chunks, |done, total| {
+        on_embedding_progress(document.id, done, total)
+    })
+    .await
+    {
+        Ok(embeddings) => rag_db
+            .lock()
+            .await
+            .replace_document_chunks(
+                document.id,
+                None,
+                new_chunks(pieces, embeddings),
+                Some(&embedding_service.model_key()),
+            )
+            .await
+            .map_err(CommandError::from),
+        Err(e) => Err(e),
+    };
+
+    let db = rag_db.lock().await;
+    let chunks_created = match stored {
+        Ok(chunks_created) => chunks_created,
+        Err(e) => {
+            if let Err(e) = db.delete_document(document.id).await {
+                tracing::error!("Failed to remove document {}: {}", document.id, e);
+            }
+            return Err(e);
+        }
+    };
+    save_search_indexes(&db).await;
+
+    Ok(AddDocumentResponse {
+        document_id: document.id,
+        chunks_created,
+        was_duplicate: false,
+    })
+}
+
+/// Add a document from a file on disk, extracting the text of PDFs
+/// Chunks of a PDF record the page they come from. Progress is reported via
+/// 'embedding-progress' events and completion via 'embedding-complete'.
+#[tauri::command]
+pub async fn add_document_from_file(
+    app_handle: AppHandle,
+    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    config_store: tauri::State<'_, Arc<Mutex<ConfigStore>>>,
+    limits: tauri::State<'_, Arc<LimitsState>>,
+    request: AddDocumentFromFileRequest,
+) -> Result<CommandResult<AddDocumentResponse>, String> {
+    match add_file_document(
+        &rag_db,
+        &config_store,
+        &limits.get(),
+        request,
+        |document_id, done, total| {
+            let _ = app_handle.emit_all(
+                "embedding-progress",
+                EmbeddingProgressEvent {
+                    document_id,
+                    done,
+                    total,
+                },
+            );
+        },
+    )
+    .await
+    {
+        Ok(response) if response.was_duplicate => Ok(CommandResult::ok(response)),
+        Ok(response) => {
+            let _ = app_handle.emit_all(
+                "embedding-complete",
+                EmbeddingCompleteEvent {
+                    document_id: response.document_id,
+                    chunks_created: response.chunks_created,
+                },
+            );
+            Ok(CommandResult::ok(response))
+        }
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// Strip control characters from document content, or reject it if they aren't to be stripped
+/// Binary data pasted as text would otherwise be stored and embedded as-is.
+fn sanitize_document_content(
+    content: String,
+    strip: Option<bool>,
+) -> Result<String, ValidationError> {
+    if strip.unwrap_or(true) {
+        return Ok(validation::strip_control_chars(&content));
+    }
+    validation::validate_no_control_chars("content", &content)?;
+    Ok(content)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateDocumentContentRequest {
+    pub document_id: i64,
+    /// New document text; None re-indexes the stored original text
+    pub content: Option<String>,
+    pub provider_id: String, // Provider to use for embeddings
+    pub chunk_config: Option<ChunkConfig>,
+    /// Same as `AddDocumentRequest::strip_control_chars`, applied to new content
+    pub strip_control_chars: Option<bool>,
+}
+
+/// Re-chunk and re-embed a document under the same document id, using new content
+/// if given or the stored original text otherwise
+/// Progress is reported via 'embedding-progress' events and completion via 'embedding-complete'
+#[tauri::command]
+pub async fn update_document_content(
+    app_handle: AppHandle,
+    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    config_store: tauri::State<'_, Arc<Mutex<ConfigStore>>>,
+    limits: tauri::State<'_, Arc<LimitsState>>,
+    request: UpdateDocumentContentRequest,
+) -> Result<CommandResult<AddDocumentResponse>, String> {
+    // Validate inputs
+    let content = match request
+        .content
+        .map(|content| sanitize_document_content(content, request.strip_control_chars))
+        .transpose()
+    {
+        Ok(content) => content,
+        Err(e) => return Ok(CommandResult::err(e)),
+    };
+    if let Some(content) = &content {
+        if let Err(e) = validation::validate_document_content(content, &limits.get()) {
+            return Ok(CommandResult::err(e));
+        }
+    }
+    if let Err(e) = validation::validate_not_empty("provider_id", &request.provider_id) {
+        return Ok(CommandResult::err(e));
+    }
+    if let Some(config) = &request.chunk_config {
+        if let Err(e) = validation::validate_chunk_config(config.chunk_size, config.overlap) {
+            return Ok(CommandResult::err(e));
+        }
+    }
+
+    // Get provider for embeddings
+    let store = config_store.lock().await;
+    let provider_config = match store.get_enabled_provider(&request.provider_id) {
+        Ok(config) => config,
+        Err(e) => return Ok(CommandResult::err(e)),
+    };
+    drop(store);
+
+    let provider = match create_provider(&provider_config) {
+        Ok(p) => p,
+        Err(e) => return Ok(CommandResult::err(e)),
+    };
+
+    let embedding_service = EmbeddingService::new(provider);
+
+    // Look the document up first, so a missing one fails before anything is embedded
+    let db = rag_db.lock().await;
+    let document = match db.get_document(request.document_id).await {
+        Ok(document) => document,
+        Err(e) => return Ok(CommandResult::err(e)),
+    };
+    if let Err(e) = check_embedding_model(&db, document.project_id, &embedding_service).await {
+        return Ok(CommandResult::err(e));
+    }
+    let text = match &content {
+        Some(content) => content.clone(),
+        None => match db.get_document_content(request.document_id).await {
+            Ok(Some(content)) => content,
+            Ok(None) => {
+                return Ok(CommandResult::err(CommandError::Validation(format!(
+                    "Document {} has no stored content; provide content to re-index it",
+                    request.document_id
+                ))))
+            }
+            Err(e) => return Ok(CommandResult::err(e)),
+        },
+    };
+    drop(db);
+
+    // Embed before touching the database, so a provider failure leaves the old chunks intact
+    let pieces = chunk_pages(&text, request.chunk_config);
+    let embeddings = match embed_document_chunks(
+        &app_handle,
+        &rag_db,
+        &embedding_service,
+        request.document_id,
+        &chunk_contents(&pieces),
+    )
+    .await
+    {
+        Ok(emb) => emb,
+        Err(e) => return Ok(CommandResult::err(e)),
+    };
+
+    let db = rag_db.lock().await;
+    match db
+        .replace_document_chunks(
+            request.document_id,
+            content,
+            new_chunks(pieces, embeddings),
+            Some(&embedding_service.model_key()),
+        )
+        .await
+    {
+        Ok(chunks_created) => {
+            save_search_indexes(&db).await;
+
+            let _ = app_handle.emit_all(
+                "embedding-complete",
+                EmbeddingCompleteEvent {
+                    document_id: request.document_id,
+                    chunks_created,
+                },
+            );
+
+            Ok(CommandResult::ok(AddDocumentResponse {
+                document_id: request.document_id,
+                chunks_created,
+                was_duplicate: false,
+            }))
+        }
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// Remove all cached chunk embeddings, returning the number of entries removed
+#[tauri::command]
+pub async fn clear_embedding_cache(
+    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+) -> Result<CommandResult<u64>, String> {
+    let db = rag_db.lock().await;
+
+    match db.clear_embedding_cache().await {
+        Ok(removed) => Ok(CommandResult::ok(removed)),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RagSearchRequest {
+    pub project_id: i64,
+    pub query: String,
+    pub provider_id: String,
+    pub top_k: usize,
+    /// "cosine" (default), "dot_product" or "euclidean"
+    pub metric: Option<String>,
+    /// Results scoring below this are dropped, even if fewer than `top_k` remain
+    pub min_similarity: Option<f32>,
+    /// Neighboring chunks on either side of each match to merge into `expanded_content`
+    pub context_window: Option<usize>,
+    /// Candidate list size for large, indexed projects: higher is slower but finds more
+    /// of the true nearest chunks. Ignored when the project is searched exactly.
+    pub ef_search: Option<usize>,
+    /// Attach ranking diagnostics to each result
+    #[serde(default)]
+    pub explain: bool,
+    /// Only search chunks of these documents
+    pub document_ids: Option<Vec<i64>>,
+    /// Only search documents whose name matches this SQL `LIKE` pattern (e.g. "%v1%");
+    /// combined with `document_ids`, a document must satisfy both
+    pub name_like: Option<String>,
+}
+
+/// Validate the optional metric, similarity threshold and context window of a search
+fn validate_search_options(
+    metric: Option<&str>,
+    min_similarity: Option<f32>,
+    context_window: Option<usize>,
+) -> Result<(), ValidationError> {
+    if let Some(context_window) = context_window {
+        validation::validate_context_window(context_window)?;
+    }
+    if let Some(metric) = metric {
+        validation::validate_metric(metric)?;
+    }
+    // Only cosine similarity has a fixed range
+    if let Some(min_similarity) = min_similarity {
+        if metric.unwrap_or("cosine") == "cosine" {
+            validation::validate_range("min_similarity", min_similarity, -1.0, 1.0)?;
+        }
+    }
+    Ok(())
+}
+
+/// Validate the optional document filter of a search
+fn validate_document_filter(
+    document_ids: Option<&[i64]>,
+    name_like: Option<&str>,
+) -> Result<(), ValidationError> {
+    if let Some(document_ids) = document_ids {
+        validation::validate_range("document_ids count", document_ids.len(), 1, 10_000)?;
+    }
+    if let Some(name_like) = name_like {
+        validation::validate_not_empty("name_like", name_like)?;
+    }
+    Ok(())
+}
+
+/// Resolve a search's document filter to the ids of the documents to search
+/// None means the whole project; an empty list means no document matched.
+async fn resolve_document_filter(
+    db: &RagDatabase,
+    project_id: i64,
+    document_ids: Option<Vec<i64>>,
+    name_like: Option<&str>,
+) -> Result<Option<Vec<i64>>, CommandError> {
+    let Some(name_like) = name_like else {
+        return Ok(document_ids);
+    };
+    let mut named = db.find_document_ids(project_id, name_like).await?;
+    if let Some(document_ids) = document_ids {
+        named.retain(|id| document_ids.contains(id));
+    }
+    Ok(Some(named))
+}
+
+/// Embed a search query with the given provider
+async fn embed_query(
+    rag_db: &Arc<Mutex<RagDatabase>>,
+    config_store: &Arc<Mutex<ConfigStore>>,
+    project_id: i64,
+    provider_id: &str,
+    query: String,
+) -> Result<Vec<f32>, CommandError> {
+    // Get provider for query embedding
+    let store = config_store.lock().await;
+    let provider_config = store.get_enabled_provider(provider_id)?;
+    drop(store);
+
+    let provider = create_provider(&provider_config)?;
+
+    let embedding_service = EmbeddingService::new(provider);
+
+    let embedding = embedding_service.embed_query(provider_id, &query).await?;
+    let info = rag_db
+        .lock()
+        .await
+        .get_project_embedding_info(project_id)
+        .await?;
+    check_project_embedding(&info, &embedding_service.model_key(), Some(embedding.len()))?;
+    Ok(embedding)
+}
+
+/// Validate a RAG search request
+pub(crate) fn validate_search_request(
+    request: &RagSearchRequest,
+    limits: &ValidationLimits,
+) -> Result<(), ValidationError> {
+    validation::validate_query(&request.query, limits)?;
+    validation::validate_top_k(request.top_k, limits)?;
+    validation::validate_not_empty("provider_id", &request.provider_id)?;
+    if let Some(ef_search) = request.ef_search {
+        validation::validate_ef_search(ef_search)?;
+    }
+    validate_document_filter(
+        request.document_ids.as_deref(),
+        request.name_like.as_deref(),
+    )?;
+    validate_search_options(
+        request.metric.as_deref(),
+        request.min_similarity,
+        request.context_window,
+    )
+}
+
+/// Embed the query with the given provider and return the most similar chunks
+pub(crate) async fn search_chunks(
+    rag_db: &Arc<Mutex<RagDatabase>>,
+    config_store: &Arc<Mutex<ConfigStore>>,
+    request: RagSearchRequest,
+) -> Result<Vec<ChunkMatch>, CommandError> {
+    let query_embedding = embed_query(
+        rag_db,
+        config_store,
+        request.project_id,
+        &request.provider_id,
+        request.query.clone(),
+    )
+    .await?;
+
+    let metric = request
+        .metric
+        .as_deref()
+        .and_then(DistanceMetric::parse)
+        .unwrap_or_default();
+
+    // Search
+    let db = rag_db.lock().await;
+    let document_ids = resolve_document_filter(
+        &db,
+        request.project_id,
+        request.document_ids,
+        request.name_like.as_deref(),
+    )
+    .await?;
+    let mut results = search_similar(
+        &db,
+        request.project_id,
+        query_embedding,
+        request.top_k,
+        metric,
+        request.ef_search,
+        document_ids.as_deref(),
+    )
+    .await?;
+
+    if let Some(min_similarity) = request.min_similarity {
+        results.retain(|m| m.similarity >= min_similarity);
+    }
+
+    if let Some(context_window) = request.context_window {
+        expand_matches(&db, &mut results, context_window).await?;
+    }
+
+    if request.explain {
+        explain_matches(&mut results, metric);
+    }
+    add_snippets(&mut results, &request.query);
+
+    Ok(results)
+}
+
+/// Search for relevant chunks
+#[tauri::command]
+pub async fn rag_search(
+    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    config_store: tauri::State<'_, Arc<Mutex<ConfigStore>>>,
+    limits: tauri::State<'_, Arc<LimitsState>>,
+    request: RagSearchRequest,
+) -> Result<CommandResult<Vec<ChunkMatch>>, String> {
+    if let Err(e) = validate_search_request(&request, &limits.get()) {
+        return Ok(CommandResult::err(e));
+    }
+
+    match search_chunks(&rag_db, &config_store, request).await {
+        Ok(results) => Ok(CommandResult::ok(results)),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RagSearchRerankRequest {
+    pub project_id: i64,
+    pub query: String,
+    pub provider_id: String,
+    pub top_k: usize,
+    /// Candidates retrieved per result before diversity re-ranking (2 to 10); the
+    /// candidates in all, `top_k * candidate_multiplier`, may be at most 2000
+    pub candidate_multiplier: usize,
+    /// "cosine" (default), "dot_product" or "euclidean"
+    pub metric: Option<String>,
+    /// Provider whose rerank model scores the candidates against the query (e.g. "cohere");
+    /// None uses diversity re-ranking
+    pub rerank_provider_id: Option<String>,
+}
+
+/// Search for relevant chunks, re-ranked so results are diverse rather than near-duplicates,
+/// or by a provider's rerank model when `rerank_provider_id` is set
+#[tauri::command]
+pub async fn rag_search_rerank(
+    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    config_store: tauri::State<'_, Arc<Mutex<ConfigStore>>>,
+    limits: tauri::State<'_, Arc<LimitsState>>,
+    request: RagSearchRerankRequest,
+) -> Result<CommandResult<Vec<ChunkMatch>>, String> {
+    // Validate inputs
+    let limits = limits.get();
+    if let Err(e) = validation::validate_query(&request.query, &limits) {
+        return Ok(CommandResult::err(e));
+    }
+    if let Err(e) = validation::validate_top_k(request.top_k, &limits) {
+        return Ok(CommandResult::err(e));
+    }
+    if let Err(e) = validation::validate_candidate_multiplier(request.candidate_multiplier) {
+        return Ok(CommandResult::err(e));
+    }
+    if let Err(e) =
+        validation::validate_candidate_count(request.top_k, request.candidate_multiplier)
+    {
+        return Ok(CommandResult::err(e));
+    }
+    if let Err(e) = validation::validate_not_empty("provider_id", &request.provider_id) {
+        return Ok(CommandResult::err(e));
+    }
+    if let Err(e) = validate_search_options(request.metric.as_deref(), None, None) {
+        return Ok(CommandResult::err(e));
+    }
+    if let Some(provider_id) = &request.rerank_provider_id {
+        if let Err(e) = validation::validate_not_empty("rerank_provider_id", provider_id) {
+            return Ok(CommandResult::err(e));
+        }
+    }
+
+    let reranker = match &request.rerank_provider_id {
+        Some(provider_id) => {
+            let store = config_store.lock().await;
+            match store
+                .get_enabled_provider(provider_id)
+                .map_err(CommandError::from)
+                .and_then(|config| create_provider(&config).map_err(CommandError::from))
+            {
+                Ok(provider) => Some(provider),
+                Err(e) => return Ok(CommandResult::err(e)),
+            }
+        }
+        None => None,
+    };
+
+    let query_embedding = match embed_query(
+        &rag_db,
+        &config_store,
+        request.project_id,
+        &request.provider_id,
+        request.query.clone(),
+    )
+    .await
+    {
+        Ok(embedding) => embedding,
+        Err(e) => return Ok(CommandResult::err(e)),
+    };
+
+    let metric = request
+        .metric
+        .as_deref()
+        .and_then(DistanceMetric::parse)
+        .unwrap_or_default();
+
+    let results = match reranker {
+        Some(reranker) => {
+            // The database is released before the reranker's network call
+            let candidates = search_similar(
+                &*rag_db.lock().await,
+                request.project_id,
+                query_embedding,
+                request.top_k.saturating_mul(request.candidate_multiplier),
+                metric,
+                None,
+                None,
+            )
+            .await;
+            match candidates {
+                Ok(candidates) => {
+                    rerank_candidates(reranker.as_ref(), &request.query, candidates, request.top_k)
+                        .await
+                }
+                Err(e) => Err(e),
+            }
+        }
+        None => {
+            search_with_rerank(
+                &*rag_db.lock().await,
+                request.project_id,
+                query_embedding,
+                request.top_k,
+                request.candidate_multiplier,
+                metric,
+            )
+            .await
+        }
+    };
+    match results {
+        Ok(mut results) => {
+            add_snippets(&mut results, &request.query);
+            Ok(CommandResult::ok(results))
+        }
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// Paraphrases generated by `rag_search_expanded` when the request doesn't say
+const DEFAULT_QUERY_EXPANSIONS: usize = 3;
+
+#[derive(Debug, Deserialize)]
+pub struct RagSearchExpandedRequest {
+    pub project_id: i64,
+    pub query: String,
+    /// Provider that embeds the query and its paraphrases
+    pub provider_id: String,
+    pub top_k: usize,
+    /// "cosine" (default), "dot_product" or "euclidean"
+    pub metric: Option<String>,
+    /// Provider that writes paraphrases of the query; None searches with the query alone
+    pub expansion_provider_id: Option<String>,
+    /// Empty uses the expansion provider's default model
+    #[serde(default)]
+    pub expansion_model: String,
+    /// Paraphrases to ask for (1 to 10, default 3)
+    pub expansions: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RagSearchExpandedResponse {
+    /// The queries searched: the original one, then the paraphrases
+    pub queries: Vec<String>,
+    pub results: Vec<ChunkMatch>,
+}
+
+/// Validate an expanded search request
+fn validate_search_expanded_request(
+    request: &RagSearchExpandedRequest,
+    limits: &ValidationLimits,
+) -> Result<(), ValidationError> {
+    validation::validate_query(&request.query, limits)?;
+    validation::validate_top_k(request.top_k, limits)?;
+    validation::validate_not_empty("provider_id", &request.provider_id)?;
+    if let Some(provider_id) = &request.expansion_provider_id {
+        validation::validate_not_empty("expansion_provider_id", provider_id)?;
+    }
+    if let Some(expansions) = request.expansions {
+        validation::validate_range("expansions", expansions, 1, 10)?;
+    }
+    validate_search_options(request.metric.as_deref(), None, None)
+}
+
+/// Ask a chat model for `count` rephrasings of a search query, recording the token usage
+async fn expand_query(
+    rag_db: &Arc<Mutex<RagDatabase>>,
+    provider_id: &str,
+    provider: &dyn LlmProvider,
+    model: String,
+    query: &str,
+    count: usize,
+) -> Result<Vec<String>, CommandError> {
+    let prompt = format!(
+        "Write {} different rephrasings of the search query below, using other words and \
+         phrasings that could match relevant documents. Reply with one rephrasing per line \
+         and nothing else.\n\nQuery: {}",
+        count, query
+    );
+    let response = provider
+        .chat(ChatRequest {
+            model,
+            messages: vec![ChatMessage {
+                role: ChatRole::User,
+                content: prompt,
+            }],
+            temperature: Some(0.7),
+            max_tokens: Some(512),
+            top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            seed: None,
+            response_format: None,
+            stream: false,
+        })
+        .await?;
+
+    if let Some(usage) = &response.usage {
+        record_usage(
+            rag_db,
+            provider_id,
+            &response.model,
+            usage.prompt_tokens,
+            usage.completion_tokens,
+            None,
+        )
+        .await;
+    }
+
+    Ok(parse_expansions(&response.content, query, count))
+}
+
+/// The rephrasings in a model's reply: one per line, without list markers or quotes,
+/// leaving out blank lines, repeats and the query itself
+fn parse_expansions(reply: &str, query: &str, count: usize) -> Vec<String> {
+    let mut seen = vec![query.trim().to_lowercase()];
+    let mut expansions = Vec::new();
+
+    for line in reply.lines() {
+        let expansion = strip_list_marker(line).trim_matches('"').trim();
+        if expansion.is_empty() || seen.contains(&expansion.to_lowercase()) {
+            continue;
+        }
+        seen.push(expansion.to_lowercase());
+        expansions.push(expansion.to_string());
+        if expansions.len() == count {
+            break;
+        }
+    }
+
+    expansions
+}
+
+/// A line without its leading "1." / "1)" number or "-" / "*" / "•" bullet
+fn strip_list_marker(line: &str) -> &str {
+    let line = line.trim();
+    let unnumbered = line.trim_start_matches(|c: char| c.is_ascii_digit());
+    if unnumbered.len() < line.len() {
+        if let Some(rest) = unnumbered.strip_prefix(['.', ')']) {
+            return rest.trim_start();
+        }
+    }
+    line.strip_prefix(['-', '*', '•'])
+        .map_or(line, str::trim_start)
+}
+
+/// Search with the query and model-written paraphrases of it, fusing the results with
+/// Reciprocal Rank Fusion so chunks matching several phrasings rank first
+#[tauri::command]
+pub async fn rag_search_expanded(
+    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    config_store: tauri::State<'_, Arc<Mutex<ConfigStore>>>,
+    limits: tauri::State<'_, Arc<LimitsState>>,
+    request: RagSearchExpandedRequest,
+) -> Result<CommandResult<RagSearchExpandedResponse>, String> {
+    if let Err(e) = validate_search_expanded_request(&request, &limits.get()) {
+        return Ok(CommandResult::err(e));
+    }
+
+    match search_expanded(&rag_db, &config_store, request).await {
+        Ok(response) => Ok(CommandResult::ok(response)),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// Expand the query if asked to, embed every phrasing and fuse their search results
+pub(crate) async fn search_expanded(
+    rag_db: &Arc<Mutex<RagDatabase>>,
+    config_store: &Arc<Mutex<ConfigStore>>,
+    request: RagSearchExpandedRequest,
+) -> Result<RagSearchExpandedResponse, CommandError> {
+    let store = config_store.lock().await;
+    let embedding_config = store.get_enabled_provider(&request.provider_id)?;
+    let expansion_config = request
+        .expansion_provider_id
+        .as_deref()
+        .map(|provider_id| store.get_enabled_provider(provider_id))
+        .transpose()?;
+    drop(store);
+
+    let mut queries = vec![request.query.clone()];
+    if let Some(expansion_config) = expansion_config {
+        let model = expansion_config.resolve_model(&request.expansion_model)?;
+        let provider = create_provider(&expansion_config)?;
+        let count = request.expansions.unwrap_or(DEFAULT_QUERY_EXPANSIONS);
+        queries.extend(
+            expand_query(
+                rag_db,
+                &expansion_config.provider_id,
+                provider.as_ref(),
+                model,
+                &request.query,
+                count,
+            )
+            .await?,
+        );
+    }
+
+    let embedding_service = EmbeddingService::new(create_provider(&embedding_config)?);
+    let mut query_embeddings = Vec::with_capacity(queries.len());
+    for query in &queries {
+        query_embeddings.push(
+            embedding_service
+                .embed_query(&request.provider_id, query)
+                .await?,
+        );
+    }
+
+    let metric = request
+        .metric
+        .as_deref()
+        .and_then(DistanceMetric::parse)
+        .unwrap_or_default();
+
+    let db = rag_db.lock().await;
+    check_project_embedding(
+        &db.get_project_embedding_info(request.project_id).await?,
+        &embedding_service.model_key(),
+        query_embeddings.first().map(Vec::len),
+    )?;
+    let mut results = search_multi_query(
+        &db,
+        request.project_id,
+        query_embeddings,
+        request.top_k,
+        metric,
+    )
+    .await?;
+    // Highlight the words of every phrasing, since any of them may have matched
+    add_snippets(&mut results, &queries.join(" "));
+
+    Ok(RagSearchExpandedResponse { queries, results })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RagChatRequest {
+    pub project_id: i64,
+    pub query: String,
+    pub provider_id: String,
+    /// Empty uses the provider's default model
+    #[serde(default)]
+    pub model: String,
+    pub top_k: usize,
+    pub temperature: Option<f32>,
+    pub max_tokens: Option<u32>,
+    /// Token budget for the retrieved context; lowest-scoring sources are dropped to fit
+    pub max_context_tokens: Option<usize>,
+    /// Prior turns to include before the query; takes precedence over `conversation_id`
+    pub history: Option<Vec<ChatMessage>>,
+    /// Conversation whose stored messages are used as history
+    pub conversation_id: Option<i64>,
+    /// Search distance metric: "cosine" (default), "dot_product" or "euclidean"
+    pub metric: Option<String>,
+    /// Sources scoring below this are not used; if none remain the answer has no context
+    pub min_similarity: Option<f32>,
+    /// Neighboring chunks on either side of each source to include with it
+    pub context_window: Option<usize>,
+    /// Chunks buffered between the provider and the UI when streaming
+    pub stream_capacity: Option<usize>,
+    /// JSON Schema the answer must match; a non-matching answer is sent back once to be
+    /// fixed before the request fails (non-streaming only)
+    #[serde(default)]
+    pub json_schema: Option<Value>,
+    /// Only use chunks of these documents as sources
+    pub document_ids: Option<Vec<i64>>,
+    /// Only use documents whose name matches this SQL `LIKE` pattern as sources
+    pub name_like: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RagChatResponse {
+    pub response: String,
+    pub sources: Vec<ChunkMatch>,
+    pub model: String,
+    /// Number of sources that fit in the context budget
+    pub sources_included: usize,
+    /// Token usage of the chat completion, if the provider reports it
+    /// (none of the current embedding endpoints report token counts)
+    pub usage: Option<Usage>,
+    /// Indices into `sources` cited in the response as `[n]` markers
+    pub citations: Vec<usize>,
+    /// True when no source passed the similarity threshold and the answer has no context
+    pub no_relevant_sources: bool,
+}
+
+/// Validate a RAG chat request
+fn validate_rag_chat_request(
+    request: &RagChatRequest,
+    limits: &ValidationLimits,
+) -> Result<(), ValidationError> {
+    validation::validate_query(&request.query, limits)?;
+    validation::validate_top_k(request.top_k, limits)?;
+    validation::validate_not_empty("provider_id", &request.provider_id)?;
+    if let Some(temp) = request.temperature {
+        validation::validate_temperature(temp)?;
+    }
+    if let Some(max_tokens) = request.max_tokens {
+        validation::validate_max_tokens(max_tokens)?;
+    }
+    if let Some(max_context_tokens) = request.max_context_tokens {
+        validation::validate_range("max_context_tokens", max_context_tokens, 1, 2_000_000)?;
+    }
+    if let Some(capacity) = request.stream_capacity {
+        validation::validate_stream_capacity(capacity)?;
+    }
+    if let Some(schema) = &request.json_schema {
+        validation::validate_json_schema("json_schema", schema)?;
+    }
+    validate_document_filter(
+        request.document_ids.as_deref(),
+        request.name_like.as_deref(),
+    )?;
+    validate_search_options(
+        request.metric.as_deref(),
+        request.min_similarity,
+        request.context_window,
+    )?;
+    Ok(())
+}
+
+/// System prompt preceding the retrieved context
+const RAG_SYSTEM_PROMPT: &str =
+    "You are a helpful assistant. Use the following context to answer the user's question. \
+     Cite the sources you use inline as [1], [2], etc., matching the source numbers.\n\nContext:\n";
+
+/// System prompt used when no source passed the similarity threshold
+const NO_SOURCES_SYSTEM_PROMPT: &str =
+    "You are a helpful assistant. No relevant sources were found in the user's documents for \
+     this question. Say so, and only answer if you can do so reliably without them.";
+
+/// A RAG chat ready to send: retrieved sources, provider and the prompt built from them
+struct PreparedRagChat {
+    sources: Vec<ChunkMatch>,
+    /// The search found nothing above the similarity threshold
+    no_relevant_sources: bool,
+    provider: Arc<dyn LlmProvider>,
+    chat_request: ChatRequest,
+}
+
+/// Search for relevant sources and build the chat request with them as context
+async fn prepare_rag_chat(
+    rag_db: &Arc<Mutex<RagDatabase>>,
+    config_store: &Arc<Mutex<ConfigStore>>,
+    limits: &ValidationLimits,
+    request: RagChatRequest,
+    stream: bool,
+) -> Result<PreparedRagChat, CommandError> {
+    validate_rag_chat_request(&request, limits)?;
 
     // First, perform RAG search
     let search_request = RagSearchRequest {
@@ -286,78 +1865,852 @@ pub async fn rag_chat(
         query: request.query.clone(),
         provider_id: request.provider_id.clone(),
         top_k: request.top_k,
+        metric: request.metric.clone(),
+        min_similarity: request.min_similarity,
+        context_window: request.context_window,
+        ef_search: None,
+        explain: false,
+        document_ids: request.document_ids.clone(),
+        name_like: request.name_like.clone(),
     };
 
-    let search_result = rag_search(rag_db, config_store.clone(), search_request).await?;
+    let mut sources = search_chunks(rag_db, config_store, search_request).await?;
+    let no_relevant_sources = sources.is_empty();
 
-    let sources = match search_result.data {
-        Some(s) => s,
-        None => {
-            return Ok(CommandResult::err(
-                search_result.error.unwrap_or_else(|| "Search failed".to_string()),
-            ))
+    // Load prior turns, either passed in directly or from the stored conversation
+    let mut history = match (request.history, request.conversation_id) {
+        (Some(history), _) => history,
+        (None, Some(conversation_id)) => {
+            let db = rag_db.lock().await;
+            db.get_conversation_messages(conversation_id)
+                .await?
+                .into_iter()
+                .filter_map(|m| {
+                    ChatRole::parse(&m.role).map(|role| ChatMessage {
+                        role,
+                        content: m.content,
+                    })
+                })
+                .collect()
         }
+        (None, None) => Vec::new(),
     };
 
-    // Build context from sources
-    let context = sources
-        .iter()
-        .enumerate()
-        .map(|(i, chunk_match)| {
-            format!(
-                "[Source {}: {}]\n{}",
-                i + 1,
-                chunk_match.document_name,
-                chunk_match.chunk.content
-            )
-        })
-        .collect::<Vec<_>>()
-        .join("\n\n");
+    // The query is sent as the final message, so a copy already saved as the last turn
+    // (e.g. by the UI before asking) is left out of the history
+    if history.last().is_some_and(|m| {
+        matches!(m.role, ChatRole::User) && m.content.trim() == request.query.trim()
+    }) {
+        history.pop();
+    }
+
+    // History may use at most half of the context budget; sources get the rest
+    let history = trim_history(history, request.max_context_tokens.map(|max| max / 2));
+    let history_tokens: usize = history.iter().map(|m| count_tokens(&m.content)).sum();
+
+    // Drop the lowest-scoring sources that don't fit the context budget
+    if let Some(max_context_tokens) = request.max_context_tokens {
+        let retrieved = sources.len();
+        let budget = max_context_tokens.saturating_sub(
+            count_tokens(RAG_SYSTEM_PROMPT) + count_tokens(&request.query) + history_tokens,
+        );
+        sources = fit_sources_to_budget(sources, budget);
+
+        if sources.len() < retrieved {
+            tracing::info!(
+                "Dropped {} of {} sources to fit {} context tokens",
+                retrieved - sources.len(),
+                retrieved,
+                max_context_tokens
+            );
+        }
+    }
 
     // Build prompt with context
-    let system_message = format!(
-        "You are a helpful assistant. Use the following context to answer the user's question.\n\nContext:\n{}",
-        context
-    );
+    let system_message = if no_relevant_sources {
+        NO_SOURCES_SYSTEM_PROMPT.to_string()
+    } else {
+        format!("{}{}", RAG_SYSTEM_PROMPT, build_context(&sources))
+    };
 
     // Get provider
     let store = config_store.lock().await;
-    let provider_config = match store.get_provider(&request.provider_id) {
-        Ok(config) => config,
-        Err(e) => return Ok(CommandResult::err(e.to_string())),
-    };
+    let provider_config = store.get_enabled_provider(&request.provider_id)?;
     drop(store);
 
-    let provider = match create_provider(&provider_config) {
-        Ok(p) => p,
-        Err(e) => return Ok(CommandResult::err(e.to_string())),
-    };
+    let model = provider_config.resolve_model(&request.model)?;
+    let provider = create_provider(&provider_config)?;
+
+    // Chat request with context: system message, prior turns, then the query
+    let mut messages = Vec::with_capacity(history.len() + 2);
+    messages.push(ChatMessage {
+        role: ChatRole::System,
+        content: system_message,
+    });
+    messages.extend(history);
+    messages.push(ChatMessage {
+        role: ChatRole::User,
+        content: request.query,
+    });
 
-    // Send chat request with context
     let chat_request = ChatRequest {
-        model: request.model,
-        messages: vec![
-            ChatMessage {
-                role: ChatRole::System,
-                content: system_message,
-            },
-            ChatMessage {
-                role: ChatRole::User,
-                content: request.query,
-            },
-        ],
+        model,
+        messages,
         temperature: request.temperature,
         max_tokens: request.max_tokens,
         top_p: None,
-        stream: false,
+        frequency_penalty: None,
+        presence_penalty: None,
+        seed: None,
+        response_format: None,
+        stream,
+    };
+
+    Ok(PreparedRagChat {
+        sources,
+        no_relevant_sources,
+        provider,
+        chat_request,
+    })
+}
+
+/// Answer a query with RAG context, recording the completion's token usage
+pub(crate) async fn answer_rag_chat(
+    rag_db: &Arc<Mutex<RagDatabase>>,
+    config_store: &Arc<Mutex<ConfigStore>>,
+    limits: &ValidationLimits,
+    request: RagChatRequest,
+) -> Result<RagChatResponse, CommandError> {
+    let provider_id = request.provider_id.clone();
+    let conversation_id = request.conversation_id;
+    let json_schema = request.json_schema.clone();
+    let validator = json_schema
+        .as_ref()
+        .map(json_schema_validator)
+        .transpose()?;
+
+    let prepared = prepare_rag_chat(rag_db, config_store, limits, request, false).await?;
+    let mut response = prepared
+        .provider
+        .chat(prepared.chat_request.clone())
+        .await?;
+    let mut schema_errors = Vec::new();
+    if let (Some(schema), Some(validator)) = (&json_schema, &validator) {
+        (response, schema_errors) = check_json_schema(
+            prepared.provider.as_ref(),
+            &prepared.chat_request,
+            response,
+            schema,
+            validator,
+        )
+        .await;
+    }
+
+    if let Some(usage) = &response.usage {
+        record_usage(
+            rag_db,
+            &provider_id,
+            &response.model,
+            usage.prompt_tokens,
+            usage.completion_tokens,
+            conversation_id,
+        )
+        .await;
+    }
+    check_schema_errors(&schema_errors)?;
+
+    // Keep only citations that refer to an actual source; JSON answers are kept verbatim
+    // since stripping markers could break them
+    let (mut text, citations) = extract_citations(&response.content, prepared.sources.len());
+    if json_schema.is_some() {
+        text = response.content;
+    }
+
+    Ok(RagChatResponse {
+        response: text,
+        sources_included: prepared.sources.len(),
+        sources: prepared.sources,
+        model: response.model,
+        usage: response.usage,
+        citations,
+        no_relevant_sources: prepared.no_relevant_sources,
+    })
+}
+
+/// Chat with RAG context
+#[tauri::command]
+pub async fn rag_chat(
+    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    config_store: tauri::State<'_, Arc<Mutex<ConfigStore>>>,
+    requests: tauri::State<'_, Arc<RequestRegistry>>,
+    limits: tauri::State<'_, Arc<LimitsState>>,
+    request: RagChatRequest,
+    request_id: Option<String>,
+) -> Result<CommandResult<RagChatResponse>, String> {
+    if let Some(request_id) = &request_id {
+        if let Err(e) = validation::validate_not_empty("request_id", request_id) {
+            return Ok(CommandResult::err(e));
+        }
+    }
+    let guard = match register_optional(&requests, request_id.as_deref()) {
+        Ok(guard) => guard,
+        Err(e) => return Ok(CommandResult::err(e)),
     };
 
-    match provider.chat(chat_request).await {
-        Ok(response) => Ok(CommandResult::ok(RagChatResponse {
-            response: response.content,
-            sources,
-            model: response.model,
-        })),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+    match run_cancellable(
+        guard.as_ref(),
+        answer_rag_chat(&rag_db, &config_store, &limits.get(), request),
+    )
+    .await
+    {
+        Ok(response) => Ok(CommandResult::ok(response)),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub(crate) struct RagSourcesEvent {
+    request_id: String,
+    sources: Vec<ChunkMatch>,
+    no_relevant_sources: bool,
+}
+
+/// Chat with RAG context, streaming the answer
+/// Sources are emitted first via the 'rag-sources' event, then answer
+/// chunks via 'chat-chunk', then 'chat-complete' or 'chat-error'
+#[tauri::command]
+pub async fn rag_chat_stream(
+    app_handle: AppHandle,
+    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    config_store: tauri::State<'_, Arc<Mutex<ConfigStore>>>,
+    requests: tauri::State<'_, Arc<RequestRegistry>>,
+    limits: tauri::State<'_, Arc<LimitsState>>,
+    request: RagChatRequest,
+    request_id: String, // Unique ID for this request
+) -> Result<CommandResult<()>, String> {
+    match start_rag_chat_stream(
+        &rag_db,
+        &config_store,
+        &requests,
+        &limits.get(),
+        request,
+        request_id,
+        move |event| event.emit(&app_handle),
+    )
+    .await
+    {
+        Ok(_) => Ok(CommandResult::ok(())),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+/// Find the sources for a RAG chat and start streaming the answer through `on_event`
+/// Returns the task forwarding the stream, which ends after the terminal event.
+pub(crate) async fn start_rag_chat_stream(
+    rag_db: &Arc<Mutex<RagDatabase>>,
+    config_store: &Arc<Mutex<ConfigStore>>,
+    requests: &Arc<RequestRegistry>,
+    limits: &ValidationLimits,
+    request: RagChatRequest,
+    request_id: String,
+    on_event: impl Fn(StreamEvent) + Send + Sync + 'static,
+) -> Result<JoinHandle<()>, CommandError> {
+    validation::validate_not_empty("request_id", &request_id)?;
+    if request.json_schema.is_some() {
+        return Err(CommandError::Validation(
+            "json_schema is not supported when streaming".to_string(),
+        ));
+    }
+    let guard = requests.register(&request_id)?;
+
+    let provider_id = request.provider_id.clone();
+    let conversation_id = request.conversation_id;
+    let stream_capacity = request.stream_capacity.unwrap_or(DEFAULT_STREAM_CAPACITY);
+
+    let prepared = guard
+        .until_cancelled(prepare_rag_chat(
+            rag_db,
+            config_store,
+            limits,
+            request,
+            true,
+        ))
+        .await?;
+
+    on_event(StreamEvent::Sources(RagSourcesEvent {
+        request_id: request_id.clone(),
+        sources: prepared.sources,
+        no_relevant_sources: prepared.no_relevant_sources,
+    }));
+
+    // Streams don't report usage, so it is recorded from token estimates
+    let model = prepared.chat_request.model.clone();
+    let prompt_tokens = count_prompt_tokens(&provider_id, &prepared.chat_request.messages);
+
+    // Create channel for streaming
+    let (tx, rx) = tokio::sync::mpsc::channel::<ChatChunk>(stream_capacity);
+
+    let mut timing = StreamTiming::start();
+    let stream = spawn_cancellable_stream(prepared.provider, prepared.chat_request, tx, &guard);
+
+    // Spawn task to receive chunks and emit events
+    let rag_db = rag_db.clone();
+    Ok(tokio::spawn(async move {
+        // Keep the request cancellable until its stream has ended
+        let _guard = guard;
+        let full_text = forward_chunks(&on_event, &request_id, rx, &mut timing).await;
+        let result = stream_result(stream).await;
+
+        if !full_text.is_empty() {
+            record_usage(
+                &rag_db,
+                &provider_id,
+                &model,
+                prompt_tokens as u32,
+                count_provider_tokens(&provider_id, &full_text) as u32,
+                conversation_id,
+            )
+            .await;
+        }
+
+        emit_stream_end(&on_event, &request_id, result, timing);
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_support;
+    use crate::llm_providers::mock::{MockProvider, MOCK_EMBED_ERROR};
+    use crate::rag::UsageGroupBy;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tempfile::TempDir;
+
+    /// Stores with the mock provider configured and a project holding one document per text
+    async fn setup(
+        dir: &TempDir,
+        texts: &[&str],
+    ) -> (Arc<Mutex<RagDatabase>>, Arc<Mutex<ConfigStore>>, i64) {
+        let store = test_support::config_store(dir);
+        let db = test_support::rag_database(dir).await;
+        let project = db.create_project("Project".to_string()).await.unwrap();
+
+        let service =
+            EmbeddingService::new(create_provider(&store.get_provider("mock").unwrap()).unwrap());
+        for (i, text) in texts.iter().enumerate() {
+            let document = db
+                .create_document(project.id, format!("doc{}", i), None, None)
+                .await
+                .unwrap();
+            let embedding = service.embed_text(text.to_string()).await.unwrap();
+            db.replace_document_chunks(
+                document.id,
+                None,
+                vec![NewChunk::new(text.to_string(), embedding)],
+                None,
+            )
+            .await
+            .unwrap();
+        }
+
+        (
+            Arc::new(Mutex::new(db)),
+            Arc::new(Mutex::new(store)),
+            project.id,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_ingest_documents_reports_each_document() {
+        let dir = TempDir::new().unwrap();
+        let (rag_db, config_store, project_id) = setup(&dir, &[]).await;
+        let provider =
+            create_provider(&config_store.lock().await.get_provider("mock").unwrap()).unwrap();
+        let service = EmbeddingService::new(provider);
+
+        let documents = vec![
+            NewDocument {
+                name: "a".to_string(),
+                content: "rust ownership".to_string(),
+            },
+            NewDocument {
+                name: "empty".to_string(),
+                content: "   ".to_string(),
+            },
+            NewDocument {
+                name: "b".to_string(),
+                content: "sourdough bread".to_string(),
+            },
+            NewDocument {
+                name: "a again".to_string(),
+                content: "rust ownership".to_string(),
+            },
+        ];
+        let handled = AtomicUsize::new(0);
+        let results = ingest_documents(
+            &rag_db,
+            &service,
+            project_id,
+            documents,
+            DocumentChecks {
+                strip_control_chars: None,
+                limits: ValidationLimits::default(),
+            },
+            |_, _| {},
+            |done, _| handled.store(done, Ordering::Relaxed),
+        )
+        .await
+        .unwrap();
+
+        // The invalid document doesn't stop the others
+        assert_eq!(handled.load(Ordering::Relaxed), 4);
+        assert!(results[0].document_id.is_some() && results[0].error.is_none());
+        assert!(results[1].document_id.is_none() && results[1].error.is_some());
+        assert_eq!(results[2].chunks_created, 1);
+        // Content already in the project isn't stored twice
+        assert!(results[3].was_duplicate);
+        assert_eq!(results[3].document_id, results[0].document_id);
+
+        let db = rag_db.lock().await;
+        let documents = db.list_documents(project_id, 10, 0).await.unwrap();
+        assert_eq!(documents.total, 2);
+        let content = db
+            .get_document_content(results[2].document_id.unwrap())
+            .await
+            .unwrap();
+        assert_eq!(content.as_deref(), Some("sourdough bread"));
+    }
+
+    #[tokio::test]
+    async fn test_offline_mode_keeps_to_projects_indexed_offline() {
+        let dir = TempDir::new().unwrap();
+        let (rag_db, config_store, project_id) = setup(&dir, &["rust ownership"]).await;
+        config_store.lock().await.set_offline_mode(true).unwrap();
+        let offline = EmbeddingService::new(
+            create_provider(&config_store.lock().await.get_provider("mock").unwrap()).unwrap(),
+        );
+        let search = |project_id| RagSearchRequest {
+            top_k: 1,
+            ..test_support::search_request(project_id, "rust")
+        };
+        let ingest = |project_id| {
+            ingest_documents(
+                &rag_db,
+                &offline,
+                project_id,
+                vec![NewDocument {
+                    name: "offline".to_string(),
+                    content: "rust borrowing".to_string(),
+                }],
+                DocumentChecks {
+                    strip_control_chars: None,
+                    limits: ValidationLimits::default(),
+                },
+                |_, _| {},
+                |_, _| {},
+            )
+        };
+
+        // A project indexed online is neither searched nor added to with offline embeddings
+        let err = search_chunks(&rag_db, &config_store, search(project_id))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, CommandError::Validation(_)));
+        assert!(err.to_string().contains("offline mode"));
+        let err = ingest(project_id).await.unwrap_err();
+        assert!(matches!(err, CommandError::Validation(_)));
+
+        // A project indexed offline can be used offline, and only offline
+        let offline_project = rag_db
+            .lock()
+            .await
+            .create_project("Offline".to_string())
+            .await
+            .unwrap()
+            .id;
+        ingest(offline_project).await.unwrap();
+        let results = search_chunks(&rag_db, &config_store, search(offline_project))
+            .await
+            .unwrap();
+        assert_eq!(results[0].chunk.content, "rust borrowing");
+
+        config_store.lock().await.set_offline_mode(false).unwrap();
+        let err = search_chunks(&rag_db, &config_store, search(offline_project))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, CommandError::Validation(_)));
+        assert!(err.to_string().contains("offline mode"));
+    }
+
+    #[tokio::test]
+    async fn test_search_expanded_records_expansion_usage() {
+        let dir = TempDir::new().unwrap();
+        let texts = ["rust ownership and borrowing", "baking sourdough bread"];
+        let (rag_db, config_store, project_id) = setup(&dir, &texts).await;
+
+        let request = RagSearchExpandedRequest {
+            project_id,
+            query: "rust borrowing".to_string(),
+            provider_id: "mock".to_string(),
+            top_k: 1,
+            metric: None,
+            expansion_provider_id: Some("mock".to_string()),
+            expansion_model: "mock-model".to_string(),
+            expansions: Some(2),
+        };
+        let response = search_expanded(&rag_db, &config_store, request)
+            .await
+            .unwrap();
+
+        // The mock echoes the prompt, whose first lines stand in for the paraphrases
+        assert_eq!(response.queries.len(), 3);
+        assert_eq!(response.queries[0], "rust borrowing");
+        assert_eq!(response.results[0].chunk.content, texts[0]);
+
+        let usage = rag_db
+            .lock()
+            .await
+            .get_usage_summary(None, None, UsageGroupBy::Provider)
+            .await
+            .unwrap();
+        assert_eq!(usage.len(), 1);
+        assert_eq!(usage[0].group_key.as_deref(), Some("mock"));
+        assert_eq!(usage[0].model, "mock-model");
+        assert_eq!(usage[0].requests, 1);
+        assert!(usage[0].prompt_tokens > 0);
+    }
+
+    #[tokio::test]
+    async fn test_ingest_documents_keeps_going_after_provider_error() {
+        let dir = TempDir::new().unwrap();
+        let (rag_db, _, project_id) = setup(&dir, &[]).await;
+        let service = EmbeddingService::new(Arc::new(MockProvider::new()));
+
+        let documents = vec![
+            NewDocument {
+                name: "a".to_string(),
+                content: "rust ownership".to_string(),
+            },
+            NewDocument {
+                name: "broken".to_string(),
+                content: format!("text the provider rejects {}", MOCK_EMBED_ERROR),
+            },
+            NewDocument {
+                name: "b".to_string(),
+                content: "sourdough bread".to_string(),
+            },
+        ];
+        let handled = std::sync::Mutex::new(Vec::new());
+        let results = ingest_documents(
+            &rag_db,
+            &service,
+            project_id,
+            documents,
+            DocumentChecks {
+                strip_control_chars: None,
+                limits: ValidationLimits::default(),
+            },
+            |_, _| {},
+            |done, result| {
+                handled
+                    .lock()
+                    .unwrap()
+                    .push((done, result.document_id.is_some()))
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            handled.into_inner().unwrap(),
+            vec![(1, true), (2, false), (3, true)]
+        );
+        assert!(results[1]
+            .error
+            .as_deref()
+            .unwrap()
+            .contains("mock embedding failed"));
+        let documents = rag_db
+            .lock()
+            .await
+            .list_documents(project_id, 10, 0)
+            .await
+            .unwrap();
+        assert_eq!(documents.total, 2);
+    }
+
+    #[tokio::test]
+    async fn test_add_file_document_keeps_pages() {
+        let dir = TempDir::new().unwrap();
+        let (rag_db, config_store, project_id) = setup(&dir, &[]).await;
+        let path = dir.path().join("notes.txt");
+        std::fs::write(&path, "rust ownership\u{c}sourdough bread").unwrap();
+        let request = |path: &std::path::Path| AddDocumentFromFileRequest {
+            project_id,
+            path: path.to_string_lossy().into_owned(),
+            provider_id: "mock".to_string(),
+            name: None,
+            force: false,
+        };
+
+        let progress = AtomicUsize::new(0);
+        let response = add_file_document(
+            &rag_db,
+            &config_store,
+            &ValidationLimits::default(),
+            request(&path),
+            |_, done, _| progress.store(done, Ordering::Relaxed),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.chunks_created, 2);
+        assert_eq!(progress.load(Ordering::Relaxed), 2);
+
+        let db = rag_db.lock().await;
+        let document = db.get_document(response.document_id).await.unwrap();
+        assert_eq!(document.name, "notes.txt");
+        let mut pages: Vec<Option<i32>> = db
+            .get_chunks_for_project(project_id)
+            .await
+            .unwrap()
+            .iter()
+            .map(|chunk| chunk.page_number)
+            .collect();
+        pages.sort();
+        assert_eq!(pages, vec![Some(1), Some(2)]);
+        drop(db);
+
+        // The same file again is found rather than embedded
+        let again = add_file_document(
+            &rag_db,
+            &config_store,
+            &ValidationLimits::default(),
+            request(&path),
+            |_, _, _| {},
+        )
+        .await
+        .unwrap();
+        assert!(again.was_duplicate);
+        assert_eq!(again.document_id, response.document_id);
+
+        // Files over the size limit are refused before anything is stored
+        let limits = ValidationLimits {
+            max_document_bytes: 10,
+            ..ValidationLimits::default()
+        };
+        let result = add_file_document(
+            &rag_db,
+            &config_store,
+            &limits,
+            request(&path),
+            |_, _, _| {},
+        )
+        .await;
+        assert!(matches!(result, Err(CommandError::Validation(_))));
+        let documents = rag_db
+            .lock()
+            .await
+            .list_documents(project_id, 10, 0)
+            .await
+            .unwrap();
+        assert_eq!(documents.total, 1);
+    }
+
+    #[test]
+    fn test_parse_expansions() {
+        let reply = "1. rust memory safety\n2) 3D printing\n- \"Rust borrow checker\"\n\nrust borrowing rules\n* rust memory safety\nlifetimes in rust";
+        assert_eq!(
+            parse_expansions(reply, "Rust borrowing rules", 4),
+            vec![
+                "rust memory safety",
+                "3D printing",
+                "Rust borrow checker",
+                "lifetimes in rust"
+            ]
+        );
+        assert_eq!(
+            parse_expansions(reply, "query", 10),
+            vec![
+                "rust memory safety",
+                "3D printing",
+                "Rust borrow checker",
+                "rust borrowing rules",
+                "lifetimes in rust"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rag_chat_with_mock_provider() {
+        let dir = TempDir::new().unwrap();
+        let texts = ["rust ownership and borrowing", "baking sourdough bread"];
+        let (rag_db, config_store, project_id) = setup(&dir, &texts).await;
+
+        let request = test_support::rag_chat_request(project_id, "rust borrowing rules");
+        let prepared = prepare_rag_chat(
+            &rag_db,
+            &config_store,
+            &ValidationLimits::default(),
+            request,
+            false,
+        )
+        .await
+        .unwrap();
+
+        assert!(!prepared.no_relevant_sources);
+        assert_eq!(prepared.sources[0].document_name, "doc0");
+
+        let system = &prepared.chat_request.messages[0].content;
+        assert!(system.contains("[Source 1: doc0]\nrust ownership and borrowing"));
+
+        // The mock echoes the query back
+        let response = prepared.provider.chat(prepared.chat_request).await.unwrap();
+        assert_eq!(response.content, "rust borrowing rules");
+    }
+
+    #[tokio::test]
+    async fn test_rag_chat_stream_sends_sources_then_answer() {
+        let dir = TempDir::new().unwrap();
+        let texts = ["rust ownership and borrowing", "baking sourdough bread"];
+        let (rag_db, config_store, project_id) = setup(&dir, &texts).await;
+
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = events.clone();
+        start_rag_chat_stream(
+            &rag_db,
+            &config_store,
+            &Arc::new(RequestRegistry::default()),
+            &ValidationLimits::default(),
+            test_support::rag_chat_request(project_id, "rust borrowing rules"),
+            "rag-stream".to_string(),
+            move |event| {
+                let payload = serde_json::to_value(&event).unwrap();
+                recorded.lock().unwrap().push((event.name(), payload));
+            },
+        )
+        .await
+        .unwrap()
+        .await
+        .unwrap();
+
+        let events = events.lock().unwrap();
+        let (name, sources) = &events[0];
+        assert_eq!(*name, "rag-sources");
+        assert_eq!(sources["sources"][0]["document_name"], "doc0");
+        assert_eq!(sources["request_id"], "rag-stream");
+
+        // The mock echoes the query back
+        let answer: String = events
+            .iter()
+            .filter(|(name, _)| *name == "chat-chunk")
+            .map(|(_, chunk)| chunk["delta"].as_str().unwrap())
+            .collect();
+        assert_eq!(answer, "rust borrowing rules");
+        assert_eq!(events.last().unwrap().0, "chat-complete");
+    }
+
+    #[tokio::test]
+    async fn test_rag_chat_sends_saved_query_once() {
+        let dir = TempDir::new().unwrap();
+        let (rag_db, config_store, project_id) = setup(&dir, &["rust ownership"]).await;
+        let conversation_id = {
+            let db = rag_db.lock().await;
+            let conversation = db
+                .create_conversation(
+                    "Chat".to_string(),
+                    "mock".to_string(),
+                    "mock-model".to_string(),
+                    Some(project_id),
+                )
+                .await
+                .unwrap();
+            for (role, content) in [
+                ("user", "what is ownership?"),
+                ("assistant", "a set of rules"),
+                ("user", "and borrowing?"),
+            ] {
+                db.add_message(conversation.id, role.to_string(), content.to_string())
+                    .await
+                    .unwrap();
+            }
+            conversation.id
+        };
+
+        let mut request = test_support::rag_chat_request(project_id, "and borrowing?");
+        request.conversation_id = Some(conversation_id);
+        let prepared = prepare_rag_chat(
+            &rag_db,
+            &config_store,
+            &ValidationLimits::default(),
+            request,
+            false,
+        )
+        .await
+        .unwrap();
+
+        let contents: Vec<&str> = prepared.chat_request.messages[1..]
+            .iter()
+            .map(|m| m.content.as_str())
+            .collect();
+        assert_eq!(
+            contents,
+            vec!["what is ownership?", "a set of rules", "and borrowing?"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rag_chat_without_relevant_sources() {
+        let dir = TempDir::new().unwrap();
+        let (rag_db, config_store, project_id) = setup(&dir, &["baking sourdough bread"]).await;
+
+        let request = RagChatRequest {
+            min_similarity: Some(0.99),
+            ..test_support::rag_chat_request(project_id, "rust borrowing rules")
+        };
+        let prepared = prepare_rag_chat(
+            &rag_db,
+            &config_store,
+            &ValidationLimits::default(),
+            request,
+            false,
+        )
+        .await
+        .unwrap();
+
+        assert!(prepared.no_relevant_sources);
+        assert!(prepared.sources.is_empty());
+        assert_eq!(
+            prepared.chat_request.messages[0].content,
+            NO_SOURCES_SYSTEM_PROMPT
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rag_chat_restricted_to_named_documents() {
+        let dir = TempDir::new().unwrap();
+        let texts = ["rust ownership and borrowing", "baking sourdough bread"];
+        let (rag_db, config_store, project_id) = setup(&dir, &texts).await;
+        let doc1 = rag_db
+            .lock()
+            .await
+            .find_document_ids(project_id, "doc1")
+            .await
+            .unwrap();
+
+        let mut request = test_support::rag_chat_request(project_id, "rust borrowing rules");
+        request.name_like = Some("doc%".to_string());
+        request.document_ids = Some(doc1);
+        let prepared = prepare_rag_chat(
+            &rag_db,
+            &config_store,
+            &ValidationLimits::default(),
+            request,
+            false,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(prepared.sources.len(), 1);
+        assert_eq!(prepared.sources[0].document_name, "doc1");
     }
 }
@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
+
+use super::config_commands::CommandResult;
+use super::CommandError;
+
+/// Cancellation tokens of in-flight chat requests, keyed by the caller's request id
+/// Shared by the streaming and non-streaming chat commands, so `cancel_request` stops
+/// either kind the same way.
+#[derive(Default)]
+pub struct RequestRegistry {
+    tokens: std::sync::Mutex<HashMap<String, CancellationToken>>,
+}
+
+impl RequestRegistry {
+    /// Track a request until the returned guard is dropped
+    /// Fails if a request with the same id is still in flight.
+    pub fn register(self: &Arc<Self>, request_id: &str) -> Result<RequestGuard, CommandError> {
+        let mut tokens = self.tokens.lock().unwrap_or_else(|e| e.into_inner());
+        if tokens.contains_key(request_id) {
+            return Err(CommandError::Validation(format!(
+                "Request {} is already in flight",
+                request_id
+            )));
+        }
+
+        let token = CancellationToken::new();
+        tokens.insert(request_id.to_string(), token.clone());
+        Ok(RequestGuard {
+            registry: self.clone(),
+            request_id: request_id.to_string(),
+            token,
+        })
+    }
+
+    /// Cancel an in-flight request; false if no request has this id
+    pub fn cancel(&self, request_id: &str) -> bool {
+        let tokens = self.tokens.lock().unwrap_or_else(|e| e.into_inner());
+        match tokens.get(request_id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// A registered request; unregisters it when dropped
+pub struct RequestGuard {
+    registry: Arc<RequestRegistry>,
+    request_id: String,
+    token: CancellationToken,
+}
+
+impl RequestGuard {
+    /// Token cancelled by `cancel_request`, for work that outlives the command
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    /// Run `future` unless the request is cancelled first
+    /// On cancel the future is dropped, aborting any HTTP request it has in flight.
+    pub async fn until_cancelled<T>(
+        &self,
+        future: impl Future<Output = Result<T, CommandError>>,
+    ) -> Result<T, CommandError> {
+        tokio::select! {
+            result = future => result,
+            _ = self.token.cancelled() => Err(CommandError::Cancelled(format!(
+                "Request {} was cancelled",
+                self.request_id
+            ))),
+        }
+    }
+}
+
+impl Drop for RequestGuard {
+    fn drop(&mut self) {
+        let mut tokens = self
+            .registry
+            .tokens
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        tokens.remove(&self.request_id);
+    }
+}
+
+/// Register `request_id` if the caller gave one, so the request can be cancelled
+pub(crate) fn register_optional(
+    registry: &Arc<RequestRegistry>,
+    request_id: Option<&str>,
+) -> Result<Option<RequestGuard>, CommandError> {
+    request_id
+        .map(|request_id| registry.register(request_id))
+        .transpose()
+}
+
+/// Run `future`, cancellable through `guard` when there is one
+pub(crate) async fn run_cancellable<T>(
+    guard: Option<&RequestGuard>,
+    future: impl Future<Output = Result<T, CommandError>>,
+) -> Result<T, CommandError> {
+    match guard {
+        Some(guard) => guard.until_cancelled(future).await,
+        None => future.await,
+    }
+}
+
+/// Cancel an in-flight chat request (streaming or not) by the id it was started with
+/// A cancelled non-streaming command fails with a "cancelled" error; a cancelled stream
+/// ends with what it received so far. Returns false if no such request is running.
+#[tauri::command]
+pub async fn cancel_request(
+    requests: tauri::State<'_, Arc<RequestRegistry>>,
+    request_id: String,
+) -> Result<CommandResult<bool>, String> {
+    Ok(CommandResult::ok(requests.cancel(&request_id)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_cancel_aborts_registered_request() {
+        let registry = Arc::new(RequestRegistry::default());
+        let guard = registry.register("req-1").unwrap();
+        assert!(registry.register("req-1").is_err());
+
+        let cancel = {
+            let registry = registry.clone();
+            async move {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                assert!(registry.cancel("req-1"));
+            }
+        };
+        let slow = async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(())
+        };
+        let (result, ()) = tokio::join!(guard.until_cancelled(slow), cancel);
+        assert!(matches!(result, Err(CommandError::Cancelled(_))));
+
+        drop(guard);
+        assert!(!registry.cancel("req-1"));
+        assert!(registry.register("req-1").is_ok());
+    }
+}
@@ -0,0 +1,100 @@
+use serde::Serialize;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use super::config_commands::CommandResult;
+use super::CommandError;
+use crate::config::{ConfigError, ConfigStore};
+use crate::rag::RagDatabase;
+use crate::security::delete_master_key;
+use crate::security::keychain::KeychainError;
+
+/// Text `factory_reset` must be given, so it is never run by accident
+pub const FACTORY_RESET_CONFIRMATION: &str = "DELETE ALL DATA";
+
+#[derive(Debug, Serialize)]
+pub struct FactoryResetResult {
+    /// Files and directories that were deleted
+    pub removed_paths: Vec<String>,
+    /// Whether the master key was removed from the OS keychain
+    pub master_key_deleted: bool,
+}
+
+/// Delete the keychain master key, the RAG database and the config files
+/// The key goes first, so a keychain error leaves everything in place. The store is
+/// marked as reset before any file is deleted, so nothing can write a new config
+/// with the old key.
+pub(crate) async fn reset_all(
+    db: &RagDatabase,
+    store: &ConfigStore,
+) -> Result<FactoryResetResult, CommandError> {
+    // A passphrase-derived key was never stored in the keychain
+    let master_key_deleted = store.uses_keychain()
+        && match delete_master_key() {
+            Ok(()) => true,
+            Err(KeychainError::KeyringError(keyring::Error::NoEntry)) => false,
+            Err(e) => return Err(ConfigError::from(e).into()),
+        };
+
+    store.mark_reset();
+    let mut removed = db.delete_files().await?;
+    removed.extend(store.delete_files()?);
+
+    tracing::warn!("Factory reset removed {} paths", removed.len());
+    Ok(FactoryResetResult {
+        removed_paths: removed
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect(),
+        master_key_deleted,
+    })
+}
+
+/// Wipe all local data: the RAG database, `config.enc` and the keychain master key
+/// `confirmation` must equal `FACTORY_RESET_CONFIRMATION`. The app has to be restarted
+/// afterwards, as the database is closed and the config key is gone.
+#[tauri::command]
+pub async fn factory_reset(
+    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    config_store: tauri::State<'_, Arc<Mutex<ConfigStore>>>,
+    confirmation: String,
+) -> Result<CommandResult<FactoryResetResult>, String> {
+    if confirmation != FACTORY_RESET_CONFIRMATION {
+        return Ok(CommandResult::err(CommandError::Validation(format!(
+            "Confirm the reset by passing \"{}\"",
+            FACTORY_RESET_CONFIRMATION
+        ))));
+    }
+
+    let db = rag_db.lock().await;
+    let store = config_store.lock().await;
+    match reset_all(&db, &store).await {
+        Ok(result) => Ok(CommandResult::ok(result)),
+        Err(e) => Ok(CommandResult::err(e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_support::{config_store, rag_database};
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_reset_all() {
+        let dir = TempDir::new().unwrap();
+        let db = rag_database(&dir).await;
+        let store = config_store(&dir);
+
+        let result = reset_all(&db, &store).await.unwrap();
+        assert!(!result.master_key_deleted);
+        assert!(!dir.path().join("rag.db").exists());
+        assert!(!dir.path().join("config.enc").exists());
+        assert!(!dir.path().join("key.salt").exists());
+
+        // The old key must not be used to write a fresh config
+        let err = store.set_offline_mode(true).unwrap_err();
+        assert!(matches!(err, ConfigError::ResetPending));
+        assert!(!dir.path().join("config.enc").exists());
+    }
+}
@@ -0,0 +1,104 @@
+//! Fixtures shared by the command tests
+
+use super::rag_commands::{RagChatRequest, RagSearchRequest};
+use crate::config::{ConfigStore, KeySource, ProviderUpdate};
+use crate::llm_providers::{ChatMessage, ChatRequest, ChatRole};
+use crate::rag::RagDatabase;
+use std::sync::Arc;
+use tempfile::TempDir;
+use tokio::sync::Mutex;
+
+/// A passphrase-encrypted config store in `dir` with the mock provider enabled
+pub(crate) fn config_store(dir: &TempDir) -> ConfigStore {
+    let store = ConfigStore::new(
+        dir.path().to_path_buf(),
+        KeySource::Passphrase("test".to_string()),
+    )
+    .unwrap();
+    store
+        .update_provider(
+            "mock".to_string(),
+            ProviderUpdate {
+                enabled: Some(true),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    store
+}
+
+/// An empty RAG database in `dir`
+pub(crate) async fn rag_database(dir: &TempDir) -> RagDatabase {
+    let db_path = dir.path().join("rag.db");
+    std::fs::File::create(&db_path).unwrap();
+    RagDatabase::new(db_path, 1).await.unwrap()
+}
+
+/// Both stores, shared the way the app manages them
+pub(crate) async fn stores(dir: &TempDir) -> (Arc<Mutex<RagDatabase>>, Arc<Mutex<ConfigStore>>) {
+    let db = rag_database(dir).await;
+    (
+        Arc::new(Mutex::new(db)),
+        Arc::new(Mutex::new(config_store(dir))),
+    )
+}
+
+/// A non-streaming chat request with one user message and no sampling options;
+/// tests change the fields they need with struct update syntax
+pub(crate) fn chat_request(content: &str) -> ChatRequest {
+    ChatRequest {
+        model: "model".to_string(),
+        messages: vec![ChatMessage {
+            role: ChatRole::User,
+            content: content.to_string(),
+        }],
+        temperature: None,
+        max_tokens: None,
+        top_p: None,
+        frequency_penalty: None,
+        presence_penalty: None,
+        seed: None,
+        response_format: None,
+        stream: false,
+    }
+}
+
+/// A search of `project_id` with the mock provider, returning the top 2 chunks
+pub(crate) fn search_request(project_id: i64, query: &str) -> RagSearchRequest {
+    RagSearchRequest {
+        project_id,
+        query: query.to_string(),
+        provider_id: "mock".to_string(),
+        top_k: 2,
+        metric: None,
+        min_similarity: None,
+        context_window: None,
+        ef_search: None,
+        explain: false,
+        document_ids: None,
+        name_like: None,
+    }
+}
+
+/// A RAG chat over `project_id` with the mock provider and its top 2 chunks as context
+pub(crate) fn rag_chat_request(project_id: i64, query: &str) -> RagChatRequest {
+    RagChatRequest {
+        project_id,
+        query: query.to_string(),
+        provider_id: "mock".to_string(),
+        model: "mock-model".to_string(),
+        top_k: 2,
+        temperature: None,
+        max_tokens: None,
+        max_context_tokens: None,
+        history: None,
+        conversation_id: None,
+        metric: None,
+        min_similarity: None,
+        context_window: None,
+        stream_capacity: None,
+        json_schema: None,
+        document_ids: None,
+        name_like: None,
+    }
+}
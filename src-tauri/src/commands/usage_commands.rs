@@ -0,0 +1,110 @@
+use crate::pricing::estimate_cost;
+use crate::rag::{RagDatabase, UsageGroupBy};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use super::config_commands::CommandResult;
+use super::CommandError;
+
+#[derive(Debug, Serialize)]
+pub struct UsageSummaryEntry {
+    /// Provider id, model, conversation id or day (YYYY-MM-DD), per `group_by`;
+    /// None for usage outside a conversation
+    pub group_key: Option<String>,
+    pub requests: i64,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    /// Cost at list prices of the models with a known price
+    pub estimated_cost_usd: f64,
+    /// Requests to models with no known price, which the cost leaves out
+    pub unpriced_requests: i64,
+}
+
+/// Record the token usage of a chat completion
+/// Failures are logged rather than returned, so they never fail the chat itself.
+pub(crate) async fn record_usage(
+    rag_db: &Arc<Mutex<RagDatabase>>,
+    provider_id: &str,
+    model: &str,
+    prompt_tokens: u32,
+    completion_tokens: u32,
+    conversation_id: Option<i64>,
+) {
+    let db = rag_db.lock().await;
+    if let Err(e) = db
+        .record_usage(
+            provider_id,
+            model,
+            prompt_tokens,
+            completion_tokens,
+            conversation_id,
+        )
+        .await
+    {
+        tracing::error!("Failed to record usage: {}", e);
+    }
+}
+
+/// Get token usage and estimated cost between `from` (inclusive) and `to` (exclusive)
+#[tauri::command]
+pub async fn get_usage_summary(
+    rag_db: tauri::State<'_, Arc<Mutex<RagDatabase>>>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    group_by: Option<UsageGroupBy>,
+) -> Result<CommandResult<Vec<UsageSummaryEntry>>, String> {
+    if let (Some(from), Some(to)) = (from, to) {
+        if from >= to {
+            return Ok(CommandResult::err(CommandError::Validation(
+                "from must be before to".to_string(),
+            )));
+        }
+    }
+
+    let db = rag_db.lock().await;
+    let totals = match db
+        .get_usage_summary(from, to, group_by.unwrap_or_default())
+        .await
+    {
+        Ok(totals) => totals,
+        Err(e) => return Ok(CommandResult::err(e)),
+    };
+    drop(db);
+
+    // Totals come per group and model, ordered by group; price each model, then merge
+    let mut entries: Vec<UsageSummaryEntry> = Vec::new();
+    for total in totals {
+        let cost = estimate_cost(
+            &total.model,
+            total.prompt_tokens as usize,
+            total.completion_tokens as usize,
+        );
+
+        let entry = match entries.last_mut() {
+            Some(entry) if entry.group_key == total.group_key => entry,
+            _ => {
+                entries.push(UsageSummaryEntry {
+                    group_key: total.group_key,
+                    requests: 0,
+                    prompt_tokens: 0,
+                    completion_tokens: 0,
+                    estimated_cost_usd: 0.0,
+                    unpriced_requests: 0,
+                });
+                entries.last_mut().unwrap()
+            }
+        };
+
+        entry.requests += total.requests;
+        entry.prompt_tokens += total.prompt_tokens;
+        entry.completion_tokens += total.completion_tokens;
+        match cost {
+            Some(cost) => entry.estimated_cost_usd += cost,
+            None => entry.unpriced_requests += total.requests,
+        }
+    }
+
+    Ok(CommandResult::ok(entries))
+}
@@ -1,3 +1,6 @@
 pub mod store;
 
-pub use store::{ConfigStore, ProviderConfig, AppConfig, MaskedProviderConfig};
+pub use store::{
+    AppConfig, AuthScheme, ConfigError, ConfigExport, ConfigImportSummary, ConfigStore, KeySource,
+    MaskedProviderConfig, ProviderConfig, ProviderUpdate,
+};
@@ -1,9 +1,17 @@
-use crate::security::{decrypt, encrypt, get_master_key};
+use crate::llm_providers::is_known_provider;
+use crate::llm_providers::offline::OFFLINE_MODEL;
+use crate::security::{
+    decrypt, derive_master_key, encrypt_with, get_master_key, read_master_key, Cipher,
+};
+use crate::validation::ValidationLimits;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use thiserror::Error;
+use zeroize::{Zeroize, Zeroizing};
 
 #[derive(Error, Debug)]
 pub enum ConfigError {
@@ -19,31 +27,197 @@ pub enum ConfigError {
     #[error("Keychain error: {0}")]
     KeychainError(#[from] crate::security::keychain::KeychainError),
 
+    #[error("Passphrase error: {0}")]
+    PassphraseError(#[from] crate::security::passphrase::PassphraseError),
+
     #[error("Provider '{0}' not found")]
     ProviderNotFound(String),
+
+    #[error("Provider '{0}' is disabled")]
+    ProviderDisabled(String),
+
+    #[error("No model given and provider '{0}' has no default model")]
+    NoModel(String),
+
+    #[error("The master key in the OS keychain no longer matches the one in use")]
+    MasterKeyChanged,
+
+    #[error("Invalid config export: {0}")]
+    InvalidExport(String),
+
+    #[error("All local data was reset; restart the app before changing the config")]
+    ResetPending,
+}
+
+/// How the API key is sent to an OpenAI-compatible endpoint
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthScheme {
+    /// `Authorization: Bearer <key>`
+    #[default]
+    Bearer,
+    /// `x-api-key: <key>`
+    XApiKey,
+}
+
+/// Environment variable whose value replaces the stored keys of `provider_id`
+/// `LLMWB_<PROVIDER_ID>_API_KEY`, upper-cased with other characters than letters and
+/// digits turned into `_`, e.g. `LLMWB_OPENAI_API_KEY`.
+pub fn api_key_env_var(provider_id: &str) -> String {
+    let id: String = provider_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("LLMWB_{}_API_KEY", id)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProviderConfig {
     pub provider_id: String,
-    pub api_key: String, // Encrypted when stored, decrypted when loaded
+    /// Legacy single-key field, migrated into `api_keys` on load
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub api_key: String,
+    #[serde(default)]
+    pub api_keys: Vec<String>, // Encrypted when stored, decrypted when loaded
     #[serde(default)]
     pub base_url: Option<String>,
     #[serde(default)]
     pub default_model: Option<String>,
+    /// Model used for embeddings; None uses the provider's default
+    #[serde(default)]
+    pub embedding_model: Option<String>,
     #[serde(default)]
     pub enabled: bool,
+    /// Only used by the "openai-compatible" provider
+    #[serde(default)]
+    pub auth_scheme: AuthScheme,
+    /// Providers to try, in order, when this one is rate limited or down
+    #[serde(default)]
+    pub fallback_provider_ids: Vec<String>,
+    /// Request budget shared by every caller of this provider; None means unlimited
+    #[serde(default)]
+    pub requests_per_minute: Option<u32>,
+    /// Log request and response bodies at debug level (target `provider_requests`),
+    /// with the API key redacted
+    #[serde(default)]
+    pub log_requests: bool,
+    /// Whether `api_keys` holds the key from `api_key_env_var` rather than the stored ones
+    #[serde(skip)]
+    pub api_key_from_env: bool,
+    /// Whether requests go to the local offline stub because `offline_mode` is on
+    #[serde(skip)]
+    pub offline: bool,
+    /// Round-robin position across `api_keys`, shared by every config the store hands out
+    /// for this provider so rotation carries over from one request to the next
+    #[serde(skip)]
+    pub key_cursor: Arc<AtomicUsize>,
+}
+
+/// Decrypted API keys are wiped from memory when a config, or any copy of it, is dropped
+impl Drop for ProviderConfig {
+    fn drop(&mut self) {
+        self.api_key.zeroize();
+        self.api_keys.zeroize();
+    }
 }
 
 impl ProviderConfig {
+    /// A disabled provider with no keys or settings
+    pub fn empty(provider_id: String) -> Self {
+        Self {
+            provider_id,
+            api_key: String::new(),
+            api_keys: Vec::new(),
+            base_url: None,
+            default_model: None,
+            embedding_model: None,
+            enabled: false,
+            auth_scheme: AuthScheme::default(),
+            fallback_provider_ids: Vec::new(),
+            requests_per_minute: None,
+            log_requests: false,
+            api_key_from_env: false,
+            offline: false,
+            key_cursor: Arc::default(),
+        }
+    }
+
+    /// Route this provider's requests to the offline stub when `offline` is set
+    /// A provider without a default model gets the stub's, so chats need no setup.
+    pub fn with_offline_mode(mut self, offline: bool) -> Self {
+        if offline {
+            self.offline = true;
+            self.default_model
+                .get_or_insert_with(|| OFFLINE_MODEL.to_string());
+        }
+        self
+    }
+
+    /// Use the key from the provider's environment variable in place of the stored keys
+    /// The environment takes precedence when the variable is set and not empty; the key
+    /// is only held in memory and never saved.
+    pub fn with_env_key(mut self) -> Self {
+        if let Ok(key) = std::env::var(api_key_env_var(&self.provider_id)) {
+            if !key.trim().is_empty() {
+                self.set_api_keys(vec![key.trim().to_string()]);
+                self.api_key_from_env = true;
+            }
+        }
+        self
+    }
+
+    /// Replace the API keys, wiping the old ones from memory
+    pub fn set_api_keys(&mut self, keys: Vec<String>) {
+        self.api_keys.zeroize();
+        self.api_keys = keys;
+    }
+
+    /// Move a legacy single `api_key` into `api_keys`
+    pub fn migrate_legacy_key(&mut self) {
+        if self.api_key.is_empty() {
+            return;
+        }
+        let key = std::mem::take(&mut self.api_key);
+        if !self.api_keys.contains(&key) {
+            self.api_keys.insert(0, key);
+        }
+    }
+
+    /// The model to use for a request: `requested`, or `default_model` when it's empty
+    pub fn resolve_model(&self, requested: &str) -> Result<String, ConfigError> {
+        let requested = requested.trim();
+        if !requested.is_empty() {
+            return Ok(requested.to_string());
+        }
+        self.default_model
+            .as_deref()
+            .map(str::trim)
+            .filter(|model| !model.is_empty())
+            .map(str::to_string)
+            .ok_or_else(|| ConfigError::NoModel(self.provider_id.clone()))
+    }
+
     /// Create a masked version for safe frontend display
     pub fn masked(&self) -> MaskedProviderConfig {
         MaskedProviderConfig {
             provider_id: self.provider_id.clone(),
-            has_api_key: !self.api_key.is_empty(),
+            has_api_key: !self.api_keys.is_empty(),
+            api_key_count: self.api_keys.len(),
             base_url: self.base_url.clone(),
             default_model: self.default_model.clone(),
+            embedding_model: self.embedding_model.clone(),
             enabled: self.enabled,
+            auth_scheme: self.auth_scheme,
+            fallback_provider_ids: self.fallback_provider_ids.clone(),
+            requests_per_minute: self.requests_per_minute,
+            log_requests: self.log_requests,
+            api_key_from_env: self.api_key_from_env,
         }
     }
 }
@@ -52,9 +226,75 @@ impl ProviderConfig {
 pub struct MaskedProviderConfig {
     pub provider_id: String,
     pub has_api_key: bool,
+    pub api_key_count: usize,
     pub base_url: Option<String>,
     pub default_model: Option<String>,
+    pub embedding_model: Option<String>,
     pub enabled: bool,
+    pub auth_scheme: AuthScheme,
+    pub fallback_provider_ids: Vec<String>,
+    pub requests_per_minute: Option<u32>,
+    #[serde(default)]
+    pub log_requests: bool,
+    /// The key comes from the environment and overrides any stored one
+    #[serde(default)]
+    pub api_key_from_env: bool,
+}
+
+impl MaskedProviderConfig {
+    /// Copy every setting except the keys onto `provider`
+    fn apply_settings(&self, provider: &mut ProviderConfig) {
+        provider.base_url = self.base_url.clone();
+        provider.default_model = self.default_model.clone();
+        provider.embedding_model = self.embedding_model.clone();
+        provider.enabled = self.enabled;
+        provider.auth_scheme = self.auth_scheme;
+        provider.fallback_provider_ids = self.fallback_provider_ids.clone();
+        provider.requests_per_minute = self.requests_per_minute;
+        provider.log_requests = self.log_requests;
+    }
+}
+
+/// Version of the document written by `ConfigStore::export_config`
+pub const CONFIG_EXPORT_VERSION: u32 = 1;
+
+/// Shareable copy of the provider setup, with the API keys only when asked for
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigExport {
+    pub format_version: u32,
+    pub providers: Vec<MaskedProviderConfig>,
+    /// API keys by provider id, in plaintext; absent unless secrets were included
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_keys: Option<HashMap<String, Vec<String>>>,
+    #[serde(default)]
+    pub general: GeneralConfig,
+}
+
+/// Providers changed by `ConfigStore::import_config`
+#[derive(Debug, Default, Serialize)]
+pub struct ConfigImportSummary {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    /// Providers whose keys were set from the export
+    pub keys_imported: Vec<String>,
+}
+
+/// Changes to apply to a provider configuration; `None` fields are left as they are
+#[derive(Debug, Default, Deserialize)]
+pub struct ProviderUpdate {
+    /// Replaces all keys with a single one
+    pub api_key: Option<String>,
+    /// Replaces the whole key list
+    pub api_keys: Option<Vec<String>>,
+    pub base_url: Option<String>,
+    pub default_model: Option<String>,
+    pub embedding_model: Option<String>,
+    pub enabled: Option<bool>,
+    pub auth_scheme: Option<AuthScheme>,
+    pub fallback_provider_ids: Option<Vec<String>>,
+    /// 0 removes the limit
+    pub requests_per_minute: Option<u32>,
+    pub log_requests: Option<bool>,
 }
 
 #[derive(Debug, Default, Serialize, Deserialize)]
@@ -72,6 +312,15 @@ pub struct GeneralConfig {
 
     #[serde(default)]
     pub default_provider: Option<String>,
+
+    /// Input size limits, loaded into app state on startup
+    #[serde(default)]
+    pub validation_limits: ValidationLimits,
+
+    /// Answer every provider request locally with canned replies and hash-based
+    /// embeddings, without network access or API keys
+    #[serde(default)]
+    pub offline_mode: bool,
 }
 
 impl Default for GeneralConfig {
@@ -79,32 +328,116 @@ impl Default for GeneralConfig {
         Self {
             theme: "light".to_string(),
             default_provider: None,
+            validation_limits: ValidationLimits::default(),
+            offline_mode: false,
         }
     }
 }
 
+/// Where the master encryption key comes from
+#[derive(Debug, Clone)]
+pub enum KeySource {
+    /// Random key stored in the OS keychain
+    Keychain,
+    /// Key derived from a user passphrase (Argon2id, salt stored in `key.salt`)
+    Passphrase(String),
+}
+
 pub struct ConfigStore {
     config_path: PathBuf,
-    master_key: Vec<u8>,
+    /// Wiped from memory when the store is dropped
+    master_key: Zeroizing<Vec<u8>>,
+    /// Whether `master_key` came from the OS keychain rather than a passphrase
+    uses_keychain: bool,
+    /// Cipher the config is written with; files written with either cipher can be read
+    cipher: Cipher,
+    /// `ProviderConfig::key_cursor` per provider id
+    key_cursors: Mutex<HashMap<String, Arc<AtomicUsize>>>,
+    /// Set by `mark_reset`; `save` refuses to write once the files and key are gone
+    reset: AtomicBool,
 }
 
 impl ConfigStore {
     /// Create a new ConfigStore with the app config directory
-    pub fn new(app_data_dir: PathBuf) -> Result<Self, ConfigError> {
+    pub fn new(app_data_dir: PathBuf, key_source: KeySource) -> Result<Self, ConfigError> {
         // Ensure config directory exists
         fs::create_dir_all(&app_data_dir)?;
 
         let config_path = app_data_dir.join("config.enc");
-
-        // Get or create master key from OS keychain
-        let master_key = get_master_key()?;
+        let uses_keychain = matches!(key_source, KeySource::Keychain);
+
+        let master_key = Zeroizing::new(match key_source {
+            // Get or create master key from OS keychain
+            KeySource::Keychain => get_master_key()?,
+            // Derive master key from passphrase; only the salt is stored on disk
+            KeySource::Passphrase(passphrase) => {
+                let passphrase = Zeroizing::new(passphrase);
+                derive_master_key(&passphrase, &app_data_dir.join("key.salt"))?
+            }
+        });
 
         Ok(Self {
             config_path,
             master_key,
+            uses_keychain,
+            cipher: Cipher::default(),
+            key_cursors: Mutex::default(),
+            reset: AtomicBool::new(false),
         })
     }
 
+    /// Write the config with `cipher` from now on
+    pub fn with_cipher(mut self, cipher: Cipher) -> Self {
+        self.cipher = cipher;
+        self
+    }
+
+    /// Whether the master key came from the OS keychain rather than a passphrase
+    pub fn uses_keychain(&self) -> bool {
+        self.uses_keychain
+    }
+
+    /// Check the OS keychain still holds the master key in use
+    /// Always succeeds for a passphrase-derived key, which is never stored.
+    pub fn check_master_key(&self) -> Result<(), ConfigError> {
+        if !self.uses_keychain {
+            return Ok(());
+        }
+        let key = Zeroizing::new(read_master_key()?);
+        if *key != *self.master_key {
+            return Err(ConfigError::MasterKeyChanged);
+        }
+        Ok(())
+    }
+
+    /// Stop `save` from writing until the app restarts
+    /// Used by a factory reset: a config written afterwards would be encrypted with a
+    /// master key that no longer exists anywhere but in memory.
+    pub fn mark_reset(&self) {
+        self.reset.store(true, Ordering::SeqCst);
+    }
+
+    /// Delete the config file, its backup and the passphrase salt
+    /// The in-memory master key stays until the store is dropped; the keychain copy is
+    /// deleted separately. Returns the paths that were removed.
+    pub fn delete_files(&self) -> Result<Vec<PathBuf>, ConfigError> {
+        let dir = self.config_path.parent().unwrap_or(Path::new("."));
+        let paths = [
+            self.config_path.clone(),
+            self.config_path.with_extension("enc.bak"),
+            dir.join("key.salt"),
+        ];
+
+        let mut removed = Vec::new();
+        for path in paths {
+            if path.exists() {
+                fs::remove_file(&path)?;
+                removed.push(path);
+            }
+        }
+        Ok(removed)
+    }
+
     /// Load config from disk, or create default if doesn't exist
     pub fn load(&self) -> Result<AppConfig, ConfigError> {
         if !self.config_path.exists() {
@@ -113,8 +446,13 @@ impl ConfigStore {
         }
 
         let encrypted_data = fs::read_to_string(&self.config_path)?;
-        let decrypted_bytes = decrypt(&encrypted_data, &self.master_key)?;
-        let config: AppConfig = serde_json::from_slice(&decrypted_bytes)?;
+        // The plaintext holds every API key; wipe it once parsed
+        let decrypted_bytes = Zeroizing::new(decrypt(&encrypted_data, &self.master_key)?);
+        let mut config: AppConfig = serde_json::from_slice(&decrypted_bytes)?;
+
+        for provider in config.providers.values_mut() {
+            provider.migrate_legacy_key();
+        }
 
         tracing::info!("Loaded config with {} providers", config.providers.len());
         Ok(config)
@@ -122,8 +460,11 @@ impl ConfigStore {
 
     /// Save config to disk (encrypted)
     pub fn save(&self, config: &AppConfig) -> Result<(), ConfigError> {
-        let json = serde_json::to_string_pretty(config)?;
-        let encrypted = encrypt(json.as_bytes(), &self.master_key)?;
+        if self.reset.load(Ordering::SeqCst) {
+            return Err(ConfigError::ResetPending);
+        }
+        let json = Zeroizing::new(serde_json::to_string_pretty(config)?);
+        let encrypted = encrypt_with(self.cipher, json.as_bytes(), &self.master_key)?;
         fs::write(&self.config_path, encrypted)?;
 
         tracing::info!("Saved config with {} providers", config.providers.len());
@@ -134,50 +475,85 @@ impl ConfigStore {
     pub fn update_provider(
         &self,
         provider_id: String,
-        api_key: Option<String>,
-        base_url: Option<String>,
-        default_model: Option<String>,
-        enabled: Option<bool>,
+        update: ProviderUpdate,
     ) -> Result<(), ConfigError> {
         let mut config = self.load()?;
 
         let provider_config = config
             .providers
             .entry(provider_id.clone())
-            .or_insert_with(|| ProviderConfig {
-                provider_id: provider_id.clone(),
-                api_key: String::new(),
-                base_url: None,
-                default_model: None,
-                enabled: false,
-            });
+            .or_insert_with(|| ProviderConfig::empty(provider_id.clone()));
 
         // Update fields
-        if let Some(key) = api_key {
-            provider_config.api_key = key;
+        if let Some(key) = update.api_key {
+            provider_config.set_api_keys(vec![key]);
+        }
+        if let Some(keys) = update.api_keys {
+            provider_config.set_api_keys(keys.into_iter().filter(|k| !k.is_empty()).collect());
         }
-        if let Some(url) = base_url {
+        if let Some(url) = update.base_url {
             provider_config.base_url = Some(url);
         }
-        if let Some(model) = default_model {
+        if let Some(model) = update.default_model {
             provider_config.default_model = Some(model);
         }
-        if let Some(en) = enabled {
+        if let Some(model) = update.embedding_model {
+            provider_config.embedding_model = Some(model);
+        }
+        if let Some(en) = update.enabled {
             provider_config.enabled = en;
         }
+        if let Some(scheme) = update.auth_scheme {
+            provider_config.auth_scheme = scheme;
+        }
+        if let Some(ids) = update.fallback_provider_ids {
+            provider_config.fallback_provider_ids = ids;
+        }
+        if let Some(rate) = update.requests_per_minute {
+            provider_config.requests_per_minute = (rate > 0).then_some(rate);
+        }
+        if let Some(log_requests) = update.log_requests {
+            provider_config.log_requests = log_requests;
+        }
 
         self.save(&config)?;
         Ok(())
     }
 
-    /// Get a specific provider's config
+    /// Get a specific provider's config, with its key from the environment if set there
+    /// In offline mode, providers that were never set up can be used too.
+    /// Every config returned for a provider shares its key rotation cursor.
     pub fn get_provider(&self, provider_id: &str) -> Result<ProviderConfig, ConfigError> {
         let config = self.load()?;
-        config
-            .providers
-            .get(provider_id)
-            .cloned()
-            .ok_or_else(|| ConfigError::ProviderNotFound(provider_id.to_string()))
+        let offline = config.general.offline_mode;
+        let mut provider = match config.providers.get(provider_id) {
+            Some(provider) => provider.clone().with_env_key(),
+            None if offline => {
+                let mut provider = ProviderConfig::empty(provider_id.to_string());
+                provider.enabled = true;
+                provider
+            }
+            None => return Err(ConfigError::ProviderNotFound(provider_id.to_string())),
+        };
+        provider.key_cursor = self
+            .key_cursors
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(provider_id.to_string())
+            .or_default()
+            .clone();
+        Ok(provider.with_offline_mode(offline))
+    }
+
+    /// Get a provider's config for making requests, failing if the user disabled it
+    /// Settings operations (testing a connection, listing models) use `get_provider`
+    /// so a provider can be checked before it's enabled. Offline, every provider is enabled.
+    pub fn get_enabled_provider(&self, provider_id: &str) -> Result<ProviderConfig, ConfigError> {
+        let provider = self.get_provider(provider_id)?;
+        if !provider.enabled && !provider.offline {
+            return Err(ConfigError::ProviderDisabled(provider_id.to_string()));
+        }
+        Ok(provider)
     }
 
     /// Get all providers (masked for frontend)
@@ -185,7 +561,21 @@ impl ConfigStore {
         let config = self.load()?;
         Ok(config
             .providers
-            .values()
+            .into_values()
+            .map(|p| p.with_env_key().masked())
+            .collect())
+    }
+
+    /// Get the enabled providers with at least one API key (masked for frontend)
+    /// Offline, every configured provider is listed since none needs a key.
+    pub fn get_enabled_providers_masked(&self) -> Result<Vec<MaskedProviderConfig>, ConfigError> {
+        let config = self.load()?;
+        let offline = config.general.offline_mode;
+        Ok(config
+            .providers
+            .into_values()
+            .map(ProviderConfig::with_env_key)
+            .filter(|p| offline || (p.enabled && !p.api_keys.is_empty()))
             .map(|p| p.masked())
             .collect())
     }
@@ -197,6 +587,114 @@ impl ConfigStore {
         self.save(&config)?;
         Ok(())
     }
+
+    /// Turn offline mode on or off; it applies to the next provider request
+    pub fn set_offline_mode(&self, enabled: bool) -> Result<(), ConfigError> {
+        let mut config = self.load()?;
+        config.general.offline_mode = enabled;
+        self.save(&config)
+    }
+
+    /// Whether offline mode is on
+    pub fn offline_mode(&self) -> Result<bool, ConfigError> {
+        Ok(self.load()?.general.offline_mode)
+    }
+
+    /// Save the validation limits to apply from the next startup
+    pub fn set_validation_limits(&self, limits: ValidationLimits) -> Result<(), ConfigError> {
+        let mut config = self.load()?;
+        config.general.validation_limits = limits;
+        self.save(&config)
+    }
+
+    /// Export the provider settings and general config, with API keys only if `include_secrets`
+    pub fn export_config(&self, include_secrets: bool) -> Result<ConfigExport, ConfigError> {
+        let config = self.load()?;
+
+        let mut providers: Vec<MaskedProviderConfig> =
+            config.providers.values().map(|p| p.masked()).collect();
+        providers.sort_by(|a, b| a.provider_id.cmp(&b.provider_id));
+
+        let api_keys = include_secrets.then(|| {
+            config
+                .providers
+                .values()
+                .filter(|p| !p.api_keys.is_empty())
+                .map(|p| (p.provider_id.clone(), p.api_keys.clone()))
+                .collect()
+        });
+
+        Ok(ConfigExport {
+            format_version: CONFIG_EXPORT_VERSION,
+            providers,
+            api_keys,
+            general: config.general,
+        })
+    }
+
+    /// Merge an export into the config: its providers' settings, and with `import_general`
+    /// its theme and default provider. Keys from the export are only set on providers
+    /// without any, unless `overwrite_keys`. Nothing is imported if any provider is unknown.
+    pub fn import_config(
+        &self,
+        export: &ConfigExport,
+        overwrite_keys: bool,
+        import_general: bool,
+    ) -> Result<ConfigImportSummary, ConfigError> {
+        if export.format_version != CONFIG_EXPORT_VERSION {
+            return Err(ConfigError::InvalidExport(format!(
+                "unsupported format version {}",
+                export.format_version
+            )));
+        }
+        if let Some(unknown) = export
+            .providers
+            .iter()
+            .find(|provider| !is_known_provider(&provider.provider_id))
+        {
+            return Err(ConfigError::InvalidExport(format!(
+                "unknown provider {:?}",
+                unknown.provider_id
+            )));
+        }
+
+        let mut config = self.load()?;
+        let mut summary = ConfigImportSummary::default();
+
+        for imported in &export.providers {
+            let id = imported.provider_id.clone();
+            if config.providers.contains_key(&id) {
+                summary.updated.push(id.clone());
+            } else {
+                summary.added.push(id.clone());
+            }
+            let provider = config
+                .providers
+                .entry(id.clone())
+                .or_insert_with(|| ProviderConfig::empty(id.clone()));
+            imported.apply_settings(provider);
+
+            let keys = export
+                .api_keys
+                .as_ref()
+                .and_then(|keys| keys.get(&id))
+                .filter(|keys| !keys.is_empty());
+            if let Some(keys) = keys {
+                if overwrite_keys || provider.api_keys.is_empty() {
+                    provider.set_api_keys(keys.clone());
+                    summary.keys_imported.push(id);
+                }
+            }
+        }
+        // Limits and offline mode suit the machine they were set on, so the local ones are kept
+        if import_general {
+            config.general.theme = export.general.theme.clone();
+            config.general.default_provider = export.general.default_provider.clone();
+        }
+
+        self.save(&config)?;
+        Ok(summary)
+    }
 }
 
 #[cfg(test)]
@@ -207,7 +705,11 @@ mod tests {
     #[test]
     fn test_config_roundtrip() {
         let temp_dir = TempDir::new().unwrap();
-        let store = ConfigStore::new(temp_dir.path().to_path_buf()).unwrap();
+        let store = ConfigStore::new(
+            temp_dir.path().to_path_buf(),
+            KeySource::Passphrase("test".to_string()),
+        )
+        .unwrap();
 
         // Create config
         let mut config = AppConfig::default();
@@ -215,10 +717,19 @@ mod tests {
             "test".to_string(),
             ProviderConfig {
                 provider_id: "test".to_string(),
-                api_key: "secret123".to_string(),
+                api_key: String::new(),
+                api_keys: vec!["secret123".to_string(), "secret456".to_string()],
                 base_url: Some("https://api.example.com".to_string()),
                 default_model: Some("model-1".to_string()),
+                embedding_model: Some("text-embedding-004".to_string()),
                 enabled: true,
+                auth_scheme: AuthScheme::default(),
+                fallback_provider_ids: Vec::new(),
+                requests_per_minute: None,
+                log_requests: false,
+                api_key_from_env: false,
+                offline: false,
+                key_cursor: Arc::default(),
             },
         );
 
@@ -228,7 +739,279 @@ mod tests {
 
         assert_eq!(loaded.providers.len(), 1);
         let provider = loaded.providers.get("test").unwrap();
-        assert_eq!(provider.api_key, "secret123");
-        assert_eq!(provider.base_url.as_deref(), Some("https://api.example.com"));
+        assert_eq!(provider.api_keys, vec!["secret123", "secret456"]);
+        assert_eq!(provider.masked().api_key_count, 2);
+        assert_eq!(
+            provider.base_url.as_deref(),
+            Some("https://api.example.com")
+        );
+        assert_eq!(
+            provider.embedding_model.as_deref(),
+            Some("text-embedding-004")
+        );
+    }
+
+    #[test]
+    fn test_passphrase_key_source_roundtrip() {
+        let temp_dir = TempDir::new().unwrap();
+        let passphrase = KeySource::Passphrase("hunter2".to_string());
+        let store = ConfigStore::new(temp_dir.path().to_path_buf(), passphrase.clone()).unwrap();
+
+        let mut config = AppConfig::default();
+        config.providers.insert(
+            "test".to_string(),
+            ProviderConfig {
+                provider_id: "test".to_string(),
+                api_key: String::new(),
+                api_keys: vec!["secret123".to_string()],
+                base_url: None,
+                default_model: None,
+                embedding_model: None,
+                enabled: true,
+                auth_scheme: AuthScheme::default(),
+                fallback_provider_ids: Vec::new(),
+                requests_per_minute: None,
+                log_requests: false,
+                api_key_from_env: false,
+                offline: false,
+                key_cursor: Arc::default(),
+            },
+        );
+        store.save(&config).unwrap();
+
+        // Only the salt is stored alongside the config
+        assert!(temp_dir.path().join("key.salt").exists());
+
+        // Reopening with the same passphrase decrypts the config
+        let reopened = ConfigStore::new(temp_dir.path().to_path_buf(), passphrase).unwrap();
+        let loaded = reopened.load().unwrap();
+        assert_eq!(
+            loaded.providers.get("test").unwrap().api_keys,
+            vec!["secret123"]
+        );
+
+        // A different passphrase cannot
+        let wrong = KeySource::Passphrase("wrong".to_string());
+        let wrong_store = ConfigStore::new(temp_dir.path().to_path_buf(), wrong).unwrap();
+        assert!(wrong_store.load().is_err());
+    }
+
+    #[test]
+    fn test_config_readable_across_ciphers() {
+        let temp_dir = TempDir::new().unwrap();
+        let passphrase = KeySource::Passphrase("test".to_string());
+        let aes_store = ConfigStore::new(temp_dir.path().to_path_buf(), passphrase.clone())
+            .unwrap()
+            .with_cipher(Cipher::Aes256Gcm);
+
+        let mut config = AppConfig::default();
+        config.general.theme = "dark".to_string();
+        aes_store.save(&config).unwrap();
+
+        // Switching cipher keeps existing files readable
+        let store = ConfigStore::new(temp_dir.path().to_path_buf(), passphrase).unwrap();
+        assert_eq!(store.load().unwrap().general.theme, "dark");
+    }
+
+    #[test]
+    fn test_delete_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let passphrase = KeySource::Passphrase("test".to_string());
+        let store = ConfigStore::new(temp_dir.path().to_path_buf(), passphrase).unwrap();
+        store.save(&AppConfig::default()).unwrap();
+
+        let removed = store.delete_files().unwrap();
+        assert_eq!(removed.len(), 2);
+        assert!(!temp_dir.path().join("config.enc").exists());
+        assert!(!temp_dir.path().join("key.salt").exists());
+        assert!(store.delete_files().unwrap().is_empty());
+
+        store.mark_reset();
+        assert!(matches!(
+            store.save(&AppConfig::default()),
+            Err(ConfigError::ResetPending)
+        ));
+        assert!(!temp_dir.path().join("config.enc").exists());
+    }
+
+    #[test]
+    fn test_export_import_config() {
+        let temp_dir = TempDir::new().unwrap();
+        let passphrase = KeySource::Passphrase("test".to_string());
+        let store = ConfigStore::new(temp_dir.path().to_path_buf(), passphrase).unwrap();
+        store
+            .update_provider(
+                "claude".to_string(),
+                ProviderUpdate {
+                    api_key: Some("sk-mine".to_string()),
+                    default_model: Some("gpt-4o".to_string()),
+                    enabled: Some(true),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        let shared = store.export_config(false).unwrap();
+        assert!(shared.api_keys.is_none());
+        assert!(!serde_json::to_string(&shared).unwrap().contains("sk-mine"));
+
+        // Settings merge in, but an existing key is kept unless asked to overwrite
+        let mut incoming = store.export_config(true).unwrap();
+        incoming.providers[0].default_model = Some("gpt-4o-mini".to_string());
+        incoming.api_keys = Some(HashMap::from([(
+            "claude".to_string(),
+            vec!["sk-theirs".to_string()],
+        )]));
+        incoming.general.theme = "dark".to_string();
+        let summary = store.import_config(&incoming, false, false).unwrap();
+        assert_eq!(summary.updated, vec!["claude"]);
+        assert!(summary.keys_imported.is_empty());
+        let provider = store.get_provider("claude").unwrap();
+        assert_eq!(provider.default_model.as_deref(), Some("gpt-4o-mini"));
+        assert_eq!(provider.api_keys, vec!["sk-mine"]);
+        // General settings are only taken when asked for
+        assert_eq!(store.load().unwrap().general.theme, "light");
+
+        store.import_config(&incoming, true, true).unwrap();
+        assert_eq!(
+            store.get_provider("claude").unwrap().api_keys,
+            vec!["sk-theirs"]
+        );
+        assert_eq!(store.load().unwrap().general.theme, "dark");
+
+        let mut unknown = incoming.clone();
+        unknown.providers[0].provider_id = "nonexistent".to_string();
+        assert!(matches!(
+            store.import_config(&unknown, false, false),
+            Err(ConfigError::InvalidExport(_))
+        ));
+
+        incoming.format_version = CONFIG_EXPORT_VERSION + 1;
+        assert!(matches!(
+            store.import_config(&incoming, false, false),
+            Err(ConfigError::InvalidExport(_))
+        ));
+    }
+
+    #[test]
+    fn test_env_key_overrides_stored_key() {
+        assert_eq!(api_key_env_var("openai"), "LLMWB_OPENAI_API_KEY");
+        assert_eq!(
+            api_key_env_var("openai-compatible"),
+            "LLMWB_OPENAI_COMPATIBLE_API_KEY"
+        );
+
+        let temp_dir = TempDir::new().unwrap();
+        let passphrase = KeySource::Passphrase("test".to_string());
+        let store = ConfigStore::new(temp_dir.path().to_path_buf(), passphrase).unwrap();
+        store
+            .update_provider(
+                "env-override-test".to_string(),
+                ProviderUpdate {
+                    api_key: Some("stored".to_string()),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        std::env::set_var(api_key_env_var("env-override-test"), "from-env");
+        let provider = store.get_provider("env-override-test").unwrap();
+        assert_eq!(provider.api_keys, vec!["from-env"]);
+        assert!(store.get_all_providers_masked().unwrap()[0].api_key_from_env);
+
+        // Never written back
+        store
+            .update_provider("env-override-test".to_string(), ProviderUpdate::default())
+            .unwrap();
+        std::env::remove_var(api_key_env_var("env-override-test"));
+        let provider = store.get_provider("env-override-test").unwrap();
+        assert_eq!(provider.api_keys, vec!["stored"]);
+        assert!(!provider.api_key_from_env);
+    }
+
+    #[test]
+    fn test_disabled_provider_rejected() {
+        let temp_dir = TempDir::new().unwrap();
+        let passphrase = KeySource::Passphrase("test".to_string());
+        let store = ConfigStore::new(temp_dir.path().to_path_buf(), passphrase).unwrap();
+
+        let update = |enabled| ProviderUpdate {
+            api_key: Some("secret123".to_string()),
+            enabled: Some(enabled),
+            ..Default::default()
+        };
+        store
+            .update_provider("on".to_string(), update(true))
+            .unwrap();
+        store
+            .update_provider("off".to_string(), update(false))
+            .unwrap();
+
+        assert!(store.get_enabled_provider("on").is_ok());
+        assert!(matches!(
+            store.get_enabled_provider("off"),
+            Err(ConfigError::ProviderDisabled(_))
+        ));
+        assert!(store.get_provider("off").is_ok());
+
+        let enabled = store.get_enabled_providers_masked().unwrap();
+        assert_eq!(enabled.len(), 1);
+        assert_eq!(enabled[0].provider_id, "on");
+    }
+
+    #[test]
+    fn test_offline_mode_serves_any_provider() {
+        let temp_dir = TempDir::new().unwrap();
+        let passphrase = KeySource::Passphrase("test".to_string());
+        let store = ConfigStore::new(temp_dir.path().to_path_buf(), passphrase).unwrap();
+        store
+            .update_provider(
+                "off".to_string(),
+                ProviderUpdate {
+                    enabled: Some(false),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert!(store.get_provider("missing").is_err());
+
+        store.set_offline_mode(true).unwrap();
+        assert!(store.offline_mode().unwrap());
+
+        // Unconfigured and disabled providers work, keyless, with the stub's model
+        let missing = store.get_enabled_provider("missing").unwrap();
+        assert!(missing.offline);
+        assert_eq!(missing.resolve_model("").unwrap(), OFFLINE_MODEL);
+        assert!(store.get_enabled_provider("off").unwrap().offline);
+        assert_eq!(store.get_enabled_providers_masked().unwrap().len(), 1);
+
+        store.set_offline_mode(false).unwrap();
+        assert!(!store.get_provider("off").unwrap().offline);
+    }
+
+    #[test]
+    fn test_resolve_model() {
+        let json = r#"{"provider_id": "test", "default_model": "model-a"}"#;
+        let mut provider: ProviderConfig = serde_json::from_str(json).unwrap();
+
+        assert_eq!(provider.resolve_model("model-b").unwrap(), "model-b");
+        assert_eq!(provider.resolve_model("").unwrap(), "model-a");
+
+        provider.default_model = None;
+        assert!(matches!(
+            provider.resolve_model("  "),
+            Err(ConfigError::NoModel(_))
+        ));
+    }
+
+    #[test]
+    fn test_legacy_single_key_migrated() {
+        let json = r#"{"provider_id": "test", "api_key": "legacy", "enabled": true}"#;
+        let mut provider: ProviderConfig = serde_json::from_str(json).unwrap();
+        provider.migrate_legacy_key();
+
+        assert!(provider.api_key.is_empty());
+        assert_eq!(provider.api_keys, vec!["legacy"]);
+        assert_eq!(provider.masked().api_key_count, 1);
     }
 }
@@ -1,14 +1,20 @@
+use super::request_log::RequestLogger;
 use super::traits::*;
 use super::ProviderError;
+use crate::tokenizer::count_tokens;
 use async_trait::async_trait;
 use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
+/// System prompts shorter than this aren't marked for caching; the API won't cache them
+const MIN_CACHEABLE_TOKENS: usize = 1024;
+
 pub struct ClaudeProvider {
     api_key: String,
     base_url: String,
     client: reqwest::Client,
+    log: RequestLogger,
 }
 
 impl ClaudeProvider {
@@ -17,26 +23,95 @@ impl ClaudeProvider {
             api_key,
             base_url: base_url.unwrap_or_else(|| "https://api.anthropic.com".to_string()),
             client: reqwest::Client::new(),
+            log: RequestLogger::default(),
         }
     }
 
+    /// Log request and response bodies at debug level, with the API key redacted
+    pub fn with_request_logging(mut self, enabled: bool) -> Self {
+        self.log = RequestLogger::new(enabled);
+        self
+    }
+
     fn create_headers(&self) -> Result<HeaderMap, ProviderError> {
         let mut headers = HeaderMap::new();
         headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
 
-        let api_key_value = HeaderValue::from_str(&self.api_key)
-            .map_err(|e| ProviderError::ConfigError(format!("Invalid API key format: {}", e)))?;
+        let api_key_value = HeaderValue::from_str(&self.api_key).map_err(|e| {
+            ProviderError::InvalidConfiguration(format!("Invalid API key format: {}", e))
+        })?;
         headers.insert("x-api-key", api_key_value);
 
-        headers.insert(
-            "anthropic-version",
-            HeaderValue::from_static("2023-06-01"),
-        );
+        headers.insert("anthropic-version", HeaderValue::from_static("2023-06-01"));
 
         Ok(headers)
     }
 
-    fn convert_messages(&self, messages: &[ChatMessage]) -> (Option<String>, Vec<serde_json::Value>) {
+    /// Build the messages request body
+    /// The Messages API has no repetition penalties, so setting them is an error
+    /// rather than being silently dropped.
+    fn request_body(
+        &self,
+        request: &ChatRequest,
+        stream: bool,
+    ) -> Result<serde_json::Value, ProviderError> {
+        if request.frequency_penalty.is_some() || request.presence_penalty.is_some() {
+            return Err(ProviderError::UnsupportedFeature(
+                "Claude does not support frequency_penalty or presence_penalty".to_string(),
+            ));
+        }
+
+        let (system_prompt, messages) = self.convert_messages(&request.messages);
+
+        let mut body = json!({
+            "model": request.model,
+            "messages": messages,
+            "max_tokens": request.max_tokens.unwrap_or(4096),
+        });
+
+        if stream {
+            body["stream"] = json!(true);
+        }
+        // No native JSON mode: ask for it in the system prompt instead
+        let instruction = request
+            .response_format
+            .as_ref()
+            .and_then(ResponseFormat::instruction);
+        let system_prompt = match (system_prompt, instruction) {
+            (Some(system), Some(instruction)) => Some(format!("{}\n\n{}", system, instruction)),
+            (system, instruction) => system.or(instruction),
+        };
+
+        if let Some(system) = system_prompt {
+            body["system"] = Self::system_blocks(system);
+        }
+        if let Some(temp) = request.temperature {
+            body["temperature"] = json!(temp);
+        }
+        if let Some(top_p) = request.top_p {
+            body["top_p"] = json!(top_p);
+        }
+
+        Ok(body)
+    }
+
+    /// The system field: large prompts (such as RAG context) are sent as a text block
+    /// marked for prompt caching, so repeated requests reuse it at a lower cost
+    fn system_blocks(system: String) -> serde_json::Value {
+        if count_tokens(&system) < MIN_CACHEABLE_TOKENS {
+            return json!(system);
+        }
+        json!([{
+            "type": "text",
+            "text": system,
+            "cache_control": {"type": "ephemeral"}
+        }])
+    }
+
+    fn convert_messages(
+        &self,
+        messages: &[ChatMessage],
+    ) -> (Option<String>, Vec<serde_json::Value>) {
         let mut system_prompt = None;
         let mut claude_messages = Vec::new();
 
@@ -82,8 +157,20 @@ struct ClaudeContent {
 
 #[derive(Debug, Deserialize)]
 struct ClaudeUsage {
+    /// Input tokens not read from or written to the prompt cache
     input_tokens: u32,
     output_tokens: u32,
+    #[serde(default)]
+    cache_creation_input_tokens: u32,
+    #[serde(default)]
+    cache_read_input_tokens: u32,
+}
+
+impl ClaudeUsage {
+    /// All input tokens, cached or not
+    fn prompt_tokens(&self) -> u32 {
+        self.input_tokens + self.cache_creation_input_tokens + self.cache_read_input_tokens
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -115,6 +202,17 @@ struct ClaudeMessageEvent {
     usage: ClaudeUsage,
 }
 
+#[derive(Debug, Deserialize)]
+struct ClaudeModelList {
+    data: Vec<ClaudeModel>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ClaudeModel {
+    id: String,
+    display_name: Option<String>,
+}
+
 #[async_trait]
 impl LlmProvider for ClaudeProvider {
     fn id(&self) -> &'static str {
@@ -128,23 +226,8 @@ impl LlmProvider for ClaudeProvider {
     async fn chat(&self, request: ChatRequest) -> Result<ChatResponse, ProviderError> {
         let url = format!("{}/v1/messages", self.base_url);
 
-        let (system_prompt, messages) = self.convert_messages(&request.messages);
-
-        let mut body = json!({
-            "model": request.model,
-            "messages": messages,
-            "max_tokens": request.max_tokens.unwrap_or(4096),
-        });
-
-        if let Some(system) = system_prompt {
-            body["system"] = json!(system);
-        }
-        if let Some(temp) = request.temperature {
-            body["temperature"] = json!(temp);
-        }
-        if let Some(top_p) = request.top_p {
-            body["top_p"] = json!(top_p);
-        }
+        let body = self.request_body(&request, false)?;
+        self.log.request(self.id(), &self.api_key, &url, &body);
 
         let response = self
             .client
@@ -155,14 +238,13 @@ impl LlmProvider for ClaudeProvider {
             .await?;
 
         if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(ProviderError::ApiError(format!(
-                "Claude API error: {}",
-                error_text
-            )));
+            return Err(self.log.error(self.id(), &self.api_key, response).await);
         }
 
-        let claude_response: ClaudeResponse = response.json().await?;
+        let claude_response: ClaudeResponse = self
+            .log
+            .read_json(self.id(), &self.api_key, response)
+            .await?;
 
         let text = claude_response
             .content
@@ -175,11 +257,13 @@ impl LlmProvider for ClaudeProvider {
             model: claude_response.model,
             finish_reason: claude_response.stop_reason,
             usage: Some(Usage {
-                prompt_tokens: claude_response.usage.input_tokens,
+                prompt_tokens: claude_response.usage.prompt_tokens(),
                 completion_tokens: claude_response.usage.output_tokens,
-                total_tokens: claude_response.usage.input_tokens
+                total_tokens: claude_response.usage.prompt_tokens()
                     + claude_response.usage.output_tokens,
             }),
+            system_fingerprint: None,
+            reasoning_content: None,
         })
     }
 
@@ -188,29 +272,13 @@ impl LlmProvider for ClaudeProvider {
         request: ChatRequest,
         tx: tokio::sync::mpsc::Sender<ChatChunk>,
     ) -> Result<(), ProviderError> {
+        use futures::StreamExt;
         use reqwest_eventsource::{Event, EventSource};
-        use futures_util::stream::StreamExt;
 
         let url = format!("{}/v1/messages", self.base_url);
 
-        let (system_prompt, messages) = self.convert_messages(&request.messages);
-
-        let mut body = json!({
-            "model": request.model,
-            "messages": messages,
-            "max_tokens": request.max_tokens.unwrap_or(4096),
-            "stream": true,
-        });
-
-        if let Some(system) = system_prompt {
-            body["system"] = json!(system);
-        }
-        if let Some(temp) = request.temperature {
-            body["temperature"] = json!(temp);
-        }
-        if let Some(top_p) = request.top_p {
-            body["top_p"] = json!(top_p);
-        }
+        let body = self.request_body(&request, true)?;
+        self.log.request(self.id(), &self.api_key, &url, &body);
 
         let req_builder = self
             .client
@@ -223,6 +291,8 @@ impl LlmProvider for ClaudeProvider {
         while let Some(event) = event_source.next().await {
             match event {
                 Ok(Event::Message(message)) => {
+                    self.log
+                        .response(self.id(), &self.api_key, None, &message.data);
                     let event: ClaudeStreamEvent = match serde_json::from_str(&message.data) {
                         Ok(e) => e,
                         Err(e) => {
@@ -238,6 +308,7 @@ impl LlmProvider for ClaudeProvider {
                                     let _ = tx
                                         .send(ChatChunk {
                                             delta: text,
+                                            reasoning_delta: None,
                                             finish_reason: None,
                                         })
                                         .await;
@@ -250,6 +321,7 @@ impl LlmProvider for ClaudeProvider {
                                     let _ = tx
                                         .send(ChatChunk {
                                             delta: String::new(),
+                                            reasoning_delta: None,
                                             finish_reason: Some(stop_reason),
                                         })
                                         .await;
@@ -266,8 +338,9 @@ impl LlmProvider for ClaudeProvider {
                     tracing::debug!("Claude stream opened");
                 }
                 Err(e) => {
+                    let e = self.log.stream_error(self.id(), &self.api_key, e).await;
                     tracing::error!("Claude stream error: {}", e);
-                    return Err(ProviderError::ApiError(format!("Stream error: {}", e)));
+                    return Err(e);
                 }
             }
         }
@@ -275,4 +348,177 @@ impl LlmProvider for ClaudeProvider {
         event_source.close();
         Ok(())
     }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, ProviderError> {
+        let url = format!("{}/v1/models?limit=1000", self.base_url);
+
+        let response = self
+            .client
+            .get(&url)
+            .headers(self.create_headers()?)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(self.log.error(self.id(), &self.api_key, response).await);
+        }
+
+        let model_list: ClaudeModelList = self
+            .log
+            .read_json(self.id(), &self.api_key, response)
+            .await?;
+
+        Ok(model_list
+            .data
+            .into_iter()
+            .map(|m| ModelInfo {
+                display_name: m.display_name.unwrap_or_else(|| m.id.clone()),
+                id: m.id,
+                context_window: None,
+            })
+            .collect())
+    }
+
+    /// The models endpoint needs a valid key but costs nothing
+    async fn verify_credentials(&self) -> Result<(), ProviderError> {
+        self.list_models().await.map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(messages: Vec<ChatMessage>) -> ChatRequest {
+        ChatRequest {
+            model: "claude-3-5-sonnet-latest".to_string(),
+            messages,
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            seed: None,
+            response_format: None,
+            stream: false,
+        }
+    }
+
+    fn message(role: ChatRole, content: &str) -> ChatMessage {
+        ChatMessage {
+            role,
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_seed_is_ignored() {
+        let provider = ClaudeProvider::new("key".to_string(), None);
+        let body = provider
+            .request_body(
+                &ChatRequest {
+                    seed: Some(42),
+                    ..request(vec![message(ChatRole::User, "Hello")])
+                },
+                false,
+            )
+            .unwrap();
+        assert!(body.get("seed").is_none());
+    }
+
+    #[test]
+    fn test_large_system_prompt_is_marked_for_caching() {
+        let provider = ClaudeProvider::new("key".to_string(), None);
+        let context = "The invoice total is 42 euros. ".repeat(MIN_CACHEABLE_TOKENS);
+        let body = provider
+            .request_body(
+                &request(vec![
+                    message(ChatRole::System, &context),
+                    message(ChatRole::User, "What is the total?"),
+                ]),
+                false,
+            )
+            .unwrap();
+        assert_eq!(
+            body["system"],
+            json!([{
+                "type": "text",
+                "text": context,
+                "cache_control": {"type": "ephemeral"}
+            }])
+        );
+        assert!(body["messages"][0].get("cache_control").is_none());
+    }
+
+    #[test]
+    fn test_small_system_prompt_is_sent_as_plain_text() {
+        let provider = ClaudeProvider::new("key".to_string(), None);
+        let body = provider
+            .request_body(
+                &request(vec![
+                    message(ChatRole::System, "Be brief."),
+                    message(ChatRole::User, "Hello"),
+                ]),
+                false,
+            )
+            .unwrap();
+        assert_eq!(body["system"], "Be brief.");
+    }
+
+    #[test]
+    fn test_json_format_instruction_is_appended_to_system_prompt() {
+        let provider = ClaudeProvider::new("key".to_string(), None);
+        let body = provider
+            .request_body(
+                &ChatRequest {
+                    response_format: Some(ResponseFormat::JsonObject),
+                    ..request(vec![
+                        message(ChatRole::System, "You extract invoices."),
+                        message(ChatRole::User, "Hello"),
+                    ])
+                },
+                false,
+            )
+            .unwrap();
+        assert_eq!(
+            body["system"],
+            format!(
+                "You extract invoices.\n\n{}",
+                ResponseFormat::JsonObject.instruction().unwrap()
+            )
+        );
+        assert!(body.get("response_format").is_none());
+    }
+
+    #[test]
+    fn test_json_format_instruction_becomes_system_prompt() {
+        let provider = ClaudeProvider::new("key".to_string(), None);
+        let schema = json!({"type": "object"});
+        let format = ResponseFormat::JsonSchema(schema);
+        let body = provider
+            .request_body(
+                &ChatRequest {
+                    response_format: Some(format.clone()),
+                    ..request(vec![message(ChatRole::User, "Hello")])
+                },
+                false,
+            )
+            .unwrap();
+        assert_eq!(body["system"], format.instruction().unwrap());
+    }
+
+    #[test]
+    fn test_text_format_leaves_system_prompt_alone() {
+        let provider = ClaudeProvider::new("key".to_string(), None);
+        let body = provider
+            .request_body(
+                &ChatRequest {
+                    response_format: Some(ResponseFormat::Text),
+                    ..request(vec![message(ChatRole::User, "Hello")])
+                },
+                false,
+            )
+            .unwrap();
+        assert!(body.get("system").is_none());
+    }
 }
@@ -0,0 +1,288 @@
+//! Cohere embeddings and reranking. Chat is not supported.
+
+use super::request_log::RequestLogger;
+use super::traits::*;
+use super::ProviderError;
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::json;
+
+/// Embedding model used when the provider config doesn't name one
+pub const DEFAULT_COHERE_EMBEDDING_MODEL: &str = "embed-english-v3.0";
+
+/// Rerank model used when the provider config doesn't name one
+pub const DEFAULT_COHERE_RERANK_MODEL: &str = "rerank-english-v3.0";
+
+/// Maximum number of texts accepted by a single embed request
+const MAX_BATCH_EMBED_SIZE: usize = 96;
+
+/// Documents scored per rerank request; larger sets are scored in several requests
+const MAX_RERANK_DOCUMENTS: usize = 1000;
+
+pub struct CohereProvider {
+    api_key: String,
+    base_url: String,
+    embedding_model: String,
+    rerank_model: String,
+    client: reqwest::Client,
+    log: RequestLogger,
+}
+
+impl CohereProvider {
+    pub fn new(api_key: String, base_url: Option<String>) -> Self {
+        Self {
+            api_key,
+            base_url: base_url
+                .unwrap_or_else(|| "https://api.cohere.com".to_string())
+                .trim_end_matches('/')
+                .to_string(),
+            embedding_model: DEFAULT_COHERE_EMBEDDING_MODEL.to_string(),
+            rerank_model: DEFAULT_COHERE_RERANK_MODEL.to_string(),
+            client: reqwest::Client::new(),
+            log: RequestLogger::default(),
+        }
+    }
+
+    /// Use a different embedding model (e.g. "embed-multilingual-v3.0")
+    pub fn with_embedding_model(mut self, model: Option<String>) -> Self {
+        if let Some(model) = model.filter(|model| !model.trim().is_empty()) {
+            self.embedding_model = model.trim().to_string();
+        }
+        self
+    }
+
+    /// Use a different rerank model (e.g. "rerank-multilingual-v3.0")
+    pub fn with_rerank_model(mut self, model: Option<String>) -> Self {
+        if let Some(model) = model.filter(|model| !model.trim().is_empty()) {
+            self.rerank_model = model.trim().to_string();
+        }
+        self
+    }
+
+    /// Log request and response bodies at debug level, with the API key redacted
+    pub fn with_request_logging(mut self, enabled: bool) -> Self {
+        self.log = RequestLogger::new(enabled);
+        self
+    }
+
+    fn create_headers(&self) -> Result<HeaderMap, ProviderError> {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+        let auth_value =
+            HeaderValue::from_str(&format!("Bearer {}", self.api_key)).map_err(|e| {
+                ProviderError::InvalidConfiguration(format!("Invalid API key format: {}", e))
+            })?;
+        headers.insert(AUTHORIZATION, auth_value);
+
+        Ok(headers)
+    }
+
+    /// POST `body` to `path` and read the JSON response
+    async fn post<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: serde_json::Value,
+    ) -> Result<T, ProviderError> {
+        let url = format!("{}{}", self.base_url, path);
+        self.log.request(self.id(), &self.api_key, &url, &body);
+
+        let response = self
+            .client
+            .post(&url)
+            .headers(self.create_headers()?)
+            .json(&body)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(self.log.error(self.id(), &self.api_key, response).await);
+        }
+
+        self.log.read_json(self.id(), &self.api_key, response).await
+    }
+
+    /// Embed one batch of texts with a single request
+    /// Embeddings are returned in the same order as the input texts
+    async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, ProviderError> {
+        // v3 models need an input type; chunks and queries are both embedded as
+        // documents since `embed` doesn't say which it is given. Queries would match
+        // a little better as "search_query"; the limitation is noted in the settings
+        let response: CohereEmbedResponse = self
+            .post(
+                "/v1/embed",
+                json!({
+                    "texts": texts,
+                    "model": self.embedding_model,
+                    "input_type": "search_document",
+                }),
+            )
+            .await?;
+
+        if response.embeddings.len() != texts.len() {
+            return Err(ProviderError::InvalidResponse(format!(
+                "Cohere returned {} embeddings for {} texts",
+                response.embeddings.len(),
+                texts.len()
+            )));
+        }
+
+        Ok(response.embeddings)
+    }
+
+    /// Score one batch of documents; indices are relative to the batch
+    async fn rerank_batch(
+        &self,
+        query: &str,
+        documents: &[String],
+    ) -> Result<Vec<(usize, f32)>, ProviderError> {
+        let response: CohereRerankResponse = self
+            .post(
+                "/v1/rerank",
+                json!({
+                    "model": self.rerank_model,
+                    "query": query,
+                    "documents": documents,
+                }),
+            )
+            .await?;
+
+        response
+            .results
+            .into_iter()
+            .map(|r| {
+                if r.index < documents.len() {
+                    Ok((r.index, r.relevance_score))
+                } else {
+                    Err(ProviderError::InvalidResponse(format!(
+                        "Cohere ranked document {} of {}",
+                        r.index,
+                        documents.len()
+                    )))
+                }
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CohereEmbedResponse {
+    embeddings: Vec<Vec<f32>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CohereRerankResponse {
+    results: Vec<CohereRerankResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CohereRerankResult {
+    index: usize,
+    relevance_score: f32,
+}
+
+#[async_trait]
+impl LlmProvider for CohereProvider {
+    fn id(&self) -> &'static str {
+        "cohere"
+    }
+
+    fn name(&self) -> &'static str {
+        "Cohere"
+    }
+
+    async fn chat(&self, _request: ChatRequest) -> Result<ChatResponse, ProviderError> {
+        Err(ProviderError::UnsupportedFeature(
+            "Cohere provider only supports embeddings and reranking, not chat".to_string(),
+        ))
+    }
+
+    async fn stream_chat(
+        &self,
+        _request: ChatRequest,
+        _tx: tokio::sync::mpsc::Sender<ChatChunk>,
+    ) -> Result<(), ProviderError> {
+        Err(ProviderError::UnsupportedFeature(
+            "Cohere provider only supports embeddings and reranking, not chat".to_string(),
+        ))
+    }
+
+    fn embedding_model(&self) -> Option<&str> {
+        Some(&self.embedding_model)
+    }
+
+    fn embedding_dimension(&self) -> Option<usize> {
+        match self.embedding_model.as_str() {
+            "embed-english-v3.0" | "embed-multilingual-v3.0" => Some(1024),
+            "embed-english-light-v3.0" | "embed-multilingual-light-v3.0" => Some(384),
+            _ => None,
+        }
+    }
+
+    async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, ProviderError> {
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(MAX_BATCH_EMBED_SIZE) {
+            embeddings.extend(self.embed_batch(batch).await?);
+        }
+        Ok(embeddings)
+    }
+
+    async fn rerank(
+        &self,
+        query: &str,
+        documents: &[String],
+    ) -> Result<Vec<(usize, f32)>, ProviderError> {
+        // Relevance scores are absolute, so batches can be merged by score
+        let mut ranked = Vec::with_capacity(documents.len());
+        for (batch_idx, batch) in documents.chunks(MAX_RERANK_DOCUMENTS).enumerate() {
+            let offset = batch_idx * MAX_RERANK_DOCUMENTS;
+            ranked.extend(
+                self.rerank_batch(query, batch)
+                    .await?
+                    .into_iter()
+                    .map(|(idx, score)| (offset + idx, score)),
+            );
+        }
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        Ok(ranked)
+    }
+
+    /// There is no free key check, so the key is checked by embedding a single word
+    async fn verify_credentials(&self) -> Result<(), ProviderError> {
+        self.embed_batch(&["ping".to_string()]).await.map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_models() {
+        let provider = CohereProvider::new("key".to_string(), None);
+        assert_eq!(provider.embedding_model(), Some("embed-english-v3.0"));
+        assert_eq!(provider.embedding_dimension(), Some(1024));
+        assert_eq!(provider.rerank_model, DEFAULT_COHERE_RERANK_MODEL);
+
+        let provider = provider
+            .with_embedding_model(Some("embed-english-light-v3.0".to_string()))
+            .with_rerank_model(Some(" ".to_string()));
+        assert_eq!(provider.embedding_dimension(), Some(384));
+        assert_eq!(provider.rerank_model, DEFAULT_COHERE_RERANK_MODEL);
+    }
+
+    #[test]
+    fn test_parse_rerank_response() {
+        let response: CohereRerankResponse = serde_json::from_str(
+            r#"{"id": "x", "results": [
+                {"index": 2, "relevance_score": 0.91},
+                {"index": 0, "relevance_score": 0.12}
+            ], "meta": {"api_version": {"version": "1"}}}"#,
+        )
+        .unwrap();
+        assert_eq!(response.results[0].index, 2);
+        assert!((response.results[1].relevance_score - 0.12).abs() < 1e-6);
+    }
+}
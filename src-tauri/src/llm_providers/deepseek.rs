@@ -1,93 +1,33 @@
+use super::openai_compatible::OpenAiCompatibleProvider;
 use super::traits::*;
 use super::ProviderError;
+use crate::config::AuthScheme;
 use async_trait::async_trait;
-use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
-use serde::{Deserialize, Serialize};
-use serde_json::json;
 
+/// DeepSeek speaks the OpenAI chat completions API under `/v1`
+/// Its JSON mode only guarantees a JSON object, so schemas are passed in the prompt.
 pub struct DeepSeekProvider {
-    api_key: String,
-    base_url: String,
-    client: reqwest::Client,
+    inner: OpenAiCompatibleProvider,
 }
 
 impl DeepSeekProvider {
     pub fn new(api_key: String, base_url: Option<String>) -> Self {
+        let base_url = base_url.unwrap_or_else(|| "https://api.deepseek.com".to_string());
         Self {
-            api_key,
-            base_url: base_url.unwrap_or_else(|| "https://api.deepseek.com".to_string()),
-            client: reqwest::Client::new(),
+            inner: OpenAiCompatibleProvider::new(
+                api_key,
+                format!("{}/v1", base_url.trim_end_matches('/')),
+                AuthScheme::Bearer,
+            )
+            .without_json_schema(),
         }
     }
 
-    fn create_headers(&self) -> Result<HeaderMap, ProviderError> {
-        let mut headers = HeaderMap::new();
-        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-
-        let auth_value = HeaderValue::from_str(&format!("Bearer {}", self.api_key))
-            .map_err(|e| ProviderError::ConfigError(format!("Invalid API key format: {}", e)))?;
-        headers.insert(AUTHORIZATION, auth_value);
-
-        Ok(headers)
+    /// Log request and response bodies at debug level, with the API key redacted
+    pub fn with_request_logging(mut self, enabled: bool) -> Self {
+        self.inner = self.inner.with_request_logging(enabled);
+        self
     }
-
-    fn convert_messages(&self, messages: &[ChatMessage]) -> Vec<serde_json::Value> {
-        messages
-            .iter()
-            .map(|msg| {
-                json!({
-                    "role": match msg.role {
-                        ChatRole::System => "system",
-                        ChatRole::User => "user",
-                        ChatRole::Assistant => "assistant",
-                    },
-                    "content": msg.content
-                })
-            })
-            .collect()
-    }
-}
-
-#[derive(Debug, Deserialize)]
-struct DeepSeekResponse {
-    choices: Vec<DeepSeekChoice>,
-    usage: Option<DeepSeekUsage>,
-    model: String,
-}
-
-#[derive(Debug, Deserialize)]
-struct DeepSeekChoice {
-    message: DeepSeekMessage,
-    finish_reason: Option<String>,
-}
-
-#[derive(Debug, Deserialize)]
-struct DeepSeekMessage {
-    content: String,
-}
-
-#[derive(Debug, Deserialize)]
-struct DeepSeekUsage {
-    prompt_tokens: u32,
-    completion_tokens: u32,
-    total_tokens: u32,
-}
-
-#[derive(Debug, Deserialize)]
-struct DeepSeekStreamChunk {
-    choices: Vec<DeepSeekStreamChoice>,
-}
-
-#[derive(Debug, Deserialize)]
-struct DeepSeekStreamChoice {
-    delta: DeepSeekDelta,
-    finish_reason: Option<String>,
-}
-
-#[derive(Debug, Deserialize)]
-struct DeepSeekDelta {
-    #[serde(default)]
-    content: Option<String>,
 }
 
 #[async_trait]
@@ -101,50 +41,7 @@ impl LlmProvider for DeepSeekProvider {
     }
 
     async fn chat(&self, request: ChatRequest) -> Result<ChatResponse, ProviderError> {
-        let url = format!("{}/v1/chat/completions", self.base_url);
-
-        let body = json!({
-            "model": request.model,
-            "messages": self.convert_messages(&request.messages),
-            "temperature": request.temperature,
-            "max_tokens": request.max_tokens,
-            "top_p": request.top_p,
-            "stream": false,
-        });
-
-        let response = self
-            .client
-            .post(&url)
-            .headers(self.create_headers()?)
-            .json(&body)
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(ProviderError::ApiError(format!(
-                "DeepSeek API error: {}",
-                error_text
-            )));
-        }
-
-        let deepseek_response: DeepSeekResponse = response.json().await?;
-
-        let choice = deepseek_response
-            .choices
-            .first()
-            .ok_or_else(|| ProviderError::ApiError("No choices in response".to_string()))?;
-
-        Ok(ChatResponse {
-            content: choice.message.content.clone(),
-            model: deepseek_response.model,
-            finish_reason: choice.finish_reason.clone(),
-            usage: deepseek_response.usage.map(|u| Usage {
-                prompt_tokens: u.prompt_tokens,
-                completion_tokens: u.completion_tokens,
-                total_tokens: u.total_tokens,
-            }),
-        })
+        self.inner.chat(request).await
     }
 
     async fn stream_chat(
@@ -152,65 +49,14 @@ impl LlmProvider for DeepSeekProvider {
         request: ChatRequest,
         tx: tokio::sync::mpsc::Sender<ChatChunk>,
     ) -> Result<(), ProviderError> {
-        use reqwest_eventsource::{Event, EventSource};
-        use futures_util::stream::StreamExt;
-
-        let url = format!("{}/v1/chat/completions", self.base_url);
-
-        let body = json!({
-            "model": request.model,
-            "messages": self.convert_messages(&request.messages),
-            "temperature": request.temperature,
-            "max_tokens": request.max_tokens,
-            "top_p": request.top_p,
-            "stream": true,
-        });
-
-        let req_builder = self
-            .client
-            .post(&url)
-            .headers(self.create_headers()?)
-            .json(&body);
-
-        let mut event_source = EventSource::new(req_builder)?;
-
-        while let Some(event) = event_source.next().await {
-            match event {
-                Ok(Event::Message(message)) => {
-                    if message.data == "[DONE]" {
-                        break;
-                    }
-
-                    let chunk: DeepSeekStreamChunk = match serde_json::from_str(&message.data) {
-                        Ok(c) => c,
-                        Err(e) => {
-                            tracing::warn!("Failed to parse chunk: {}", e);
-                            continue;
-                        }
-                    };
+        self.inner.stream_chat(request, tx).await
+    }
 
-                    if let Some(choice) = chunk.choices.first() {
-                        if let Some(content) = &choice.delta.content {
-                            let _ = tx
-                                .send(ChatChunk {
-                                    delta: content.clone(),
-                                    finish_reason: choice.finish_reason.clone(),
-                                })
-                                .await;
-                        }
-                    }
-                }
-                Ok(Event::Open) => {
-                    tracing::debug!("DeepSeek stream opened");
-                }
-                Err(e) => {
-                    tracing::error!("DeepSeek stream error: {}", e);
-                    return Err(ProviderError::ApiError(format!("Stream error: {}", e)));
-                }
-            }
-        }
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, ProviderError> {
+        self.inner.list_models().await
+    }
 
-        event_source.close();
-        Ok(())
+    async fn verify_credentials(&self) -> Result<(), ProviderError> {
+        self.inner.verify_credentials().await
     }
 }
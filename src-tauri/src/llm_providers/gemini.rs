@@ -1,3 +1,4 @@
+use super::request_log::RequestLogger;
 use super::traits::*;
 use super::ProviderError;
 use async_trait::async_trait;
@@ -5,32 +6,195 @@ use futures::StreamExt;
 use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
 use reqwest_eventsource::{Event, EventSource};
 use serde::{Deserialize, Serialize};
-use serde_json::json;
+use serde_json::{json, Value};
+
+/// Embedding model used when the provider config doesn't name one
+const DEFAULT_EMBEDDING_MODEL: &str = "embedding-001";
+
+/// Maximum number of texts accepted by a single batchEmbedContents request
+const MAX_BATCH_EMBED_SIZE: usize = 100;
+
+/// Finish reasons meaning the candidate was withheld rather than completed
+const BLOCKED_FINISH_REASONS: &[&str] = &[
+    "SAFETY",
+    "RECITATION",
+    "BLOCKLIST",
+    "PROHIBITED_CONTENT",
+    "SPII",
+];
 
 pub struct GeminiProvider {
     api_key: String,
     base_url: String,
+    embedding_model: String,
     client: reqwest::Client,
+    log: RequestLogger,
 }
 
 impl GeminiProvider {
     pub fn new(api_key: String, base_url: Option<String>) -> Self {
         Self {
             api_key,
-            base_url: base_url.unwrap_or_else(|| {
-                "https://generativelanguage.googleapis.com/v1".to_string()
-            }),
+            base_url: base_url
+                .unwrap_or_else(|| "https://generativelanguage.googleapis.com/v1".to_string()),
+            embedding_model: DEFAULT_EMBEDDING_MODEL.to_string(),
             client: reqwest::Client::new(),
+            log: RequestLogger::default(),
+        }
+    }
+
+    /// Log request and response bodies at debug level, with the API key redacted
+    pub fn with_request_logging(mut self, enabled: bool) -> Self {
+        self.log = RequestLogger::new(enabled);
+        self
+    }
+
+    /// Use a different embedding model (e.g. "text-embedding-004")
+    pub fn with_embedding_model(mut self, model: Option<String>) -> Self {
+        if let Some(model) = model {
+            // Accept both "text-embedding-004" and "models/text-embedding-004"
+            self.embedding_model = model.trim_start_matches("models/").to_string();
         }
+        self
     }
 
-    fn create_headers(&self) -> HeaderMap {
+    /// Embed one batch of texts with a single batchEmbedContents request
+    /// Embeddings are returned in the same order as the input texts
+    async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, ProviderError> {
+        // Note: Using v1beta for batchEmbedContents support
+        let url = format!(
+            "{}/models/{}:batchEmbedContents",
+            self.base_url.replace("/v1", "/v1beta"),
+            self.embedding_model
+        );
+        let model = format!("models/{}", self.embedding_model);
+
+        // Build batch request with all texts
+        let requests: Vec<_> = texts
+            .iter()
+            .map(|text| {
+                json!({
+                    "model": model,
+                    "content": {
+                        "parts": [{"text": text}]
+                    }
+                })
+            })
+            .collect();
+
+        let body = json!({
+            "requests": requests
+        });
+        self.log.request(self.id(), &self.api_key, &url, &body);
+
+        let response = self
+            .client
+            .post(&url)
+            .headers(self.create_headers()?)
+            .json(&body)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(self.log.error(self.id(), &self.api_key, response).await);
+        }
+
+        let batch_response: GeminiBatchEmbedResponse = self
+            .log
+            .read_json(self.id(), &self.api_key, response)
+            .await?;
+
+        // The API answers positionally, so a short or empty result can't be matched to its text
+        if batch_response.embeddings.len() != texts.len() {
+            return Err(ProviderError::InvalidResponse(format!(
+                "Gemini returned {} embeddings for {} texts",
+                batch_response.embeddings.len(),
+                texts.len()
+            )));
+        }
+
+        batch_response
+            .embeddings
+            .into_iter()
+            .enumerate()
+            .map(|(idx, e)| {
+                if e.values.is_empty() {
+                    Err(ProviderError::InvalidResponse(format!(
+                        "Gemini returned an empty embedding for text {}",
+                        idx
+                    )))
+                } else {
+                    Ok(e.values)
+                }
+            })
+            .collect()
+    }
+
+    /// The API key goes in a header rather than the `key` query parameter,
+    /// so it never appears in request URLs that end up in errors and logs
+    fn create_headers(&self) -> Result<HeaderMap, ProviderError> {
         let mut headers = HeaderMap::new();
         headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-        headers
+
+        let key_value = HeaderValue::from_str(&self.api_key).map_err(|e| {
+            ProviderError::InvalidConfiguration(format!("Invalid API key format: {}", e))
+        })?;
+        headers.insert("x-goog-api-key", key_value);
+
+        Ok(headers)
     }
 
-    fn convert_messages(&self, messages: &[ChatMessage]) -> (Option<String>, Vec<serde_json::Value>) {
+    /// Build the generateContent request body (shared by streaming and non-streaming calls)
+    fn request_body(&self, request: &ChatRequest) -> Result<serde_json::Value, ProviderError> {
+        let (system_instruction, contents) = self.convert_messages(&request.messages);
+
+        let mut body = json!({
+            "contents": contents,
+            "generationConfig": {}
+        });
+
+        if let Some(system) = system_instruction {
+            body["systemInstruction"] = json!({
+                "parts": [{"text": system}]
+            });
+        }
+
+        if let Some(temp) = request.temperature {
+            body["generationConfig"]["temperature"] = json!(temp);
+        }
+        if let Some(max_tokens) = request.max_tokens {
+            body["generationConfig"]["maxOutputTokens"] = json!(max_tokens);
+        }
+        if let Some(top_p) = request.top_p {
+            body["generationConfig"]["topP"] = json!(top_p);
+        }
+        if let Some(penalty) = request.frequency_penalty {
+            body["generationConfig"]["frequencyPenalty"] = json!(penalty);
+        }
+        if let Some(penalty) = request.presence_penalty {
+            body["generationConfig"]["presencePenalty"] = json!(penalty);
+        }
+        if let Some(seed) = request.seed {
+            body["generationConfig"]["seed"] = json!(seed);
+        }
+        match &request.response_format {
+            Some(ResponseFormat::JsonObject) => {
+                body["generationConfig"]["responseMimeType"] = json!("application/json");
+            }
+            Some(ResponseFormat::JsonSchema(schema)) => {
+                body["generationConfig"]["responseMimeType"] = json!("application/json");
+                body["generationConfig"]["responseSchema"] = response_schema(schema, "schema")?;
+            }
+            Some(ResponseFormat::Text) | None => {}
+        }
+
+        Ok(body)
+    }
+
+    fn convert_messages(
+        &self,
+        messages: &[ChatMessage],
+    ) -> (Option<String>, Vec<serde_json::Value>) {
         let mut system_instruction = None;
         let mut contents = Vec::new();
 
@@ -59,22 +223,190 @@ impl GeminiProvider {
     }
 }
 
+/// Schema keywords Gemini's responseSchema accepts as they are
+const SCHEMA_KEYWORDS: &[&str] = &[
+    "format",
+    "title",
+    "description",
+    "nullable",
+    "default",
+    "example",
+    "required",
+    "propertyOrdering",
+    "minItems",
+    "maxItems",
+    "minProperties",
+    "maxProperties",
+    "minLength",
+    "maxLength",
+    "pattern",
+    "minimum",
+    "maximum",
+];
+
+/// Keywords that only annotate a JSON Schema document and can be dropped
+const IGNORED_SCHEMA_KEYWORDS: &[&str] = &["$schema", "$id", "$comment", "examples"];
+
+/// Translate a JSON Schema into the OpenAPI subset Gemini's responseSchema accepts.
+/// `["string", "null"]` types become `nullable`, `const` a one-value `enum`, an `enum`
+/// without a type gets `"type": "string"` (Gemini requires one, and only string enums
+/// are accepted) and `additionalProperties: false` is dropped (Gemini never adds
+/// unlisted properties).
+/// Anything else outside the subset, such as `$ref`, `oneOf` or `allOf`, is an error
+/// naming its location (`path`) rather than being silently ignored.
+fn response_schema(schema: &Value, path: &str) -> Result<Value, ProviderError> {
+    let unsupported = |what: String| {
+        ProviderError::UnsupportedFeature(format!(
+            "Gemini's responseSchema doesn't support {} (at {})",
+            what, path
+        ))
+    };
+    let object = schema
+        .as_object()
+        .ok_or_else(|| unsupported("a schema that isn't an object".to_string()))?;
+
+    let mut converted = serde_json::Map::new();
+    for (keyword, value) in object {
+        match keyword.as_str() {
+            "type" => match value {
+                Value::String(ty) if ty != "null" => {
+                    converted.insert("type".to_string(), value.clone());
+                }
+                Value::Array(types) => {
+                    let (nulls, others): (Vec<&Value>, Vec<&Value>) =
+                        types.iter().partition(|ty| ty.as_str() == Some("null"));
+                    match others.as_slice() {
+                        [ty] if ty.as_str().is_some() => {
+                            converted.insert("type".to_string(), (*ty).clone());
+                            if !nulls.is_empty() {
+                                converted.insert("nullable".to_string(), json!(true));
+                            }
+                        }
+                        _ => return Err(unsupported(format!("the union type {}", value))),
+                    }
+                }
+                _ => return Err(unsupported(format!("the type {}", value))),
+            },
+            "enum" => {
+                if !value
+                    .as_array()
+                    .is_some_and(|values| values.iter().all(Value::is_string))
+                {
+                    return Err(unsupported("enum values other than strings".to_string()));
+                }
+                converted.insert("enum".to_string(), value.clone());
+            }
+            "const" => {
+                if !value.is_string() {
+                    return Err(unsupported("const values other than strings".to_string()));
+                }
+                converted.insert("enum".to_string(), json!([value]));
+            }
+            "properties" => {
+                let properties = value
+                    .as_object()
+                    .ok_or_else(|| unsupported("properties that aren't an object".to_string()))?;
+                let mut converted_properties = serde_json::Map::new();
+                for (name, property) in properties {
+                    converted_properties.insert(
+                        name.clone(),
+                        response_schema(property, &format!("{}.properties.{}", path, name))?,
+                    );
+                }
+                converted.insert(
+                    "properties".to_string(),
+                    Value::Object(converted_properties),
+                );
+            }
+            "items" => {
+                converted.insert(
+                    "items".to_string(),
+                    response_schema(value, &format!("{}.items", path))?,
+                );
+            }
+            "anyOf" => {
+                let variants = value
+                    .as_array()
+                    .ok_or_else(|| unsupported("anyOf that isn't an array".to_string()))?;
+                let converted_variants = variants
+                    .iter()
+                    .enumerate()
+                    .map(|(i, variant)| response_schema(variant, &format!("{}.anyOf[{}]", path, i)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                converted.insert("anyOf".to_string(), Value::Array(converted_variants));
+            }
+            "additionalProperties" if value == &Value::Bool(false) => {}
+            keyword if SCHEMA_KEYWORDS.contains(&keyword) => {
+                converted.insert(keyword.to_string(), value.clone());
+            }
+            keyword if IGNORED_SCHEMA_KEYWORDS.contains(&keyword) => {}
+            keyword => return Err(unsupported(format!("the `{}` keyword", keyword))),
+        }
+    }
+    if converted.contains_key("enum") && !converted.contains_key("type") {
+        converted.insert("type".to_string(), json!("string"));
+    }
+
+    Ok(Value::Object(converted))
+}
+
 #[derive(Debug, Deserialize)]
 struct GeminiResponse {
+    /// Absent when the prompt itself was blocked
+    #[serde(default)]
     candidates: Vec<GeminiCandidate>,
     #[serde(rename = "usageMetadata")]
     usage_metadata: Option<GeminiUsage>,
+    #[serde(rename = "promptFeedback")]
+    prompt_feedback: Option<GeminiPromptFeedback>,
+}
+
+impl GeminiResponse {
+    /// The first candidate, or an error if the prompt or the candidate was blocked
+    /// A blocked response has no text, which would otherwise look like an empty answer.
+    fn candidate(&self) -> Result<&GeminiCandidate, ProviderError> {
+        let Some(candidate) = self.candidates.first() else {
+            return Err(
+                match self
+                    .prompt_feedback
+                    .as_ref()
+                    .and_then(|f| f.block_reason.as_deref())
+                {
+                    Some(reason) => {
+                        ProviderError::ContentBlocked(format!("prompt blocked ({})", reason))
+                    }
+                    None => ProviderError::InvalidResponse("No candidates in response".to_string()),
+                },
+            );
+        };
+
+        match candidate.finish_reason.as_deref() {
+            Some(reason) if BLOCKED_FINISH_REASONS.contains(&reason) => Err(
+                ProviderError::ContentBlocked(format!("response blocked ({})", reason)),
+            ),
+            _ => Ok(candidate),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct GeminiPromptFeedback {
+    #[serde(rename = "blockReason")]
+    block_reason: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 struct GeminiCandidate {
+    /// Absent when the candidate was blocked
+    #[serde(default)]
     content: GeminiContent,
     #[serde(rename = "finishReason")]
     finish_reason: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 struct GeminiContent {
+    #[serde(default)]
     parts: Vec<GeminiPart>,
 }
 
@@ -83,6 +415,18 @@ struct GeminiPart {
     text: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct GeminiBatchEmbedResponse {
+    #[serde(default)]
+    embeddings: Vec<GeminiEmbedding>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GeminiEmbedding {
+    #[serde(default)]
+    values: Vec<f32>,
+}
+
 #[derive(Debug, Deserialize)]
 struct GeminiUsage {
     #[serde(rename = "promptTokenCount")]
@@ -93,6 +437,21 @@ struct GeminiUsage {
     total_token_count: u32,
 }
 
+#[derive(Debug, Deserialize)]
+struct GeminiModelList {
+    #[serde(default)]
+    models: Vec<GeminiModel>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GeminiModel {
+    name: String,
+    #[serde(rename = "displayName")]
+    display_name: Option<String>,
+    #[serde(rename = "inputTokenLimit")]
+    input_token_limit: Option<u32>,
+}
+
 #[async_trait]
 impl LlmProvider for GeminiProvider {
     fn id(&self) -> &'static str {
@@ -103,57 +462,34 @@ impl LlmProvider for GeminiProvider {
         "Google Gemini"
     }
 
-    async fn chat(&self, request: ChatRequest) -> Result<ChatResponse, ProviderError> {
-        let url = format!(
-            "{}/models/{}:generateContent?key={}",
-            self.base_url, request.model, self.api_key
-        );
-
-        let (system_instruction, contents) = self.convert_messages(&request.messages);
-
-        let mut body = json!({
-            "contents": contents,
-            "generationConfig": {}
-        });
+    fn embedding_model(&self) -> Option<&str> {
+        Some(&self.embedding_model)
+    }
 
-        if let Some(system) = system_instruction {
-            body["systemInstruction"] = json!({
-                "parts": [{"text": system}]
-            });
-        }
+    async fn chat(&self, request: ChatRequest) -> Result<ChatResponse, ProviderError> {
+        let url = format!("{}/models/{}:generateContent", self.base_url, request.model);
 
-        if let Some(temp) = request.temperature {
-            body["generationConfig"]["temperature"] = json!(temp);
-        }
-        if let Some(max_tokens) = request.max_tokens {
-            body["generationConfig"]["maxOutputTokens"] = json!(max_tokens);
-        }
-        if let Some(top_p) = request.top_p {
-            body["generationConfig"]["topP"] = json!(top_p);
-        }
+        let body = self.request_body(&request)?;
+        self.log.request(self.id(), &self.api_key, &url, &body);
 
         let response = self
             .client
             .post(&url)
-            .headers(self.create_headers())
+            .headers(self.create_headers()?)
             .json(&body)
             .send()
             .await?;
 
         if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(ProviderError::ApiError(format!(
-                "Gemini API error: {}",
-                error_text
-            )));
+            return Err(self.log.error(self.id(), &self.api_key, response).await);
         }
 
-        let gemini_response: GeminiResponse = response.json().await?;
+        let gemini_response: GeminiResponse = self
+            .log
+            .read_json(self.id(), &self.api_key, response)
+            .await?;
 
-        let candidate = gemini_response
-            .candidates
-            .first()
-            .ok_or_else(|| ProviderError::ApiError("No candidates in response".to_string()))?;
+        let candidate = gemini_response.candidate()?;
 
         let text = candidate
             .content
@@ -171,6 +507,8 @@ impl LlmProvider for GeminiProvider {
                 completion_tokens: u.candidates_token_count,
                 total_tokens: u.total_token_count,
             }),
+            system_fingerprint: None,
+            reasoning_content: None,
         })
     }
 
@@ -180,39 +518,19 @@ impl LlmProvider for GeminiProvider {
         tx: tokio::sync::mpsc::Sender<ChatChunk>,
     ) -> Result<(), ProviderError> {
         let url = format!(
-            "{}/models/{}:streamGenerateContent?alt=sse&key={}",
-            self.base_url, request.model, self.api_key
+            "{}/models/{}:streamGenerateContent?alt=sse",
+            self.base_url, request.model
         );
 
-        let (system_instruction, contents) = self.convert_messages(&request.messages);
-
-        let mut body = json!({
-            "contents": contents,
-            "generationConfig": {}
-        });
-
-        if let Some(system) = system_instruction {
-            body["systemInstruction"] = json!({
-                "parts": [{"text": system}]
-            });
-        }
-
-        if let Some(temp) = request.temperature {
-            body["generationConfig"]["temperature"] = json!(temp);
-        }
-        if let Some(max_tokens) = request.max_tokens {
-            body["generationConfig"]["maxOutputTokens"] = json!(max_tokens);
-        }
-        if let Some(top_p) = request.top_p {
-            body["generationConfig"]["topP"] = json!(top_p);
-        }
+        let body = self.request_body(&request)?;
+        self.log.request(self.id(), &self.api_key, &url, &body);
 
         // Create EventSource for SSE streaming
         let event_source = EventSource::new(
             self.client
                 .post(&url)
-                .headers(self.create_headers())
-                .json(&body)
+                .headers(self.create_headers()?)
+                .json(&body),
         )?;
 
         let mut stream = event_source;
@@ -223,12 +541,23 @@ impl LlmProvider for GeminiProvider {
                     // Connection opened, continue
                 }
                 Ok(Event::Message(message)) => {
+                    self.log
+                        .response(self.id(), &self.api_key, None, &message.data);
                     // Parse the SSE message data
-                    if let Ok(gemini_response) = serde_json::from_str::<GeminiResponse>(&message.data) {
-                        if let Some(candidate) = gemini_response.candidates.first() {
+                    if let Ok(gemini_response) =
+                        serde_json::from_str::<GeminiResponse>(&message.data)
+                    {
+                        let candidate = match gemini_response.candidate() {
+                            Ok(candidate) => Some(candidate),
+                            // Blocked mid-stream: report it instead of ending as if complete
+                            Err(e @ ProviderError::ContentBlocked(_)) => return Err(e),
+                            Err(_) => None,
+                        };
+                        if let Some(candidate) = candidate {
                             if let Some(part) = candidate.content.parts.first() {
                                 let chunk = ChatChunk {
                                     delta: part.text.clone(),
+                                    reasoning_delta: None,
                                     finish_reason: candidate.finish_reason.clone(),
                                 };
 
@@ -242,11 +571,9 @@ impl LlmProvider for GeminiProvider {
                 }
                 Err(err) => {
                     // Stream error
-                    tracing::error!("Gemini SSE stream error: {}", err);
-                    return Err(ProviderError::ApiError(format!(
-                        "Stream error: {}",
-                        err
-                    )));
+                    let e = self.log.stream_error(self.id(), &self.api_key, err).await;
+                    tracing::error!("Gemini SSE stream error: {}", e);
+                    return Err(e);
                 }
             }
         }
@@ -260,66 +587,218 @@ impl LlmProvider for GeminiProvider {
             return Ok(Vec::new());
         }
 
-        // Use batch embedding endpoint for better performance
-        // Note: Using v1beta for batchEmbedContents support
-        let url = format!(
-            "{}/models/embedding-001:batchEmbedContents?key={}",
-            self.base_url.replace("/v1", "/v1beta"),
-            self.api_key
-        );
+        // batchEmbedContents accepts a limited number of texts per request
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(MAX_BATCH_EMBED_SIZE) {
+            embeddings.extend(self.embed_batch(batch).await?);
+        }
 
-        // Build batch request with all texts
-        let requests: Vec<_> = texts
-            .iter()
-            .map(|text| {
-                json!({
-                    "model": "models/embedding-001",
-                    "content": {
-                        "parts": [{"text": text}]
-                    }
-                })
-            })
-            .collect();
+        Ok(embeddings)
+    }
 
-        let body = json!({
-            "requests": requests
-        });
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, ProviderError> {
+        let url = format!("{}/models?pageSize=1000", self.base_url);
 
         let response = self
             .client
-            .post(&url)
-            .headers(self.create_headers())
-            .json(&body)
+            .get(&url)
+            .headers(self.create_headers()?)
             .send()
             .await?;
 
         if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(ProviderError::ApiError(format!(
-                "Gemini batch embedding API error: {}",
-                error_text
-            )));
+            return Err(self.log.error(self.id(), &self.api_key, response).await);
+        }
+
+        let model_list: GeminiModelList = self
+            .log
+            .read_json(self.id(), &self.api_key, response)
+            .await?;
+
+        Ok(model_list
+            .models
+            .into_iter()
+            .map(|m| {
+                // Names come back as "models/gemini-1.5-pro"; requests take the bare id
+                let id = m.name.trim_start_matches("models/").to_string();
+                ModelInfo {
+                    display_name: m.display_name.unwrap_or_else(|| id.clone()),
+                    id,
+                    context_window: m.input_token_limit,
+                }
+            })
+            .collect())
+    }
+
+    /// Listing models checks the key without spending any quota
+    async fn verify_credentials(&self) -> Result<(), ProviderError> {
+        self.list_models().await.map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json_request(schema: Value) -> ChatRequest {
+        ChatRequest {
+            model: "gemini-1.5-flash".to_string(),
+            messages: vec![ChatMessage {
+                role: ChatRole::User,
+                content: "Extract the invoice".to_string(),
+            }],
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            seed: None,
+            response_format: Some(ResponseFormat::JsonSchema(schema)),
+            stream: false,
         }
+    }
+
+    #[test]
+    fn test_json_schema_sets_mime_type_and_converted_schema() {
+        let provider = GeminiProvider::new("key".to_string(), None);
+        let body = provider
+            .request_body(&json_request(json!({
+                "$schema": "https://json-schema.org/draft/2020-12/schema",
+                "type": "object",
+                "properties": {
+                    "total": {"type": "number"},
+                    "currency": {"const": "EUR"},
+                    "notes": {"type": ["string", "null"]},
+                    "lines": {"type": "array", "items": {"type": "string"}}
+                },
+                "required": ["total"],
+                "additionalProperties": false
+            })))
+            .unwrap();
+
+        let config = &body["generationConfig"];
+        assert_eq!(config["responseMimeType"], "application/json");
+        assert_eq!(
+            config["responseSchema"],
+            json!({
+                "type": "object",
+                "properties": {
+                    "total": {"type": "number"},
+                    "currency": {"type": "string", "enum": ["EUR"]},
+                    "notes": {"type": "string", "nullable": true},
+                    "lines": {"type": "array", "items": {"type": "string"}}
+                },
+                "required": ["total"]
+            })
+        );
+    }
+
+    fn response(value: Value) -> GeminiResponse {
+        serde_json::from_value(value).unwrap()
+    }
 
-        #[derive(Deserialize)]
-        struct BatchEmbedResponse {
-            embeddings: Vec<EmbeddingData>,
+    #[test]
+    fn test_candidate_returns_completed_answer() {
+        let response = response(json!({
+            "candidates": [{
+                "content": {"parts": [{"text": "Hello"}], "role": "model"},
+                "finishReason": "STOP"
+            }]
+        }));
+        let candidate = response.candidate().unwrap();
+        assert_eq!(candidate.content.parts[0].text, "Hello");
+    }
+
+    #[test]
+    fn test_candidate_reports_blocked_prompt() {
+        let response = response(json!({
+            "promptFeedback": {"blockReason": "SAFETY"}
+        }));
+        match response.candidate() {
+            Err(ProviderError::ContentBlocked(message)) => {
+                assert_eq!(message, "prompt blocked (SAFETY)")
+            }
+            other => panic!("expected ContentBlocked, got {:?}", other),
         }
+    }
 
-        #[derive(Deserialize)]
-        struct EmbeddingData {
-            values: Vec<f32>,
+    #[test]
+    fn test_candidate_reports_blocked_response() {
+        let response = response(json!({
+            "candidates": [{"finishReason": "RECITATION"}]
+        }));
+        match response.candidate() {
+            Err(ProviderError::ContentBlocked(message)) => {
+                assert_eq!(message, "response blocked (RECITATION)")
+            }
+            other => panic!("expected ContentBlocked, got {:?}", other),
         }
+    }
+
+    #[test]
+    fn test_candidate_rejects_empty_response() {
+        let response = response(json!({"candidates": []}));
+        assert!(matches!(
+            response.candidate(),
+            Err(ProviderError::InvalidResponse(_))
+        ));
+    }
 
-        let batch_response: BatchEmbedResponse = response.json().await?;
+    #[test]
+    fn test_json_object_sets_mime_type_only() {
+        let provider = GeminiProvider::new("key".to_string(), None);
+        let body = provider
+            .request_body(&ChatRequest {
+                response_format: Some(ResponseFormat::JsonObject),
+                ..json_request(json!({}))
+            })
+            .unwrap();
+        let config = &body["generationConfig"];
+        assert_eq!(config["responseMimeType"], "application/json");
+        assert!(config.get("responseSchema").is_none());
+    }
 
-        // Extract embeddings in the same order as input
-        let embeddings: Vec<Vec<f32>> = batch_response
-            .embeddings
-            .into_iter()
-            .map(|e| e.values)
-            .collect();
+    #[test]
+    fn test_text_format_sets_no_mime_type() {
+        let provider = GeminiProvider::new("key".to_string(), None);
+        let body = provider
+            .request_body(&ChatRequest {
+                response_format: Some(ResponseFormat::Text),
+                ..json_request(json!({}))
+            })
+            .unwrap();
+        assert!(body["generationConfig"].get("responseMimeType").is_none());
+    }
 
-        Ok(embeddings)
+    #[test]
+    fn test_seed_goes_in_generation_config() {
+        let provider = GeminiProvider::new("key".to_string(), None);
+        let body = provider
+            .request_body(&ChatRequest {
+                seed: Some(42),
+                response_format: None,
+                ..json_request(json!({}))
+            })
+            .unwrap();
+        assert_eq!(body["generationConfig"]["seed"], 42);
+    }
+
+    #[test]
+    fn test_schema_outside_gemini_subset_is_rejected() {
+        let provider = GeminiProvider::new("key".to_string(), None);
+        let result = provider.request_body(&json_request(json!({
+            "type": "object",
+            "properties": {"item": {"$ref": "#/$defs/item"}}
+        })));
+        match result {
+            Err(ProviderError::UnsupportedFeature(message)) => {
+                assert!(message.contains("`$ref`"));
+                assert!(message.contains("schema.properties.item"));
+            }
+            other => panic!("expected UnsupportedFeature, got {:?}", other),
+        }
+
+        let result = provider.request_body(&json_request(json!({"type": ["string", "integer"]})));
+        assert!(matches!(result, Err(ProviderError::UnsupportedFeature(_))));
     }
 }
@@ -0,0 +1,145 @@
+//! Embeddings computed on this machine with an ONNX sentence-transformer model,
+//! so documents never leave it. Chat is not supported.
+
+use super::traits::*;
+use super::ProviderError;
+use async_trait::async_trait;
+use fastembed::{EmbeddingModel, InitOptions, TextEmbedding};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Model used when the provider config names none
+pub const DEFAULT_LOCAL_EMBEDDING_MODEL: &str = "Qdrant/all-MiniLM-L6-v2-onnx";
+
+/// Loaded models by name, shared across provider instances
+/// (providers are created per request, and loading a model takes seconds)
+fn loaded_models() -> &'static Mutex<HashMap<String, Arc<TextEmbedding>>> {
+    static MODELS: OnceLock<Mutex<HashMap<String, Arc<TextEmbedding>>>> = OnceLock::new();
+    MODELS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// The loaded model, loading (and if needed downloading) it on first use
+fn load_model(
+    model_name: &str,
+    model: EmbeddingModel,
+) -> Result<Arc<TextEmbedding>, ProviderError> {
+    let mut models = loaded_models().lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(loaded) = models.get(model_name) {
+        return Ok(loaded.clone());
+    }
+
+    tracing::info!("Loading local embedding model {}", model_name);
+    let loaded = TextEmbedding::try_new(
+        InitOptions::new(model)
+            .with_cache_dir(model_cache_dir())
+            .with_show_download_progress(false),
+    )
+    .map_err(|e| {
+        ProviderError::InvalidConfiguration(format!(
+            "Failed to load embedding model {}: {}",
+            model_name, e
+        ))
+    })?;
+
+    let loaded = Arc::new(loaded);
+    models.insert(model_name.to_string(), loaded.clone());
+    Ok(loaded)
+}
+
+/// Where downloaded model files are kept between runs
+fn model_cache_dir() -> PathBuf {
+    tauri::api::path::cache_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("llm-workbench")
+        .join("models")
+}
+
+/// Embeds text on the CPU; the model is downloaded once on first use, after which
+/// no network access is needed
+pub struct LocalEmbeddingProvider {
+    model_name: String,
+    model: EmbeddingModel,
+    dimension: usize,
+}
+
+impl LocalEmbeddingProvider {
+    /// `model_name` is a fastembed model code, e.g. "Qdrant/all-MiniLM-L6-v2-onnx"
+    pub fn new(model_name: Option<&str>) -> Result<Self, ProviderError> {
+        let model_name = model_name
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .unwrap_or(DEFAULT_LOCAL_EMBEDDING_MODEL);
+        let model: EmbeddingModel = model_name
+            .parse()
+            .map_err(ProviderError::InvalidConfiguration)?;
+        let dimension = TextEmbedding::get_model_info(&model)
+            .map_err(|e| ProviderError::InvalidConfiguration(e.to_string()))?
+            .dim;
+
+        Ok(Self {
+            model_name: model_name.to_string(),
+            model,
+            dimension,
+        })
+    }
+}
+
+#[async_trait]
+impl LlmProvider for LocalEmbeddingProvider {
+    fn id(&self) -> &'static str {
+        "local-embed"
+    }
+
+    fn name(&self) -> &'static str {
+        "Local embeddings"
+    }
+
+    async fn chat(&self, _request: ChatRequest) -> Result<ChatResponse, ProviderError> {
+        Err(ProviderError::UnsupportedFeature(
+            "Local embeddings provider does not support chat".to_string(),
+        ))
+    }
+
+    async fn stream_chat(
+        &self,
+        _request: ChatRequest,
+        _tx: tokio::sync::mpsc::Sender<ChatChunk>,
+    ) -> Result<(), ProviderError> {
+        Err(ProviderError::UnsupportedFeature(
+            "Local embeddings provider does not support chat".to_string(),
+        ))
+    }
+
+    fn embedding_model(&self) -> Option<&str> {
+        Some(&self.model_name)
+    }
+
+    fn embedding_dimension(&self) -> Option<usize> {
+        Some(self.dimension)
+    }
+
+    async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, ProviderError> {
+        // Loading and inference are CPU-bound; keep them off the async workers
+        let model_name = self.model_name.clone();
+        let model = self.model.clone();
+        tokio::task::spawn_blocking(move || {
+            load_model(&model_name, model)?
+                .embed(texts, None)
+                .map_err(|e| ProviderError::InvalidResponse(e.to_string()))
+        })
+        .await
+        .map_err(|e| ProviderError::InvalidResponse(format!("Embedding task failed: {}", e)))?
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, ProviderError> {
+        Ok(TextEmbedding::list_supported_models()
+            .into_iter()
+            .map(|info| ModelInfo {
+                id: info.model_code,
+                display_name: info.description,
+                context_window: None,
+            })
+            .collect())
+    }
+}
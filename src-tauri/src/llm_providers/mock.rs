@@ -0,0 +1,353 @@
+//! Deterministic provider for tests: no network, same output for the same input
+
+use super::offline::hash_embedding;
+use super::traits::*;
+use super::ProviderError;
+use crate::tokenizer::count_tokens;
+use async_trait::async_trait;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Dimension of the embeddings returned by `MockProvider::embed`
+pub const MOCK_EMBEDDING_DIM: usize = 16;
+
+/// Word that makes a streamed reply fail when the stream reaches it
+pub const MOCK_STREAM_ERROR: &str = "MOCK_STREAM_ERROR";
+
+/// Word that makes embedding any batch of texts containing it fail
+pub const MOCK_EMBED_ERROR: &str = "MOCK_EMBED_ERROR";
+
+/// Provider that echoes the last message back (unless given scripted replies) and
+/// embeds text by hashing its words
+/// Texts sharing words get similar embeddings, so search results are meaningful in tests.
+#[derive(Debug, Default)]
+pub struct MockProvider {
+    /// Calls answered with a 429 before any succeeds
+    rate_limited_calls: usize,
+    calls: AtomicUsize,
+    /// Replies given in order before falling back to the echo
+    replies: Mutex<VecDeque<String>>,
+    /// Replies that finish with "length", as if cut off by max_tokens
+    truncated_replies: usize,
+    /// Chat requests answered so far, streamed or not
+    requests: Mutex<Vec<ChatRequest>>,
+}
+
+impl MockProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer the first `calls` calls with a 429 and a zero `Retry-After`
+    pub fn with_rate_limits(mut self, calls: usize) -> Self {
+        self.rate_limited_calls = calls;
+        self
+    }
+
+    /// Answer chat requests with `replies`, in order, instead of the echo
+    pub fn with_replies<S: Into<String>>(self, replies: impl IntoIterator<Item = S>) -> Self {
+        *self.replies.lock().unwrap() = replies.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Have the first `count` replies finish with "length" instead of "stop"
+    pub fn with_truncated_replies(mut self, count: usize) -> Self {
+        self.truncated_replies = count;
+        self
+    }
+
+    /// Number of calls made so far (chat, stream, embed, model listing)
+    pub fn calls(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+
+    /// Chat requests answered so far, in order
+    pub fn requests(&self) -> Vec<ChatRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Count a call, failing it while rate limited calls remain
+    fn start_call(&self) -> Result<(), ProviderError> {
+        if self.calls.fetch_add(1, Ordering::SeqCst) < self.rate_limited_calls {
+            return Err(ProviderError::RateLimited {
+                retry_after: Some(Duration::ZERO),
+                body: "mock rate limit".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// The reply to a request and its finish reason: the next scripted reply, or else
+    /// the content of the request's last message cut to whole words within
+    /// `max_tokens`
+    fn reply(&self, request: &ChatRequest) -> (String, &'static str) {
+        let mut requests = self.requests.lock().unwrap();
+        let finish_reason = if requests.len() < self.truncated_replies {
+            "length"
+        } else {
+            "stop"
+        };
+        requests.push(request.clone());
+
+        if let Some(scripted) = self.replies.lock().unwrap().pop_front() {
+            return (scripted, finish_reason);
+        }
+        let content = request
+            .messages
+            .last()
+            .map(|m| m.content.clone())
+            .unwrap_or_default();
+        match request.max_tokens {
+            Some(max) if count_tokens(&content) > max as usize => {
+                let mut cut = String::new();
+                for word in content.split_inclusive(' ') {
+                    if count_tokens(format!("{}{}", cut, word).trim_end()) > max as usize {
+                        break;
+                    }
+                    cut.push_str(word);
+                }
+                (cut.trim_end().to_string(), "length")
+            }
+            _ => (content, finish_reason),
+        }
+    }
+
+    /// Bag-of-words embedding of `MOCK_EMBEDDING_DIM` buckets
+    fn embed_text(text: &str) -> Vec<f32> {
+        hash_embedding(text, MOCK_EMBEDDING_DIM)
+    }
+}
+
+#[async_trait]
+impl LlmProvider for MockProvider {
+    fn id(&self) -> &'static str {
+        "mock"
+    }
+
+    fn name(&self) -> &'static str {
+        "Mock"
+    }
+
+    fn embedding_model(&self) -> Option<&str> {
+        Some("mock-embedding")
+    }
+
+    fn embedding_dimension(&self) -> Option<usize> {
+        Some(MOCK_EMBEDDING_DIM)
+    }
+
+    async fn chat(&self, request: ChatRequest) -> Result<ChatResponse, ProviderError> {
+        self.start_call()?;
+        let (content, finish_reason) = self.reply(&request);
+        let prompt_tokens: usize = request
+            .messages
+            .iter()
+            .map(|m| count_tokens(&m.content))
+            .sum();
+        let completion_tokens = count_tokens(&content);
+
+        Ok(ChatResponse {
+            content,
+            model: request.model,
+            finish_reason: Some(finish_reason.to_string()),
+            usage: Some(Usage {
+                prompt_tokens: prompt_tokens as u32,
+                completion_tokens: completion_tokens as u32,
+                total_tokens: (prompt_tokens + completion_tokens) as u32,
+            }),
+            system_fingerprint: None,
+            reasoning_content: None,
+        })
+    }
+
+    /// Streams the reply one word at a time, ending with an empty "stop" chunk
+    /// A reply containing `MOCK_STREAM_ERROR` fails with a 500 after the words before it.
+    async fn stream_chat(
+        &self,
+        request: ChatRequest,
+        tx: tokio::sync::mpsc::Sender<ChatChunk>,
+    ) -> Result<(), ProviderError> {
+        self.start_call()?;
+        let (reply, finish_reason) = self.reply(&request);
+
+        for word in reply.split_inclusive(' ') {
+            if word.trim_end() == MOCK_STREAM_ERROR {
+                return Err(ProviderError::ApiError {
+                    status: 500,
+                    body: "mock stream interrupted".to_string(),
+                });
+            }
+            let chunk = ChatChunk {
+                delta: word.to_string(),
+                reasoning_delta: None,
+                finish_reason: None,
+            };
+            if tx.send(chunk).await.is_err() {
+                return Ok(());
+            }
+        }
+
+        let _ = tx
+            .send(ChatChunk {
+                delta: String::new(),
+                reasoning_delta: None,
+                finish_reason: Some(finish_reason.to_string()),
+            })
+            .await;
+
+        Ok(())
+    }
+
+    async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, ProviderError> {
+        self.start_call()?;
+        if texts
+            .iter()
+            .any(|text| text.split_whitespace().any(|word| word == MOCK_EMBED_ERROR))
+        {
+            return Err(ProviderError::ApiError {
+                status: 500,
+                body: "mock embedding failed".to_string(),
+            });
+        }
+        Ok(texts.iter().map(|text| Self::embed_text(text)).collect())
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, ProviderError> {
+        self.start_call()?;
+        Ok(vec![ModelInfo {
+            id: "mock-model".to_string(),
+            display_name: "Mock Model".to_string(),
+            context_window: None,
+        }])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(content: &str) -> ChatRequest {
+        ChatRequest {
+            model: "mock-model".to_string(),
+            messages: vec![ChatMessage {
+                role: ChatRole::User,
+                content: content.to_string(),
+            }],
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            seed: None,
+            response_format: None,
+            stream: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chat_echoes_last_message() {
+        let response = MockProvider::new()
+            .chat(request("hello there"))
+            .await
+            .unwrap();
+        assert_eq!(response.content, "hello there");
+        assert_eq!(response.model, "mock-model");
+    }
+
+    #[tokio::test]
+    async fn test_scripted_replies_then_echo() {
+        let provider = MockProvider::new()
+            .with_replies(["first", "second"])
+            .with_truncated_replies(1);
+
+        let first = provider.chat(request("a")).await.unwrap();
+        assert_eq!(first.content, "first");
+        assert!(first.is_truncated());
+        let second = provider.chat(request("b")).await.unwrap();
+        assert_eq!(second.content, "second");
+        assert_eq!(second.finish_reason.as_deref(), Some("stop"));
+        assert_eq!(provider.chat(request("c")).await.unwrap().content, "c");
+
+        let requests = provider.requests();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[1].messages[0].content, "b");
+    }
+
+    #[tokio::test]
+    async fn test_echo_is_cut_to_max_tokens() {
+        let provider = MockProvider::new();
+        let mut long = request("one two three four five six");
+        long.max_tokens = Some(3);
+
+        let response = provider.chat(long).await.unwrap();
+        assert_eq!(response.content, "one two three");
+        assert!(response.is_truncated());
+    }
+
+    #[tokio::test]
+    async fn test_stream_chat_reassembles_reply() {
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        MockProvider::new()
+            .stream_chat(request("one two three"), tx)
+            .await
+            .unwrap();
+
+        let mut chunks = Vec::new();
+        while let Some(chunk) = rx.recv().await {
+            chunks.push(chunk);
+        }
+        let text: String = chunks.iter().map(|c| c.delta.as_str()).collect();
+
+        assert_eq!(text, "one two three");
+        assert_eq!(chunks.len(), 4);
+        assert_eq!(
+            chunks.last().unwrap().finish_reason.as_deref(),
+            Some("stop")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_chat_fails_at_error_marker() {
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        let result = MockProvider::new()
+            .stream_chat(request(&format!("one {} two", MOCK_STREAM_ERROR)), tx)
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(ProviderError::ApiError { status: 500, .. })
+        ));
+        assert_eq!(rx.recv().await.unwrap().delta, "one ");
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_embed_is_deterministic() {
+        let provider = MockProvider::new();
+        let texts = vec!["The quick fox".to_string(), "the QUICK fox".to_string()];
+        let embeddings = provider.embed(texts).await.unwrap();
+
+        assert_eq!(embeddings[0].len(), MOCK_EMBEDDING_DIM);
+        assert_eq!(embeddings[0], embeddings[1]);
+        assert_eq!(embeddings[0].iter().sum::<f32>(), 3.0);
+    }
+
+    #[tokio::test]
+    async fn test_reported_dimension_matches_embeddings() {
+        let provider = MockProvider::new();
+        let embeddings = provider.embed(vec!["hello".to_string()]).await.unwrap();
+
+        assert_eq!(provider.embedding_dimension(), Some(embeddings[0].len()));
+    }
+
+    #[tokio::test]
+    async fn test_verify_credentials_not_applicable() {
+        // There is no API key to check
+        assert!(matches!(
+            MockProvider::new().verify_credentials().await,
+            Err(ProviderError::UnsupportedFeature(_))
+        ));
+    }
+}
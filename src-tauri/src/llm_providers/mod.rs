@@ -1,30 +1,78 @@
-pub mod traits;
+pub mod claude;
+pub mod cohere;
 pub mod deepseek;
 pub mod gemini;
-pub mod claude;
+#[cfg(feature = "local-embed")]
+pub mod local_embed;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod mock;
+pub mod offline;
+pub mod openai_compatible;
+pub mod rate_limit;
+pub mod request_log;
+pub mod rotation;
+pub mod traits;
+pub mod voyage;
 
-pub use traits::{LlmProvider, ChatRequest, ChatResponse, ChatMessage, ChatRole, ChatChunk};
+pub use claude::ClaudeProvider;
+pub use cohere::CohereProvider;
 pub use deepseek::DeepSeekProvider;
 pub use gemini::GeminiProvider;
-pub use claude::ClaudeProvider;
+#[cfg(feature = "local-embed")]
+pub use local_embed::LocalEmbeddingProvider;
+#[cfg(any(test, feature = "test-utils"))]
+pub use mock::MockProvider;
+pub use offline::OfflineProvider;
+pub use openai_compatible::OpenAiCompatibleProvider;
+pub use rate_limit::RateLimitedProvider;
+pub use rotation::RotatingProvider;
+pub use traits::{
+    ChatChunk, ChatMessage, ChatRequest, ChatResponse, ChatRole, LlmProvider, ModelInfo,
+    ResponseFormat, Usage,
+};
+pub use voyage::VoyageProvider;
 
 use crate::config::ProviderConfig;
+use crate::security::{redact_secret, redact_url};
+use chrono::Utc;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use std::num::NonZeroU32;
 use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum ProviderError {
     #[error("HTTP request error: {0}")]
-    RequestError(#[from] reqwest::Error),
+    RequestError(reqwest::Error),
+
+    /// Non-success HTTP response, other than a rate limit
+    #[error("API error (HTTP {status}): {body}")]
+    ApiError { status: u16, body: String },
 
-    #[error("API error: {0}")]
-    ApiError(String),
+    /// A successful response that couldn't be used (missing or malformed content)
+    #[error("Invalid response: {0}")]
+    InvalidResponse(String),
 
     #[error("Serialization error: {0}")]
     SerializationError(#[from] serde_json::Error),
 
     #[error("EventSource error: {0}")]
-    EventSourceError(#[from] reqwest_eventsource::Error),
+    EventSourceError(Box<reqwest_eventsource::Error>),
+
+    #[error("Streaming request error: {0}")]
+    CannotCloneRequest(#[from] reqwest_eventsource::CannotCloneRequestError),
+
+    /// The provider withheld the prompt or response (e.g. a safety filter)
+    #[error("Content blocked: {0}")]
+    ContentBlocked(String),
+
+    /// HTTP 429; `retry_after` is the delay the provider asked for, if it sent `Retry-After`
+    #[error("Rate limited: {body}")]
+    RateLimited {
+        retry_after: Option<Duration>,
+        body: String,
+    },
 
     #[error("Unsupported feature: {0}")]
     UnsupportedFeature(String),
@@ -33,21 +81,193 @@ pub enum ProviderError {
     InvalidConfiguration(String),
 }
 
+impl ProviderError {
+    /// Whether the failure is on the provider's side and may not happen with another provider:
+    /// rate limits, 5xx responses, or the provider being unreachable
+    pub fn is_provider_outage(&self) -> bool {
+        match self {
+            ProviderError::RateLimited { .. } => true,
+            ProviderError::ApiError { status, .. } => *status >= 500,
+            ProviderError::RequestError(e) => e.is_connect() || e.is_timeout(),
+            _ => false,
+        }
+    }
+}
+
+// Request URLs are kept in errors for debugging, minus any API key query parameter
+impl From<reqwest::Error> for ProviderError {
+    fn from(mut error: reqwest::Error) -> Self {
+        if let Some(url) = error.url_mut() {
+            redact_url(url);
+        }
+        ProviderError::RequestError(error)
+    }
+}
+
+impl From<reqwest_eventsource::Error> for ProviderError {
+    fn from(error: reqwest_eventsource::Error) -> Self {
+        match error {
+            reqwest_eventsource::Error::Transport(e) => {
+                let ProviderError::RequestError(e) = e.into() else {
+                    unreachable!("reqwest errors convert to RequestError")
+                };
+                ProviderError::EventSourceError(Box::new(reqwest_eventsource::Error::Transport(e)))
+            }
+            e => ProviderError::EventSourceError(Box::new(e)),
+        }
+    }
+}
+
+/// Build the error for a non-success HTTP response, reading its body
+/// 429 responses map to `RateLimited` with the `Retry-After` delay, so callers can back off
+/// or retry with another key; anything else keeps its status code in `ApiError`.
+/// `api_key` is scrubbed from the body in case the provider echoes it back.
+pub(crate) async fn response_error(response: reqwest::Response, api_key: &str) -> ProviderError {
+    let status = response.status();
+    let retry_after = parse_retry_after(response.headers());
+    let body = redact_secret(&response.text().await.unwrap_or_default(), api_key);
+
+    if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        ProviderError::RateLimited { retry_after, body }
+    } else {
+        ProviderError::ApiError {
+            status: status.as_u16(),
+            body,
+        }
+    }
+}
+
+/// Build the error for a failed SSE stream
+pub(crate) async fn stream_error(
+    error: reqwest_eventsource::Error,
+    api_key: &str,
+) -> ProviderError {
+    match error {
+        reqwest_eventsource::Error::InvalidStatusCode(_, response) => {
+            response_error(response, api_key).await
+        }
+        e => e.into(),
+    }
+}
+
+/// Read a `Retry-After` header, given either as seconds or as an HTTP date
+fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    // A date in the past means the request can be retried right away
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (date.with_timezone(&Utc) - Utc::now())
+            .to_std()
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+/// Whether `create_provider` can build a provider with this id
+pub fn is_known_provider(provider_id: &str) -> bool {
+    match provider_id {
+        "deepseek" | "gemini" | "claude" | "openai-compatible" | "voyage" | "cohere" => true,
+        #[cfg(feature = "local-embed")]
+        "local-embed" => true,
+        #[cfg(any(test, feature = "test-utils"))]
+        "mock" => true,
+        _ => false,
+    }
+}
+
 /// Create a provider instance from configuration
+/// With more than one API key, requests rotate across keys and retry on rate limits.
+/// With `requests_per_minute` set, calls are throttled to that rate across all instances.
+/// In offline mode every provider is the local `OfflineProvider`.
 pub fn create_provider(config: &ProviderConfig) -> Result<Arc<dyn LlmProvider>, ProviderError> {
-    let provider: Arc<dyn LlmProvider> = match config.provider_id.as_str() {
-        "deepseek" => Arc::new(DeepSeekProvider::new(
-            config.api_key.clone(),
-            config.base_url.clone(),
-        )),
-        "gemini" => Arc::new(GeminiProvider::new(
-            config.api_key.clone(),
-            config.base_url.clone(),
-        )),
-        "claude" => Arc::new(ClaudeProvider::new(
-            config.api_key.clone(),
-            config.base_url.clone(),
+    if config.offline {
+        return Ok(Arc::new(OfflineProvider::new()));
+    }
+
+    let mut providers = config
+        .api_keys
+        .iter()
+        .map(|key| create_provider_with_key(config, key.clone()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let provider = match providers.len() {
+        0 => create_provider_with_key(config, String::new())?,
+        1 => providers.remove(0),
+        _ => Arc::new(RotatingProvider::new(
+            config.provider_id.clone(),
+            providers,
+            config.key_cursor.clone(),
+        )?),
+    };
+
+    match config.requests_per_minute.and_then(NonZeroU32::new) {
+        // With several keys, a 429 is already retried on the next key
+        Some(rate) => Ok(Arc::new(
+            RateLimitedProvider::new(config.provider_id.clone(), provider, rate)
+                .with_retries(config.api_keys.len() <= 1),
         )),
+        None => Ok(provider),
+    }
+}
+
+/// Create a provider instance for a single API key
+fn create_provider_with_key(
+    config: &ProviderConfig,
+    api_key: String,
+) -> Result<Arc<dyn LlmProvider>, ProviderError> {
+    let provider: Arc<dyn LlmProvider> = match config.provider_id.as_str() {
+        "deepseek" => Arc::new(
+            DeepSeekProvider::new(api_key, config.base_url.clone())
+                .with_request_logging(config.log_requests),
+        ),
+        "gemini" => Arc::new(
+            GeminiProvider::new(api_key, config.base_url.clone())
+                .with_embedding_model(config.embedding_model.clone())
+                .with_request_logging(config.log_requests),
+        ),
+        "claude" => Arc::new(
+            ClaudeProvider::new(api_key, config.base_url.clone())
+                .with_request_logging(config.log_requests),
+        ),
+        "openai-compatible" => {
+            let base_url = config.base_url.clone().ok_or_else(|| {
+                ProviderError::InvalidConfiguration(
+                    "base_url is required for openai-compatible providers".to_string(),
+                )
+            })?;
+            Arc::new(
+                OpenAiCompatibleProvider::new(api_key, base_url, config.auth_scheme)
+                    .with_request_logging(config.log_requests),
+            )
+        }
+        // Embeddings only, so the default model is taken as the embedding model
+        "voyage" => Arc::new(
+            VoyageProvider::new(api_key, config.base_url.clone())
+                .with_embedding_model(
+                    config
+                        .embedding_model
+                        .clone()
+                        .or_else(|| config.default_model.clone()),
+                )
+                .with_request_logging(config.log_requests),
+        ),
+        // No chat, so the default model is taken as the rerank model
+        "cohere" => Arc::new(
+            CohereProvider::new(api_key, config.base_url.clone())
+                .with_embedding_model(config.embedding_model.clone())
+                .with_rerank_model(config.default_model.clone())
+                .with_request_logging(config.log_requests),
+        ),
+        #[cfg(feature = "local-embed")]
+        "local-embed" => Arc::new(LocalEmbeddingProvider::new(
+            config.embedding_model.as_deref(),
+        )?),
+        #[cfg(any(test, feature = "test-utils"))]
+        "mock" => Arc::new(MockProvider::new()),
         _ => {
             return Err(ProviderError::InvalidConfiguration(format!(
                 "Unknown provider: {}",
@@ -58,3 +278,72 @@ pub fn create_provider(config: &ProviderConfig) -> Result<Arc<dyn LlmProvider>,
 
     Ok(provider)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn headers(retry_after: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_str(retry_after).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_parse_retry_after_seconds() {
+        assert_eq!(
+            parse_retry_after(&headers("120")),
+            Some(Duration::from_secs(120))
+        );
+    }
+
+    #[test]
+    fn test_parse_retry_after_date() {
+        let future = (Utc::now() + chrono::Duration::seconds(60)).to_rfc2822();
+        let delay = parse_retry_after(&headers(&future)).unwrap();
+        assert!(delay > Duration::from_secs(50) && delay <= Duration::from_secs(60));
+
+        let past = "Wed, 21 Oct 2015 07:28:00 GMT";
+        assert_eq!(parse_retry_after(&headers(past)), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn test_parse_retry_after_missing_or_invalid() {
+        assert_eq!(parse_retry_after(&HeaderMap::new()), None);
+        assert_eq!(parse_retry_after(&headers("soon")), None);
+    }
+
+    #[test]
+    fn test_openai_compatible_requires_base_url() {
+        let mut config: ProviderConfig = serde_json::from_str(
+            r#"{"provider_id": "openai-compatible", "api_keys": ["key"], "auth_scheme": "x_api_key"}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            create_provider(&config),
+            Err(ProviderError::InvalidConfiguration(_))
+        ));
+
+        config.base_url = Some("http://localhost:1234/v1".to_string());
+        let provider = create_provider(&config).unwrap();
+        assert_eq!(provider.id(), "openai-compatible");
+    }
+
+    #[test]
+    fn test_is_provider_outage() {
+        let api_error = |status| ProviderError::ApiError {
+            status,
+            body: String::new(),
+        };
+        assert!(api_error(503).is_provider_outage());
+        assert!(!api_error(400).is_provider_outage());
+        assert!(!api_error(401).is_provider_outage());
+        assert!(ProviderError::RateLimited {
+            retry_after: None,
+            body: String::new(),
+        }
+        .is_provider_outage());
+        assert!(!ProviderError::UnsupportedFeature(String::new()).is_provider_outage());
+    }
+}
@@ -0,0 +1,222 @@
+//! Stand-in for every provider while offline mode is on: canned chat replies and
+//! hash-based embeddings, computed locally and the same for the same input
+
+use super::traits::*;
+use super::ProviderError;
+use crate::rag::embeddings::cosine_similarity;
+use crate::tokenizer::count_tokens;
+use async_trait::async_trait;
+
+/// Model reported by offline replies, and used when a provider has no default model
+pub const OFFLINE_MODEL: &str = "offline";
+
+/// Dimension of the embeddings returned by `OfflineProvider::embed`
+pub const OFFLINE_EMBEDDING_DIM: usize = 256;
+
+/// Embedding model reported by `OfflineProvider`
+pub const OFFLINE_EMBEDDING_MODEL: &str = "offline-embedding";
+
+/// Longest excerpt of the prompt quoted in a canned reply, in characters
+const QUOTED_PROMPT_CHARS: usize = 200;
+
+/// Whether a "provider/model" key, as recorded for a project, names the offline embeddings
+pub fn is_offline_model_key(model_key: &str) -> bool {
+    model_key.split_once('/').map(|(_, model)| model) == Some(OFFLINE_EMBEDDING_MODEL)
+}
+
+/// Bag-of-words embedding: each lowercased word adds 1 to a bucket chosen by its FNV-1a hash
+/// Texts sharing words get similar embeddings, so search results are still meaningful.
+pub fn hash_embedding(text: &str, dimension: usize) -> Vec<f32> {
+    let mut embedding = vec![0.0; dimension];
+
+    for word in text.split_whitespace() {
+        let hash = word
+            .to_lowercase()
+            .bytes()
+            .fold(0xcbf29ce484222325u64, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+            });
+        embedding[(hash % dimension as u64) as usize] += 1.0;
+    }
+
+    embedding
+}
+
+/// Answers without any network access, so the app can be demoed or developed offline
+#[derive(Debug, Default)]
+pub struct OfflineProvider;
+
+impl OfflineProvider {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// The canned reply to a request, quoting the start of its last message
+    fn reply(request: &ChatRequest) -> String {
+        let prompt = request
+            .messages
+            .last()
+            .map(|m| m.content.trim())
+            .unwrap_or_default();
+        let mut quoted: String = prompt.chars().take(QUOTED_PROMPT_CHARS).collect();
+        if quoted.len() < prompt.len() {
+            quoted.push_str("...");
+        }
+        format!(
+            "[Offline mode] This is a simulated reply; no provider was contacted. \
+             You said: \"{}\"",
+            quoted
+        )
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OfflineProvider {
+    fn id(&self) -> &'static str {
+        "offline"
+    }
+
+    fn name(&self) -> &'static str {
+        "Offline"
+    }
+
+    fn embedding_model(&self) -> Option<&str> {
+        Some(OFFLINE_EMBEDDING_MODEL)
+    }
+
+    fn embedding_dimension(&self) -> Option<usize> {
+        Some(OFFLINE_EMBEDDING_DIM)
+    }
+
+    async fn chat(&self, request: ChatRequest) -> Result<ChatResponse, ProviderError> {
+        let content = Self::reply(&request);
+        let prompt_tokens: usize = request
+            .messages
+            .iter()
+            .map(|m| count_tokens(&m.content))
+            .sum();
+        let completion_tokens = count_tokens(&content);
+
+        Ok(ChatResponse {
+            content,
+            model: OFFLINE_MODEL.to_string(),
+            finish_reason: Some("stop".to_string()),
+            usage: Some(Usage {
+                prompt_tokens: prompt_tokens as u32,
+                completion_tokens: completion_tokens as u32,
+                total_tokens: (prompt_tokens + completion_tokens) as u32,
+            }),
+            system_fingerprint: None,
+            reasoning_content: None,
+        })
+    }
+
+    /// Streams the reply one word at a time, ending with an empty "stop" chunk
+    async fn stream_chat(
+        &self,
+        request: ChatRequest,
+        tx: tokio::sync::mpsc::Sender<ChatChunk>,
+    ) -> Result<(), ProviderError> {
+        for word in Self::reply(&request).split_inclusive(' ') {
+            let chunk = ChatChunk {
+                delta: word.to_string(),
+                reasoning_delta: None,
+                finish_reason: None,
+            };
+            if tx.send(chunk).await.is_err() {
+                return Ok(());
+            }
+        }
+
+        let _ = tx
+            .send(ChatChunk {
+                delta: String::new(),
+                reasoning_delta: None,
+                finish_reason: Some("stop".to_string()),
+            })
+            .await;
+
+        Ok(())
+    }
+
+    async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, ProviderError> {
+        Ok(texts
+            .iter()
+            .map(|text| hash_embedding(text, OFFLINE_EMBEDDING_DIM))
+            .collect())
+    }
+
+    /// Scores documents by the similarity of their hash embeddings to the query's
+    async fn rerank(
+        &self,
+        query: &str,
+        documents: &[String],
+    ) -> Result<Vec<(usize, f32)>, ProviderError> {
+        let query = hash_embedding(query, OFFLINE_EMBEDDING_DIM);
+        let mut ranked: Vec<(usize, f32)> = documents
+            .iter()
+            .map(|doc| cosine_similarity(&query, &hash_embedding(doc, OFFLINE_EMBEDDING_DIM)))
+            .enumerate()
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        Ok(ranked)
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, ProviderError> {
+        Ok(vec![ModelInfo {
+            id: OFFLINE_MODEL.to_string(),
+            display_name: "Offline (simulated)".to_string(),
+            context_window: None,
+        }])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(content: &str) -> ChatRequest {
+        ChatRequest {
+            model: "claude-3-5-sonnet-20241022".to_string(),
+            messages: vec![ChatMessage {
+                role: ChatRole::User,
+                content: content.to_string(),
+            }],
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            seed: None,
+            response_format: None,
+            stream: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chat_is_deterministic() {
+        let provider = OfflineProvider::new();
+        let first = provider.chat(request("hello there")).await.unwrap();
+        let second = provider.chat(request("hello there")).await.unwrap();
+        assert_eq!(first.content, second.content);
+        assert!(first.content.contains("hello there"));
+        assert_eq!(first.model, OFFLINE_MODEL);
+
+        let long = provider.chat(request(&"é".repeat(500))).await.unwrap();
+        assert!(long.content.ends_with("...\""));
+    }
+
+    #[tokio::test]
+    async fn test_rerank_prefers_shared_words() {
+        let documents = vec![
+            "the weather is cold".to_string(),
+            "rust borrow checker rules".to_string(),
+        ];
+        let ranked = OfflineProvider::new()
+            .rerank("how does the borrow checker work", &documents)
+            .await
+            .unwrap();
+        assert_eq!(ranked[0].0, 1);
+        assert_eq!(ranked.len(), 2);
+    }
+}
@@ -0,0 +1,575 @@
+//! Provider for any service speaking the OpenAI chat completions API
+//! (OpenAI itself, Together, Groq, OpenRouter, LM Studio, vLLM, ...)
+
+use super::request_log::RequestLogger;
+use super::traits::*;
+use super::ProviderError;
+use crate::config::AuthScheme;
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+pub struct OpenAiCompatibleProvider {
+    api_key: String,
+    /// API root including any version prefix, e.g. "https://api.groq.com/openai/v1"
+    base_url: String,
+    auth_scheme: AuthScheme,
+    /// Whether `response_format` accepts "json_schema"; without it, schema requests use
+    /// JSON object mode plus a prompt instruction carrying the schema
+    supports_json_schema: bool,
+    client: reqwest::Client,
+    log: RequestLogger,
+}
+
+impl OpenAiCompatibleProvider {
+    pub fn new(api_key: String, base_url: String, auth_scheme: AuthScheme) -> Self {
+        Self {
+            api_key,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            auth_scheme,
+            supports_json_schema: true,
+            client: reqwest::Client::new(),
+            log: RequestLogger::default(),
+        }
+    }
+
+    /// Log request and response bodies at debug level, with the API key redacted
+    pub fn with_request_logging(mut self, enabled: bool) -> Self {
+        self.log = RequestLogger::new(enabled);
+        self
+    }
+
+    /// For services whose JSON mode only guarantees a JSON object
+    pub fn without_json_schema(mut self) -> Self {
+        self.supports_json_schema = false;
+        self
+    }
+
+    fn create_headers(&self) -> Result<HeaderMap, ProviderError> {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+        // Local servers often need no key at all
+        if self.api_key.is_empty() {
+            return Ok(headers);
+        }
+
+        let invalid_key =
+            |e| ProviderError::InvalidConfiguration(format!("Invalid API key format: {}", e));
+        match self.auth_scheme {
+            AuthScheme::Bearer => {
+                let auth_value = HeaderValue::from_str(&format!("Bearer {}", self.api_key))
+                    .map_err(invalid_key)?;
+                headers.insert(AUTHORIZATION, auth_value);
+            }
+            AuthScheme::XApiKey => {
+                let key_value = HeaderValue::from_str(&self.api_key).map_err(invalid_key)?;
+                headers.insert("x-api-key", key_value);
+            }
+        }
+
+        Ok(headers)
+    }
+
+    /// Build the chat completions request body
+    /// Unset parameters are left out rather than sent as null, since not every
+    /// compatible server accepts nulls (or knows every parameter).
+    fn request_body(&self, request: &ChatRequest, stream: bool) -> serde_json::Value {
+        let mut messages = self.convert_messages(&request.messages);
+        let response_format = match &request.response_format {
+            Some(ResponseFormat::Text) => Some(json!({"type": "text"})),
+            Some(ResponseFormat::JsonSchema(schema)) if self.supports_json_schema => Some(json!({
+                "type": "json_schema",
+                "json_schema": {"name": "response", "schema": schema}
+            })),
+            // JSON object mode expects the prompt to ask for JSON, so say so up front
+            Some(format) => {
+                if let Some(instruction) = format.instruction() {
+                    messages.insert(0, json!({"role": "system", "content": instruction}));
+                }
+                Some(json!({"type": "json_object"}))
+            }
+            None => None,
+        };
+
+        let mut body = json!({
+            "model": request.model,
+            "messages": messages,
+            "stream": stream,
+        });
+
+        if let Some(temp) = request.temperature {
+            body["temperature"] = json!(temp);
+        }
+        if let Some(max_tokens) = request.max_tokens {
+            body["max_tokens"] = json!(max_tokens);
+        }
+        if let Some(top_p) = request.top_p {
+            body["top_p"] = json!(top_p);
+        }
+        if let Some(penalty) = request.frequency_penalty {
+            body["frequency_penalty"] = json!(penalty);
+        }
+        if let Some(penalty) = request.presence_penalty {
+            body["presence_penalty"] = json!(penalty);
+        }
+        if let Some(seed) = request.seed {
+            body["seed"] = json!(seed);
+        }
+        if let Some(response_format) = response_format {
+            body["response_format"] = response_format;
+        }
+
+        body
+    }
+
+    fn convert_messages(&self, messages: &[ChatMessage]) -> Vec<serde_json::Value> {
+        messages
+            .iter()
+            .map(|msg| {
+                json!({
+                    "role": match msg.role {
+                        ChatRole::System => "system",
+                        ChatRole::User => "user",
+                        ChatRole::Assistant => "assistant",
+                    },
+                    "content": msg.content
+                })
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAiResponse {
+    choices: Vec<OpenAiChoice>,
+    usage: Option<OpenAiUsage>,
+    model: String,
+    #[serde(default)]
+    system_fingerprint: Option<String>,
+}
+
+impl OpenAiResponse {
+    fn into_chat_response(self) -> Result<ChatResponse, ProviderError> {
+        let choice =
+            self.choices.into_iter().next().ok_or_else(|| {
+                ProviderError::InvalidResponse("No choices in response".to_string())
+            })?;
+
+        Ok(ChatResponse {
+            content: choice.message.content.unwrap_or_default(),
+            model: self.model,
+            finish_reason: choice.finish_reason,
+            usage: self.usage.map(|u| Usage {
+                prompt_tokens: u.prompt_tokens,
+                completion_tokens: u.completion_tokens,
+                total_tokens: u.total_tokens,
+            }),
+            system_fingerprint: self.system_fingerprint,
+            reasoning_content: choice.message.reasoning_content,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAiChoice {
+    message: OpenAiMessage,
+    finish_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAiMessage {
+    /// Null when the model only made tool calls
+    #[serde(default)]
+    content: Option<String>,
+    /// Chain of thought returned by reasoning models (e.g. deepseek-reasoner)
+    #[serde(default)]
+    reasoning_content: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAiUsage {
+    prompt_tokens: u32,
+    completion_tokens: u32,
+    total_tokens: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAiStreamChunk {
+    choices: Vec<OpenAiStreamChoice>,
+}
+
+impl OpenAiStreamChunk {
+    /// The chunk to forward, if it carries any answer or reasoning text
+    fn into_chat_chunk(self) -> Option<ChatChunk> {
+        let choice = self.choices.into_iter().next()?;
+        let delta = choice.delta;
+        if delta.content.is_none() && delta.reasoning_content.is_none() {
+            return None;
+        }
+        Some(ChatChunk {
+            delta: delta.content.unwrap_or_default(),
+            reasoning_delta: delta.reasoning_content,
+            finish_reason: choice.finish_reason,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAiStreamChoice {
+    delta: OpenAiDelta,
+    finish_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAiDelta {
+    #[serde(default)]
+    content: Option<String>,
+    #[serde(default)]
+    reasoning_content: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAiModelList {
+    data: Vec<OpenAiModel>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAiModel {
+    id: String,
+}
+
+#[async_trait]
+impl LlmProvider for OpenAiCompatibleProvider {
+    fn id(&self) -> &'static str {
+        "openai-compatible"
+    }
+
+    fn name(&self) -> &'static str {
+        "OpenAI-compatible"
+    }
+
+    async fn chat(&self, request: ChatRequest) -> Result<ChatResponse, ProviderError> {
+        let url = format!("{}/chat/completions", self.base_url);
+
+        let body = self.request_body(&request, false);
+        self.log.request(self.id(), &self.api_key, &url, &body);
+
+        let response = self
+            .client
+            .post(&url)
+            .headers(self.create_headers()?)
+            .json(&body)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(self.log.error(self.id(), &self.api_key, response).await);
+        }
+
+        let api_response: OpenAiResponse = self
+            .log
+            .read_json(self.id(), &self.api_key, response)
+            .await?;
+        api_response.into_chat_response()
+    }
+
+    async fn stream_chat(
+        &self,
+        request: ChatRequest,
+        tx: tokio::sync::mpsc::Sender<ChatChunk>,
+    ) -> Result<(), ProviderError> {
+        use futures::StreamExt;
+        use reqwest_eventsource::{Event, EventSource};
+
+        let url = format!("{}/chat/completions", self.base_url);
+
+        let body = self.request_body(&request, true);
+        self.log.request(self.id(), &self.api_key, &url, &body);
+
+        let req_builder = self
+            .client
+            .post(&url)
+            .headers(self.create_headers()?)
+            .json(&body);
+
+        let mut event_source = EventSource::new(req_builder)?;
+
+        while let Some(event) = event_source.next().await {
+            match event {
+                Ok(Event::Message(message)) => {
+                    self.log
+                        .response(self.id(), &self.api_key, None, &message.data);
+                    if message.data == "[DONE]" {
+                        break;
+                    }
+
+                    let chunk: OpenAiStreamChunk = match serde_json::from_str(&message.data) {
+                        Ok(c) => c,
+                        Err(e) => {
+                            tracing::warn!("Failed to parse chunk: {}", e);
+                            continue;
+                        }
+                    };
+
+                    if let Some(chunk) = chunk.into_chat_chunk() {
+                        let _ = tx.send(chunk).await;
+                    }
+                }
+                Ok(Event::Open) => {
+                    tracing::debug!("OpenAI-compatible stream opened");
+                }
+                Err(e) => {
+                    let e = self.log.stream_error(self.id(), &self.api_key, e).await;
+                    tracing::error!("OpenAI-compatible stream error: {}", e);
+                    return Err(e);
+                }
+            }
+        }
+
+        event_source.close();
+        Ok(())
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, ProviderError> {
+        let url = format!("{}/models", self.base_url);
+
+        let response = self
+            .client
+            .get(&url)
+            .headers(self.create_headers()?)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(self.log.error(self.id(), &self.api_key, response).await);
+        }
+
+        let model_list: OpenAiModelList = self
+            .log
+            .read_json(self.id(), &self.api_key, response)
+            .await?;
+
+        // The OpenAI-style listing carries ids only
+        Ok(model_list
+            .data
+            .into_iter()
+            .map(|m| ModelInfo {
+                display_name: m.id.clone(),
+                id: m.id,
+                context_window: None,
+            })
+            .collect())
+    }
+
+    /// Model listing is authenticated and free; local servers answer it without a key
+    async fn verify_credentials(&self) -> Result<(), ProviderError> {
+        self.list_models().await.map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request() -> ChatRequest {
+        ChatRequest {
+            model: "gpt-4o-mini".to_string(),
+            messages: vec![ChatMessage {
+                role: ChatRole::User,
+                content: "Hello".to_string(),
+            }],
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            seed: None,
+            response_format: None,
+            stream: false,
+        }
+    }
+
+    fn provider() -> OpenAiCompatibleProvider {
+        OpenAiCompatibleProvider::new(
+            "key".to_string(),
+            "http://localhost:1234/v1".to_string(),
+            AuthScheme::Bearer,
+        )
+    }
+
+    #[test]
+    fn test_seed_sent_only_when_set() {
+        let body = provider().request_body(
+            &ChatRequest {
+                seed: Some(42),
+                ..request()
+            },
+            false,
+        );
+        assert_eq!(body["seed"], 42);
+
+        let body = provider().request_body(&request(), false);
+        assert!(body.get("seed").is_none());
+    }
+
+    #[test]
+    fn test_response_keeps_reasoning_content() {
+        let response: OpenAiResponse = serde_json::from_value(json!({
+            "model": "deepseek-reasoner",
+            "choices": [{
+                "message": {
+                    "role": "assistant",
+                    "content": "42",
+                    "reasoning_content": "Six times seven."
+                },
+                "finish_reason": "stop"
+            }]
+        }))
+        .unwrap();
+        let response = response.into_chat_response().unwrap();
+        assert_eq!(response.content, "42");
+        assert_eq!(
+            response.reasoning_content.as_deref(),
+            Some("Six times seven.")
+        );
+    }
+
+    #[test]
+    fn test_response_without_reasoning_content() {
+        let response: OpenAiResponse = serde_json::from_value(json!({
+            "model": "gpt-4o-mini",
+            "choices": [{
+                "message": {"role": "assistant", "content": "Hi"},
+                "finish_reason": "stop"
+            }]
+        }))
+        .unwrap();
+        assert!(response
+            .into_chat_response()
+            .unwrap()
+            .reasoning_content
+            .is_none());
+    }
+
+    #[test]
+    fn test_stream_chunk_with_only_reasoning_is_forwarded() {
+        let chunk: OpenAiStreamChunk = serde_json::from_value(json!({
+            "choices": [{
+                "delta": {"reasoning_content": "Let me think"},
+                "finish_reason": null
+            }]
+        }))
+        .unwrap();
+        let chunk = chunk.into_chat_chunk().unwrap();
+        assert_eq!(chunk.delta, "");
+        assert_eq!(chunk.reasoning_delta.as_deref(), Some("Let me think"));
+    }
+
+    #[test]
+    fn test_empty_stream_chunk_is_skipped() {
+        let chunk: OpenAiStreamChunk = serde_json::from_value(json!({
+            "choices": [{"delta": {"role": "assistant"}, "finish_reason": null}]
+        }))
+        .unwrap();
+        assert!(chunk.into_chat_chunk().is_none());
+    }
+
+    #[test]
+    fn test_bearer_auth_header() {
+        let headers = provider().create_headers().unwrap();
+        assert_eq!(headers[AUTHORIZATION], "Bearer key");
+        assert!(headers.get("x-api-key").is_none());
+    }
+
+    #[test]
+    fn test_x_api_key_auth_header() {
+        let provider = OpenAiCompatibleProvider::new(
+            "key".to_string(),
+            "http://localhost:1234/v1".to_string(),
+            AuthScheme::XApiKey,
+        );
+        let headers = provider.create_headers().unwrap();
+        assert_eq!(headers["x-api-key"], "key");
+        assert!(headers.get(AUTHORIZATION).is_none());
+    }
+
+    #[test]
+    fn test_empty_key_sends_no_auth_header() {
+        let provider = OpenAiCompatibleProvider::new(
+            String::new(),
+            "http://localhost:1234/v1".to_string(),
+            AuthScheme::Bearer,
+        );
+        let headers = provider.create_headers().unwrap();
+        assert!(headers.get(AUTHORIZATION).is_none());
+        assert_eq!(headers[CONTENT_TYPE], "application/json");
+    }
+
+    #[test]
+    fn test_json_schema_maps_to_native_json_schema() {
+        let schema = json!({"type": "object", "properties": {"answer": {"type": "string"}}});
+        let body = provider().request_body(
+            &ChatRequest {
+                response_format: Some(ResponseFormat::JsonSchema(schema.clone())),
+                ..request()
+            },
+            false,
+        );
+        assert_eq!(
+            body["response_format"],
+            json!({
+                "type": "json_schema",
+                "json_schema": {"name": "response", "schema": schema}
+            })
+        );
+        assert_eq!(body["messages"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_text_format_maps_to_text_type() {
+        let body = provider().request_body(
+            &ChatRequest {
+                response_format: Some(ResponseFormat::Text),
+                ..request()
+            },
+            false,
+        );
+        assert_eq!(body["response_format"], json!({"type": "text"}));
+        assert_eq!(body["messages"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_json_object_format_asks_for_json_in_a_system_message() {
+        let body = provider().request_body(
+            &ChatRequest {
+                response_format: Some(ResponseFormat::JsonObject),
+                ..request()
+            },
+            false,
+        );
+        assert_eq!(body["response_format"], json!({"type": "json_object"}));
+        assert_eq!(body["messages"][0]["role"], "system");
+        assert_eq!(
+            body["messages"][0]["content"],
+            ResponseFormat::JsonObject.instruction().unwrap()
+        );
+        assert_eq!(body["messages"][1]["content"], "Hello");
+    }
+
+    #[test]
+    fn test_json_schema_without_native_support_falls_back_to_json_object() {
+        let schema = json!({"type": "object", "properties": {"answer": {"type": "string"}}});
+        let body = provider().without_json_schema().request_body(
+            &ChatRequest {
+                response_format: Some(ResponseFormat::JsonSchema(schema.clone())),
+                ..request()
+            },
+            false,
+        );
+        assert_eq!(body["response_format"], json!({"type": "json_object"}));
+        let instruction = body["messages"][0]["content"].as_str().unwrap();
+        assert!(instruction.contains(&schema.to_string()));
+    }
+}
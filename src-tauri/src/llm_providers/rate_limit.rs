@@ -0,0 +1,227 @@
+use super::traits::*;
+use super::ProviderError;
+use async_trait::async_trait;
+use governor::{DefaultDirectRateLimiter, Quota, RateLimiter};
+use std::collections::HashMap;
+use std::future::Future;
+use std::num::NonZeroU32;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+/// Retries after a 429 that carried `Retry-After`
+const MAX_RATE_LIMIT_RETRIES: usize = 2;
+
+/// Longest `Retry-After` we wait out; anything longer is returned to the caller
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(30);
+
+/// Configured rate and its limiter, per provider id
+type LimiterMap = HashMap<String, (NonZeroU32, Arc<DefaultDirectRateLimiter>)>;
+
+/// Token bucket per provider id, shared across provider instances
+/// (providers are created per request, so the bucket can't live on the instance)
+fn limiters() -> &'static Mutex<LimiterMap> {
+    static LIMITERS: OnceLock<Mutex<LimiterMap>> = OnceLock::new();
+    LIMITERS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// The shared limiter for `provider_id`, replaced when its rate changes
+fn shared_limiter(
+    provider_id: &str,
+    requests_per_minute: NonZeroU32,
+) -> Arc<DefaultDirectRateLimiter> {
+    let mut limiters = limiters().lock().unwrap_or_else(|e| e.into_inner());
+    match limiters.get(provider_id) {
+        Some((rate, limiter)) if *rate == requests_per_minute => limiter.clone(),
+        _ => {
+            let limiter = Arc::new(RateLimiter::direct(Quota::per_minute(requests_per_minute)));
+            limiters.insert(
+                provider_id.to_string(),
+                (requests_per_minute, limiter.clone()),
+            );
+            limiter
+        }
+    }
+}
+
+/// Holds calls to a provider to at most `requests_per_minute`
+/// Calls wait for a free slot instead of failing. A 429 with `Retry-After`
+/// is waited out and retried (unless disabled with `with_retries`), so a burst
+/// from several windows or canvas nodes slows down rather than erroring.
+pub struct RateLimitedProvider {
+    provider_id: String,
+    inner: Arc<dyn LlmProvider>,
+    limiter: Arc<DefaultDirectRateLimiter>,
+    retry_rate_limits: bool,
+}
+
+impl RateLimitedProvider {
+    pub fn new(
+        provider_id: String,
+        inner: Arc<dyn LlmProvider>,
+        requests_per_minute: NonZeroU32,
+    ) -> Self {
+        let limiter = shared_limiter(&provider_id, requests_per_minute);
+        Self {
+            provider_id,
+            inner,
+            limiter,
+            retry_rate_limits: true,
+        }
+    }
+
+    /// Whether to wait out and retry a 429; turn off when `inner` already
+    /// handles rate limits itself, so retries don't multiply
+    pub fn with_retries(mut self, enabled: bool) -> Self {
+        self.retry_rate_limits = enabled;
+        self
+    }
+
+    /// Run `call` once a slot is free, retrying while the provider asks us to wait
+    async fn limited<T, F, Fut>(&self, mut call: F) -> Result<T, ProviderError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, ProviderError>>,
+    {
+        let mut retries = 0;
+
+        loop {
+            self.limiter.until_ready().await;
+
+            match call().await {
+                Err(ProviderError::RateLimited {
+                    retry_after: Some(delay),
+                    ..
+                }) if self.retry_rate_limits
+                    && retries < MAX_RATE_LIMIT_RETRIES
+                    && delay <= MAX_RETRY_AFTER =>
+                {
+                    retries += 1;
+                    tracing::warn!(
+                        "{} rate limited, retrying in {:?} ({}/{})",
+                        self.provider_id,
+                        delay,
+                        retries,
+                        MAX_RATE_LIMIT_RETRIES
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for RateLimitedProvider {
+    fn id(&self) -> &'static str {
+        self.inner.id()
+    }
+
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn embedding_model(&self) -> Option<&str> {
+        self.inner.embedding_model()
+    }
+
+    fn embedding_dimension(&self) -> Option<usize> {
+        self.inner.embedding_dimension()
+    }
+
+    async fn chat(&self, request: ChatRequest) -> Result<ChatResponse, ProviderError> {
+        self.limited(|| self.inner.chat(request.clone())).await
+    }
+
+    async fn stream_chat(
+        &self,
+        request: ChatRequest,
+        tx: tokio::sync::mpsc::Sender<ChatChunk>,
+    ) -> Result<(), ProviderError> {
+        // A 429 arrives before any chunk is sent, so retrying on the same channel is safe
+        self.limited(|| self.inner.stream_chat(request.clone(), tx.clone()))
+            .await
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, ProviderError> {
+        self.limited(|| self.inner.list_models()).await
+    }
+
+    async fn verify_credentials(&self) -> Result<(), ProviderError> {
+        self.limited(|| self.inner.verify_credentials()).await
+    }
+
+    async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, ProviderError> {
+        self.limited(|| self.inner.embed(texts.clone())).await
+    }
+
+    async fn rerank(
+        &self,
+        query: &str,
+        documents: &[String],
+    ) -> Result<Vec<(usize, f32)>, ProviderError> {
+        self.limited(|| self.inner.rerank(query, documents)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm_providers::mock::MockProvider;
+
+    fn limited(
+        provider_id: &str,
+        rate_limited_calls: usize,
+    ) -> (Arc<MockProvider>, RateLimitedProvider) {
+        let inner = Arc::new(MockProvider::new().with_rate_limits(rate_limited_calls));
+        let provider = RateLimitedProvider::new(
+            provider_id.to_string(),
+            inner.clone(),
+            NonZeroU32::new(6000).unwrap(),
+        );
+        (inner, provider)
+    }
+
+    #[tokio::test]
+    async fn test_retries_after_retry_after() {
+        let (inner, provider) = limited("flaky-retry", 2);
+
+        assert!(provider.list_models().await.is_ok());
+        assert_eq!(inner.calls(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_retries() {
+        let (inner, provider) = limited("flaky-give-up", usize::MAX);
+
+        assert!(matches!(
+            provider.list_models().await,
+            Err(ProviderError::RateLimited { .. })
+        ));
+        assert_eq!(inner.calls(), MAX_RATE_LIMIT_RETRIES + 1);
+    }
+
+    #[tokio::test]
+    async fn test_no_retries_when_disabled() {
+        let (inner, provider) = limited("flaky-no-retry", 1);
+        let provider = provider.with_retries(false);
+
+        assert!(matches!(
+            provider.list_models().await,
+            Err(ProviderError::RateLimited { .. })
+        ));
+        assert_eq!(inner.calls(), 1);
+    }
+
+    #[test]
+    fn test_limiter_shared_per_provider() {
+        let rate = NonZeroU32::new(60).unwrap();
+        let first = shared_limiter("shared", rate);
+
+        assert!(Arc::ptr_eq(&first, &shared_limiter("shared", rate)));
+        assert!(!Arc::ptr_eq(
+            &first,
+            &shared_limiter("shared", NonZeroU32::new(120).unwrap())
+        ));
+    }
+}
@@ -0,0 +1,138 @@
+use super::{response_error, stream_error, ProviderError};
+use crate::security::{redact_secret, redact_url};
+use reqwest::{StatusCode, Url};
+use serde::de::DeserializeOwned;
+
+/// Log target for request bodies, so they can be enabled on their own with
+/// `RUST_LOG=provider_requests=debug`
+pub const REQUEST_LOG_TARGET: &str = "provider_requests";
+
+/// Debug logging of a provider's request and response bodies, enabled per provider by
+/// `log_requests`. Each call takes the provider's API key, which is redacted from URLs
+/// and bodies before anything is logged.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RequestLogger {
+    enabled: bool,
+}
+
+impl RequestLogger {
+    pub fn new(enabled: bool) -> Self {
+        Self { enabled }
+    }
+
+    /// Log an outgoing request body
+    pub fn request(&self, provider: &str, api_key: &str, url: &str, body: &serde_json::Value) {
+        if !self.enabled {
+            return;
+        }
+        tracing::debug!(
+            target: REQUEST_LOG_TARGET,
+            "{} request POST {}: {}",
+            provider,
+            redact_logged_url(url, api_key),
+            redact_secret(&body.to_string(), api_key)
+        );
+    }
+
+    /// Log a raw response body, or one event of a streamed response
+    pub fn response(&self, provider: &str, api_key: &str, status: Option<StatusCode>, body: &str) {
+        if !self.enabled {
+            return;
+        }
+        match status {
+            Some(status) => tracing::debug!(
+                target: REQUEST_LOG_TARGET,
+                "{} response {}: {}",
+                provider,
+                status.as_u16(),
+                redact_secret(body, api_key)
+            ),
+            None => tracing::debug!(
+                target: REQUEST_LOG_TARGET,
+                "{} stream event: {}",
+                provider,
+                redact_secret(body, api_key)
+            ),
+        }
+    }
+
+    /// Read a successful response's JSON body, logging it first when enabled
+    pub async fn read_json<T: DeserializeOwned>(
+        &self,
+        provider: &str,
+        api_key: &str,
+        response: reqwest::Response,
+    ) -> Result<T, ProviderError> {
+        if !self.enabled {
+            return Ok(response.json().await?);
+        }
+        let status = response.status();
+        let body = response.text().await?;
+        self.response(provider, api_key, Some(status), &body);
+        Ok(serde_json::from_str(&body)?)
+    }
+
+    /// Build the error for a non-success response, logging its body when enabled
+    pub async fn error(
+        &self,
+        provider: &str,
+        api_key: &str,
+        response: reqwest::Response,
+    ) -> ProviderError {
+        let status = response.status();
+        let error = response_error(response, api_key).await;
+        if let ProviderError::ApiError { body, .. } | ProviderError::RateLimited { body, .. } =
+            &error
+        {
+            self.response(provider, api_key, Some(status), body);
+        }
+        error
+    }
+
+    /// Build the error for a failed stream, logging the response body when enabled
+    pub async fn stream_error(
+        &self,
+        provider: &str,
+        api_key: &str,
+        error: reqwest_eventsource::Error,
+    ) -> ProviderError {
+        match error {
+            reqwest_eventsource::Error::InvalidStatusCode(_, response) => {
+                self.error(provider, api_key, response).await
+            }
+            e => stream_error(e, api_key).await,
+        }
+    }
+}
+
+/// Redact key query parameters and `api_key` from a URL to be logged
+fn redact_logged_url(url: &str, api_key: &str) -> String {
+    match Url::parse(url) {
+        Ok(mut url) => {
+            redact_url(&mut url);
+            redact_secret(url.as_str(), api_key)
+        }
+        Err(_) => redact_secret(url, api_key),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redacts_key_from_url_and_body() {
+        // Any key query parameter is redacted, not only the configured key
+        let url = redact_logged_url(
+            "https://example.com/v1/models?alt=sse&key=other-key",
+            "sk-secret",
+        );
+        assert!(!url.contains("other-key"));
+        assert!(url.contains("alt=sse"));
+
+        let body = serde_json::json!({"auth": "Bearer sk-secret", "prompt": "hi"}).to_string();
+        let redacted = redact_secret(&body, "sk-secret");
+        assert!(!redacted.contains("sk-secret"));
+        assert!(redacted.contains("hi"));
+    }
+}
@@ -0,0 +1,182 @@
+use super::traits::*;
+use super::ProviderError;
+use async_trait::async_trait;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Spreads requests across several API keys of the same provider
+/// Each call starts at the next key in round-robin order; if a key is
+/// rate limited (HTTP 429), the call is retried with the following key.
+pub struct RotatingProvider {
+    provider_id: String,
+    providers: Vec<Arc<dyn LlmProvider>>,
+    /// Key the next call starts at; providers are created per request, so the
+    /// config store hands every instance for a provider id the same cursor
+    cursor: Arc<AtomicUsize>,
+}
+
+impl RotatingProvider {
+    /// `providers` must contain one instance per API key (at least one)
+    pub fn new(
+        provider_id: String,
+        providers: Vec<Arc<dyn LlmProvider>>,
+        cursor: Arc<AtomicUsize>,
+    ) -> Result<Self, ProviderError> {
+        if providers.is_empty() {
+            return Err(ProviderError::InvalidConfiguration(format!(
+                "{} has no API keys to rotate",
+                provider_id
+            )));
+        }
+        Ok(Self {
+            provider_id,
+            providers,
+            cursor,
+        })
+    }
+
+    /// Key indices to try for the next call, starting at the round-robin cursor
+    fn key_order(&self) -> Vec<usize> {
+        let count = self.providers.len();
+        let start = self.cursor.fetch_add(1, Ordering::Relaxed) % count;
+
+        (0..count).map(|offset| (start + offset) % count).collect()
+    }
+
+    /// Run `call` with each key index in turn until one isn't rate limited
+    async fn rotate<T, F, Fut>(&self, call: F) -> Result<T, ProviderError>
+    where
+        F: Fn(usize) -> Fut,
+        Fut: Future<Output = Result<T, ProviderError>>,
+    {
+        let mut last_error = None;
+
+        for idx in self.key_order() {
+            match call(idx).await {
+                Err(e @ ProviderError::RateLimited { .. }) => {
+                    tracing::warn!(
+                        "{} key #{} rate limited, trying next key",
+                        self.provider_id,
+                        idx + 1
+                    );
+                    last_error = Some(e);
+                }
+                result => return result,
+            }
+        }
+
+        Err(last_error.expect("at least one key was tried"))
+    }
+}
+
+#[async_trait]
+impl LlmProvider for RotatingProvider {
+    fn id(&self) -> &'static str {
+        self.providers[0].id()
+    }
+
+    fn name(&self) -> &'static str {
+        self.providers[0].name()
+    }
+
+    fn embedding_model(&self) -> Option<&str> {
+        self.providers[0].embedding_model()
+    }
+
+    fn embedding_dimension(&self) -> Option<usize> {
+        self.providers[0].embedding_dimension()
+    }
+
+    async fn chat(&self, request: ChatRequest) -> Result<ChatResponse, ProviderError> {
+        self.rotate(|idx| self.providers[idx].chat(request.clone()))
+            .await
+    }
+
+    async fn stream_chat(
+        &self,
+        request: ChatRequest,
+        tx: tokio::sync::mpsc::Sender<ChatChunk>,
+    ) -> Result<(), ProviderError> {
+        // A 429 arrives before any chunk is sent, so retrying on the same channel is safe
+        self.rotate(|idx| self.providers[idx].stream_chat(request.clone(), tx.clone()))
+            .await
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, ProviderError> {
+        self.rotate(|idx| self.providers[idx].list_models()).await
+    }
+
+    async fn verify_credentials(&self) -> Result<(), ProviderError> {
+        self.rotate(|idx| self.providers[idx].verify_credentials())
+            .await
+    }
+
+    async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, ProviderError> {
+        self.rotate(|idx| self.providers[idx].embed(texts.clone()))
+            .await
+    }
+
+    async fn rerank(
+        &self,
+        query: &str,
+        documents: &[String],
+    ) -> Result<Vec<(usize, f32)>, ProviderError> {
+        self.rotate(|idx| self.providers[idx].rerank(query, documents))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm_providers::MockProvider;
+
+    fn rotating(keys: Vec<Arc<MockProvider>>) -> RotatingProvider {
+        let providers = keys
+            .into_iter()
+            .map(|key| key as Arc<dyn LlmProvider>)
+            .collect();
+        RotatingProvider::new("mock".to_string(), providers, Default::default()).unwrap()
+    }
+
+    #[test]
+    fn test_requires_a_key() {
+        assert!(matches!(
+            RotatingProvider::new("mock".to_string(), Vec::new(), Default::default()),
+            Err(ProviderError::InvalidConfiguration(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_calls_start_at_the_next_key() {
+        let keys = vec![Arc::new(MockProvider::new()), Arc::new(MockProvider::new())];
+        let provider = rotating(keys.clone());
+
+        for _ in 0..3 {
+            provider.list_models().await.unwrap();
+        }
+        assert_eq!(keys[0].calls(), 2);
+        assert_eq!(keys[1].calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_key_moves_on() {
+        let keys = vec![
+            Arc::new(MockProvider::new().with_rate_limits(usize::MAX)),
+            Arc::new(MockProvider::new()),
+        ];
+        let provider = rotating(keys.clone());
+
+        provider.list_models().await.unwrap();
+        assert_eq!((keys[0].calls(), keys[1].calls()), (1, 1));
+
+        let all_limited = rotating(vec![Arc::new(
+            MockProvider::new().with_rate_limits(usize::MAX),
+        )]);
+        assert!(matches!(
+            all_limited.list_models().await,
+            Err(ProviderError::RateLimited { .. })
+        ));
+    }
+}
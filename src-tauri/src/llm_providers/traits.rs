@@ -11,6 +11,27 @@ pub enum ChatRole {
     Assistant,
 }
 
+impl ChatRole {
+    /// Role name as stored in the messages table
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChatRole::System => "system",
+            ChatRole::User => "user",
+            ChatRole::Assistant => "assistant",
+        }
+    }
+
+    /// Parse a stored role name
+    pub fn parse(role: &str) -> Option<Self> {
+        match role {
+            "system" => Some(ChatRole::System),
+            "user" => Some(ChatRole::User),
+            "assistant" => Some(ChatRole::Assistant),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatMessage {
     pub role: ChatRole,
@@ -31,10 +52,55 @@ pub struct ChatRequest {
     #[serde(default)]
     pub top_p: Option<f32>,
 
+    /// Penalizes tokens by how often they've appeared so far (-2.0 to 2.0)
+    #[serde(default)]
+    pub frequency_penalty: Option<f32>,
+
+    /// Penalizes tokens that have appeared at all so far (-2.0 to 2.0)
+    #[serde(default)]
+    pub presence_penalty: Option<f32>,
+
+    /// Sampling seed for reproducible outputs; ignored by providers that don't support it
+    #[serde(default)]
+    pub seed: Option<u64>,
+
+    /// Output format; None leaves it to the provider (plain text)
+    #[serde(default)]
+    pub response_format: Option<ResponseFormat>,
+
     #[serde(default)]
     pub stream: bool,
 }
 
+/// Output format requested from the model
+/// Serialized as `{"type": "text"}`, `{"type": "json_object"}` or
+/// `{"type": "json_schema", "schema": {...}}`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "schema", rename_all = "snake_case")]
+pub enum ResponseFormat {
+    Text,
+    /// Any valid JSON object
+    JsonObject,
+    /// JSON matching the given JSON Schema
+    JsonSchema(serde_json::Value),
+}
+
+impl ResponseFormat {
+    /// System prompt instruction for providers without a (complete) native JSON mode
+    pub fn instruction(&self) -> Option<String> {
+        match self {
+            ResponseFormat::Text => None,
+            ResponseFormat::JsonObject => {
+                Some("Respond only with a valid JSON object, with no other text.".to_string())
+            }
+            ResponseFormat::JsonSchema(schema) => Some(format!(
+                "Respond only with a valid JSON object matching this JSON Schema, with no other text:\n{}",
+                schema
+            )),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatResponse {
     pub content: String,
@@ -45,6 +111,25 @@ pub struct ChatResponse {
 
     #[serde(default)]
     pub usage: Option<Usage>,
+
+    /// Backend configuration fingerprint; a change means seeded outputs may differ
+    #[serde(default)]
+    pub system_fingerprint: Option<String>,
+
+    /// Reasoning ("thinking") that preceded the answer, for models that return it separately
+    #[serde(default)]
+    pub reasoning_content: Option<String>,
+}
+
+impl ChatResponse {
+    /// Whether generation stopped at the max_tokens limit rather than finishing
+    /// ("length" for OpenAI-compatible APIs, "max_tokens" for Claude, "MAX_TOKENS" for Gemini)
+    pub fn is_truncated(&self) -> bool {
+        matches!(
+            self.finish_reason.as_deref(),
+            Some("length" | "max_tokens" | "MAX_TOKENS")
+        )
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -54,17 +139,32 @@ pub struct Usage {
     pub total_tokens: u32,
 }
 
+/// A model offered by a provider, as reported by its model listing endpoint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelInfo {
+    pub id: String,
+    pub display_name: String,
+
+    /// Maximum input tokens, if the provider reports it
+    #[serde(default)]
+    pub context_window: Option<u32>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatChunk {
     pub delta: String,
 
+    /// Reasoning ("thinking") text, for models that stream it separately from the answer
+    #[serde(default)]
+    pub reasoning_delta: Option<String>,
+
     #[serde(default)]
     pub finish_reason: Option<String>,
 }
 
 #[async_trait]
 pub trait LlmProvider: Send + Sync {
-    /// Provider identifier (e.g., "deepseek", "gemini", "claude")
+    /// Provider identifier (e.g., "deepseek", "gemini", "claude", "voyage")
     fn id(&self) -> &'static str;
 
     /// Human-readable provider name
@@ -81,6 +181,16 @@ pub trait LlmProvider: Send + Sync {
         tx: tokio::sync::mpsc::Sender<ChatChunk>,
     ) -> Result<(), ProviderError>;
 
+    /// Model used by `embed`, if this provider supports embeddings
+    fn embedding_model(&self) -> Option<&str> {
+        None
+    }
+
+    /// Length of the vectors `embed` returns, if it is fixed and known without calling it
+    fn embedding_dimension(&self) -> Option<usize> {
+        None
+    }
+
     /// Generate embeddings for text (used for RAG)
     async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, ProviderError> {
         // Default implementation: not supported
@@ -89,4 +199,36 @@ pub trait LlmProvider: Send + Sync {
             "Embeddings not supported by this provider".to_string(),
         ))
     }
+
+    /// Score `documents` by relevance to `query` with a reranking (cross-encoder) model
+    /// Returns (index into `documents`, relevance score) pairs, most relevant first.
+    async fn rerank(
+        &self,
+        query: &str,
+        documents: &[String],
+    ) -> Result<Vec<(usize, f32)>, ProviderError> {
+        // Default implementation: not supported
+        let _ = (query, documents);
+        Err(ProviderError::UnsupportedFeature(
+            "Reranking not supported by this provider".to_string(),
+        ))
+    }
+
+    /// List the models available to this API key
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, ProviderError> {
+        // Default implementation: not supported
+        Err(ProviderError::UnsupportedFeature(
+            "Model listing not supported by this provider".to_string(),
+        ))
+    }
+
+    /// Check that the provider accepts the API key, without paying for a completion
+    /// Fails with `UnsupportedFeature` if the provider has no such check, as is the case
+    /// for providers that take no API key.
+    async fn verify_credentials(&self) -> Result<(), ProviderError> {
+        // Default implementation: not supported
+        Err(ProviderError::UnsupportedFeature(
+            "Credential check not supported by this provider".to_string(),
+        ))
+    }
 }
@@ -0,0 +1,216 @@
+//! Voyage AI embeddings, for users whose chat provider has no embedding endpoint
+//! (such as Claude). Chat is not supported.
+
+use super::request_log::RequestLogger;
+use super::traits::*;
+use super::ProviderError;
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+use serde::Deserialize;
+use serde_json::json;
+
+/// Embedding model used when the provider config doesn't name one
+pub const DEFAULT_VOYAGE_EMBEDDING_MODEL: &str = "voyage-3";
+
+/// Texts sent per embeddings request, keeping batches well under the API's token limit
+const MAX_BATCH_EMBED_SIZE: usize = 128;
+
+pub struct VoyageProvider {
+    api_key: String,
+    base_url: String,
+    embedding_model: String,
+    client: reqwest::Client,
+    log: RequestLogger,
+}
+
+impl VoyageProvider {
+    pub fn new(api_key: String, base_url: Option<String>) -> Self {
+        Self {
+            api_key,
+            base_url: base_url
+                .unwrap_or_else(|| "https://api.voyageai.com/v1".to_string())
+                .trim_end_matches('/')
+                .to_string(),
+            embedding_model: DEFAULT_VOYAGE_EMBEDDING_MODEL.to_string(),
+            client: reqwest::Client::new(),
+            log: RequestLogger::default(),
+        }
+    }
+
+    /// Use a different embedding model (e.g. "voyage-3-lite", "voyage-code-3")
+    pub fn with_embedding_model(mut self, model: Option<String>) -> Self {
+        if let Some(model) = model.filter(|model| !model.trim().is_empty()) {
+            self.embedding_model = model.trim().to_string();
+        }
+        self
+    }
+
+    /// Log request and response bodies at debug level, with the API key redacted
+    pub fn with_request_logging(mut self, enabled: bool) -> Self {
+        self.log = RequestLogger::new(enabled);
+        self
+    }
+
+    fn create_headers(&self) -> Result<HeaderMap, ProviderError> {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+        let auth_value =
+            HeaderValue::from_str(&format!("Bearer {}", self.api_key)).map_err(|e| {
+                ProviderError::InvalidConfiguration(format!("Invalid API key format: {}", e))
+            })?;
+        headers.insert(AUTHORIZATION, auth_value);
+
+        Ok(headers)
+    }
+
+    /// Embed one batch of texts with a single request
+    /// Embeddings are returned in the same order as the input texts
+    async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, ProviderError> {
+        let url = format!("{}/embeddings", self.base_url);
+        let body = json!({
+            "input": texts,
+            "model": self.embedding_model,
+        });
+        self.log.request(self.id(), &self.api_key, &url, &body);
+
+        let response = self
+            .client
+            .post(&url)
+            .headers(self.create_headers()?)
+            .json(&body)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(self.log.error(self.id(), &self.api_key, response).await);
+        }
+
+        let mut embed_response: VoyageEmbedResponse = self
+            .log
+            .read_json(self.id(), &self.api_key, response)
+            .await?;
+
+        if embed_response.data.len() != texts.len() {
+            return Err(ProviderError::InvalidResponse(format!(
+                "Voyage returned {} embeddings for {} texts",
+                embed_response.data.len(),
+                texts.len()
+            )));
+        }
+
+        // Each embedding carries the position of its text; don't rely on the list order
+        embed_response.data.sort_by_key(|e| e.index);
+        Ok(embed_response
+            .data
+            .into_iter()
+            .map(|e| e.embedding)
+            .collect())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct VoyageEmbedResponse {
+    data: Vec<VoyageEmbedding>,
+}
+
+#[derive(Debug, Deserialize)]
+struct VoyageEmbedding {
+    embedding: Vec<f32>,
+    index: usize,
+}
+
+#[async_trait]
+impl LlmProvider for VoyageProvider {
+    fn id(&self) -> &'static str {
+        "voyage"
+    }
+
+    fn name(&self) -> &'static str {
+        "Voyage AI"
+    }
+
+    async fn chat(&self, _request: ChatRequest) -> Result<ChatResponse, ProviderError> {
+        Err(ProviderError::UnsupportedFeature(
+            "Voyage AI only provides embeddings, not chat".to_string(),
+        ))
+    }
+
+    async fn stream_chat(
+        &self,
+        _request: ChatRequest,
+        _tx: tokio::sync::mpsc::Sender<ChatChunk>,
+    ) -> Result<(), ProviderError> {
+        Err(ProviderError::UnsupportedFeature(
+            "Voyage AI only provides embeddings, not chat".to_string(),
+        ))
+    }
+
+    fn embedding_model(&self) -> Option<&str> {
+        Some(&self.embedding_model)
+    }
+
+    fn embedding_dimension(&self) -> Option<usize> {
+        match self.embedding_model.as_str() {
+            "voyage-3" | "voyage-3-large" | "voyage-code-3" | "voyage-finance-2"
+            | "voyage-law-2" => Some(1024),
+            "voyage-3-lite" => Some(512),
+            _ => None,
+        }
+    }
+
+    async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, ProviderError> {
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(MAX_BATCH_EMBED_SIZE) {
+            embeddings.extend(self.embed_batch(batch).await?);
+        }
+        Ok(embeddings)
+    }
+
+    /// There is no model listing, so the key is checked by embedding a single word
+    async fn verify_credentials(&self) -> Result<(), ProviderError> {
+        self.embed_batch(&["ping".to_string()]).await.map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embedding_model_defaults_to_voyage_3() {
+        let provider = VoyageProvider::new("key".to_string(), None);
+        assert_eq!(provider.embedding_model(), Some("voyage-3"));
+        assert_eq!(provider.embedding_dimension(), Some(1024));
+
+        let provider = provider.with_embedding_model(Some("voyage-3-lite".to_string()));
+        assert_eq!(provider.embedding_model(), Some("voyage-3-lite"));
+        assert_eq!(provider.embedding_dimension(), Some(512));
+    }
+
+    #[tokio::test]
+    async fn test_chat_is_unsupported() {
+        let provider = VoyageProvider::new("key".to_string(), None);
+        let tx = tokio::sync::mpsc::channel(1).0;
+        let request = ChatRequest {
+            model: "voyage-3".to_string(),
+            messages: Vec::new(),
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            seed: None,
+            response_format: None,
+            stream: false,
+        };
+        assert!(matches!(
+            provider.chat(request.clone()).await,
+            Err(ProviderError::UnsupportedFeature(_))
+        ));
+        assert!(matches!(
+            provider.stream_chat(request, tx).await,
+            Err(ProviderError::UnsupportedFeature(_))
+        ));
+    }
+}
@@ -4,12 +4,19 @@
 mod commands;
 mod config;
 mod llm_providers;
+#[cfg(feature = "mcp")]
+mod mcp;
+mod pricing;
 mod rag;
 mod security;
+mod tokenizer;
 mod validation;
 
-use config::ConfigStore;
+use commands::RequestRegistry;
+use config::{ConfigError, ConfigStore, KeySource};
+use rag::database::DEFAULT_MAX_CONNECTIONS;
 use rag::RagDatabase;
+use security::Cipher;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use tracing_subscriber;
@@ -20,26 +27,55 @@ async fn main() {
     tracing_subscriber::fmt::init();
 
     // Get app data directory
-    let app_data_dir = tauri::api::path::app_config_dir(&tauri::Config::default())
-        .unwrap_or_else(|| {
+    let app_data_dir =
+        tauri::api::path::app_config_dir(&tauri::Config::default()).unwrap_or_else(|| {
             eprintln!("ERROR: Failed to get application config directory.");
             eprintln!("Please ensure the application has proper file system permissions.");
             std::process::exit(1);
         });
 
     // Initialize config store
-    let config_store = Arc::new(Mutex::new(
-        ConfigStore::new(app_data_dir.clone()).unwrap_or_else(|e| {
-            eprintln!("ERROR: Failed to initialize configuration store: {}", e);
-            eprintln!("Config directory: {:?}", app_data_dir);
-            std::process::exit(1);
-        }),
-    ));
+    // Prefer the OS keychain; fall back to a passphrase-derived key when no
+    // keychain backend is available (headless Linux, CI, containers).
+    // An existing salt file means the config was encrypted with a passphrase.
+    let config_store = if app_data_dir.join("key.salt").exists() {
+        ConfigStore::new(
+            app_data_dir.clone(),
+            KeySource::Passphrase(read_passphrase()),
+        )
+    } else {
+        match ConfigStore::new(app_data_dir.clone(), KeySource::Keychain) {
+            Err(ConfigError::KeychainError(e)) => {
+                tracing::warn!(
+                    "OS keychain unavailable ({}), falling back to passphrase",
+                    e
+                );
+                ConfigStore::new(
+                    app_data_dir.clone(),
+                    KeySource::Passphrase(read_passphrase()),
+                )
+            }
+            result => result,
+        }
+    }
+    .map(|store| store.with_cipher(Cipher::from_env()))
+    .unwrap_or_else(|e| {
+        eprintln!("ERROR: Failed to initialize configuration store: {}", e);
+        eprintln!("Config directory: {:?}", app_data_dir);
+        std::process::exit(1);
+    });
+    // Limits saved with `set_validation_limits`, or the defaults
+    let limits = config_store
+        .load()
+        .map(|config| config.general.validation_limits)
+        .unwrap_or_default();
+    let limits = Arc::new(LimitsState::new(limits));
+    let config_store = Arc::new(Mutex::new(config_store));
 
     // Initialize RAG database
     let db_path = app_data_dir.join("rag.db");
     let rag_db = Arc::new(Mutex::new(
-        RagDatabase::new(db_path.clone())
+        RagDatabase::new(db_path.clone(), DEFAULT_MAX_CONNECTIONS)
             .await
             .unwrap_or_else(|e| {
                 eprintln!("ERROR: Failed to initialize RAG database: {}", e);
@@ -53,37 +89,100 @@ async fn main() {
     tauri::Builder::default()
         .manage(config_store)
         .manage(rag_db)
+        .manage(Arc::new(RequestRegistry::default()))
+        .manage(limits)
         .invoke_handler(tauri::generate_handler![
             // Config commands
             commands::get_providers,
+            commands::list_enabled_providers,
             commands::update_provider,
             commands::delete_provider,
+            commands::export_config,
+            commands::import_config,
+            commands::get_validation_limits,
+            commands::set_validation_limits,
+            commands::get_offline_mode,
+            commands::set_offline_mode,
             commands::test_provider_connection,
+            commands::list_provider_models,
             // Chat commands
             commands::send_chat_message,
             commands::send_chat_message_stream,
+            commands::estimate_tokens,
+            commands::cancel_request,
+            commands::factory_reset,
             // RAG commands
             commands::create_project,
             commands::list_projects,
+            commands::update_project,
             commands::delete_project,
+            commands::get_project_stats,
+            commands::get_project_embedding_info,
+            commands::rebuild_index,
+            commands::export_project,
+            commands::import_project,
             commands::list_documents,
+            commands::rename_document,
+            commands::get_document_stats,
             commands::delete_document,
             commands::add_document,
+            commands::add_documents,
+            commands::add_document_from_file,
+            commands::update_document_content,
+            commands::clear_embedding_cache,
             commands::rag_search,
+            commands::rag_search_rerank,
+            commands::rag_search_expanded,
             commands::rag_chat,
+            commands::rag_chat_stream,
             // Canvas commands
             commands::get_canvas_state,
             commands::save_canvas_state,
+            commands::update_canvas_node,
+            commands::update_canvas_edge,
+            commands::list_canvas_versions,
+            commands::restore_canvas_version,
+            commands::run_canvas,
             // Conversation commands
             commands::create_conversation,
+            commands::start_conversation,
             commands::list_conversations,
+            commands::list_conversations_for_project,
             commands::get_conversation_with_messages,
             commands::update_conversation_title,
+            commands::update_conversation_settings,
+            commands::generate_conversation_title,
+            commands::fork_conversation,
             commands::delete_conversation,
+            commands::list_trashed_conversations,
+            commands::restore_conversation,
+            commands::purge_conversation,
             commands::add_message,
+            commands::chat_in_conversation,
+            commands::regenerate_last_response,
             commands::get_conversation_messages,
+            commands::edit_message,
             commands::delete_message,
+            // Usage commands
+            commands::get_usage_summary,
+            // Health commands
+            commands::health_check,
+            // MCP server commands
+            commands::start_mcp_server,
+            commands::stop_mcp_server,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+/// Read the config passphrase, exiting if none can be obtained
+fn read_passphrase() -> String {
+    security::prompt_passphrase().unwrap_or_else(|e| {
+        let message = format!("Failed to read passphrase: {}", e);
+        eprintln!("ERROR: {}", message);
+        // Release builds on Windows have no console, so show the error instead
+        #[cfg(all(windows, not(debug_assertions)))]
+        tauri::api::dialog::blocking::message::<tauri::Wry>(None, "LLM Workbench", &message);
+        std::process::exit(1);
+    })
+}
@@ -0,0 +1,626 @@
+//! Model Context Protocol server exposing RAG projects to editors and agents
+//! Speaks JSON-RPC 2.0 over HTTP POST (the "streamable HTTP" transport, without
+//! server-initiated streams). Projects are listed as resources; `rag_search` and
+//! `rag_chat` are tools backed by the same code as the app's commands.
+
+use crate::commands::{
+    answer_rag_chat, search_chunks, validate_search_request, CommandError, RagChatRequest,
+    RagSearchRequest,
+};
+use crate::config::ConfigStore;
+use crate::rag::RagDatabase;
+use crate::validation::LimitsState;
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::header::{CONTENT_TYPE, HOST, ORIGIN};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::sync::{oneshot, Mutex};
+
+/// Protocol revisions this server understands, newest first
+const PROTOCOL_VERSIONS: [&str; 3] = ["2025-06-18", "2025-03-26", "2024-11-05"];
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+
+/// Results per search when the client doesn't say
+const DEFAULT_TOP_K: usize = 5;
+
+fn default_top_k() -> usize {
+    DEFAULT_TOP_K
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchArgs {
+    project_id: i64,
+    query: String,
+    provider_id: String,
+    #[serde(default = "default_top_k")]
+    top_k: usize,
+    min_similarity: Option<f32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatArgs {
+    project_id: i64,
+    query: String,
+    provider_id: String,
+    #[serde(default)]
+    model: String,
+    #[serde(default = "default_top_k")]
+    top_k: usize,
+}
+
+/// A JSON-RPC error response for request `id`
+fn rpc_error(id: Value, code: i64, message: impl Into<String>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message.into() },
+    })
+}
+
+/// A tool result: errors are reported to the model as content, not as protocol errors
+fn tool_result(result: Result<Value, CommandError>) -> Value {
+    match result {
+        Ok(value) => json!({
+            "content": [{ "type": "text", "text": value.to_string() }],
+            "isError": false,
+        }),
+        Err(e) => json!({
+            "content": [{ "type": "text", "text": e.to_string() }],
+            "isError": true,
+        }),
+    }
+}
+
+pub struct McpServer {
+    rag_db: Arc<Mutex<RagDatabase>>,
+    config_store: Arc<Mutex<ConfigStore>>,
+    limits: Arc<LimitsState>,
+}
+
+impl McpServer {
+    pub fn new(
+        rag_db: Arc<Mutex<RagDatabase>>,
+        config_store: Arc<Mutex<ConfigStore>>,
+        limits: Arc<LimitsState>,
+    ) -> Self {
+        Self {
+            rag_db,
+            config_store,
+            limits,
+        }
+    }
+
+    /// Handle one JSON-RPC message; notifications get no response
+    pub async fn handle(&self, message: Value) -> Option<Value> {
+        let Some(method) = message.get("method").and_then(Value::as_str) else {
+            let id = message.get("id").cloned().unwrap_or(Value::Null);
+            return Some(rpc_error(id, INVALID_REQUEST, "Missing method"));
+        };
+        let id = message.get("id").cloned()?;
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+
+        let result = match method {
+            "initialize" => Ok(self.initialize(&params)),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(self.list_tools()),
+            "tools/call" => self.call_tool(params).await,
+            "resources/list" => self.list_resources().await,
+            "resources/read" => self.read_resource(params).await,
+            _ => Err((METHOD_NOT_FOUND, format!("Unknown method: {}", method))),
+        };
+
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => rpc_error(id, code, message),
+        })
+    }
+
+    fn initialize(&self, params: &Value) -> Value {
+        let requested = params.get("protocolVersion").and_then(Value::as_str);
+        let version = PROTOCOL_VERSIONS
+            .into_iter()
+            .find(|v| Some(*v) == requested)
+            .unwrap_or(PROTOCOL_VERSIONS[0]);
+
+        json!({
+            "protocolVersion": version,
+            "capabilities": { "tools": {}, "resources": {} },
+            "serverInfo": { "name": "llm-workbench", "version": env!("CARGO_PKG_VERSION") },
+        })
+    }
+
+    fn list_tools(&self) -> Value {
+        let max_top_k = self.limits.get().max_top_k;
+        json!({
+            "tools": [
+                {
+                    "name": "rag_search",
+                    "description": "Find the chunks of a project's documents most similar to a query",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "project_id": { "type": "integer" },
+                            "query": { "type": "string" },
+                            "provider_id": { "type": "string", "description": "Provider used to embed the query" },
+                            "top_k": { "type": "integer", "minimum": 1, "maximum": max_top_k, "default": DEFAULT_TOP_K },
+                            "min_similarity": { "type": "number" },
+                        },
+                        "required": ["project_id", "query", "provider_id"],
+                    },
+                },
+                {
+                    "name": "rag_chat",
+                    "description": "Answer a question using a project's documents as context",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "project_id": { "type": "integer" },
+                            "query": { "type": "string" },
+                            "provider_id": { "type": "string" },
+                            "model": { "type": "string", "description": "Omit for the provider's default model" },
+                            "top_k": { "type": "integer", "minimum": 1, "maximum": max_top_k, "default": DEFAULT_TOP_K },
+                        },
+                        "required": ["project_id", "query", "provider_id"],
+                    },
+                },
+            ],
+        })
+    }
+
+    async fn call_tool(&self, params: Value) -> Result<Value, (i64, String)> {
+        let name = params
+            .get("name")
+            .and_then(Value::as_str)
+            .ok_or((INVALID_PARAMS, "Missing tool name".to_string()))?;
+        let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+        let invalid = |e: serde_json::Error| (INVALID_PARAMS, format!("Invalid arguments: {}", e));
+
+        match name {
+            "rag_search" => {
+                let args: SearchArgs = serde_json::from_value(arguments).map_err(invalid)?;
+                Ok(tool_result(self.search(args).await))
+            }
+            "rag_chat" => {
+                let args: ChatArgs = serde_json::from_value(arguments).map_err(invalid)?;
+                Ok(tool_result(self.chat(args).await))
+            }
+            _ => Err((INVALID_PARAMS, format!("Unknown tool: {}", name))),
+        }
+    }
+
+    async fn search(&self, args: SearchArgs) -> Result<Value, CommandError> {
+        let request = RagSearchRequest {
+            project_id: args.project_id,
+            query: args.query,
+            provider_id: args.provider_id,
+            top_k: args.top_k,
+            metric: None,
+            min_similarity: args.min_similarity,
+            context_window: None,
+            ef_search: None,
+            explain: false,
+            document_ids: None,
+            name_like: None,
+        };
+        validate_search_request(&request, &self.limits.get())?;
+
+        let matches = search_chunks(&self.rag_db, &self.config_store, request).await?;
+        Ok(json!(matches
+            .into_iter()
+            .map(|m| json!({
+                "document": m.document_name,
+                "similarity": m.similarity,
+                "content": m.chunk.content,
+            }))
+            .collect::<Vec<_>>()))
+    }
+
+    async fn chat(&self, args: ChatArgs) -> Result<Value, CommandError> {
+        let request = RagChatRequest {
+            project_id: args.project_id,
+            query: args.query,
+            provider_id: args.provider_id,
+            model: args.model,
+            top_k: args.top_k,
+            temperature: None,
+            max_tokens: None,
+            max_context_tokens: None,
+            history: None,
+            conversation_id: None,
+            metric: None,
+            min_similarity: None,
+            context_window: None,
+            stream_capacity: None,
+            json_schema: None,
+            document_ids: None,
+            name_like: None,
+        };
+
+        let response = answer_rag_chat(
+            &self.rag_db,
+            &self.config_store,
+            &self.limits.get(),
+            request,
+        )
+        .await?;
+        Ok(json!({
+            "answer": response.response,
+            "sources": response
+                .sources
+                .iter()
+                .map(|m| m.document_name.clone())
+                .collect::<Vec<_>>(),
+        }))
+    }
+
+    async fn list_resources(&self) -> Result<Value, (i64, String)> {
+        let projects = self
+            .rag_db
+            .lock()
+            .await
+            .list_projects()
+            .await
+            .map_err(|e| (INTERNAL_ERROR, e.to_string()))?;
+
+        Ok(json!({
+            "resources": projects
+                .into_iter()
+                .map(|p| json!({
+                    "uri": format!("project://{}", p.id),
+                    "name": p.name,
+                    "mimeType": "application/json",
+                }))
+                .collect::<Vec<_>>(),
+        }))
+    }
+
+    async fn read_resource(&self, params: Value) -> Result<Value, (i64, String)> {
+        let uri = params
+            .get("uri")
+            .and_then(Value::as_str)
+            .ok_or((INVALID_PARAMS, "Missing uri".to_string()))?;
+        let project_id: i64 = uri
+            .strip_prefix("project://")
+            .and_then(|id| id.parse().ok())
+            .ok_or((INVALID_PARAMS, format!("Unknown resource: {}", uri)))?;
+
+        let db = self.rag_db.lock().await;
+        let project = db
+            .get_project(project_id)
+            .await
+            .map_err(|e| (INVALID_PARAMS, e.to_string()))?;
+        let stats = db
+            .get_project_stats(project_id)
+            .await
+            .map_err(|e| (INTERNAL_ERROR, e.to_string()))?;
+
+        let text = json!({
+            "id": project.id,
+            "name": project.name,
+            "document_count": stats.document_count,
+            "chunk_count": stats.chunk_count,
+        });
+        Ok(json!({
+            "contents": [{ "uri": uri, "mimeType": "application/json", "text": text.to_string() }],
+        }))
+    }
+}
+
+/// Whether `host` names this machine's loopback interface: localhost or any loopback
+/// address, IPv4 or IPv6
+fn is_loopback_host(host: url::Host<&str>) -> bool {
+    match host {
+        url::Host::Domain(domain) => domain.eq_ignore_ascii_case("localhost"),
+        url::Host::Ipv4(ip) => ip.is_loopback(),
+        url::Host::Ipv6(ip) => ip.is_loopback(),
+    }
+}
+
+/// Whether a Host header value, with or without a port, names the loopback interface
+fn is_loopback_authority(authority: &str) -> bool {
+    url::Url::parse(&format!("http://{}", authority))
+        .ok()
+        .is_some_and(|url| url.host().is_some_and(is_loopback_host))
+}
+
+/// Reject requests a web page could have sent: any page can POST to a port on
+/// localhost, so the Origin (when present) must be local and the Host must be
+/// localhost, which also stops DNS rebinding. Requiring a JSON content type
+/// keeps cross-site form posts, which can't set it without a preflight, out.
+fn check_transport(headers: &HeaderMap) -> Result<(), (StatusCode, &'static str)> {
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+
+    if let Some(origin) = headers.get(ORIGIN) {
+        let local = origin
+            .to_str()
+            .ok()
+            .and_then(|origin| url::Url::parse(origin).ok())
+            .is_some_and(|origin| origin.host().is_some_and(is_loopback_host));
+        if !local {
+            return Err((StatusCode::FORBIDDEN, "Origin not allowed"));
+        }
+    }
+
+    if !header(HOST).is_some_and(is_loopback_authority) {
+        return Err((StatusCode::FORBIDDEN, "Host not allowed"));
+    }
+
+    let json = header(CONTENT_TYPE).is_some_and(|content_type| {
+        content_type
+            .split(';')
+            .next()
+            .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("application/json"))
+    });
+    if !json {
+        return Err((
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "Content-Type must be application/json",
+        ));
+    }
+
+    Ok(())
+}
+
+/// POST /mcp: a single JSON-RPC message or a batch
+async fn post_message(
+    State(server): State<Arc<McpServer>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    if let Err(rejection) = check_transport(&headers) {
+        return rejection.into_response();
+    }
+
+    let message: Value = match serde_json::from_slice(&body) {
+        Ok(message) => message,
+        Err(e) => return Json(rpc_error(Value::Null, PARSE_ERROR, e.to_string())).into_response(),
+    };
+
+    let response = match message {
+        Value::Array(batch) => {
+            let mut responses = Vec::new();
+            for message in batch {
+                responses.extend(server.handle(message).await);
+            }
+            (!responses.is_empty()).then_some(Value::Array(responses))
+        }
+        message => server.handle(message).await,
+    };
+
+    match response {
+        Some(response) => Json(response).into_response(),
+        None => StatusCode::ACCEPTED.into_response(),
+    }
+}
+
+/// Serve MCP on `listener` until `shutdown` fires
+pub async fn serve(
+    server: McpServer,
+    listener: TcpListener,
+    shutdown: oneshot::Receiver<()>,
+) -> std::io::Result<()> {
+    let app = Router::new()
+        .route("/mcp", post(post_message))
+        .with_state(Arc::new(server));
+
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async {
+            let _ = shutdown.await;
+        })
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_support;
+
+    async fn server(dir: &tempfile::TempDir) -> McpServer {
+        let (rag_db, config_store) = test_support::stores(dir).await;
+        rag_db
+            .lock()
+            .await
+            .create_project("Notes".to_string())
+            .await
+            .unwrap();
+
+        McpServer::new(rag_db, config_store, Arc::new(LimitsState::default()))
+    }
+
+    fn request(method: &str, params: Value) -> Value {
+        json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params })
+    }
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.parse().unwrap(), value.parse().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_transport_checks() {
+        let local = [
+            ("host", "127.0.0.1:3917"),
+            ("content-type", "application/json"),
+        ];
+        assert!(check_transport(&headers(&local)).is_ok());
+        assert!(check_transport(&headers(&[
+            ("host", "localhost:3917"),
+            ("origin", "http://localhost:5173"),
+            ("content-type", "application/json; charset=utf-8"),
+        ]))
+        .is_ok());
+        assert!(check_transport(&headers(&[
+            ("host", "[::1]:3917"),
+            ("origin", "http://[::1]:5173"),
+            local[1],
+        ]))
+        .is_ok());
+
+        let status = |pairs: &[(&'static str, &'static str)]| {
+            check_transport(&headers(pairs)).unwrap_err().0
+        };
+        assert_eq!(
+            status(&[("origin", "https://evil.example"), local[0], local[1]]),
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            status(&[("origin", "null"), local[0], local[1]]),
+            StatusCode::FORBIDDEN
+        );
+        // DNS rebinding: a foreign name resolving to 127.0.0.1
+        assert_eq!(
+            status(&[("host", "evil.example:3917"), local[1]]),
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(status(&[local[1]]), StatusCode::FORBIDDEN);
+        assert_eq!(
+            status(&[local[0], ("content-type", "text/plain")]),
+            StatusCode::UNSUPPORTED_MEDIA_TYPE
+        );
+        assert_eq!(status(&[local[0]]), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[test]
+    fn test_loopback_authorities() {
+        for authority in [
+            "localhost",
+            "LOCALHOST:3917",
+            "127.0.0.1",
+            "127.0.0.1:3917",
+            "127.1.2.3:3917",
+            "[::1]",
+            "[::1]:3917",
+        ] {
+            assert!(is_loopback_authority(authority), "{}", authority);
+        }
+        for authority in [
+            "",
+            "evil.example:3917",
+            "localhost.evil.example",
+            "10.0.0.1:3917",
+            "[::2]:3917",
+            "0.0.0.0:3917",
+        ] {
+            assert!(!is_loopback_authority(authority), "{}", authority);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_post_message_rejects_cross_site_requests() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let server = Arc::new(server(&dir).await);
+        let body = Bytes::from(request("ping", json!({})).to_string());
+
+        let response = post_message(
+            State(server.clone()),
+            headers(&[
+                ("host", "127.0.0.1:3917"),
+                ("origin", "https://evil.example"),
+                ("content-type", "application/json"),
+            ]),
+            body.clone(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = post_message(
+            State(server),
+            headers(&[
+                ("host", "127.0.0.1:3917"),
+                ("content-type", "application/json"),
+            ]),
+            body,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_initialize_and_list_tools() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let server = server(&dir).await;
+
+        let response = server
+            .handle(request(
+                "initialize",
+                json!({ "protocolVersion": "2024-11-05" }),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response["result"]["protocolVersion"], "2024-11-05");
+
+        let response = server
+            .handle(request("tools/list", json!({})))
+            .await
+            .unwrap();
+        let names: Vec<&str> = response["result"]["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|t| t["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, ["rag_search", "rag_chat"]);
+    }
+
+    #[tokio::test]
+    async fn test_projects_listed_as_resources() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let server = server(&dir).await;
+
+        let response = server
+            .handle(request("resources/list", json!({})))
+            .await
+            .unwrap();
+        let resource = &response["result"]["resources"][0];
+        assert_eq!(resource["name"], "Notes");
+
+        let response = server
+            .handle(request("resources/read", json!({ "uri": resource["uri"] })))
+            .await
+            .unwrap();
+        let text = response["result"]["contents"][0]["text"].as_str().unwrap();
+        assert!(text.contains("\"document_count\":0"));
+    }
+
+    #[tokio::test]
+    async fn test_errors() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let server = server(&dir).await;
+
+        let notification = json!({ "jsonrpc": "2.0", "method": "notifications/initialized" });
+        assert!(server.handle(notification).await.is_none());
+
+        let response = server
+            .handle(request("no/such/method", json!({})))
+            .await
+            .unwrap();
+        assert_eq!(response["error"]["code"], METHOD_NOT_FOUND);
+
+        // Invalid tool input is reported to the model, not as a protocol error
+        let response = server
+            .handle(request(
+                "tools/call",
+                json!({
+                    "name": "rag_search",
+                    "arguments": { "project_id": 1, "query": "", "provider_id": "mock" },
+                }),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response["result"]["isError"], true);
+    }
+}
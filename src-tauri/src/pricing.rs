@@ -0,0 +1,50 @@
+/// List prices (USD per 1M tokens) as (model id prefix, input, output)
+/// Estimates only: cached input, batch discounts and price changes aren't reflected.
+/// A model uses the entry with the longest matching prefix.
+const MODEL_PRICES: &[(&str, f64, f64)] = &[
+    ("claude-3-5-sonnet", 3.0, 15.0),
+    ("claude-3-5-haiku", 0.8, 4.0),
+    ("claude-3-opus", 15.0, 75.0),
+    ("claude-3-sonnet", 3.0, 15.0),
+    ("claude-3-haiku", 0.25, 1.25),
+    ("deepseek-chat", 0.27, 1.1),
+    ("deepseek-coder", 0.27, 1.1),
+    ("deepseek-reasoner", 0.55, 2.19),
+    ("gemini-1.5-pro", 1.25, 5.0),
+    ("gemini-1.5-flash", 0.075, 0.3),
+    ("gemini-1.0-pro", 0.5, 1.5),
+    ("gpt-4o", 2.5, 10.0),
+    ("gpt-4o-mini", 0.15, 0.6),
+];
+
+/// Estimated cost in USD of a request, or None if the model's price is unknown
+pub fn estimate_cost(model: &str, prompt_tokens: usize, completion_tokens: usize) -> Option<f64> {
+    let (_, input, output) = MODEL_PRICES
+        .iter()
+        .filter(|(prefix, _, _)| model.starts_with(prefix))
+        .max_by_key(|(prefix, _, _)| prefix.len())?;
+
+    Some((prompt_tokens as f64 * input + completion_tokens as f64 * output) / 1_000_000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_cost() {
+        let cost = estimate_cost("claude-3-5-sonnet-20241022", 1_000_000, 0).unwrap();
+        assert!((cost - 3.0).abs() < 1e-9);
+
+        let cost = estimate_cost("deepseek-chat", 500_000, 1_000_000).unwrap();
+        assert!((cost - (0.135 + 1.1)).abs() < 1e-9);
+
+        assert_eq!(estimate_cost("unknown-model", 1_000, 1_000), None);
+    }
+
+    #[test]
+    fn test_longest_prefix_wins() {
+        let cost = estimate_cost("gpt-4o-mini-2024-07-18", 1_000_000, 0).unwrap();
+        assert!((cost - 0.15).abs() < 1e-9);
+    }
+}
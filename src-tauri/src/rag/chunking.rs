@@ -1,10 +1,17 @@
-/// Simple text chunking with sliding window and overlap
-/// This is a basic implementation; production systems might use more sophisticated chunking
-/// (e.g., semantic chunking, sentence-aware chunking, etc.)
+//! Simple text chunking with sliding window and overlap
+//! This is a basic implementation; production systems might use more sophisticated chunking
+//! (e.g., semantic chunking, sentence-aware chunking, etc.)
+
+use serde::Deserialize;
 
 const DEFAULT_CHUNK_SIZE: usize = 512; // ~512 tokens ≈ 2048 characters
 const DEFAULT_OVERLAP: usize = 50; // ~50 tokens ≈ 200 characters
 
+/// Separates pages in the stored text of paginated documents (form feed)
+pub const PAGE_SEPARATOR: char = '\u{c}';
+
+/// Chunk size and overlap, both in characters
+#[derive(Debug, Clone, Deserialize)]
 pub struct ChunkConfig {
     pub chunk_size: usize,
     pub overlap: usize,
@@ -19,17 +26,44 @@ impl Default for ChunkConfig {
     }
 }
 
+/// A chunk of text and where it lies in the original
+/// Offsets count characters (Unicode scalar values), not bytes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkPiece {
+    pub content: String,
+    /// Position of the chunk's first character
+    pub start_offset: usize,
+    /// Position just past the chunk's last character
+    pub end_offset: usize,
+    /// Page (1-based) of paginated text; see `chunk_pages`
+    pub page_number: Option<i32>,
+}
+
+impl ChunkPiece {
+    fn new(content: &str, start_offset: usize) -> Self {
+        Self {
+            content: content.to_string(),
+            start_offset,
+            end_offset: start_offset + content.chars().count(),
+            page_number: None,
+        }
+    }
+}
+
 /// Chunk text into overlapping segments
-/// Returns a vector of text chunks
-pub fn chunk_text(text: &str, config: Option<ChunkConfig>) -> Vec<String> {
+/// Returns the chunks in order, each with its character offsets in `text`
+pub fn chunk_text(text: &str, config: Option<ChunkConfig>) -> Vec<ChunkPiece> {
     let config = config.unwrap_or_default();
 
     if text.len() <= config.chunk_size {
-        return vec![text.to_string()];
+        return vec![ChunkPiece::new(text, 0)];
     }
 
     let mut chunks = Vec::new();
     let mut start = 0;
+    // Character offset of `start`, advanced as `start` moves forward
+    let mut start_char = 0;
+    let mut counted_to = 0;
 
     while start < text.len() {
         let end = (start + config.chunk_size).min(text.len());
@@ -43,7 +77,9 @@ pub fn chunk_text(text: &str, config: Option<ChunkConfig>) -> Vec<String> {
             end
         };
 
-        chunks.push(text[start..chunk_end].to_string());
+        start_char += text[counted_to..start].chars().count();
+        counted_to = start;
+        chunks.push(ChunkPiece::new(&text[start..chunk_end], start_char));
 
         // Move start forward, accounting for overlap
         if chunk_end >= text.len() {
@@ -61,11 +97,60 @@ pub fn chunk_text(text: &str, config: Option<ChunkConfig>) -> Vec<String> {
     chunks
 }
 
+/// Chunk text that may hold several pages separated by `PAGE_SEPARATOR`
+/// Pages are chunked separately, so no chunk spans two of them, and each chunk comes
+/// with its 1-based page number; offsets are still positions in the whole text.
+/// Text without separators is chunked as with `chunk_text`, without page numbers.
+pub fn chunk_pages(text: &str, config: Option<ChunkConfig>) -> Vec<ChunkPiece> {
+    if !text.contains(PAGE_SEPARATOR) {
+        return chunk_text(text, config);
+    }
+
+    let mut chunks = Vec::new();
+    let mut page_start = 0;
+    for (idx, page) in text.split(PAGE_SEPARATOR).enumerate() {
+        if !page.trim().is_empty() {
+            chunks.extend(
+                chunk_text(page, config.clone())
+                    .into_iter()
+                    .map(|chunk| ChunkPiece {
+                        start_offset: page_start + chunk.start_offset,
+                        end_offset: page_start + chunk.end_offset,
+                        page_number: Some(idx as i32 + 1),
+                        ..chunk
+                    }),
+            );
+        }
+        // Skip the page and its separator
+        page_start += page.chars().count() + 1;
+    }
+
+    chunks
+}
+
+/// Join consecutive chunks of a document back into one text, dropping the overlap
+/// `chunk_text` repeats at the start of each chunk
+pub fn merge_chunks<S: AsRef<str>>(chunks: &[S]) -> String {
+    let mut merged = String::new();
+
+    for chunk in chunks {
+        let chunk = chunk.as_ref();
+        let overlap = (1..=merged.len().min(chunk.len()))
+            .rev()
+            .filter(|&len| chunk.is_char_boundary(len))
+            .find(|&len| merged.ends_with(&chunk[..len]))
+            .unwrap_or(0);
+        merged.push_str(&chunk[overlap..]);
+    }
+
+    merged
+}
+
 /// Find a good boundary (sentence or word) to break the text
 /// Returns the offset from the start of the text
 fn find_boundary(text: &str) -> Option<usize> {
     // Try to find sentence ending (. ! ?)
-    if let Some(pos) = text.rfind(|c| c == '.' || c == '!' || c == '?') {
+    if let Some(pos) = text.rfind(['.', '!', '?']) {
         return Some(pos + 1);
     }
 
@@ -91,7 +176,7 @@ mod tests {
         let text = "This is a small text.";
         let chunks = chunk_text(text, None);
         assert_eq!(chunks.len(), 1);
-        assert_eq!(chunks[0], text);
+        assert_eq!(chunks[0].content, text);
     }
 
     #[test]
@@ -105,8 +190,8 @@ mod tests {
 
         assert!(chunks.len() > 1);
         // Check that chunks have some overlap
-        for i in 0..chunks.len() - 1 {
-            assert!(chunks[i].len() <= 1000 + 10); // Allow some margin
+        for chunk in &chunks[..chunks.len() - 1] {
+            assert!(chunk.content.len() <= 1000 + 10); // Allow some margin
         }
     }
 
@@ -122,7 +207,74 @@ mod tests {
         // Chunks should ideally break at sentence boundaries
         assert!(chunks.len() > 1);
         for chunk in &chunks {
-            println!("Chunk: {}", chunk);
+            println!("Chunk: {}", chunk.content);
+        }
+    }
+
+    #[test]
+    fn test_chunk_offsets_locate_chunks() {
+        let text = "Première phrase. Deuxième phrase. Troisième phrase. Quatrième phrase.";
+        let config = ChunkConfig {
+            chunk_size: 30,
+            overlap: 5,
+        };
+        let chars: Vec<char> = text.chars().collect();
+
+        for chunk in chunk_text(text, Some(config)) {
+            let located: String = chars[chunk.start_offset..chunk.end_offset].iter().collect();
+            assert_eq!(located, chunk.content);
         }
     }
+
+    #[test]
+    fn test_chunk_pages_numbers_each_page() {
+        let text = "First page.\u{c}\u{c}Third page.";
+        let chunks = chunk_pages(text, None);
+        let located: Vec<(&str, Option<i32>, usize, usize)> = chunks
+            .iter()
+            .map(|c| {
+                (
+                    c.content.as_str(),
+                    c.page_number,
+                    c.start_offset,
+                    c.end_offset,
+                )
+            })
+            .collect();
+        assert_eq!(
+            located,
+            vec![
+                ("First page.", Some(1), 0, 11),
+                ("Third page.", Some(3), 13, 24)
+            ]
+        );
+
+        let chunks = chunk_pages("No pages.", None);
+        assert_eq!(chunks[0].page_number, None);
+    }
+
+    #[test]
+    fn test_merge_chunks_restores_text() {
+        let text = "First sentence. Second sentence. Third sentence. Fourth sentence.";
+        let config = ChunkConfig {
+            chunk_size: 30,
+            overlap: 5,
+        };
+        let chunks: Vec<String> = chunk_text(text, Some(config))
+            .into_iter()
+            .map(|chunk| chunk.content)
+            .collect();
+
+        assert_eq!(merge_chunks(&chunks), text);
+        assert_eq!(
+            merge_chunks(&chunks[1..]),
+            text[text.find(&chunks[1]).unwrap()..]
+        );
+    }
+
+    #[test]
+    fn test_merge_chunks_without_overlap() {
+        assert_eq!(merge_chunks(&["abc", "def"]), "abcdef");
+        assert_eq!(merge_chunks::<&str>(&[]), "");
+    }
 }
@@ -0,0 +1,196 @@
+/// Validate the `[n]` citation markers in `text` against the number of sources
+/// Markers outside `1..=source_count` are stripped from the text. Code spans and fenced
+/// code blocks are left alone, as is an index right after an identifier (`arr[0]`).
+/// Returns the cleaned text and the cited source indices (0-based, sorted, unique).
+pub fn extract_citations(text: &str, source_count: usize) -> (String, Vec<usize>) {
+    let mut cleaned = String::with_capacity(text.len());
+    let mut citations = Vec::new();
+
+    for (is_code, segment) in code_segments(text) {
+        if is_code {
+            cleaned.push_str(segment);
+        } else {
+            extract_markers(segment, source_count, &mut cleaned, &mut citations);
+        }
+    }
+
+    citations.sort_unstable();
+    citations.dedup();
+    (cleaned, citations)
+}
+
+/// Copy `text` to `cleaned`, keeping valid markers and collecting their indices
+fn extract_markers(
+    text: &str,
+    source_count: usize,
+    cleaned: &mut String,
+    citations: &mut Vec<usize>,
+) {
+    let mut rest = text;
+
+    while let Some(open) = rest.find('[') {
+        cleaned.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+
+        let digits = after.len() - after.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let follows_identifier = cleaned
+            .chars()
+            .next_back()
+            .is_some_and(|c| c.is_alphanumeric() || c == '_');
+        let is_marker = digits > 0 && after[digits..].starts_with(']') && !follows_identifier;
+
+        if !is_marker {
+            cleaned.push('[');
+            rest = after;
+            continue;
+        }
+
+        let marker = &rest[open..open + digits + 2];
+        match after[..digits].parse::<usize>() {
+            Ok(n) if n >= 1 && n <= source_count => {
+                cleaned.push_str(marker);
+                citations.push(n - 1);
+            }
+            _ => {
+                // Invalid citation: drop it along with the space before it
+                if cleaned.ends_with(' ') {
+                    cleaned.pop();
+                }
+            }
+        }
+        rest = &after[digits + 1..];
+    }
+    cleaned.push_str(rest);
+}
+
+/// Split Markdown into prose and code, as `(is_code, text)` in order
+/// Code is a fenced block (a line starting with three or more backticks, up to a closing
+/// fence or the end of the text) or an inline span closed by a run of as many backticks.
+/// An unclosed inline run is prose.
+fn code_segments(text: &str) -> Vec<(bool, &str)> {
+    let mut segments = Vec::new();
+    let mut prose_start = 0;
+    let mut pos = 0;
+
+    while let Some(offset) = text[pos..].find('`') {
+        let start = pos + offset;
+        let run = backtick_run(&text[start..]);
+        let line_start = text[..start].rfind('\n').map_or(0, |n| n + 1);
+        let end = if run >= 3 && text[line_start..start].trim().is_empty() {
+            Some(fence_end(text, start, run))
+        } else {
+            span_end(text, start + run, run)
+        };
+
+        match end {
+            Some(end) => {
+                if prose_start < start {
+                    segments.push((false, &text[prose_start..start]));
+                }
+                segments.push((true, &text[start..end]));
+                prose_start = end;
+                pos = end;
+            }
+            None => pos = start + run,
+        }
+    }
+
+    if prose_start < text.len() {
+        segments.push((false, &text[prose_start..]));
+    }
+    segments
+}
+
+/// Number of backticks `text` starts with
+fn backtick_run(text: &str) -> usize {
+    text.len() - text.trim_start_matches('`').len()
+}
+
+/// End of the inline code span whose content starts at `from`, if a closing run exists
+fn span_end(text: &str, mut from: usize, run: usize) -> Option<usize> {
+    while let Some(offset) = text[from..].find('`') {
+        let start = from + offset;
+        let len = backtick_run(&text[start..]);
+        if len == run {
+            return Some(start + len);
+        }
+        from = start + len;
+    }
+    None
+}
+
+/// End of the fenced block opened at `start`: the end of its closing fence line,
+/// or of the text if it is never closed
+fn fence_end(text: &str, start: usize, run: usize) -> usize {
+    let mut pos = text[start..]
+        .find('\n')
+        .map_or(text.len(), |n| start + n + 1);
+
+    while pos < text.len() {
+        let line_end = text[pos..].find('\n').map_or(text.len(), |n| pos + n);
+        let line = text[pos..line_end].trim();
+        if line.len() >= run && line.bytes().all(|b| b == b'`') {
+            return line_end;
+        }
+        pos = line_end + 1;
+    }
+    text.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_citations_extracted() {
+        let (text, citations) = extract_citations("Paris [2] is the capital [1][2].", 3);
+        assert_eq!(text, "Paris [2] is the capital [1][2].");
+        assert_eq!(citations, vec![0, 1]);
+    }
+
+    #[test]
+    fn test_out_of_range_citations_stripped() {
+        let (text, citations) = extract_citations("Claim [1] and another [7].", 2);
+        assert_eq!(text, "Claim [1] and another.");
+        assert_eq!(citations, vec![0]);
+    }
+
+    #[test]
+    fn test_zero_is_not_a_citation() {
+        let (text, citations) = extract_citations("Nothing [0] here", 2);
+        assert_eq!(text, "Nothing here");
+        assert!(citations.is_empty());
+    }
+
+    #[test]
+    fn test_non_numeric_brackets_kept() {
+        let (text, citations) = extract_citations("See [note] and arr[i] [", 2);
+        assert_eq!(text, "See [note] and arr[i] [");
+        assert!(citations.is_empty());
+    }
+
+    #[test]
+    fn test_code_left_alone() {
+        let text = "Use `v[7]` or `` a[`9`] `` [1].\n```rust\nlet x = arr[0];\nlet y = m[12];\n```\nDone [9].";
+        let (cleaned, citations) = extract_citations(text, 2);
+        assert_eq!(
+            cleaned,
+            "Use `v[7]` or `` a[`9`] `` [1].\n```rust\nlet x = arr[0];\nlet y = m[12];\n```\nDone."
+        );
+        assert_eq!(citations, vec![0]);
+    }
+
+    #[test]
+    fn test_index_after_identifier_is_not_a_citation() {
+        let (text, citations) = extract_citations("Read arr[0] and data_2[1], see [2]", 2);
+        assert_eq!(text, "Read arr[0] and data_2[1], see [2]");
+        assert_eq!(citations, vec![1]);
+    }
+
+    #[test]
+    fn test_unclosed_backticks_are_prose() {
+        let (text, citations) = extract_citations("A ` tick [1] and [5]", 1);
+        assert_eq!(text, "A ` tick [1] and");
+        assert_eq!(citations, vec![0]);
+    }
+}
@@ -0,0 +1,194 @@
+use super::database::ChunkMatch;
+use crate::llm_providers::{ChatMessage, ChatRole};
+use crate::tokenizer::count_tokens;
+
+/// Maximum number of prior messages included as conversation history
+pub const MAX_HISTORY_MESSAGES: usize = 20;
+
+/// Separator placed between sources in the context
+const SOURCE_SEPARATOR: &str = "\n\n";
+
+/// Format a single source for the context, numbered from 1
+pub fn format_source(index: usize, chunk_match: &ChunkMatch) -> String {
+    format!(
+        "[Source {}: {}]\n{}",
+        index + 1,
+        chunk_match.document_name,
+        chunk_match
+            .expanded_content
+            .as_deref()
+            .unwrap_or(&chunk_match.chunk.content)
+    )
+}
+
+/// Join sources into a single context string
+pub fn build_context(sources: &[ChunkMatch]) -> String {
+    sources
+        .iter()
+        .enumerate()
+        .map(|(i, chunk_match)| format_source(i, chunk_match))
+        .collect::<Vec<_>>()
+        .join(SOURCE_SEPARATOR)
+}
+
+/// Keep the highest-scoring sources whose formatted text fits in `max_tokens`
+/// Sources are expected in descending similarity order (as returned by search);
+/// once one doesn't fit, it and all lower-scoring sources are dropped.
+pub fn fit_sources_to_budget(sources: Vec<ChunkMatch>, max_tokens: usize) -> Vec<ChunkMatch> {
+    let separator_tokens = count_tokens(SOURCE_SEPARATOR);
+    let mut used = 0;
+    let mut included = 0;
+
+    for (i, chunk_match) in sources.iter().enumerate() {
+        let mut cost = count_tokens(&format_source(i, chunk_match));
+        if i > 0 {
+            cost += separator_tokens;
+        }
+        if used + cost > max_tokens {
+            break;
+        }
+        used += cost;
+        included += 1;
+    }
+
+    let mut sources = sources;
+    sources.truncate(included);
+    sources
+}
+
+/// Keep the most recent user/assistant messages that fit in `max_tokens`
+/// At most `MAX_HISTORY_MESSAGES` are kept; system messages are skipped since
+/// the RAG system prompt replaces them.
+pub fn trim_history(history: Vec<ChatMessage>, max_tokens: Option<usize>) -> Vec<ChatMessage> {
+    let mut used = 0;
+    let mut kept: Vec<ChatMessage> = history
+        .into_iter()
+        .rev()
+        .filter(|m| !matches!(m.role, ChatRole::System))
+        .take(MAX_HISTORY_MESSAGES)
+        .take_while(|m| match max_tokens {
+            Some(max) => {
+                used += count_tokens(&m.content);
+                used <= max
+            }
+            None => true,
+        })
+        .collect();
+
+    kept.reverse();
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rag::Chunk;
+
+    fn source(content: &str, similarity: f32) -> ChunkMatch {
+        ChunkMatch {
+            chunk: Chunk {
+                id: 1,
+                document_id: 1,
+                project_id: 1,
+                content: content.to_string(),
+                embedding: Vec::new(),
+                chunk_index: 0,
+                embedding_norm: None,
+                page_number: None,
+                start_offset: None,
+                end_offset: None,
+            },
+            similarity,
+            document_name: "doc".to_string(),
+            expanded_content: None,
+            explanation: None,
+            snippet: None,
+        }
+    }
+
+    #[test]
+    fn test_all_sources_fit() {
+        let sources = vec![source("alpha", 0.9), source("beta", 0.8)];
+        let fitted = fit_sources_to_budget(sources, 1_000);
+        assert_eq!(fitted.len(), 2);
+    }
+
+    #[test]
+    fn test_lowest_scoring_sources_dropped() {
+        let long = "word ".repeat(200);
+        let sources = vec![
+            source("short", 0.9),
+            source(&long, 0.8),
+            source("tiny", 0.7),
+        ];
+        let fitted = fit_sources_to_budget(sources, 50);
+
+        assert_eq!(fitted.len(), 1);
+        assert_eq!(fitted[0].chunk.content, "short");
+    }
+
+    #[test]
+    fn test_zero_budget_drops_everything() {
+        let sources = vec![source("alpha", 0.9)];
+        assert!(fit_sources_to_budget(sources, 0).is_empty());
+    }
+
+    #[test]
+    fn test_build_context_numbers_sources() {
+        let context = build_context(&[source("alpha", 0.9), source("beta", 0.8)]);
+        assert_eq!(context, "[Source 1: doc]\nalpha\n\n[Source 2: doc]\nbeta");
+    }
+
+    #[test]
+    fn test_expanded_content_replaces_chunk_in_context() {
+        let mut expanded = source("beta", 0.9);
+        expanded.expanded_content = Some("alpha beta gamma".to_string());
+        assert_eq!(
+            build_context(&[expanded]),
+            "[Source 1: doc]\nalpha beta gamma"
+        );
+    }
+
+    fn message(role: ChatRole, content: &str) -> ChatMessage {
+        ChatMessage {
+            role,
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_trim_history_keeps_recent_in_order() {
+        let history = vec![
+            message(ChatRole::System, "ignored"),
+            message(ChatRole::User, "first"),
+            message(ChatRole::Assistant, "second"),
+            message(ChatRole::User, "third"),
+        ];
+        let trimmed = trim_history(history, None);
+
+        let contents: Vec<_> = trimmed.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, vec!["first", "second", "third"]);
+    }
+
+    #[test]
+    fn test_trim_history_drops_oldest_over_budget() {
+        let long = "word ".repeat(200);
+        let history = vec![
+            message(ChatRole::User, &long),
+            message(ChatRole::Assistant, "recent answer"),
+            message(ChatRole::User, "recent question"),
+        ];
+        let trimmed = trim_history(history, Some(50));
+
+        assert_eq!(trimmed.len(), 2);
+        assert_eq!(trimmed[0].content, "recent answer");
+    }
+
+    #[test]
+    fn test_trim_history_caps_message_count() {
+        let history = (0..50)
+            .map(|i| message(ChatRole::User, &i.to_string()))
+            .collect();
+        assert_eq!(trim_history(history, None).len(), MAX_HISTORY_MESSAGES);
+    }
+}
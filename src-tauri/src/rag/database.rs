@@ -1,7 +1,15 @@
+use super::embeddings::{l2_norm, DistanceMetric};
+use super::export::{ChunkExport, DocumentExport, ProjectExport, EXPORT_FORMAT_VERSION};
+use super::hnsw::ProjectIndexes;
 use chrono::{DateTime, Utc};
+use futures::{Stream, StreamExt};
 use serde::{Deserialize, Serialize};
-use sqlx::{sqlite::SqlitePool, FromRow, Row};
+use sha2::{Digest, Sha256};
+use sqlx::sqlite::{SqlitePool, SqlitePoolOptions, SqliteRow};
+use sqlx::{Connection, FromRow, Row, SqliteConnection};
+use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::Arc;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -26,16 +34,38 @@ pub enum DatabaseError {
 
     #[error("Message not found: {0}")]
     MessageNotFound(i64),
+
+    #[error("Canvas version not found: {0}")]
+    CanvasVersionNotFound(i64),
+
+    #[error("Unsupported schema version: {0}")]
+    UnsupportedSchemaVersion(i64),
+
+    /// A chunk's embedding doesn't match the dimension the project was first indexed with
+    #[error("Embedding dimension mismatch: the project uses {expected}-dimensional embeddings, got {got}. Re-index the project with a single embedding model")]
+    DimensionMismatch { expected: usize, got: usize },
+
+    /// Embeddings from a different model than the one the project was indexed with,
+    /// which can't be compared even at the same dimension
+    #[error("Embedding model mismatch: the project uses {expected} embeddings, got {got}. Re-index the project with a single embedding model")]
+    EmbeddingModelMismatch { expected: String, got: String },
+
+    #[error("Invalid project export: {0}")]
+    InvalidExport(String),
+
+    #[error("Search index error: {0}")]
+    Index(String),
+
+    #[error("The search index of project {0} is already being built")]
+    IndexBuildInProgress(i64),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Project {
     pub id: i64,
     pub name: String,
-    pub created_at: String,
-    pub updated_at: String,
-    #[serde(default)]
-    pub canvas_state: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -44,7 +74,7 @@ pub struct Document {
     pub project_id: i64,
     pub name: String,
     pub source_path: Option<String>,
-    pub created_at: String,
+    pub created_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -55,6 +85,56 @@ pub struct Chunk {
     pub content: String,
     pub embedding: Vec<f32>,
     pub chunk_index: i32,
+    /// Precomputed L2 norm of `embedding`; None for chunks stored before norms were recorded
+    #[serde(skip)]
+    pub embedding_norm: Option<f32>,
+    /// Page of the source document (1-based), for paginated formats like PDF
+    #[serde(default)]
+    pub page_number: Option<i32>,
+    /// Character range of the chunk in the document text; None for chunks stored before
+    /// offsets were recorded
+    #[serde(default)]
+    pub start_offset: Option<i64>,
+    #[serde(default)]
+    pub end_offset: Option<i64>,
+}
+
+/// Outcome of storing a document unless the project already holds the same content
+#[derive(Debug)]
+pub enum InsertOutcome<T> {
+    Inserted(T),
+    /// The existing document with the same content; nothing was stored
+    Duplicate(Document),
+}
+
+/// A chunk to be stored, with its embedding
+#[derive(Debug, Clone)]
+pub struct NewChunk {
+    pub content: String,
+    pub embedding: Vec<f32>,
+    pub page_number: Option<i32>,
+    pub start_offset: Option<i64>,
+    pub end_offset: Option<i64>,
+}
+
+impl NewChunk {
+    pub fn new(content: String, embedding: Vec<f32>) -> Self {
+        Self {
+            content,
+            embedding,
+            page_number: None,
+            start_offset: None,
+            end_offset: None,
+        }
+    }
+}
+
+/// The parts of a chunk needed to score it during search (no content)
+#[derive(Debug, Clone)]
+pub struct ChunkEmbedding {
+    pub id: i64,
+    pub embedding: Vec<f32>,
+    pub embedding_norm: Option<f32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -62,6 +142,27 @@ pub struct ChunkMatch {
     pub chunk: Chunk,
     pub similarity: f32,
     pub document_name: String,
+    /// The chunk merged with its neighbors in the same document, when context expansion is requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expanded_content: Option<String>,
+    /// Ranking diagnostics, when the search was asked to explain its results
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub explanation: Option<MatchExplanation>,
+    /// Excerpt of the chunk around the query's words, with the words marked as `**word**`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<String>,
+}
+
+/// Why a search result ranked where it did
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchExplanation {
+    /// Position in the results (1-based)
+    pub rank: usize,
+    /// Score under `metric`; higher is better (Euclidean distance is negated)
+    pub similarity: f32,
+    pub metric: DistanceMetric,
+    /// Length of the chunk in characters
+    pub chunk_chars: usize,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -70,136 +171,310 @@ pub struct Conversation {
     pub title: String,
     pub provider_id: String,
     pub model: String,
-    pub created_at: String,
-    pub updated_at: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Owning RAG project; None for global conversations
+    #[serde(default)]
+    pub project_id: Option<i64>,
+    /// Conversation this one was forked from, if any
+    #[serde(default)]
+    pub parent_conversation_id: Option<i64>,
+    /// When the conversation was moved to the trash; None if it isn't trashed
+    #[serde(default)]
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// Summary of the older messages, sent in their place once history outgrows its budget
+    #[serde(default)]
+    pub history_summary: Option<String>,
+    /// Last message covered by `history_summary`
+    #[serde(default)]
+    pub summary_through_message_id: Option<i64>,
+    /// Default generation parameters and system prompt
+    #[serde(default)]
+    #[sqlx(flatten)]
+    pub settings: ConversationSettings,
+}
+
+/// Generation defaults stored with a conversation; a request's own parameters take precedence
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct ConversationSettings {
+    pub temperature: Option<f32>,
+    pub max_tokens: Option<u32>,
+    pub top_p: Option<f32>,
+    /// Sent as the first message of every request
+    pub system_prompt: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Message {
     pub id: i64,
     pub conversation_id: i64,
-    pub role: String,  // "system", "user", "assistant"
+    pub role: String, // "system", "user", "assistant"
     pub content: String,
-    pub created_at: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A saved snapshot of a project's canvas (the state itself is loaded on restore)
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct CanvasVersion {
+    pub id: i64,
+    pub project_id: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Canvas snapshots kept per project; older ones are deleted as new ones are saved
+pub const MAX_CANVAS_VERSIONS: i64 = 20;
+
+/// A project's canvas, stored as one row per node and per edge
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct CanvasState {
+    pub nodes: Vec<CanvasNode>,
+    pub edges: Vec<CanvasEdge>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct CanvasNode {
+    pub id: String,
+    pub node_type: String,
+    pub position: Position,
+    pub data: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct Position {
+    pub x: f64,
+    pub y: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct CanvasEdge {
+    pub id: String,
+    pub source: String,
+    pub target: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentStats {
+    pub document_id: i64,
+    pub chunk_count: i64,
+    /// Length of the document's text; for documents stored without their text, the summed
+    /// length of their chunks, which counts the overlap between chunks twice
+    pub total_characters: i64,
+    /// Dimension of the stored embeddings; None if the document has no chunks
+    pub embedding_dim: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectStats {
+    pub project_id: i64,
+    pub document_count: i64,
+    pub chunk_count: i64,
+    /// Approximate on-disk size of chunk text and embeddings
+    pub storage_bytes: i64,
+}
+
+/// The embedding dimension and model a project's chunks are held to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectEmbeddingInfo {
+    pub project_id: i64,
+    /// None until the project has chunks
+    pub dimension: Option<usize>,
+    /// Provider and embedding model ("provider/model"); None if unknown, e.g. for
+    /// projects indexed before the model was recorded
+    pub model: Option<String>,
 }
 
+/// Default number of rows returned by paginated list queries
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+
+/// One page of a paginated list, with the total row count for the UI
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: i64,
+}
+
+/// How `get_usage_summary` groups usage rows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageGroupBy {
+    #[default]
+    Provider,
+    Model,
+    Conversation,
+    Day,
+}
+
+impl UsageGroupBy {
+    /// SQL expression producing the group key of a `usage_log` row
+    fn key_sql(self) -> &'static str {
+        match self {
+            UsageGroupBy::Provider => "provider_id",
+            UsageGroupBy::Model => "model",
+            UsageGroupBy::Conversation => "CAST(conversation_id AS TEXT)",
+            UsageGroupBy::Day => "substr(created_at, 1, 10)",
+        }
+    }
+}
+
+/// Token totals of one usage group and model
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct UsageTotal {
+    /// Provider id, model, conversation id or day (YYYY-MM-DD);
+    /// None when grouping by conversation for usage outside a conversation
+    pub group_key: Option<String>,
+    pub model: String,
+    pub requests: i64,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+}
+
+/// Default size of the SQLite connection pool
+pub const DEFAULT_MAX_CONNECTIONS: u32 = 5;
+
+/// Cheap to clone; clones share the connection pool and search indexes
+#[derive(Clone)]
 pub struct RagDatabase {
     pool: SqlitePool,
+    /// Database file, removed by `delete_files`
+    path: PathBuf,
+    /// Approximate nearest-neighbor indexes of large projects (see `hnsw`)
+    indexes: Arc<ProjectIndexes>,
 }
 
 impl RagDatabase {
-    pub async fn new(db_path: PathBuf) -> Result<Self, DatabaseError> {
+    pub async fn new(db_path: PathBuf, max_connections: u32) -> Result<Self, DatabaseError> {
         // Ensure parent directory exists
         if let Some(parent) = db_path.parent() {
             tokio::fs::create_dir_all(parent).await?;
         }
 
         let db_url = format!("sqlite:{}", db_path.display());
-        let pool = SqlitePool::connect(&db_url).await?;
+        let pool = SqlitePoolOptions::new()
+            .max_connections(max_connections)
+            .after_connect(|conn, _meta| {
+                Box::pin(async move {
+                    // WAL lets readers proceed while embeddings are being written
+                    sqlx::query("PRAGMA journal_mode = WAL")
+                        .execute(&mut *conn)
+                        .await?;
+                    sqlx::query("PRAGMA synchronous = NORMAL")
+                        .execute(&mut *conn)
+                        .await?;
+                    Ok(())
+                })
+            })
+            .connect(&db_url)
+            .await?;
 
-        let db = Self { pool };
+        // Search indexes are kept next to the database file
+        let index_dir = db_path
+            .parent()
+            .map(|parent| parent.join("indexes"))
+            .unwrap_or_else(|| PathBuf::from("indexes"));
+
+        let db = Self {
+            pool,
+            path: db_path,
+            indexes: Arc::new(ProjectIndexes::new(index_dir)),
+        };
         db.init_schema().await?;
 
         Ok(db)
     }
 
-    async fn init_schema(&self) -> Result<(), DatabaseError> {
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS projects (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                name TEXT NOT NULL,
-                created_at TEXT NOT NULL DEFAULT (datetime('now')),
-                updated_at TEXT NOT NULL DEFAULT (datetime('now')),
-                canvas_state TEXT
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await?;
+    #[cfg(test)]
+    pub(crate) fn pool(&self) -> &SqlitePool {
+        &self.pool
+    }
 
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS documents (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                project_id INTEGER NOT NULL,
-                name TEXT NOT NULL,
-                source_path TEXT,
-                created_at TEXT NOT NULL DEFAULT (datetime('now')),
-                FOREIGN KEY (project_id) REFERENCES projects(id) ON DELETE CASCADE
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await?;
+    pub(crate) fn indexes(&self) -> &ProjectIndexes {
+        &self.indexes
+    }
 
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS chunks (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                document_id INTEGER NOT NULL,
-                project_id INTEGER NOT NULL,
-                content TEXT NOT NULL,
-                embedding BLOB NOT NULL,
-                chunk_index INTEGER NOT NULL,
-                FOREIGN KEY (document_id) REFERENCES documents(id) ON DELETE CASCADE,
-                FOREIGN KEY (project_id) REFERENCES projects(id) ON DELETE CASCADE
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await?;
+    /// Close the pool and delete the database file, its WAL files and the search indexes
+    /// The database is unusable afterwards; returns the paths that were removed.
+    pub async fn delete_files(&self) -> Result<Vec<PathBuf>, DatabaseError> {
+        self.pool.close().await;
+
+        let mut removed = Vec::new();
+        for suffix in ["", "-wal", "-shm"] {
+            let mut path = self.path.clone().into_os_string();
+            path.push(suffix);
+            let path = PathBuf::from(path);
+            if path.exists() {
+                tokio::fs::remove_file(&path).await?;
+                removed.push(path);
+            }
+        }
 
-        // Create indexes for performance
-        sqlx::query("CREATE INDEX IF NOT EXISTS idx_chunks_project ON chunks(project_id)")
-            .execute(&self.pool)
-            .await?;
+        let index_dir = self.indexes.dir();
+        if index_dir.exists() {
+            tokio::fs::remove_dir_all(index_dir).await?;
+            removed.push(index_dir.to_path_buf());
+        }
 
-        sqlx::query("CREATE INDEX IF NOT EXISTS idx_chunks_document ON chunks(document_id)")
-            .execute(&self.pool)
-            .await?;
+        Ok(removed)
+    }
 
-        // Conversation tables
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS conversations (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                title TEXT NOT NULL,
-                provider_id TEXT NOT NULL,
-                model TEXT NOT NULL,
-                created_at TEXT NOT NULL DEFAULT (datetime('now')),
-                updated_at TEXT NOT NULL DEFAULT (datetime('now'))
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await?;
+    /// Bring the schema up to `SCHEMA_VERSION`, applying each pending migration in order.
+    /// Every migration runs in its own transaction together with its `schema_version` row,
+    /// so an interrupted upgrade resumes from the last completed step.
+    /// All steps share one connection: a second connection opened partway through could
+    /// keep a stale copy of the schema and misread `SELECT *` rows afterwards.
+    async fn init_schema(&self) -> Result<(), DatabaseError> {
+        let mut conn = self.pool.acquire().await?;
 
         sqlx::query(
             r#"
-            CREATE TABLE IF NOT EXISTS messages (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                conversation_id INTEGER NOT NULL,
-                role TEXT NOT NULL,
-                content TEXT NOT NULL,
-                created_at TEXT NOT NULL DEFAULT (datetime('now')),
-                FOREIGN KEY (conversation_id) REFERENCES conversations(id) ON DELETE CASCADE
+            CREATE TABLE IF NOT EXISTS schema_version (
+                version INTEGER PRIMARY KEY,
+                applied_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
             )
             "#,
         )
-        .execute(&self.pool)
+        .execute(&mut *conn)
         .await?;
 
-        sqlx::query("CREATE INDEX IF NOT EXISTS idx_messages_conversation ON messages(conversation_id)")
-            .execute(&self.pool)
+        let current: Option<i64> = sqlx::query_scalar("SELECT MAX(version) FROM schema_version")
+            .fetch_one(&mut *conn)
             .await?;
+        let current = current.unwrap_or(0);
+        if current > SCHEMA_VERSION {
+            return Err(DatabaseError::UnsupportedSchemaVersion(current));
+        }
+
+        for version in (current + 1)..=SCHEMA_VERSION {
+            let mut tx = conn.begin().await?;
+            apply_migration(&mut tx, version).await?;
+            sqlx::query("INSERT INTO schema_version (version) VALUES (?)")
+                .bind(version)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+
+            tracing::info!("Applied database migration {}", version);
+        }
+
+        Ok(())
+    }
 
+    /// Check the database answers a trivial query
+    pub async fn ping(&self) -> Result<(), DatabaseError> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
         Ok(())
     }
 
+    /// Highest migration applied to this database, or 0 for a fresh or pre-versioning one
+    pub async fn schema_version(&self) -> Result<i64, DatabaseError> {
+        let version: Option<i64> = sqlx::query_scalar("SELECT MAX(version) FROM schema_version")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(version.unwrap_or(0))
+    }
+
     // Project operations
     pub async fn create_project(&self, name: String) -> Result<Project, DatabaseError> {
-        let id = sqlx::query("INSERT INTO projects (name) VALUES (?)")
+        let id = sqlx::query("INSERT INTO projects (name, created_at, updated_at) VALUES (?, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'), strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))")
             .bind(&name)
             .execute(&self.pool)
             .await?
@@ -229,320 +504,3279 @@ impl RagDatabase {
             .bind(id)
             .execute(&self.pool)
             .await?;
+        self.indexes.discard(id);
         Ok(())
     }
 
-    pub async fn update_canvas_state(
+    pub async fn update_project_name(
         &self,
-        project_id: i64,
-        canvas_state: String,
-    ) -> Result<(), DatabaseError> {
-        sqlx::query("UPDATE projects SET canvas_state = ?, updated_at = datetime('now') WHERE id = ?")
-            .bind(canvas_state)
-            .bind(project_id)
+        id: i64,
+        name: String,
+    ) -> Result<Project, DatabaseError> {
+        let result = sqlx::query("UPDATE projects SET name = ?, updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = ?")
+            .bind(name)
+            .bind(id)
             .execute(&self.pool)
             .await?;
-        Ok(())
+
+        if result.rows_affected() == 0 {
+            return Err(DatabaseError::ProjectNotFound(id));
+        }
+
+        self.get_project(id).await
     }
 
-    // Document operations
-    pub async fn create_document(
+    /// A project's canvas, assembled from its node and edge rows; None if it has no nodes
+    pub async fn get_canvas_state(
         &self,
         project_id: i64,
-        name: String,
-        source_path: Option<String>,
-    ) -> Result<Document, DatabaseError> {
-        let id = sqlx::query("INSERT INTO documents (project_id, name, source_path) VALUES (?, ?, ?)")
-            .bind(project_id)
-            .bind(&name)
-            .bind(&source_path)
-            .execute(&self.pool)
-            .await?
-            .last_insert_rowid();
+    ) -> Result<Option<CanvasState>, DatabaseError> {
+        self.get_project(project_id).await?;
 
-        self.get_document(id).await
+        let mut conn = self.pool.acquire().await?;
+        let state = read_canvas(&mut conn, project_id).await?;
+        Ok((!state.nodes.is_empty()).then_some(state))
     }
 
-    pub async fn get_document(&self, id: i64) -> Result<Document, DatabaseError> {
-        sqlx::query_as::<_, Document>("SELECT * FROM documents WHERE id = ?")
-            .bind(id)
-            .fetch_one(&self.pool)
-            .await
-            .map_err(|_| DatabaseError::DocumentNotFound(id))
-    }
+    /// Replace a project's canvas, keeping the previous one as a version
+    /// Only nodes and edges that were added, changed or removed are written, and saving an
+    /// unchanged canvas doesn't add a version.
+    pub async fn save_canvas_state(
+        &self,
+        project_id: i64,
+        state: &CanvasState,
+    ) -> Result<(), DatabaseError> {
+        let mut tx = self.pool.begin().await?;
 
-    pub async fn list_documents(&self, project_id: i64) -> Result<Vec<Document>, DatabaseError> {
-        Ok(
-            sqlx::query_as::<_, Document>("SELECT * FROM documents WHERE project_id = ? ORDER BY created_at DESC")
-                .bind(project_id)
-                .fetch_all(&self.pool)
-                .await?,
+        touch_project(&mut tx, project_id).await?;
+        let previous = read_canvas(&mut tx, project_id).await?;
+        if previous == *state {
+            return Ok(());
+        }
+
+        if !previous.nodes.is_empty() {
+            let state_json = serde_json::to_string(&previous)
+                .map_err(|e| DatabaseError::SerializationError(e.to_string()))?;
+            sqlx::query(
+                "INSERT INTO canvas_versions (project_id, state_json, created_at) VALUES (?, ?, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))",
+            )
+            .bind(project_id)
+            .bind(state_json)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        write_canvas(&mut tx, project_id, &previous, state).await?;
+
+        sqlx::query(
+            "DELETE FROM canvas_versions WHERE project_id = ?1 AND id NOT IN (SELECT id FROM canvas_versions WHERE project_id = ?1 ORDER BY id DESC LIMIT ?2)"
         )
+        .bind(project_id)
+        .bind(MAX_CANVAS_VERSIONS)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
     }
 
-    pub async fn delete_document(&self, id: i64) -> Result<(), DatabaseError> {
-        sqlx::query("DELETE FROM documents WHERE id = ?")
-            .bind(id)
-            .execute(&self.pool)
-            .await?;
+    /// Add or replace one node of a project's canvas; a new node goes after the others
+    /// Small edits like this don't keep a version, so they don't push real versions out
+    /// of the history.
+    pub async fn upsert_canvas_node(
+        &self,
+        project_id: i64,
+        node: &CanvasNode,
+    ) -> Result<(), DatabaseError> {
+        let mut tx = self.pool.begin().await?;
+        touch_project(&mut tx, project_id).await?;
+        upsert_canvas_node(&mut tx, project_id, None, node).await?;
+        tx.commit().await?;
         Ok(())
     }
 
-    // Chunk operations
-    pub async fn insert_chunk(
+    /// Add or replace one edge of a project's canvas without keeping a version
+    /// Both ends must be existing nodes.
+    pub async fn upsert_canvas_edge(
         &self,
-        document_id: i64,
         project_id: i64,
-        content: String,
-        embedding: Vec<f32>,
-        chunk_index: i32,
-    ) -> Result<i64, DatabaseError> {
-        let embedding_bytes = bincode::serialize(&embedding)
-            .map_err(|e| DatabaseError::SerializationError(e.to_string()))?;
+        edge: &CanvasEdge,
+    ) -> Result<(), DatabaseError> {
+        let mut tx = self.pool.begin().await?;
+        touch_project(&mut tx, project_id).await?;
+        upsert_canvas_edge(&mut tx, project_id, None, edge).await?;
+        tx.commit().await?;
+        Ok(())
+    }
 
-        let id = sqlx::query(
-            "INSERT INTO chunks (document_id, project_id, content, embedding, chunk_index) VALUES (?, ?, ?, ?, ?)"
+    /// Saved canvas versions of a project, newest first
+    pub async fn list_canvas_versions(
+        &self,
+        project_id: i64,
+    ) -> Result<Vec<CanvasVersion>, DatabaseError> {
+        self.get_project(project_id).await?;
+
+        Ok(sqlx::query_as::<_, CanvasVersion>(
+            "SELECT id, project_id, created_at FROM canvas_versions WHERE project_id = ? ORDER BY id DESC",
         )
-        .bind(document_id)
         .bind(project_id)
-        .bind(content)
-        .bind(embedding_bytes)
-        .bind(chunk_index)
-        .execute(&self.pool)
-        .await?
-        .last_insert_rowid();
-
-        Ok(id)
+        .fetch_all(&self.pool)
+        .await?)
     }
 
-    pub async fn get_chunks_for_project(&self, project_id: i64) -> Result<Vec<Chunk>, DatabaseError> {
-        let rows = sqlx::query("SELECT id, document_id, project_id, content, embedding, chunk_index FROM chunks WHERE project_id = ?")
-            .bind(project_id)
-            .fetch_all(&self.pool)
-            .await?;
-
-        let mut chunks = Vec::new();
-        for row in rows {
-            let embedding_bytes: Vec<u8> = row.get("embedding");
-            let embedding: Vec<f32> = bincode::deserialize(&embedding_bytes)
-                .map_err(|e| DatabaseError::SerializationError(e.to_string()))?;
+    /// Make a saved version the project's canvas again; the replaced canvas becomes a version
+    /// Returns the project id and the restored state
+    pub async fn restore_canvas_version(
+        &self,
+        version_id: i64,
+    ) -> Result<(i64, CanvasState), DatabaseError> {
+        let row = sqlx::query("SELECT project_id, state_json FROM canvas_versions WHERE id = ?")
+            .bind(version_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or(DatabaseError::CanvasVersionNotFound(version_id))?;
+        let project_id: i64 = row.get("project_id");
+        let state_json: String = row.get("state_json");
+        let state: CanvasState = serde_json::from_str(&state_json)
+            .map_err(|e| DatabaseError::SerializationError(e.to_string()))?;
 
-            chunks.push(Chunk {
-                id: row.get("id"),
-                document_id: row.get("document_id"),
-                project_id: row.get("project_id"),
-                content: row.get("content"),
-                embedding,
-                chunk_index: row.get("chunk_index"),
-            });
-        }
+        self.save_canvas_state(project_id, &state).await?;
 
-        Ok(chunks)
+        Ok((project_id, state))
     }
 
-    pub async fn get_chunk_with_document(
-        &self,
-        chunk_id: i64,
-    ) -> Result<(Chunk, String), DatabaseError> {
+    pub async fn get_project_stats(&self, id: i64) -> Result<ProjectStats, DatabaseError> {
+        // Fail with ProjectNotFound rather than returning zeroed stats
+        self.get_project(id).await?;
+
+        let document_count: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM documents WHERE project_id = ?")
+                .bind(id)
+                .fetch_one(&self.pool)
+                .await?;
+
         let row = sqlx::query(
-            r#"
-            SELECT c.id, c.document_id, c.project_id, c.content, c.embedding, c.chunk_index, d.name as doc_name
-            FROM chunks c
-            JOIN documents d ON c.document_id = d.id
-            WHERE c.id = ?
-            "#
+            "SELECT COUNT(*) AS chunk_count, COALESCE(SUM(LENGTH(CAST(content AS BLOB)) + LENGTH(embedding)), 0) AS storage_bytes FROM chunks WHERE project_id = ?"
         )
-        .bind(chunk_id)
+        .bind(id)
         .fetch_one(&self.pool)
         .await?;
 
-        let embedding_bytes: Vec<u8> = row.get("embedding");
-        let embedding: Vec<f32> = bincode::deserialize(&embedding_bytes)
-            .map_err(|e| DatabaseError::SerializationError(e.to_string()))?;
+        Ok(ProjectStats {
+            project_id: id,
+            document_count,
+            chunk_count: row.get("chunk_count"),
+            storage_bytes: row.get("storage_bytes"),
+        })
+    }
 
-        let chunk = Chunk {
-            id: row.get("id"),
-            document_id: row.get("document_id"),
-            project_id: row.get("project_id"),
-            content: row.get("content"),
-            embedding,
-            chunk_index: row.get("chunk_index"),
-        };
-
-        let doc_name: String = row.get("doc_name");
+    /// The embedding dimension and model new chunks of a project must match
+    pub async fn get_project_embedding_info(
+        &self,
+        project_id: i64,
+    ) -> Result<ProjectEmbeddingInfo, DatabaseError> {
+        let (dimension, model, has_chunks): (Option<i64>, Option<String>, bool) = sqlx::query_as(
+            "SELECT embedding_dim, embedding_model, EXISTS(SELECT 1 FROM chunks WHERE project_id = projects.id) FROM projects WHERE id = ?",
+        )
+        .bind(project_id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(DatabaseError::ProjectNotFound(project_id))?;
+
+        // Once every chunk is gone the project can be re-indexed with any model
+        if !has_chunks {
+            return Ok(ProjectEmbeddingInfo {
+                project_id,
+                dimension: None,
+                model: None,
+            });
+        }
 
-        Ok((chunk, doc_name))
+        Ok(ProjectEmbeddingInfo {
+            project_id,
+            dimension: dimension.map(|d| d as usize),
+            model,
+        })
     }
 
-    /// Get multiple chunks with their document names in one query (optimized)
-    pub async fn get_chunks_with_documents(
+    /// Check `dimension` against the project's embeddings, pinning it (and `model`)
+    /// if the project has no chunks yet
+    pub async fn pin_project_embedding(
         &self,
-        chunk_ids: &[i64],
-    ) -> Result<Vec<(Chunk, String)>, DatabaseError> {
-        if chunk_ids.is_empty() {
-            return Ok(Vec::new());
-        }
+        project_id: i64,
+        dimension: usize,
+        model: &str,
+    ) -> Result<(), DatabaseError> {
+        let mut conn = self.pool.acquire().await?;
+        pin_embedding(&mut conn, project_id, dimension, Some(model)).await
+    }
 
-        // Build placeholders for IN clause
-        let placeholders = chunk_ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
-        let query_str = format!(
-            r#"
-            SELECT c.id, c.document_id, c.project_id, c.content, c.embedding, c.chunk_index, d.name as doc_name
-            FROM chunks c
-            JOIN documents d ON c.document_id = d.id
-            WHERE c.id IN ({})
-            "#,
-            placeholders
-        );
+    /// A project with its documents and chunks, embeddings included as stored
+    pub async fn export_project(&self, project_id: i64) -> Result<ProjectExport, DatabaseError> {
+        let project = self.get_project(project_id).await?;
+        let info = self.get_project_embedding_info(project_id).await?;
+        let canvas_state = self
+            .get_canvas_state(project_id)
+            .await?
+            .map(|state| serde_json::to_string(&state))
+            .transpose()
+            .map_err(|e| DatabaseError::SerializationError(e.to_string()))?;
 
-        let mut query = sqlx::query(&query_str);
-        for id in chunk_ids {
-            query = query.bind(id);
+        let rows = sqlx::query(
+            "SELECT document_id, chunk_index, content, embedding, page_number, start_offset, end_offset FROM chunks WHERE project_id = ? ORDER BY document_id, chunk_index",
+        )
+        .bind(project_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut chunks: HashMap<i64, Vec<ChunkExport>> = HashMap::new();
+        for row in rows {
+            let blob: Vec<u8> = row.get("embedding");
+            chunks.entry(row.get("document_id")).or_default().push(
+                ChunkExport::new(row.get("chunk_index"), row.get("content"), &blob)
+                    .with_page_number(row.get("page_number"))
+                    .with_offsets(row.get("start_offset"), row.get("end_offset")),
+            );
         }
 
-        let rows = query.fetch_all(&self.pool).await?;
+        let documents = sqlx::query(
+            "SELECT id, name, source_path, content FROM documents WHERE project_id = ? ORDER BY id",
+        )
+        .bind(project_id)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|row| DocumentExport {
+            name: row.get("name"),
+            source_path: row.get("source_path"),
+            content: row.get("content"),
+            chunks: chunks.remove(&row.get::<i64, _>("id")).unwrap_or_default(),
+        })
+        .collect();
+
+        Ok(ProjectExport {
+            format_version: EXPORT_FORMAT_VERSION,
+            name: project.name,
+            canvas_state,
+            embedding_dim: info.dimension,
+            embedding_model: info.model,
+            documents,
+        })
+    }
 
-        let mut results = Vec::new();
-        for row in rows {
-            let embedding_bytes: Vec<u8> = row.get("embedding");
-            let embedding: Vec<f32> = bincode::deserialize(&embedding_bytes)
-                .map_err(|e| DatabaseError::SerializationError(e.to_string()))?;
+    /// Recreate an exported project under new ids, in a single transaction
+    pub async fn import_project(&self, export: &ProjectExport) -> Result<Project, DatabaseError> {
+        export.validate()?;
+        let canvas_state = export
+            .canvas_state
+            .as_deref()
+            .map(serde_json::from_str::<CanvasState>)
+            .transpose()
+            .map_err(|e| DatabaseError::InvalidExport(format!("canvas_state: {}", e)))?;
 
-            let chunk = Chunk {
-                id: row.get("id"),
-                document_id: row.get("document_id"),
-                project_id: row.get("project_id"),
-                content: row.get("content"),
-                embedding,
-                chunk_index: row.get("chunk_index"),
-            };
+        let mut tx = self.pool.begin().await?;
 
-            let doc_name: String = row.get("doc_name");
-            results.push((chunk, doc_name));
+        let project_id = sqlx::query(
+            "INSERT INTO projects (name, embedding_dim, embedding_model, created_at, updated_at) VALUES (?, ?, ?, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'), strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))",
+        )
+        .bind(&export.name)
+        .bind(export.embedding_dim.map(|d| d as i64))
+        .bind(&export.embedding_model)
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+
+        if let Some(mut canvas_state) = canvas_state {
+            drop_dangling_edges(&mut canvas_state);
+            write_canvas(&mut tx, project_id, &CanvasState::default(), &canvas_state).await?;
         }
 
-        Ok(results)
+        for document in &export.documents {
+            let document_id = sqlx::query("INSERT INTO documents (project_id, name, source_path, content, content_hash, created_at) VALUES (?, ?, ?, ?, ?, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))")
+                .bind(project_id)
+                .bind(&document.name)
+                .bind(&document.source_path)
+                .bind(&document.content)
+                .bind(document.content.as_deref().map(document_hash))
+                .execute(&mut *tx)
+                .await?
+                .last_insert_rowid();
+
+            for chunk in &document.chunks {
+                let (blob, embedding) = chunk.decode_embedding()?;
+
+                sqlx::query(
+                    "INSERT INTO chunks (document_id, project_id, content, embedding, chunk_index, embedding_norm, page_number, start_offset, end_offset) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"
+                )
+                .bind(document_id)
+                .bind(project_id)
+                .bind(&chunk.content)
+                .bind(blob)
+                .bind(chunk.chunk_index)
+                .bind(l2_norm(&embedding))
+                .bind(chunk.page_number)
+                .bind(chunk.start_offset)
+                .bind(chunk.end_offset)
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+
+        tx.commit().await?;
+
+        self.get_project(project_id).await
     }
 
-    // Conversation operations
-    pub async fn create_conversation(
+    // Document operations
+    pub async fn create_document(
         &self,
-        title: String,
-        provider_id: String,
-        model: String,
-    ) -> Result<Conversation, DatabaseError> {
-        let id = sqlx::query(
-            "INSERT INTO conversations (title, provider_id, model) VALUES (?, ?, ?)"
+        project_id: i64,
+        name: String,
+        source_path: Option<String>,
+        content: Option<String>,
+    ) -> Result<Document, DatabaseError> {
+        let mut conn = self.pool.acquire().await?;
+        let id = insert_document(&mut conn, project_id, &name, &source_path, &content).await?;
+        drop(conn);
+
+        self.get_document(id).await
+    }
+
+    /// Create a document without chunks, unless the project already has one with the
+    /// same content (see `document_hash`). The check and the insert share a transaction.
+    pub async fn create_document_unless_duplicate(
+        &self,
+        project_id: i64,
+        name: String,
+        source_path: Option<String>,
+        content: String,
+    ) -> Result<InsertOutcome<Document>, DatabaseError> {
+        let mut tx = self.pool.begin().await?;
+
+        if let Some(existing) =
+            find_document_by_hash(&mut tx, project_id, &document_hash(&content)).await?
+        {
+            return Ok(InsertOutcome::Duplicate(existing));
+        }
+        let id = insert_document(&mut tx, project_id, &name, &source_path, &Some(content)).await?;
+
+        tx.commit().await?;
+
+        Ok(InsertOutcome::Inserted(self.get_document(id).await?))
+    }
+
+    pub async fn get_document(&self, id: i64) -> Result<Document, DatabaseError> {
+        sqlx::query_as::<_, Document>(
+            "SELECT id, project_id, name, source_path, created_at FROM documents WHERE id = ?",
         )
-        .bind(&title)
-        .bind(&provider_id)
-        .bind(&model)
-        .execute(&self.pool)
-        .await?
-        .last_insert_rowid();
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|_| DatabaseError::DocumentNotFound(id))
+    }
 
-        self.get_conversation(id).await
+    /// A document in the project whose content matches `content` (see `document_hash`)
+    /// Documents stored without their text are never matched.
+    pub async fn find_duplicate_document(
+        &self,
+        project_id: i64,
+        content: &str,
+    ) -> Result<Option<Document>, DatabaseError> {
+        let mut conn = self.pool.acquire().await?;
+        find_document_by_hash(&mut conn, project_id, &document_hash(content)).await
     }
 
-    pub async fn get_conversation(&self, id: i64) -> Result<Conversation, DatabaseError> {
-        sqlx::query_as::<_, Conversation>("SELECT * FROM conversations WHERE id = ?")
+    /// Original text of a document; None for documents ingested before it was stored
+    pub async fn get_document_content(&self, id: i64) -> Result<Option<String>, DatabaseError> {
+        sqlx::query_scalar("SELECT content FROM documents WHERE id = ?")
             .bind(id)
             .fetch_one(&self.pool)
             .await
-            .map_err(|_| DatabaseError::ConversationNotFound(id))
-    }
-
-    pub async fn list_conversations(&self) -> Result<Vec<Conversation>, DatabaseError> {
-        Ok(
-            sqlx::query_as::<_, Conversation>(
-                "SELECT * FROM conversations ORDER BY updated_at DESC"
-            )
-            .fetch_all(&self.pool)
-            .await?,
-        )
+            .map_err(|_| DatabaseError::DocumentNotFound(id))
     }
 
-    pub async fn update_conversation_title(
+    pub async fn list_documents(
         &self,
-        id: i64,
-        title: String,
-    ) -> Result<(), DatabaseError> {
-        sqlx::query(
-            "UPDATE conversations SET title = ?, updated_at = datetime('now') WHERE id = ?"
+        project_id: i64,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Page<Document>, DatabaseError> {
+        let items = sqlx::query_as::<_, Document>(
+            "SELECT id, project_id, name, source_path, created_at FROM documents WHERE project_id = ? ORDER BY created_at DESC LIMIT ? OFFSET ?"
         )
-        .bind(title)
-        .bind(id)
-        .execute(&self.pool)
+        .bind(project_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
         .await?;
-        Ok(())
+
+        let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM documents WHERE project_id = ?")
+            .bind(project_id)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(Page { items, total })
     }
 
-    pub async fn delete_conversation(&self, id: i64) -> Result<(), DatabaseError> {
-        sqlx::query("DELETE FROM conversations WHERE id = ?")
+    pub async fn rename_document(&self, id: i64, name: String) -> Result<Document, DatabaseError> {
+        let result = sqlx::query("UPDATE documents SET name = ? WHERE id = ?")
+            .bind(name)
             .bind(id)
             .execute(&self.pool)
             .await?;
-        Ok(())
+
+        if result.rows_affected() == 0 {
+            return Err(DatabaseError::DocumentNotFound(id));
+        }
+
+        self.get_document(id).await
     }
 
-    pub async fn touch_conversation(&self, id: i64) -> Result<(), DatabaseError> {
-        sqlx::query("UPDATE conversations SET updated_at = datetime('now') WHERE id = ?")
+    pub async fn get_document_stats(&self, id: i64) -> Result<DocumentStats, DatabaseError> {
+        // Fail with DocumentNotFound rather than returning zeroed stats
+        self.get_document(id).await?;
+
+        let row = sqlx::query(
+            "SELECT COUNT(*) AS chunk_count, COALESCE((SELECT LENGTH(content) FROM documents WHERE id = ?1), SUM(LENGTH(content)), 0) AS total_characters FROM chunks WHERE document_id = ?1"
+        )
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        let embedding_bytes: Option<Vec<u8>> =
+            sqlx::query_scalar("SELECT embedding FROM chunks WHERE document_id = ? LIMIT 1")
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        let embedding_dim = match embedding_bytes {
+            Some(bytes) => {
+                let embedding: Vec<f32> = bincode::deserialize(&bytes)
+                    .map_err(|e| DatabaseError::SerializationError(e.to_string()))?;
+                Some(embedding.len())
+            }
+            None => None,
+        };
+
+        Ok(DocumentStats {
+            document_id: id,
+            chunk_count: row.get("chunk_count"),
+            total_characters: row.get("total_characters"),
+            embedding_dim,
+        })
+    }
+
+    pub async fn delete_document(&self, id: i64) -> Result<(), DatabaseError> {
+        let chunks: Vec<(i64, i64)> =
+            sqlx::query_as("SELECT id, project_id FROM chunks WHERE document_id = ?")
+                .bind(id)
+                .fetch_all(&self.pool)
+                .await?;
+
+        sqlx::query("DELETE FROM documents WHERE id = ?")
             .bind(id)
             .execute(&self.pool)
             .await?;
+
+        if let Some((_, project_id)) = chunks.first() {
+            let chunk_ids: Vec<i64> = chunks.iter().map(|(chunk_id, _)| *chunk_id).collect();
+            self.indexes.remove(*project_id, chunk_ids).await;
+        }
         Ok(())
     }
 
-    // Message operations
-    pub async fn add_message(
+    // Chunk operations
+    pub async fn insert_chunk(
         &self,
-        conversation_id: i64,
-        role: String,
+        document_id: i64,
+        project_id: i64,
         content: String,
-    ) -> Result<Message, DatabaseError> {
+        embedding: Vec<f32>,
+        chunk_index: i32,
+    ) -> Result<i64, DatabaseError> {
+        let embedding_norm = l2_norm(&embedding);
+        let embedding_bytes = bincode::serialize(&embedding)
+            .map_err(|e| DatabaseError::SerializationError(e.to_string()))?;
+
+        let mut tx = self.pool.begin().await?;
+
+        pin_embedding(&mut tx, project_id, embedding.len(), None).await?;
+
         let id = sqlx::query(
-            "INSERT INTO messages (conversation_id, role, content) VALUES (?, ?, ?)"
+            "INSERT INTO chunks (document_id, project_id, content, embedding, chunk_index, embedding_norm) VALUES (?, ?, ?, ?, ?, ?)"
         )
-        .bind(conversation_id)
-        .bind(&role)
-        .bind(&content)
-        .execute(&self.pool)
+        .bind(document_id)
+        .bind(project_id)
+        .bind(content)
+        .bind(embedding_bytes)
+        .bind(chunk_index)
+        .bind(embedding_norm)
+        .execute(&mut *tx)
         .await?
         .last_insert_rowid();
 
-        // Touch the conversation to update its timestamp
-        self.touch_conversation(conversation_id).await?;
+        tx.commit().await?;
 
-        self.get_message(id).await
+        self.indexes.insert(project_id, vec![(id, embedding)]).await;
+
+        Ok(id)
     }
 
-    pub async fn get_message(&self, id: i64) -> Result<Message, DatabaseError> {
-        sqlx::query_as::<_, Message>("SELECT * FROM messages WHERE id = ?")
-            .bind(id)
-            .fetch_one(&self.pool)
-            .await
-            .map_err(|_| DatabaseError::MessageNotFound(id))
+    /// Replace all chunks of a document in a single transaction, so a failure part-way
+    /// leaves the previous chunks in place. If `content` is given, the stored original
+    /// text is replaced as well. `embedding_model` is recorded for the project if this
+    /// pins its embedding dimension. Returns the number of chunks inserted.
+    pub async fn replace_document_chunks(
+        &self,
+        document_id: i64,
+        content: Option<String>,
+        chunks: Vec<NewChunk>,
+        embedding_model: Option<&str>,
+    ) -> Result<usize, DatabaseError> {
+        let document = self.get_document(document_id).await?;
+
+        let mut tx = self.pool.begin().await?;
+
+        if let Some(content) = content {
+            let hash = document_hash(&content);
+            sqlx::query("UPDATE documents SET content = ?, content_hash = ? WHERE id = ?")
+                .bind(content)
+                .bind(hash)
+                .bind(document_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        let old_ids: Vec<i64> = sqlx::query_scalar("SELECT id FROM chunks WHERE document_id = ?")
+            .bind(document_id)
+            .fetch_all(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM chunks WHERE document_id = ?")
+            .bind(document_id)
+            .execute(&mut *tx)
+            .await?;
+
+        // Checked after the delete, so re-indexing a project's only document can change model
+        let dimension = chunks.first().map_or(0, |chunk| chunk.embedding.len());
+        if !chunks.is_empty() {
+            pin_embedding(&mut tx, document.project_id, dimension, embedding_model).await?;
+        }
+
+        let inserted = insert_chunks(&mut tx, document_id, document.project_id, chunks).await?;
+
+        tx.commit().await?;
+
+        let count = inserted.len();
+        self.indexes.remove(document.project_id, old_ids).await;
+        self.indexes.insert(document.project_id, inserted).await;
+
+        Ok(count)
     }
 
-    pub async fn get_conversation_messages(
+    /// Create a document together with its chunks in a single transaction, so a failure
+    /// leaves no half-ingested document behind. `embedding_model` is recorded for the
+    /// project if this pins its embedding dimension. Nothing is stored if the project
+    /// already has a document with the same content. Returns the document and the number
+    /// of chunks inserted.
+    pub async fn create_document_with_chunks(
         &self,
-        conversation_id: i64,
-    ) -> Result<Vec<Message>, DatabaseError> {
-        Ok(
-            sqlx::query_as::<_, Message>(
-                "SELECT * FROM messages WHERE conversation_id = ? ORDER BY created_at ASC"
-            )
-            .bind(conversation_id)
-            .fetch_all(&self.pool)
-            .await?,
-        )
+        project_id: i64,
+        name: String,
+        source_path: Option<String>,
+        content: Option<String>,
+        chunks: Vec<NewChunk>,
+        embedding_model: Option<&str>,
+    ) -> Result<InsertOutcome<(Document, usize)>, DatabaseError> {
+        let mut tx = self.pool.begin().await?;
+
+        if let Some(content) = &content {
+            if let Some(existing) =
+                find_document_by_hash(&mut tx, project_id, &document_hash(content)).await?
+            {
+                return Ok(InsertOutcome::Duplicate(existing));
+            }
+        }
+
+        if let Some(chunk) = chunks.first() {
+            pin_embedding(&mut tx, project_id, chunk.embedding.len(), embedding_model).await?;
+        }
+
+        let document_id =
+            insert_document(&mut tx, project_id, &name, &source_path, &content).await?;
+        let inserted = insert_chunks(&mut tx, document_id, project_id, chunks).await?;
+
+        tx.commit().await?;
+
+        let count = inserted.len();
+        self.indexes.insert(project_id, inserted).await;
+
+        Ok(InsertOutcome::Inserted((
+            self.get_document(document_id).await?,
+            count,
+        )))
     }
 
-    pub async fn delete_message(&self, id: i64) -> Result<(), DatabaseError> {
-        sqlx::query("DELETE FROM messages WHERE id = ?")
-            .bind(id)
-            .execute(&self.pool)
+    pub async fn get_chunks_for_project(
+        &self,
+        project_id: i64,
+    ) -> Result<Vec<Chunk>, DatabaseError> {
+        let rows = sqlx::query("SELECT id, document_id, project_id, content, embedding, chunk_index, embedding_norm, page_number, start_offset, end_offset FROM chunks WHERE project_id = ?")
+            .bind(project_id)
+            .fetch_all(&self.pool)
             .await?;
-        Ok(())
+
+        let mut chunks = Vec::new();
+        for row in rows {
+            let embedding_bytes: Vec<u8> = row.get("embedding");
+            let embedding: Vec<f32> = bincode::deserialize(&embedding_bytes)
+                .map_err(|e| DatabaseError::SerializationError(e.to_string()))?;
+
+            chunks.push(Chunk {
+                id: row.get("id"),
+                document_id: row.get("document_id"),
+                project_id: row.get("project_id"),
+                content: row.get("content"),
+                embedding,
+                chunk_index: row.get("chunk_index"),
+                embedding_norm: row.get("embedding_norm"),
+                page_number: row.get("page_number"),
+                start_offset: row.get("start_offset"),
+                end_offset: row.get("end_offset"),
+            });
+        }
+
+        Ok(chunks)
+    }
+
+    /// Stream the embeddings of every chunk in a project, row by row
+    pub fn stream_chunk_embeddings(
+        &self,
+        project_id: i64,
+    ) -> impl Stream<Item = Result<ChunkEmbedding, DatabaseError>> + '_ {
+        self.stream_chunk_embeddings_after(project_id, 0)
+    }
+
+    /// Stream the embeddings of a project's chunks with ids above `after_id`, in id order
+    pub fn stream_chunk_embeddings_after(
+        &self,
+        project_id: i64,
+        after_id: i64,
+    ) -> impl Stream<Item = Result<ChunkEmbedding, DatabaseError>> + '_ {
+        sqlx::query("SELECT id, embedding, embedding_norm FROM chunks WHERE project_id = ? AND id > ? ORDER BY id")
+            .bind(project_id)
+            .bind(after_id)
+            .fetch(&self.pool)
+            .map(|row| chunk_embedding_from_row(row?))
+    }
+
+    /// Stream the embeddings of the chunks of some of a project's documents
+    /// Chunks of other documents are filtered out by the query, so they're never loaded.
+    pub fn stream_document_chunk_embeddings(
+        &self,
+        project_id: i64,
+        document_ids: &[i64],
+    ) -> impl Stream<Item = Result<ChunkEmbedding, DatabaseError>> + '_ {
+        // The ids are bound as one JSON array so the query text doesn't depend on their count
+        let document_ids = serde_json::to_string(document_ids).unwrap_or_default();
+        sqlx::query("SELECT id, embedding, embedding_norm FROM chunks WHERE project_id = ? AND document_id IN (SELECT value FROM json_each(?)) ORDER BY id")
+            .bind(project_id)
+            .bind(document_ids)
+            .fetch(&self.pool)
+            .map(|row| chunk_embedding_from_row(row?))
+    }
+
+    /// Ids of a project's documents whose name matches a SQL `LIKE` pattern
+    /// (`%` matches any text, `_` any one character; case-insensitive for ASCII)
+    pub async fn find_document_ids(
+        &self,
+        project_id: i64,
+        name_like: &str,
+    ) -> Result<Vec<i64>, DatabaseError> {
+        Ok(sqlx::query_scalar(
+            "SELECT id FROM documents WHERE project_id = ? AND name LIKE ? ORDER BY id",
+        )
+        .bind(project_id)
+        .bind(name_like)
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    pub async fn count_chunks(&self, project_id: i64) -> Result<i64, DatabaseError> {
+        Ok(
+            sqlx::query_scalar("SELECT COUNT(*) FROM chunks WHERE project_id = ?")
+                .bind(project_id)
+                .fetch_one(&self.pool)
+                .await?,
+        )
+    }
+
+    pub async fn list_chunk_ids(&self, project_id: i64) -> Result<Vec<i64>, DatabaseError> {
+        Ok(
+            sqlx::query_scalar("SELECT id FROM chunks WHERE project_id = ?")
+                .bind(project_id)
+                .fetch_all(&self.pool)
+                .await?,
+        )
+    }
+
+    /// Record embedding norms for chunks stored before norms were computed at insert time
+    pub async fn set_embedding_norms(&self, norms: &[(i64, f32)]) -> Result<(), DatabaseError> {
+        let mut tx = self.pool.begin().await?;
+
+        for (chunk_id, norm) in norms {
+            sqlx::query("UPDATE chunks SET embedding_norm = ? WHERE id = ?")
+                .bind(norm)
+                .bind(chunk_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn get_chunk_with_document(
+        &self,
+        chunk_id: i64,
+    ) -> Result<(Chunk, String), DatabaseError> {
+        let row = sqlx::query(
+            r#"
+            SELECT c.id, c.document_id, c.project_id, c.content, c.embedding, c.chunk_index, c.embedding_norm, c.page_number, c.start_offset, c.end_offset, d.name as doc_name
+            FROM chunks c
+            JOIN documents d ON c.document_id = d.id
+            WHERE c.id = ?
+            "#
+        )
+        .bind(chunk_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        let embedding_bytes: Vec<u8> = row.get("embedding");
+        let embedding: Vec<f32> = bincode::deserialize(&embedding_bytes)
+            .map_err(|e| DatabaseError::SerializationError(e.to_string()))?;
+
+        let chunk = Chunk {
+            id: row.get("id"),
+            document_id: row.get("document_id"),
+            project_id: row.get("project_id"),
+            content: row.get("content"),
+            embedding,
+            chunk_index: row.get("chunk_index"),
+            embedding_norm: row.get("embedding_norm"),
+            page_number: row.get("page_number"),
+            start_offset: row.get("start_offset"),
+            end_offset: row.get("end_offset"),
+        };
+
+        let doc_name: String = row.get("doc_name");
+
+        Ok((chunk, doc_name))
+    }
+
+    /// Get the content of a document's chunks with `chunk_index` in `[from_index, to_index]`,
+    /// ordered by index
+    pub async fn get_chunk_contents_in_range(
+        &self,
+        document_id: i64,
+        from_index: i32,
+        to_index: i32,
+    ) -> Result<Vec<(i32, String)>, DatabaseError> {
+        let rows = sqlx::query(
+            "SELECT chunk_index, content FROM chunks WHERE document_id = ? AND chunk_index BETWEEN ? AND ? ORDER BY chunk_index",
+        )
+        .bind(document_id)
+        .bind(from_index)
+        .bind(to_index)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.get("chunk_index"), row.get("content")))
+            .collect())
+    }
+
+    /// Get multiple chunks with their document names in one query (optimized)
+    pub async fn get_chunks_with_documents(
+        &self,
+        chunk_ids: &[i64],
+    ) -> Result<Vec<(Chunk, String)>, DatabaseError> {
+        if chunk_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // Build placeholders for IN clause
+        let placeholders = chunk_ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+        let query_str = format!(
+            r#"
+            SELECT c.id, c.document_id, c.project_id, c.content, c.embedding, c.chunk_index, c.embedding_norm, c.page_number, c.start_offset, c.end_offset, d.name as doc_name
+            FROM chunks c
+            JOIN documents d ON c.document_id = d.id
+            WHERE c.id IN ({})
+            "#,
+            placeholders
+        );
+
+        let mut query = sqlx::query(&query_str);
+        for id in chunk_ids {
+            query = query.bind(id);
+        }
+
+        let rows = query.fetch_all(&self.pool).await?;
+
+        let mut results = Vec::new();
+        for row in rows {
+            let embedding_bytes: Vec<u8> = row.get("embedding");
+            let embedding: Vec<f32> = bincode::deserialize(&embedding_bytes)
+                .map_err(|e| DatabaseError::SerializationError(e.to_string()))?;
+
+            let chunk = Chunk {
+                id: row.get("id"),
+                document_id: row.get("document_id"),
+                project_id: row.get("project_id"),
+                content: row.get("content"),
+                embedding,
+                chunk_index: row.get("chunk_index"),
+                embedding_norm: row.get("embedding_norm"),
+                page_number: row.get("page_number"),
+                start_offset: row.get("start_offset"),
+                end_offset: row.get("end_offset"),
+            };
+
+            let doc_name: String = row.get("doc_name");
+            results.push((chunk, doc_name));
+        }
+
+        Ok(results)
+    }
+
+    // Embedding cache operations
+
+    /// Look up cached embeddings by content hash; hashes not in the cache are absent from the map
+    pub async fn get_cached_embeddings(
+        &self,
+        content_hashes: &[String],
+    ) -> Result<HashMap<String, Vec<f32>>, DatabaseError> {
+        let mut cached = HashMap::new();
+
+        // Stay well below SQLite's limit on bound parameters per statement
+        for hashes in content_hashes.chunks(500) {
+            let placeholders = hashes.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+            let query_str = format!(
+                "SELECT content_hash, embedding FROM embedding_cache WHERE content_hash IN ({})",
+                placeholders
+            );
+
+            let mut query = sqlx::query(&query_str);
+            for hash in hashes {
+                query = query.bind(hash);
+            }
+
+            for row in query.fetch_all(&self.pool).await? {
+                let embedding_bytes: Vec<u8> = row.get("embedding");
+                let embedding: Vec<f32> = bincode::deserialize(&embedding_bytes)
+                    .map_err(|e| DatabaseError::SerializationError(e.to_string()))?;
+                cached.insert(row.get("content_hash"), embedding);
+            }
+        }
+
+        Ok(cached)
+    }
+
+    /// Store embeddings produced by `model` under their content hashes
+    pub async fn cache_embeddings(
+        &self,
+        model: &str,
+        entries: &[(String, Vec<f32>)],
+    ) -> Result<(), DatabaseError> {
+        let mut tx = self.pool.begin().await?;
+
+        for (content_hash, embedding) in entries {
+            let embedding_bytes = bincode::serialize(embedding)
+                .map_err(|e| DatabaseError::SerializationError(e.to_string()))?;
+
+            sqlx::query(
+                "INSERT OR REPLACE INTO embedding_cache (content_hash, model, embedding) VALUES (?, ?, ?)",
+            )
+            .bind(content_hash)
+            .bind(model)
+            .bind(embedding_bytes)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Remove every cached embedding, returning the number of entries removed
+    pub async fn clear_embedding_cache(&self) -> Result<u64, DatabaseError> {
+        let result = sqlx::query("DELETE FROM embedding_cache")
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    // Conversation operations
+    pub async fn create_conversation(
+        &self,
+        title: String,
+        provider_id: String,
+        model: String,
+        project_id: Option<i64>,
+    ) -> Result<Conversation, DatabaseError> {
+        self.create_conversation_with_settings(
+            title,
+            provider_id,
+            model,
+            project_id,
+            &ConversationSettings::default(),
+        )
+        .await
+    }
+
+    pub async fn create_conversation_with_settings(
+        &self,
+        title: String,
+        provider_id: String,
+        model: String,
+        project_id: Option<i64>,
+        settings: &ConversationSettings,
+    ) -> Result<Conversation, DatabaseError> {
+        let mut conn = self.pool.acquire().await?;
+        let id = insert_conversation(
+            &mut conn,
+            &title,
+            &provider_id,
+            &model,
+            project_id,
+            settings,
+        )
+        .await?;
+        drop(conn);
+
+        self.get_conversation(id).await
+    }
+
+    /// Create a conversation together with its first user message in a single
+    /// transaction, so a failure never leaves an empty conversation behind
+    pub async fn start_conversation(
+        &self,
+        title: String,
+        provider_id: String,
+        model: String,
+        project_id: Option<i64>,
+        settings: &ConversationSettings,
+        content: String,
+    ) -> Result<(Conversation, Message), DatabaseError> {
+        let mut tx = self.pool.begin().await?;
+
+        let conversation_id =
+            insert_conversation(&mut tx, &title, &provider_id, &model, project_id, settings)
+                .await?;
+        let message_id = insert_message(&mut tx, conversation_id, "user", &content).await?;
+
+        tx.commit().await?;
+
+        Ok((
+            self.get_conversation(conversation_id).await?,
+            self.get_message(message_id).await?,
+        ))
+    }
+
+    pub async fn get_conversation(&self, id: i64) -> Result<Conversation, DatabaseError> {
+        sqlx::query_as::<_, Conversation>("SELECT * FROM conversations WHERE id = ?")
+            .bind(id)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|_| DatabaseError::ConversationNotFound(id))
+    }
+
+    pub async fn list_conversations(
+        &self,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Page<Conversation>, DatabaseError> {
+        let items = sqlx::query_as::<_, Conversation>(
+            "SELECT * FROM conversations WHERE deleted_at IS NULL ORDER BY updated_at DESC LIMIT ? OFFSET ?",
+        )
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let total: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM conversations WHERE deleted_at IS NULL")
+                .fetch_one(&self.pool)
+                .await?;
+
+        Ok(Page { items, total })
+    }
+
+    /// Conversations in the trash, most recently trashed first
+    pub async fn list_trashed_conversations(
+        &self,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Page<Conversation>, DatabaseError> {
+        let items = sqlx::query_as::<_, Conversation>(
+            "SELECT * FROM conversations WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC LIMIT ? OFFSET ?",
+        )
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let total: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM conversations WHERE deleted_at IS NOT NULL")
+                .fetch_one(&self.pool)
+                .await?;
+
+        Ok(Page { items, total })
+    }
+
+    pub async fn list_conversations_for_project(
+        &self,
+        project_id: i64,
+    ) -> Result<Vec<Conversation>, DatabaseError> {
+        Ok(
+            sqlx::query_as::<_, Conversation>(
+                "SELECT * FROM conversations WHERE project_id = ? AND deleted_at IS NULL ORDER BY updated_at DESC"
+            )
+            .bind(project_id)
+            .fetch_all(&self.pool)
+            .await?,
+        )
+    }
+
+    pub async fn update_conversation_title(
+        &self,
+        id: i64,
+        title: String,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query(
+            "UPDATE conversations SET title = ?, updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = ?"
+        )
+        .bind(title)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Replace a conversation's generation defaults
+    pub async fn update_conversation_settings(
+        &self,
+        id: i64,
+        settings: &ConversationSettings,
+    ) -> Result<Conversation, DatabaseError> {
+        let result = sqlx::query(
+            "UPDATE conversations SET temperature = ?, max_tokens = ?, top_p = ?, system_prompt = ?, updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = ?"
+        )
+        .bind(settings.temperature)
+        .bind(settings.max_tokens)
+        .bind(settings.top_p)
+        .bind(&settings.system_prompt)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(DatabaseError::ConversationNotFound(id));
+        }
+        self.get_conversation(id).await
+    }
+
+    /// Store the summary of a conversation's messages up to and including `through_message_id`
+    pub async fn set_conversation_summary(
+        &self,
+        id: i64,
+        summary: &str,
+        through_message_id: i64,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query(
+            "UPDATE conversations SET history_summary = ?, summary_through_message_id = ? WHERE id = ?",
+        )
+        .bind(summary)
+        .bind(through_message_id)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Copy a conversation into a new one, keeping its messages up to and including
+    /// `up_to_message_id`. The fork records the original as its parent.
+    pub async fn fork_conversation(
+        &self,
+        id: i64,
+        up_to_message_id: i64,
+    ) -> Result<Conversation, DatabaseError> {
+        let parent = self.get_conversation(id).await?;
+        let message = self.get_message(up_to_message_id).await?;
+        if message.conversation_id != id {
+            return Err(DatabaseError::MessageNotFound(up_to_message_id));
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        let fork_id = sqlx::query(
+            "INSERT INTO conversations (title, provider_id, model, project_id, parent_conversation_id, temperature, max_tokens, top_p, system_prompt, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'), strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))"
+        )
+        .bind(&parent.title)
+        .bind(&parent.provider_id)
+        .bind(&parent.model)
+        .bind(parent.project_id)
+        .bind(id)
+        .bind(parent.settings.temperature)
+        .bind(parent.settings.max_tokens)
+        .bind(parent.settings.top_p)
+        .bind(&parent.settings.system_prompt)
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+
+        // Copied messages keep their timestamps so they stay in order
+        sqlx::query(
+            "INSERT INTO messages (conversation_id, role, content, created_at) SELECT ?1, role, content, created_at FROM messages WHERE conversation_id = ?2 AND (created_at, id) <= (SELECT created_at, id FROM messages WHERE id = ?3) ORDER BY created_at ASC, id ASC"
+        )
+        .bind(fork_id)
+        .bind(id)
+        .bind(message.id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        self.get_conversation(fork_id).await
+    }
+
+    /// Move a conversation to the trash; it keeps its messages and can be restored
+    pub async fn soft_delete_conversation(&self, id: i64) -> Result<(), DatabaseError> {
+        let result = sqlx::query(
+            "UPDATE conversations SET deleted_at = COALESCE(deleted_at, strftime('%Y-%m-%dT%H:%M:%fZ', 'now')) WHERE id = ?"
+        )
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(DatabaseError::ConversationNotFound(id));
+        }
+        Ok(())
+    }
+
+    /// Take a conversation out of the trash
+    pub async fn restore_conversation(&self, id: i64) -> Result<Conversation, DatabaseError> {
+        let result = sqlx::query("UPDATE conversations SET deleted_at = NULL WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(DatabaseError::ConversationNotFound(id));
+        }
+
+        self.get_conversation(id).await
+    }
+
+    /// Permanently delete a conversation in the trash and its messages
+    /// A conversation that isn't in the trash is reported as not found and kept.
+    pub async fn purge_conversation(&self, id: i64) -> Result<(), DatabaseError> {
+        let result =
+            sqlx::query("DELETE FROM conversations WHERE id = ? AND deleted_at IS NOT NULL")
+                .bind(id)
+                .execute(&self.pool)
+                .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(DatabaseError::ConversationNotFound(id));
+        }
+        Ok(())
+    }
+
+    pub async fn touch_conversation(&self, id: i64) -> Result<(), DatabaseError> {
+        sqlx::query("UPDATE conversations SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    // Message operations
+    pub async fn add_message(
+        &self,
+        conversation_id: i64,
+        role: String,
+        content: String,
+    ) -> Result<Message, DatabaseError> {
+        let mut conn = self.pool.acquire().await?;
+        let id = insert_message(&mut conn, conversation_id, &role, &content).await?;
+        drop(conn);
+
+        // Touch the conversation to update its timestamp
+        self.touch_conversation(conversation_id).await?;
+
+        self.get_message(id).await
+    }
+
+    /// Add a user message and the assistant's reply to it in a single transaction,
+    /// so a failure never leaves the message without its reply
+    pub async fn add_exchange(
+        &self,
+        conversation_id: i64,
+        content: String,
+        reply: String,
+    ) -> Result<(Message, Message), DatabaseError> {
+        let mut tx = self.pool.begin().await?;
+
+        let message_id = insert_message(&mut tx, conversation_id, "user", &content).await?;
+        let reply_id = insert_message(&mut tx, conversation_id, "assistant", &reply).await?;
+        sqlx::query("UPDATE conversations SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = ?")
+            .bind(conversation_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok((
+            self.get_message(message_id).await?,
+            self.get_message(reply_id).await?,
+        ))
+    }
+
+    pub async fn get_message(&self, id: i64) -> Result<Message, DatabaseError> {
+        sqlx::query_as::<_, Message>("SELECT * FROM messages WHERE id = ?")
+            .bind(id)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|_| DatabaseError::MessageNotFound(id))
+    }
+
+    pub async fn get_conversation_messages(
+        &self,
+        conversation_id: i64,
+    ) -> Result<Vec<Message>, DatabaseError> {
+        Ok(sqlx::query_as::<_, Message>(
+            "SELECT * FROM messages WHERE conversation_id = ? ORDER BY created_at ASC, id ASC",
+        )
+        .bind(conversation_id)
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    /// Replace a message's content and delete every later message in its conversation,
+    /// so the conversation can continue from the edited message
+    pub async fn edit_message(&self, id: i64, content: String) -> Result<Message, DatabaseError> {
+        let message = self.get_message(id).await?;
+
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("UPDATE messages SET content = ? WHERE id = ?")
+            .bind(&content)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(
+            "DELETE FROM messages WHERE conversation_id = ?1 AND (created_at, id) > (SELECT created_at, id FROM messages WHERE id = ?2)"
+        )
+        .bind(message.conversation_id)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query("UPDATE conversations SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = ?")
+            .bind(message.conversation_id)
+            .execute(&mut *tx)
+            .await?;
+
+        // A summary that covers the edited message no longer matches the history
+        sqlx::query(
+            "UPDATE conversations SET history_summary = NULL, summary_through_message_id = NULL WHERE id = ? AND summary_through_message_id >= ?",
+        )
+        .bind(message.conversation_id)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        self.get_message(id).await
+    }
+
+    pub async fn delete_message(&self, id: i64) -> Result<(), DatabaseError> {
+        let mut tx = self.pool.begin().await?;
+        remove_message(&mut tx, id).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Add an assistant reply to the conversation, deleting the reply it replaces in
+    /// the same transaction, so the conversation never ends up with both or neither
+    /// Fails with `MessageNotFound`, changing nothing, if `replaced` is no longer in
+    /// the conversation.
+    pub async fn replace_reply(
+        &self,
+        conversation_id: i64,
+        replaced: Option<i64>,
+        reply: String,
+    ) -> Result<Message, DatabaseError> {
+        let mut tx = self.pool.begin().await?;
+
+        if let Some(replaced) = replaced {
+            let in_conversation: Option<i64> =
+                sqlx::query_scalar("SELECT id FROM messages WHERE id = ? AND conversation_id = ?")
+                    .bind(replaced)
+                    .bind(conversation_id)
+                    .fetch_optional(&mut *tx)
+                    .await?;
+            if in_conversation.is_none() {
+                return Err(DatabaseError::MessageNotFound(replaced));
+            }
+            remove_message(&mut tx, replaced).await?;
+        }
+        let reply_id = insert_message(&mut tx, conversation_id, "assistant", &reply).await?;
+        sqlx::query("UPDATE conversations SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = ?")
+            .bind(conversation_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        self.get_message(reply_id).await
+    }
+
+    // Usage operations
+    pub async fn record_usage(
+        &self,
+        provider_id: &str,
+        model: &str,
+        prompt_tokens: u32,
+        completion_tokens: u32,
+        conversation_id: Option<i64>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query(
+            "INSERT INTO usage_log (provider_id, model, prompt_tokens, completion_tokens, conversation_id, created_at) VALUES (?, ?, ?, ?, ?, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))"
+        )
+        .bind(provider_id)
+        .bind(model)
+        .bind(prompt_tokens)
+        .bind(completion_tokens)
+        .bind(conversation_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Usage totals per group and model, for requests made in [from, to)
+    pub async fn get_usage_summary(
+        &self,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        group_by: UsageGroupBy,
+    ) -> Result<Vec<UsageTotal>, DatabaseError> {
+        // Same format as the stored timestamps, so they compare as strings
+        let format = |t: DateTime<Utc>| t.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string();
+
+        Ok(sqlx::query_as::<_, UsageTotal>(&format!(
+            "SELECT {} AS group_key, model, COUNT(*) AS requests, SUM(prompt_tokens) AS prompt_tokens, SUM(completion_tokens) AS completion_tokens FROM usage_log WHERE (?1 IS NULL OR created_at >= ?1) AND (?2 IS NULL OR created_at < ?2) GROUP BY group_key, model ORDER BY group_key, model",
+            group_by.key_sql()
+        ))
+        .bind(from.map(format))
+        .bind(to.map(format))
+        .fetch_all(&self.pool)
+        .await?)
+    }
+}
+
+/// Schema version produced by the last entry in `apply_migration`.
+/// To change the schema, add a new match arm and bump this constant; never edit an
+/// existing step, since it has already run on users' databases.
+pub const SCHEMA_VERSION: i64 = 17;
+
+/// Apply a single schema migration. Steps must be safe to run against databases created
+/// before versioning existed, which already have some of the tables and columns.
+async fn apply_migration(conn: &mut SqliteConnection, version: i64) -> Result<(), DatabaseError> {
+    match version {
+        // Initial schema
+        1 => {
+            sqlx::query(
+                r#"
+                CREATE TABLE IF NOT EXISTS projects (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    name TEXT NOT NULL,
+                    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+                    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+                    canvas_state TEXT
+                )
+                "#,
+            )
+            .execute(&mut *conn)
+            .await?;
+
+            sqlx::query(
+                r#"
+                CREATE TABLE IF NOT EXISTS documents (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    project_id INTEGER NOT NULL,
+                    name TEXT NOT NULL,
+                    source_path TEXT,
+                    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+                    FOREIGN KEY (project_id) REFERENCES projects(id) ON DELETE CASCADE
+                )
+                "#,
+            )
+            .execute(&mut *conn)
+            .await?;
+
+            sqlx::query(
+                r#"
+                CREATE TABLE IF NOT EXISTS chunks (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    document_id INTEGER NOT NULL,
+                    project_id INTEGER NOT NULL,
+                    content TEXT NOT NULL,
+                    embedding BLOB NOT NULL,
+                    chunk_index INTEGER NOT NULL,
+                    FOREIGN KEY (document_id) REFERENCES documents(id) ON DELETE CASCADE,
+                    FOREIGN KEY (project_id) REFERENCES projects(id) ON DELETE CASCADE
+                )
+                "#,
+            )
+            .execute(&mut *conn)
+            .await?;
+
+            // Create indexes for performance
+            sqlx::query("CREATE INDEX IF NOT EXISTS idx_chunks_project ON chunks(project_id)")
+                .execute(&mut *conn)
+                .await?;
+
+            sqlx::query("CREATE INDEX IF NOT EXISTS idx_chunks_document ON chunks(document_id)")
+                .execute(&mut *conn)
+                .await?;
+
+            // Conversation tables
+            sqlx::query(
+                r#"
+                CREATE TABLE IF NOT EXISTS conversations (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    title TEXT NOT NULL,
+                    provider_id TEXT NOT NULL,
+                    model TEXT NOT NULL,
+                    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+                    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+                )
+                "#,
+            )
+            .execute(&mut *conn)
+            .await?;
+
+            sqlx::query(
+                r#"
+                CREATE TABLE IF NOT EXISTS messages (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    conversation_id INTEGER NOT NULL,
+                    role TEXT NOT NULL,
+                    content TEXT NOT NULL,
+                    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+                    FOREIGN KEY (conversation_id) REFERENCES conversations(id) ON DELETE CASCADE
+                )
+                "#,
+            )
+            .execute(&mut *conn)
+            .await?;
+
+            sqlx::query(
+                "CREATE INDEX IF NOT EXISTS idx_messages_conversation ON messages(conversation_id)",
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        // Conversations can belong to a RAG project
+        2 => {
+            add_column_if_missing(
+                conn,
+                "conversations",
+                "project_id",
+                "INTEGER REFERENCES projects(id) ON DELETE SET NULL",
+            )
+            .await?;
+
+            sqlx::query(
+                "CREATE INDEX IF NOT EXISTS idx_conversations_project ON conversations(project_id)",
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        // Rewrite legacy `datetime('now')` timestamps ("YYYY-MM-DD HH:MM:SS") as ISO-8601 UTC
+        // so every row parses as `DateTime<Utc>` and string ordering stays chronological
+        3 => {
+            const COLUMNS: [(&str, &str); 6] = [
+                ("projects", "created_at"),
+                ("projects", "updated_at"),
+                ("documents", "created_at"),
+                ("conversations", "created_at"),
+                ("conversations", "updated_at"),
+                ("messages", "created_at"),
+            ];
+
+            for (table, column) in COLUMNS {
+                sqlx::query(&format!(
+                    "UPDATE {table} SET {column} = strftime('%Y-%m-%dT%H:%M:%fZ', {column}) WHERE {column} NOT LIKE '%Z'"
+                ))
+                .execute(&mut *conn)
+                .await?;
+            }
+        }
+
+        // Keep the original document text so it can be re-chunked and re-embedded
+        4 => {
+            add_column_if_missing(conn, "documents", "content", "TEXT").await?;
+        }
+
+        // Precomputed embedding norms; existing chunks stay NULL and are filled in lazily by search
+        5 => {
+            add_column_if_missing(conn, "chunks", "embedding_norm", "REAL").await?;
+        }
+
+        // Embeddings of previously seen chunk text, keyed by a hash of model and text
+        6 => {
+            sqlx::query(
+                r#"
+                CREATE TABLE IF NOT EXISTS embedding_cache (
+                    content_hash TEXT PRIMARY KEY,
+                    model TEXT NOT NULL,
+                    embedding BLOB NOT NULL
+                )
+                "#,
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        // Token usage of each chat completion
+        7 => {
+            sqlx::query(
+                r#"
+                CREATE TABLE IF NOT EXISTS usage_log (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    provider_id TEXT NOT NULL,
+                    model TEXT NOT NULL,
+                    prompt_tokens INTEGER NOT NULL,
+                    completion_tokens INTEGER NOT NULL,
+                    conversation_id INTEGER REFERENCES conversations(id) ON DELETE SET NULL,
+                    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+                )
+                "#,
+            )
+            .execute(&mut *conn)
+            .await?;
+
+            sqlx::query(
+                "CREATE INDEX IF NOT EXISTS idx_usage_log_created ON usage_log(created_at)",
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        // Forked conversations point at the conversation they were copied from
+        8 => {
+            add_column_if_missing(
+                conn,
+                "conversations",
+                "parent_conversation_id",
+                "INTEGER REFERENCES conversations(id) ON DELETE SET NULL",
+            )
+            .await?;
+        }
+
+        // Deleting a conversation moves it to the trash instead of removing it
+        9 => {
+            add_column_if_missing(conn, "conversations", "deleted_at", "TEXT").await?;
+        }
+
+        // Previous canvas states, snapshotted on each save
+        10 => {
+            sqlx::query(
+                r#"
+                CREATE TABLE IF NOT EXISTS canvas_versions (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    project_id INTEGER NOT NULL,
+                    state_json TEXT NOT NULL,
+                    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+                    FOREIGN KEY (project_id) REFERENCES projects(id) ON DELETE CASCADE
+                )
+                "#,
+            )
+            .execute(&mut *conn)
+            .await?;
+
+            sqlx::query(
+                "CREATE INDEX IF NOT EXISTS idx_canvas_versions_project ON canvas_versions(project_id)",
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        // Embedding dimension and model each project's chunks are held to. Existing projects
+        // take the dimension of their first chunk (a bincode Vec<f32> is an 8-byte length
+        // followed by 4 bytes per value); their model is unknown.
+        11 => {
+            add_column_if_missing(conn, "projects", "embedding_dim", "INTEGER").await?;
+            add_column_if_missing(conn, "projects", "embedding_model", "TEXT").await?;
+
+            sqlx::query(
+                "UPDATE projects SET embedding_dim = (SELECT (LENGTH(embedding) - 8) / 4 FROM chunks WHERE chunks.project_id = projects.id ORDER BY id LIMIT 1) WHERE embedding_dim IS NULL",
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        // Stored summary of older conversation history
+        12 => {
+            add_column_if_missing(conn, "conversations", "history_summary", "TEXT").await?;
+            add_column_if_missing(
+                conn,
+                "conversations",
+                "summary_through_message_id",
+                "INTEGER",
+            )
+            .await?;
+        }
+
+        // Per-conversation generation defaults
+        13 => {
+            add_column_if_missing(conn, "conversations", "temperature", "REAL").await?;
+            add_column_if_missing(conn, "conversations", "max_tokens", "INTEGER").await?;
+            add_column_if_missing(conn, "conversations", "top_p", "REAL").await?;
+            add_column_if_missing(conn, "conversations", "system_prompt", "TEXT").await?;
+        }
+
+        // Source page of chunks from paginated documents
+        14 => {
+            add_column_if_missing(conn, "chunks", "page_number", "INTEGER").await?;
+        }
+
+        // Character range of each chunk in its document
+        15 => {
+            add_column_if_missing(conn, "chunks", "start_offset", "INTEGER").await?;
+            add_column_if_missing(conn, "chunks", "end_offset", "INTEGER").await?;
+        }
+
+        // Hash of document content, to detect re-ingesting the same text; documents
+        // already stored with their text are hashed too
+        16 => {
+            add_column_if_missing(conn, "documents", "content_hash", "TEXT").await?;
+
+            sqlx::query(
+                "CREATE INDEX IF NOT EXISTS idx_documents_content_hash ON documents(project_id, content_hash)",
+            )
+            .execute(&mut *conn)
+            .await?;
+
+            let rows = sqlx::query(
+                "SELECT id, content FROM documents WHERE content IS NOT NULL AND content_hash IS NULL",
+            )
+            .fetch_all(&mut *conn)
+            .await?;
+            for row in rows {
+                let content: String = row.get("content");
+                sqlx::query("UPDATE documents SET content_hash = ? WHERE id = ?")
+                    .bind(document_hash(&content))
+                    .bind(row.get::<i64, _>("id"))
+                    .execute(&mut *conn)
+                    .await?;
+            }
+        }
+
+        // Canvas nodes and edges as rows rather than one JSON blob in projects.canvas_state.
+        // Saved canvases are moved over and the column cleared; one that can't be parsed
+        // is left where it is.
+        17 => {
+            sqlx::query(
+                r#"
+                CREATE TABLE IF NOT EXISTS canvas_nodes (
+                    project_id INTEGER NOT NULL,
+                    id TEXT NOT NULL,
+                    sort_order INTEGER NOT NULL,
+                    node_type TEXT NOT NULL,
+                    x REAL NOT NULL,
+                    y REAL NOT NULL,
+                    data TEXT NOT NULL,
+                    PRIMARY KEY (project_id, id),
+                    FOREIGN KEY (project_id) REFERENCES projects(id) ON DELETE CASCADE
+                )
+                "#,
+            )
+            .execute(&mut *conn)
+            .await?;
+
+            sqlx::query(
+                r#"
+                CREATE TABLE IF NOT EXISTS canvas_edges (
+                    project_id INTEGER NOT NULL,
+                    id TEXT NOT NULL,
+                    sort_order INTEGER NOT NULL,
+                    source TEXT NOT NULL,
+                    target TEXT NOT NULL,
+                    PRIMARY KEY (project_id, id),
+                    FOREIGN KEY (project_id, source) REFERENCES canvas_nodes(project_id, id) ON DELETE CASCADE,
+                    FOREIGN KEY (project_id, target) REFERENCES canvas_nodes(project_id, id) ON DELETE CASCADE
+                )
+                "#,
+            )
+            .execute(&mut *conn)
+            .await?;
+
+            for index in [
+                "CREATE INDEX IF NOT EXISTS idx_canvas_nodes_type ON canvas_nodes(project_id, node_type)",
+                "CREATE INDEX IF NOT EXISTS idx_canvas_edges_source ON canvas_edges(project_id, source)",
+                "CREATE INDEX IF NOT EXISTS idx_canvas_edges_target ON canvas_edges(project_id, target)",
+            ] {
+                sqlx::query(index).execute(&mut *conn).await?;
+            }
+
+            let rows =
+                sqlx::query("SELECT id, canvas_state FROM projects WHERE canvas_state IS NOT NULL")
+                    .fetch_all(&mut *conn)
+                    .await?;
+            for row in rows {
+                let project_id: i64 = row.get("id");
+                let state_json: String = row.get("canvas_state");
+                let mut state = match serde_json::from_str::<CanvasState>(&state_json) {
+                    Ok(state) => state,
+                    Err(e) => {
+                        tracing::warn!("Canvas of project {} can't be migrated: {}", project_id, e);
+                        continue;
+                    }
+                };
+                drop_dangling_edges(&mut state);
+                write_canvas(conn, project_id, &CanvasState::default(), &state).await?;
+
+                sqlx::query("UPDATE projects SET canvas_state = NULL WHERE id = ?")
+                    .bind(project_id)
+                    .execute(&mut *conn)
+                    .await?;
+            }
+        }
+
+        _ => return Err(DatabaseError::UnsupportedSchemaVersion(version)),
+    }
+
+    Ok(())
+}
+
+/// Insert a conversation on the caller's connection, returning its id
+async fn insert_conversation(
+    conn: &mut SqliteConnection,
+    title: &str,
+    provider_id: &str,
+    model: &str,
+    project_id: Option<i64>,
+    settings: &ConversationSettings,
+) -> Result<i64, DatabaseError> {
+    let id = sqlx::query(
+        "INSERT INTO conversations (title, provider_id, model, project_id, temperature, max_tokens, top_p, system_prompt, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'), strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))"
+    )
+    .bind(title)
+    .bind(provider_id)
+    .bind(model)
+    .bind(project_id)
+    .bind(settings.temperature)
+    .bind(settings.max_tokens)
+    .bind(settings.top_p)
+    .bind(&settings.system_prompt)
+    .execute(&mut *conn)
+    .await?
+    .last_insert_rowid();
+    Ok(id)
+}
+
+/// Delete a message, dropping the conversation's history summary if it covers it
+async fn remove_message(conn: &mut SqliteConnection, id: i64) -> Result<(), DatabaseError> {
+    // A summary that covers the deleted message no longer matches the history
+    sqlx::query(
+        "UPDATE conversations SET history_summary = NULL, summary_through_message_id = NULL WHERE id = (SELECT conversation_id FROM messages WHERE id = ?1) AND summary_through_message_id >= ?1",
+    )
+    .bind(id)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query("DELETE FROM messages WHERE id = ?")
+        .bind(id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+async fn insert_message(
+    conn: &mut SqliteConnection,
+    conversation_id: i64,
+    role: &str,
+    content: &str,
+) -> Result<i64, DatabaseError> {
+    let id = sqlx::query(
+        "INSERT INTO messages (conversation_id, role, content, created_at) VALUES (?, ?, ?, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))"
+    )
+    .bind(conversation_id)
+    .bind(role)
+    .bind(content)
+    .execute(&mut *conn)
+    .await?
+    .last_insert_rowid();
+    Ok(id)
+}
+
+/// Hex SHA-256 of a document's content, ignoring line-ending style and surrounding
+/// whitespace, so the same text added twice hashes the same
+pub fn document_hash(content: &str) -> String {
+    let normalized = content.replace("\r\n", "\n");
+    Sha256::digest(normalized.trim().as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Read a `ChunkEmbedding` from a row with `id`, `embedding` and `embedding_norm` columns
+fn chunk_embedding_from_row(row: SqliteRow) -> Result<ChunkEmbedding, DatabaseError> {
+    let embedding_bytes: Vec<u8> = row.get("embedding");
+    let embedding: Vec<f32> = bincode::deserialize(&embedding_bytes)
+        .map_err(|e| DatabaseError::SerializationError(e.to_string()))?;
+
+    Ok(ChunkEmbedding {
+        id: row.get("id"),
+        embedding,
+        embedding_norm: row.get("embedding_norm"),
+    })
+}
+
+/// Set a project's `updated_at`, failing if the project doesn't exist
+async fn touch_project(conn: &mut SqliteConnection, project_id: i64) -> Result<(), DatabaseError> {
+    let result = sqlx::query(
+        "UPDATE projects SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = ?",
+    )
+    .bind(project_id)
+    .execute(&mut *conn)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(DatabaseError::ProjectNotFound(project_id));
+    }
+    Ok(())
+}
+
+/// Read a project's canvas rows, in their saved order
+async fn read_canvas(
+    conn: &mut SqliteConnection,
+    project_id: i64,
+) -> Result<CanvasState, DatabaseError> {
+    let nodes = sqlx::query(
+        "SELECT id, node_type, x, y, data FROM canvas_nodes WHERE project_id = ? ORDER BY sort_order, id",
+    )
+    .bind(project_id)
+    .fetch_all(&mut *conn)
+    .await?
+    .into_iter()
+    .map(|row| {
+        let data: String = row.get("data");
+        Ok(CanvasNode {
+            id: row.get("id"),
+            node_type: row.get("node_type"),
+            position: Position {
+                x: row.get("x"),
+                y: row.get("y"),
+            },
+            data: serde_json::from_str(&data)
+                .map_err(|e| DatabaseError::SerializationError(e.to_string()))?,
+        })
+    })
+    .collect::<Result<Vec<_>, DatabaseError>>()?;
+
+    let edges = sqlx::query(
+        "SELECT id, source, target FROM canvas_edges WHERE project_id = ? ORDER BY sort_order, id",
+    )
+    .bind(project_id)
+    .fetch_all(&mut *conn)
+    .await?
+    .into_iter()
+    .map(|row| CanvasEdge {
+        id: row.get("id"),
+        source: row.get("source"),
+        target: row.get("target"),
+    })
+    .collect();
+
+    Ok(CanvasState { nodes, edges })
+}
+
+/// Turn a project's canvas rows from `previous` into `state`, writing only the difference:
+/// removed edges and nodes are deleted, and new or changed ones (including moved in the
+/// order) are upserted
+async fn write_canvas(
+    conn: &mut SqliteConnection,
+    project_id: i64,
+    previous: &CanvasState,
+    state: &CanvasState,
+) -> Result<(), DatabaseError> {
+    let old_edges: HashMap<&str, (usize, &CanvasEdge)> = previous
+        .edges
+        .iter()
+        .enumerate()
+        .map(|(idx, edge)| (edge.id.as_str(), (idx, edge)))
+        .collect();
+    let old_nodes: HashMap<&str, (usize, &CanvasNode)> = previous
+        .nodes
+        .iter()
+        .enumerate()
+        .map(|(idx, node)| (node.id.as_str(), (idx, node)))
+        .collect();
+
+    for id in old_edges.keys() {
+        if !state.edges.iter().any(|edge| edge.id == *id) {
+            sqlx::query("DELETE FROM canvas_edges WHERE project_id = ? AND id = ?")
+                .bind(project_id)
+                .bind(*id)
+                .execute(&mut *conn)
+                .await?;
+        }
+    }
+    // Edges still attached to a removed node are deleted with it
+    for id in old_nodes.keys() {
+        if !state.nodes.iter().any(|node| node.id == *id) {
+            sqlx::query("DELETE FROM canvas_nodes WHERE project_id = ? AND id = ?")
+                .bind(project_id)
+                .bind(*id)
+                .execute(&mut *conn)
+                .await?;
+        }
+    }
+
+    for (idx, node) in state.nodes.iter().enumerate() {
+        if old_nodes.get(node.id.as_str()) != Some(&(idx, node)) {
+            upsert_canvas_node(conn, project_id, Some(idx as i64), node).await?;
+        }
+    }
+    for (idx, edge) in state.edges.iter().enumerate() {
+        if old_edges.get(edge.id.as_str()) != Some(&(idx, edge)) {
+            upsert_canvas_edge(conn, project_id, Some(idx as i64), edge).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Insert or update a canvas node row; without `sort_order` an existing node keeps its
+/// place and a new one goes last
+async fn upsert_canvas_node(
+    conn: &mut SqliteConnection,
+    project_id: i64,
+    sort_order: Option<i64>,
+    node: &CanvasNode,
+) -> Result<(), DatabaseError> {
+    sqlx::query(
+        r#"
+        INSERT INTO canvas_nodes (project_id, id, sort_order, node_type, x, y, data)
+        VALUES (?1, ?2, COALESCE(?3, (SELECT COALESCE(MAX(sort_order) + 1, 0) FROM canvas_nodes WHERE project_id = ?1)), ?4, ?5, ?6, ?7)
+        ON CONFLICT (project_id, id) DO UPDATE SET
+            sort_order = COALESCE(?3, sort_order),
+            node_type = excluded.node_type,
+            x = excluded.x,
+            y = excluded.y,
+            data = excluded.data
+        "#,
+    )
+    .bind(project_id)
+    .bind(&node.id)
+    .bind(sort_order)
+    .bind(&node.node_type)
+    .bind(node.position.x)
+    .bind(node.position.y)
+    .bind(node.data.to_string())
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Insert or update a canvas edge row; `sort_order` works as for `upsert_canvas_node`
+async fn upsert_canvas_edge(
+    conn: &mut SqliteConnection,
+    project_id: i64,
+    sort_order: Option<i64>,
+    edge: &CanvasEdge,
+) -> Result<(), DatabaseError> {
+    sqlx::query(
+        r#"
+        INSERT INTO canvas_edges (project_id, id, sort_order, source, target)
+        VALUES (?1, ?2, COALESCE(?3, (SELECT COALESCE(MAX(sort_order) + 1, 0) FROM canvas_edges WHERE project_id = ?1)), ?4, ?5)
+        ON CONFLICT (project_id, id) DO UPDATE SET
+            sort_order = COALESCE(?3, sort_order),
+            source = excluded.source,
+            target = excluded.target
+        "#,
+    )
+    .bind(project_id)
+    .bind(&edge.id)
+    .bind(sort_order)
+    .bind(&edge.source)
+    .bind(&edge.target)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Remove edges whose ends aren't nodes of the canvas, which the edge table can't hold
+fn drop_dangling_edges(state: &mut CanvasState) {
+    let node_ids: Vec<&str> = state.nodes.iter().map(|node| node.id.as_str()).collect();
+    state.edges.retain(|edge| {
+        node_ids.contains(&edge.source.as_str()) && node_ids.contains(&edge.target.as_str())
+    });
+}
+
+/// Insert a document on the caller's connection, hashing its content, returning its id
+async fn insert_document(
+    conn: &mut SqliteConnection,
+    project_id: i64,
+    name: &str,
+    source_path: &Option<String>,
+    content: &Option<String>,
+) -> Result<i64, DatabaseError> {
+    let id = sqlx::query("INSERT INTO documents (project_id, name, source_path, content, content_hash, created_at) VALUES (?, ?, ?, ?, ?, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))")
+        .bind(project_id)
+        .bind(name)
+        .bind(source_path)
+        .bind(content)
+        .bind(content.as_deref().map(document_hash))
+        .execute(&mut *conn)
+        .await?
+        .last_insert_rowid();
+    Ok(id)
+}
+
+/// The project's first document whose content hashes to `hash`, on the caller's connection
+async fn find_document_by_hash(
+    conn: &mut SqliteConnection,
+    project_id: i64,
+    hash: &str,
+) -> Result<Option<Document>, DatabaseError> {
+    let document = sqlx::query_as::<_, Document>(
+        "SELECT id, project_id, name, source_path, created_at FROM documents WHERE project_id = ? AND content_hash = ? ORDER BY id LIMIT 1",
+    )
+    .bind(project_id)
+    .bind(hash)
+    .fetch_optional(&mut *conn)
+    .await?;
+    Ok(document)
+}
+
+/// Insert a document's chunks in order within the caller's transaction, returning each
+/// new chunk id with its embedding. Every embedding must have the first one's dimension.
+async fn insert_chunks(
+    conn: &mut SqliteConnection,
+    document_id: i64,
+    project_id: i64,
+    chunks: Vec<NewChunk>,
+) -> Result<Vec<(i64, Vec<f32>)>, DatabaseError> {
+    let dimension = chunks.first().map_or(0, |chunk| chunk.embedding.len());
+
+    let mut inserted: Vec<(i64, Vec<f32>)> = Vec::with_capacity(chunks.len());
+    for (idx, chunk) in chunks.into_iter().enumerate() {
+        if chunk.embedding.len() != dimension {
+            return Err(DatabaseError::DimensionMismatch {
+                expected: dimension,
+                got: chunk.embedding.len(),
+            });
+        }
+        let embedding_norm = l2_norm(&chunk.embedding);
+        let embedding_bytes = bincode::serialize(&chunk.embedding)
+            .map_err(|e| DatabaseError::SerializationError(e.to_string()))?;
+
+        let id = sqlx::query(
+            "INSERT INTO chunks (document_id, project_id, content, embedding, chunk_index, embedding_norm, page_number, start_offset, end_offset) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(document_id)
+        .bind(project_id)
+        .bind(chunk.content)
+        .bind(embedding_bytes)
+        .bind(idx as i32)
+        .bind(embedding_norm)
+        .bind(chunk.page_number)
+        .bind(chunk.start_offset)
+        .bind(chunk.end_offset)
+        .execute(&mut *conn)
+        .await?
+        .last_insert_rowid();
+        inserted.push((id, chunk.embedding));
+    }
+
+    Ok(inserted)
+}
+
+/// Check an embedding dimension and model against the project's, within the caller's
+/// transaction. A project without chunks takes the given dimension and model; otherwise
+/// a different dimension or model is rejected, and `model` is only recorded if none was
+/// known.
+async fn pin_embedding(
+    conn: &mut SqliteConnection,
+    project_id: i64,
+    dimension: usize,
+    model: Option<&str>,
+) -> Result<(), DatabaseError> {
+    let (pinned, pinned_model, has_chunks): (Option<i64>, Option<String>, bool) = sqlx::query_as(
+        "SELECT embedding_dim, embedding_model, EXISTS(SELECT 1 FROM chunks WHERE project_id = projects.id) FROM projects WHERE id = ?",
+    )
+    .bind(project_id)
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(DatabaseError::ProjectNotFound(project_id))?;
+
+    match pinned {
+        Some(expected) if has_chunks => {
+            if expected as usize != dimension {
+                return Err(DatabaseError::DimensionMismatch {
+                    expected: expected as usize,
+                    got: dimension,
+                });
+            }
+            if let (Some(expected), Some(got)) = (pinned_model, model) {
+                if expected != got {
+                    return Err(DatabaseError::EmbeddingModelMismatch {
+                        expected,
+                        got: got.to_string(),
+                    });
+                }
+            }
+            sqlx::query(
+                "UPDATE projects SET embedding_model = ? WHERE id = ? AND embedding_model IS NULL",
+            )
+            .bind(model)
+            .bind(project_id)
+            .execute(&mut *conn)
+            .await?;
+        }
+        _ => {
+            // Keep a known model when the dimension is unchanged and none was given
+            sqlx::query(
+                "UPDATE projects SET embedding_model = COALESCE(?1, CASE WHEN embedding_dim = ?2 THEN embedding_model END), embedding_dim = ?2 WHERE id = ?3",
+            )
+            .bind(model)
+            .bind(dimension as i64)
+            .bind(project_id)
+            .execute(&mut *conn)
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Add a column to an existing table unless it is already present
+async fn add_column_if_missing(
+    conn: &mut SqliteConnection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<(), DatabaseError> {
+    let columns = sqlx::query(&format!("PRAGMA table_info({})", table))
+        .fetch_all(&mut *conn)
+        .await?;
+
+    if columns
+        .iter()
+        .any(|row| row.get::<String, _>("name") == column)
+    {
+        return Ok(());
+    }
+
+    sqlx::query(&format!(
+        "ALTER TABLE {} ADD COLUMN {} {}",
+        table, column, definition
+    ))
+    .execute(&mut *conn)
+    .await?;
+
+    tracing::info!("Migrated {}: added column {}", table, column);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    async fn open(dir: &TempDir) -> RagDatabase {
+        let path = dir.path().join("rag.db");
+        if !path.exists() {
+            std::fs::File::create(&path).unwrap();
+        }
+        RagDatabase::new(path, DEFAULT_MAX_CONNECTIONS)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_fresh_database_is_fully_migrated() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = open(&temp_dir).await;
+
+        assert_eq!(db.schema_version().await.unwrap(), SCHEMA_VERSION);
+
+        let project = db.create_project("Project".to_string()).await.unwrap();
+        let conversation = db
+            .create_conversation(
+                "Chat".to_string(),
+                "claude".to_string(),
+                "model".to_string(),
+                Some(project.id),
+            )
+            .await
+            .unwrap();
+        assert_eq!(conversation.project_id, Some(project.id));
+    }
+
+    #[tokio::test]
+    async fn test_connections_use_wal() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = open(&temp_dir).await;
+
+        let mode: String = sqlx::query_scalar("PRAGMA journal_mode")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(mode, "wal");
+    }
+
+    #[tokio::test]
+    async fn test_migrations_run_once() {
+        let temp_dir = TempDir::new().unwrap();
+        drop(open(&temp_dir).await);
+        let db = open(&temp_dir).await;
+
+        let applied: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM schema_version")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(applied, SCHEMA_VERSION);
+    }
+
+    #[tokio::test]
+    async fn test_document_and_project_stats() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = open(&temp_dir).await;
+
+        let project = db.create_project("Project".to_string()).await.unwrap();
+        let document = db
+            .create_document(
+                project.id,
+                "Doc".to_string(),
+                None,
+                Some("hello world!".to_string()),
+            )
+            .await
+            .unwrap();
+
+        let empty = db.get_document_stats(document.id).await.unwrap();
+        assert_eq!(empty.chunk_count, 0);
+        assert_eq!(empty.embedding_dim, None);
+
+        // Overlapping chunks, as the chunker produces them
+        db.insert_chunk(
+            document.id,
+            project.id,
+            "hello wor".to_string(),
+            vec![0.1, 0.2, 0.3],
+            0,
+        )
+        .await
+        .unwrap();
+        db.insert_chunk(
+            document.id,
+            project.id,
+            "world!".to_string(),
+            vec![0.4, 0.5, 0.6],
+            1,
+        )
+        .await
+        .unwrap();
+
+        let stats = db.get_document_stats(document.id).await.unwrap();
+        assert_eq!(stats.chunk_count, 2);
+        assert_eq!(stats.total_characters, 12);
+        assert_eq!(stats.embedding_dim, Some(3));
+
+        let project_stats = db.get_project_stats(project.id).await.unwrap();
+        assert_eq!(project_stats.document_count, 1);
+        assert_eq!(project_stats.chunk_count, 2);
+        assert!(project_stats.storage_bytes > 11);
+
+        assert!(matches!(
+            db.get_document_stats(document.id + 1).await,
+            Err(DatabaseError::DocumentNotFound(_))
+        ));
+
+        // Without the document text, only the chunks can be counted
+        let untexted = db
+            .create_document(project.id, "Old".to_string(), None, None)
+            .await
+            .unwrap();
+        db.insert_chunk(
+            untexted.id,
+            project.id,
+            "hello".to_string(),
+            vec![0.1, 0.2, 0.3],
+            0,
+        )
+        .await
+        .unwrap();
+        let stats = db.get_document_stats(untexted.id).await.unwrap();
+        assert_eq!(stats.total_characters, 5);
+    }
+
+    #[tokio::test]
+    async fn test_embedding_cache_roundtrip_and_clear() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = open(&temp_dir).await;
+
+        let entries = vec![
+            ("hash-a".to_string(), vec![1.0, 2.0]),
+            ("hash-b".to_string(), vec![3.0, 4.0]),
+        ];
+        db.cache_embeddings("model", &entries).await.unwrap();
+
+        let hashes = vec!["hash-a".to_string(), "hash-missing".to_string()];
+        let cached = db.get_cached_embeddings(&hashes).await.unwrap();
+        assert_eq!(cached.len(), 1);
+        assert_eq!(cached["hash-a"], vec![1.0, 2.0]);
+
+        assert_eq!(db.clear_embedding_cache().await.unwrap(), 2);
+        assert!(db.get_cached_embeddings(&hashes).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_find_duplicate_document() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = open(&temp_dir).await;
+
+        let project = db.create_project("Project".to_string()).await.unwrap();
+        let other = db.create_project("Other".to_string()).await.unwrap();
+        let document = db
+            .create_document(
+                project.id,
+                "Doc".to_string(),
+                None,
+                Some("line one\nline two\n".to_string()),
+            )
+            .await
+            .unwrap();
+
+        let duplicate = db
+            .find_duplicate_document(project.id, "line one\r\nline two")
+            .await
+            .unwrap();
+        assert_eq!(duplicate.map(|d| d.id), Some(document.id));
+        assert!(db
+            .find_duplicate_document(other.id, "line one\nline two")
+            .await
+            .unwrap()
+            .is_none());
+        assert!(db
+            .find_duplicate_document(project.id, "line one")
+            .await
+            .unwrap()
+            .is_none());
+
+        // Replacing the content re-hashes it
+        db.replace_document_chunks(document.id, Some("new text".to_string()), Vec::new(), None)
+            .await
+            .unwrap();
+        let duplicate = db
+            .find_duplicate_document(project.id, "new text")
+            .await
+            .unwrap();
+        assert_eq!(duplicate.map(|d| d.id), Some(document.id));
+    }
+
+    #[tokio::test]
+    async fn test_create_document_unless_duplicate() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = open(&temp_dir).await;
+        let project = db.create_project("Project".to_string()).await.unwrap();
+
+        let InsertOutcome::Inserted(first) = db
+            .create_document_unless_duplicate(project.id, "A".to_string(), None, "text".to_string())
+            .await
+            .unwrap()
+        else {
+            panic!("first copy should be inserted");
+        };
+        let second = db
+            .create_document_unless_duplicate(
+                project.id,
+                "B".to_string(),
+                None,
+                "text\n".to_string(),
+            )
+            .await
+            .unwrap();
+        assert!(matches!(second, InsertOutcome::Duplicate(existing) if existing.id == first.id));
+
+        let third = db
+            .create_document_with_chunks(
+                project.id,
+                "C".to_string(),
+                None,
+                Some("text".to_string()),
+                vec![NewChunk::new("text".to_string(), vec![1.0, 0.0])],
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(matches!(third, InsertOutcome::Duplicate(existing) if existing.id == first.id));
+        assert_eq!(db.list_documents(project.id, 10, 0).await.unwrap().total, 1);
+        assert!(db
+            .get_chunks_for_project(project.id)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_migration_hashes_existing_documents() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = open(&temp_dir).await;
+        let project = db.create_project("Project".to_string()).await.unwrap();
+        let document = db
+            .create_document(
+                project.id,
+                "Doc".to_string(),
+                None,
+                Some("text".to_string()),
+            )
+            .await
+            .unwrap();
+
+        // A document stored before the step that hashes content ran
+        sqlx::query("UPDATE documents SET content_hash = NULL")
+            .execute(db.pool())
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM schema_version WHERE version >= 16")
+            .execute(db.pool())
+            .await
+            .unwrap();
+        drop(db);
+
+        let db = open(&temp_dir).await;
+        let duplicate = db
+            .find_duplicate_document(project.id, "text")
+            .await
+            .unwrap();
+        assert_eq!(duplicate.map(|d| d.id), Some(document.id));
+    }
+
+    #[tokio::test]
+    async fn test_replace_document_chunks() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = open(&temp_dir).await;
+
+        let project = db.create_project("Project".to_string()).await.unwrap();
+        let document = db
+            .create_document(project.id, "Doc".to_string(), None, Some("old".to_string()))
+            .await
+            .unwrap();
+        db.insert_chunk(document.id, project.id, "old".to_string(), vec![1.0], 0)
+            .await
+            .unwrap();
+
+        let count = db
+            .replace_document_chunks(
+                document.id,
+                Some("new a new b".to_string()),
+                vec![
+                    NewChunk::new("new a".to_string(), vec![0.5]),
+                    NewChunk {
+                        start_offset: Some(6),
+                        end_offset: Some(11),
+                        ..NewChunk::new("new b".to_string(), vec![0.25])
+                    },
+                ],
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(count, 2);
+
+        let chunks = db.get_chunks_for_project(project.id).await.unwrap();
+        let contents: Vec<&str> = chunks.iter().map(|c| c.content.as_str()).collect();
+        assert_eq!(contents.len(), 2);
+        assert!(contents.contains(&"new a") && contents.contains(&"new b"));
+        assert!(chunks.iter().all(|c| c.document_id == document.id));
+        let second = chunks.iter().find(|c| c.content == "new b").unwrap();
+        assert_eq!(
+            (second.start_offset, second.end_offset),
+            (Some(6), Some(11))
+        );
+
+        let content = db.get_document_content(document.id).await.unwrap();
+        assert_eq!(content.as_deref(), Some("new a new b"));
+    }
+
+    #[tokio::test]
+    async fn test_project_embedding_dimension_pinned() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = open(&temp_dir).await;
+
+        let project = db.create_project("Project".to_string()).await.unwrap();
+        let first = db
+            .create_document(project.id, "First".to_string(), None, None)
+            .await
+            .unwrap();
+        let second = db
+            .create_document(project.id, "Second".to_string(), None, None)
+            .await
+            .unwrap();
+
+        let info = db.get_project_embedding_info(project.id).await.unwrap();
+        assert_eq!(info.dimension, None);
+
+        db.replace_document_chunks(
+            first.id,
+            None,
+            vec![NewChunk::new("a".to_string(), vec![1.0, 0.0, 0.0])],
+            Some("mock/mock-embedding"),
+        )
+        .await
+        .unwrap();
+
+        let info = db.get_project_embedding_info(project.id).await.unwrap();
+        assert_eq!(info.dimension, Some(3));
+        assert_eq!(info.model.as_deref(), Some("mock/mock-embedding"));
+
+        assert!(matches!(
+            db.insert_chunk(second.id, project.id, "b".to_string(), vec![1.0, 0.0], 0)
+                .await,
+            Err(DatabaseError::DimensionMismatch {
+                expected: 3,
+                got: 2
+            })
+        ));
+        assert!(matches!(
+            db.pin_project_embedding(project.id, 2, "other/model").await,
+            Err(DatabaseError::DimensionMismatch { .. })
+        ));
+        // The same dimension from another model doesn't mix with the project's either
+        assert!(matches!(
+            db.replace_document_chunks(
+                second.id,
+                None,
+                vec![NewChunk::new("b".to_string(), vec![0.0, 1.0, 0.0])],
+                Some("other/model"),
+            )
+            .await,
+            Err(DatabaseError::EmbeddingModelMismatch { .. })
+        ));
+        db.pin_project_embedding(project.id, 3, "mock/mock-embedding")
+            .await
+            .unwrap();
+
+        // Re-indexing the only document with another model is allowed
+        db.replace_document_chunks(
+            first.id,
+            None,
+            vec![NewChunk::new("a".to_string(), vec![1.0, 0.0])],
+            Some("other/model"),
+        )
+        .await
+        .unwrap();
+
+        let info = db.get_project_embedding_info(project.id).await.unwrap();
+        assert_eq!(info.dimension, Some(2));
+        assert_eq!(info.model.as_deref(), Some("other/model"));
+    }
+
+    #[tokio::test]
+    async fn test_export_import_project_roundtrip() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = open(&temp_dir).await;
+
+        let project = db.create_project("Project".to_string()).await.unwrap();
+        let document = db
+            .create_document(project.id, "Doc".to_string(), None, Some("a b".to_string()))
+            .await
+            .unwrap();
+        db.replace_document_chunks(
+            document.id,
+            None,
+            vec![
+                NewChunk::new("a".to_string(), vec![0.1, -0.2, 3.0e-8]),
+                NewChunk::new("b".to_string(), vec![1.0, 0.0, f32::MIN_POSITIVE]),
+            ],
+            Some("mock/mock-embedding"),
+        )
+        .await
+        .unwrap();
+        db.save_canvas_state(project.id, &canvas(2)).await.unwrap();
+
+        let export = db.export_project(project.id).await.unwrap();
+        let json = serde_json::to_string(&export).unwrap();
+        let imported = db
+            .import_project(&serde_json::from_str(&json).unwrap())
+            .await
+            .unwrap();
+        assert_ne!(imported.id, project.id);
+        assert_eq!(imported.name, "Project");
+        assert_eq!(
+            db.get_canvas_state(imported.id).await.unwrap(),
+            Some(canvas(2))
+        );
+
+        let blobs = |project_id: i64| {
+            sqlx::query_scalar::<_, Vec<u8>>(
+                "SELECT embedding FROM chunks WHERE project_id = ? ORDER BY chunk_index",
+            )
+            .bind(project_id)
+            .fetch_all(db.pool())
+        };
+        assert_eq!(
+            blobs(project.id).await.unwrap(),
+            blobs(imported.id).await.unwrap()
+        );
+
+        let info = db.get_project_embedding_info(imported.id).await.unwrap();
+        assert_eq!(info.dimension, Some(3));
+        assert_eq!(info.model.as_deref(), Some("mock/mock-embedding"));
+
+        let documents = db.list_documents(imported.id, 10, 0).await.unwrap();
+        assert_eq!(documents.total, 1);
+        let content = db
+            .get_document_content(documents.items[0].id)
+            .await
+            .unwrap();
+        assert_eq!(content.as_deref(), Some("a b"));
+    }
+
+    #[tokio::test]
+    async fn test_delete_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = open(&temp_dir).await;
+        std::fs::create_dir_all(db.indexes().dir()).unwrap();
+
+        let removed = db.delete_files().await.unwrap();
+        assert!(removed.contains(&temp_dir.path().join("rag.db")));
+        assert!(removed.contains(&temp_dir.path().join("indexes")));
+        assert!(!temp_dir.path().join("rag.db").exists());
+        assert!(!temp_dir.path().join("rag.db-wal").exists());
+    }
+
+    #[tokio::test]
+    async fn test_upgrades_unversioned_database() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("rag.db");
+        std::fs::File::create(&path).unwrap();
+
+        // Schema as written by releases before migrations existed
+        let pool = SqlitePool::connect(&format!("sqlite:{}", path.display()))
+            .await
+            .unwrap();
+        sqlx::query(
+            "CREATE TABLE conversations (id INTEGER PRIMARY KEY AUTOINCREMENT, title TEXT NOT NULL, provider_id TEXT NOT NULL, model TEXT NOT NULL, created_at TEXT NOT NULL DEFAULT (datetime('now')), updated_at TEXT NOT NULL DEFAULT (datetime('now')))"
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query("INSERT INTO conversations (title, provider_id, model, created_at, updated_at) VALUES ('Old', 'claude', 'model', '2024-01-02 03:04:05', '2024-01-02 03:04:05')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        pool.close().await;
+
+        let db = open(&temp_dir).await;
+        assert_eq!(db.schema_version().await.unwrap(), SCHEMA_VERSION);
+
+        let page = db.list_conversations(10, 0).await.unwrap();
+        assert_eq!(page.total, 1);
+        let old = &page.items[0];
+        assert_eq!(old.project_id, None);
+        assert_eq!(old.created_at.to_rfc3339(), "2024-01-02T03:04:05+00:00");
+    }
+
+    #[tokio::test]
+    async fn test_usage_summary() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = open(&temp_dir).await;
+
+        let conversation = db
+            .create_conversation(
+                "Chat".to_string(),
+                "claude".to_string(),
+                "model".to_string(),
+                None,
+            )
+            .await
+            .unwrap();
+        db.record_usage("claude", "sonnet", 100, 10, Some(conversation.id))
+            .await
+            .unwrap();
+        db.record_usage("claude", "haiku", 50, 5, None)
+            .await
+            .unwrap();
+        db.record_usage("deepseek", "deepseek-chat", 20, 2, None)
+            .await
+            .unwrap();
+
+        let by_provider = db
+            .get_usage_summary(None, None, UsageGroupBy::Provider)
+            .await
+            .unwrap();
+        assert_eq!(by_provider.len(), 3);
+        let claude: i64 = by_provider
+            .iter()
+            .filter(|t| t.group_key.as_deref() == Some("claude"))
+            .map(|t| t.prompt_tokens)
+            .sum();
+        assert_eq!(claude, 150);
+
+        let by_conversation = db
+            .get_usage_summary(None, None, UsageGroupBy::Conversation)
+            .await
+            .unwrap();
+        assert_eq!(by_conversation[0].group_key, None);
+        assert_eq!(
+            by_conversation.last().unwrap().group_key,
+            Some(conversation.id.to_string())
+        );
+
+        let future = Utc::now() + chrono::Duration::days(1);
+        let none = db
+            .get_usage_summary(Some(future), None, UsageGroupBy::Day)
+            .await
+            .unwrap();
+        assert!(none.is_empty());
+        let all = db
+            .get_usage_summary(None, Some(future), UsageGroupBy::Day)
+            .await
+            .unwrap();
+        assert_eq!(all.iter().map(|t| t.requests).sum::<i64>(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_fork_conversation() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = open(&temp_dir).await;
+
+        let parent = db
+            .create_conversation(
+                "Chat".to_string(),
+                "claude".to_string(),
+                "model".to_string(),
+                None,
+            )
+            .await
+            .unwrap();
+        let mut messages = Vec::new();
+        for content in ["one", "two", "three"] {
+            messages.push(
+                db.add_message(parent.id, "user".to_string(), content.to_string())
+                    .await
+                    .unwrap(),
+            );
+        }
+
+        let fork = db
+            .fork_conversation(parent.id, messages[1].id)
+            .await
+            .unwrap();
+        assert_eq!(fork.parent_conversation_id, Some(parent.id));
+        assert_eq!(fork.provider_id, "claude");
+
+        let copied: Vec<String> = db
+            .get_conversation_messages(fork.id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|m| m.content)
+            .collect();
+        assert_eq!(copied, ["one", "two"]);
+
+        // The fork is independent of its parent
+        db.add_message(fork.id, "user".to_string(), "other".to_string())
+            .await
+            .unwrap();
+        assert_eq!(
+            db.get_conversation_messages(parent.id).await.unwrap().len(),
+            3
+        );
+
+        // The message must belong to the conversation being forked
+        let other = db
+            .create_conversation(
+                "Other".to_string(),
+                "claude".to_string(),
+                "m".to_string(),
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(matches!(
+            db.fork_conversation(other.id, messages[0].id).await,
+            Err(DatabaseError::MessageNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_conversation_settings() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = open(&temp_dir).await;
+
+        let settings = ConversationSettings {
+            temperature: Some(0.5),
+            max_tokens: Some(256),
+            top_p: None,
+            system_prompt: Some("Be brief.".to_string()),
+        };
+        let conversation = db
+            .create_conversation_with_settings(
+                "Chat".to_string(),
+                "claude".to_string(),
+                "model".to_string(),
+                None,
+                &settings,
+            )
+            .await
+            .unwrap();
+        assert_eq!(conversation.settings, settings);
+
+        let updated = ConversationSettings {
+            top_p: Some(0.9),
+            system_prompt: None,
+            ..settings
+        };
+        let conversation = db
+            .update_conversation_settings(conversation.id, &updated)
+            .await
+            .unwrap();
+        assert_eq!(conversation.settings, updated);
+
+        // Forks keep the parent's settings
+        let message = db
+            .add_message(conversation.id, "user".to_string(), "hi".to_string())
+            .await
+            .unwrap();
+        let fork = db
+            .fork_conversation(conversation.id, message.id)
+            .await
+            .unwrap();
+        assert_eq!(fork.settings, updated);
+
+        assert!(matches!(
+            db.update_conversation_settings(9999, &updated).await,
+            Err(DatabaseError::ConversationNotFound(9999))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_start_conversation() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = open(&temp_dir).await;
+
+        let (conversation, message) = db
+            .start_conversation(
+                "Chat".to_string(),
+                "claude".to_string(),
+                "model".to_string(),
+                None,
+                &ConversationSettings::default(),
+                "hello".to_string(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(message.conversation_id, conversation.id);
+        let messages = db.get_conversation_messages(conversation.id).await.unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].content, "hello");
+
+        // A failure leaves neither the conversation nor the message
+        let result = db
+            .start_conversation(
+                "Orphan".to_string(),
+                "claude".to_string(),
+                "model".to_string(),
+                Some(9999),
+                &ConversationSettings::default(),
+                "hello".to_string(),
+            )
+            .await;
+        assert!(result.is_err());
+        let page = db.list_conversations(10, 0).await.unwrap();
+        assert_eq!(page.total, 1);
+    }
+
+    #[tokio::test]
+    async fn test_add_exchange() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = open(&temp_dir).await;
+
+        let conversation = db
+            .create_conversation(
+                "Chat".to_string(),
+                "claude".to_string(),
+                "model".to_string(),
+                None,
+            )
+            .await
+            .unwrap();
+        let (message, reply) = db
+            .add_exchange(
+                conversation.id,
+                "question".to_string(),
+                "answer".to_string(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            (message.role.as_str(), reply.role.as_str()),
+            ("user", "assistant")
+        );
+
+        let messages = db.get_conversation_messages(conversation.id).await.unwrap();
+        let contents: Vec<&str> = messages.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, vec!["question", "answer"]);
+
+        assert!(db
+            .add_exchange(9999, "question".to_string(), "answer".to_string())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_replace_reply() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = open(&temp_dir).await;
+
+        let conversation = db
+            .create_conversation(
+                "Chat".to_string(),
+                "claude".to_string(),
+                "model".to_string(),
+                None,
+            )
+            .await
+            .unwrap();
+        let (_, reply) = db
+            .add_exchange(
+                conversation.id,
+                "question".to_string(),
+                "answer".to_string(),
+            )
+            .await
+            .unwrap();
+
+        let new_reply = db
+            .replace_reply(conversation.id, Some(reply.id), "better".to_string())
+            .await
+            .unwrap();
+        assert_eq!(new_reply.role, "assistant");
+
+        // The old reply is gone, so replacing it again must not add a second reply
+        let result = db
+            .replace_reply(conversation.id, Some(reply.id), "another".to_string())
+            .await;
+        assert!(matches!(result, Err(DatabaseError::MessageNotFound(id)) if id == reply.id));
+
+        let messages = db.get_conversation_messages(conversation.id).await.unwrap();
+        let contents: Vec<&str> = messages.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, vec!["question", "better"]);
+    }
+
+    #[tokio::test]
+    async fn test_edit_message_truncates_later_messages() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = open(&temp_dir).await;
+
+        let conversation = db
+            .create_conversation(
+                "Chat".to_string(),
+                "claude".to_string(),
+                "model".to_string(),
+                None,
+            )
+            .await
+            .unwrap();
+        let mut messages = Vec::new();
+        for content in ["question", "answer", "follow-up"] {
+            messages.push(
+                db.add_message(conversation.id, "user".to_string(), content.to_string())
+                    .await
+                    .unwrap(),
+            );
+        }
+
+        let edited = db
+            .edit_message(messages[0].id, "better question".to_string())
+            .await
+            .unwrap();
+        assert_eq!(edited.content, "better question");
+
+        let remaining = db.get_conversation_messages(conversation.id).await.unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].id, messages[0].id);
+
+        assert!(matches!(
+            db.edit_message(messages[2].id, "gone".to_string()).await,
+            Err(DatabaseError::MessageNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_conversation_trash() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = open(&temp_dir).await;
+
+        let conversation = db
+            .create_conversation(
+                "Chat".to_string(),
+                "claude".to_string(),
+                "model".to_string(),
+                None,
+            )
+            .await
+            .unwrap();
+        db.add_message(conversation.id, "user".to_string(), "hi".to_string())
+            .await
+            .unwrap();
+
+        db.soft_delete_conversation(conversation.id).await.unwrap();
+        assert_eq!(db.list_conversations(10, 0).await.unwrap().total, 0);
+        let trash = db.list_trashed_conversations(10, 0).await.unwrap();
+        assert_eq!(trash.total, 1);
+        assert!(trash.items[0].deleted_at.is_some());
+
+        let restored = db.restore_conversation(conversation.id).await.unwrap();
+        assert_eq!(restored.deleted_at, None);
+        assert_eq!(db.list_conversations(10, 0).await.unwrap().total, 1);
+        assert_eq!(
+            db.get_conversation_messages(conversation.id)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+
+        // Only conversations in the trash can be purged
+        assert!(matches!(
+            db.purge_conversation(conversation.id).await,
+            Err(DatabaseError::ConversationNotFound(_))
+        ));
+        assert!(db.get_conversation(conversation.id).await.is_ok());
+
+        db.soft_delete_conversation(conversation.id).await.unwrap();
+        db.purge_conversation(conversation.id).await.unwrap();
+        assert!(db.get_conversation(conversation.id).await.is_err());
+        assert!(matches!(
+            db.soft_delete_conversation(conversation.id).await,
+            Err(DatabaseError::ConversationNotFound(_))
+        ));
+    }
+
+    /// A two-node canvas whose first node is at x = `i`
+    fn canvas(i: i64) -> CanvasState {
+        let node = |id: &str, x: f64| CanvasNode {
+            id: id.to_string(),
+            node_type: "prompt".to_string(),
+            position: Position { x, y: 0.0 },
+            data: serde_json::json!({"template": id}),
+        };
+        CanvasState {
+            nodes: vec![node("a", i as f64), node("b", 0.0)],
+            edges: vec![CanvasEdge {
+                id: "e".to_string(),
+                source: "a".to_string(),
+                target: "b".to_string(),
+            }],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_save_canvas_writes_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = open(&temp_dir).await;
+        let project = db.create_project("Project".to_string()).await.unwrap();
+        assert_eq!(db.get_canvas_state(project.id).await.unwrap(), None);
+
+        db.save_canvas_state(project.id, &canvas(1)).await.unwrap();
+
+        // Drop node a (and with it the edge), add c, and reorder
+        let mut state = canvas(1);
+        state.nodes.remove(0);
+        state.edges.clear();
+        state.nodes.insert(
+            0,
+            CanvasNode {
+                id: "c".to_string(),
+                ..state.nodes[0].clone()
+            },
+        );
+        db.save_canvas_state(project.id, &state).await.unwrap();
+        assert_eq!(db.get_canvas_state(project.id).await.unwrap(), Some(state));
+
+        let edges: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM canvas_edges")
+            .fetch_one(db.pool())
+            .await
+            .unwrap();
+        assert_eq!(edges, 0);
+
+        // Deleting the project deletes its canvas rows
+        db.delete_project(project.id).await.unwrap();
+        let nodes: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM canvas_nodes")
+            .fetch_one(db.pool())
+            .await
+            .unwrap();
+        assert_eq!(nodes, 0);
+    }
+
+    #[tokio::test]
+    async fn test_migrates_canvas_json_to_rows() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = open(&temp_dir).await;
+        let project = db.create_project("Project".to_string()).await.unwrap();
+
+        // A canvas saved as JSON, before the step that moves it ran
+        sqlx::query("UPDATE projects SET canvas_state = ? WHERE id = ?")
+            .bind(serde_json::to_string(&canvas(3)).unwrap())
+            .bind(project.id)
+            .execute(db.pool())
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM schema_version WHERE version = 17")
+            .execute(db.pool())
+            .await
+            .unwrap();
+        drop(db);
+
+        let db = open(&temp_dir).await;
+        assert_eq!(
+            db.get_canvas_state(project.id).await.unwrap(),
+            Some(canvas(3))
+        );
+        let legacy: Option<String> =
+            sqlx::query_scalar("SELECT canvas_state FROM projects WHERE id = ?")
+                .bind(project.id)
+                .fetch_one(db.pool())
+                .await
+                .unwrap();
+        assert_eq!(legacy, None);
+    }
+
+    #[tokio::test]
+    async fn test_canvas_versions() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = open(&temp_dir).await;
+        let project = db.create_project("Project".to_string()).await.unwrap();
+
+        // The first save has no previous state to keep
+        db.save_canvas_state(project.id, &canvas(0)).await.unwrap();
+        assert!(db
+            .list_canvas_versions(project.id)
+            .await
+            .unwrap()
+            .is_empty());
+
+        for i in 1..=MAX_CANVAS_VERSIONS + 5 {
+            db.save_canvas_state(project.id, &canvas(i)).await.unwrap();
+        }
+        // Saving the same canvas again doesn't add a version
+        db.save_canvas_state(project.id, &canvas(MAX_CANVAS_VERSIONS + 5))
+            .await
+            .unwrap();
+        let versions = db.list_canvas_versions(project.id).await.unwrap();
+        assert_eq!(versions.len() as i64, MAX_CANVAS_VERSIONS);
+
+        // Newest version holds the state replaced by the last save
+        let (project_id, state) = db.restore_canvas_version(versions[0].id).await.unwrap();
+        assert_eq!(project_id, project.id);
+        assert_eq!(state, canvas(MAX_CANVAS_VERSIONS + 4));
+        assert_eq!(db.get_canvas_state(project.id).await.unwrap(), Some(state));
+
+        assert!(matches!(
+            db.restore_canvas_version(-1).await,
+            Err(DatabaseError::CanvasVersionNotFound(-1))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_list_conversations_for_project() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = open(&temp_dir).await;
+        let project = db.create_project("Project".to_string()).await.unwrap();
+        let other = db.create_project("Other".to_string()).await.unwrap();
+
+        let conversation = |title: &str, project_id: Option<i64>| {
+            db.create_conversation(
+                title.to_string(),
+                "claude".to_string(),
+                "model".to_string(),
+                project_id,
+            )
+        };
+        let older = conversation("Older", Some(project.id)).await.unwrap();
+        let newer = conversation("Newer", Some(project.id)).await.unwrap();
+        let trashed = conversation("Trashed", Some(project.id)).await.unwrap();
+        conversation("Elsewhere", Some(other.id)).await.unwrap();
+        conversation("Unfiled", None).await.unwrap();
+        db.soft_delete_conversation(trashed.id).await.unwrap();
+
+        // Most recently updated first
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        db.touch_conversation(older.id).await.unwrap();
+
+        let listed: Vec<i64> = db
+            .list_conversations_for_project(project.id)
+            .await
+            .unwrap()
+            .iter()
+            .map(|c| c.id)
+            .collect();
+        assert_eq!(listed, vec![older.id, newer.id]);
+        assert!(db
+            .list_conversations_for_project(-1)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_update_project_name_and_rename_document() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = open(&temp_dir).await;
+        let project = db.create_project("Draft".to_string()).await.unwrap();
+        let document = db
+            .create_document(project.id, "notes.txt".to_string(), None, None)
+            .await
+            .unwrap();
+
+        let renamed = db
+            .update_project_name(project.id, "Final".to_string())
+            .await
+            .unwrap();
+        assert_eq!(renamed.name, "Final");
+        assert_eq!(db.get_project(project.id).await.unwrap().name, "Final");
+
+        let renamed = db
+            .rename_document(document.id, "summary.txt".to_string())
+            .await
+            .unwrap();
+        assert_eq!(renamed.name, "summary.txt");
+        assert_eq!(renamed.project_id, project.id);
+
+        assert!(matches!(
+            db.update_project_name(-1, "Missing".to_string()).await,
+            Err(DatabaseError::ProjectNotFound(-1))
+        ));
+        assert!(matches!(
+            db.rename_document(-1, "missing.txt".to_string()).await,
+            Err(DatabaseError::DocumentNotFound(-1))
+        ));
     }
 }
@@ -1,5 +1,11 @@
 use crate::llm_providers::{LlmProvider, ProviderError};
-use std::sync::Arc;
+use futures::stream::{self, StreamExt};
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -18,14 +24,62 @@ pub struct BatchConfig {
     /// Default: 32 (good balance for most LLM APIs)
     /// For local GPU models, this can be much higher (128-512)
     pub batch_size: usize,
+    /// Batches in flight at once
+    /// Default: 4. A provider with `requests_per_minute` set still throttles them,
+    /// since every batch is one provider call.
+    pub max_concurrency: usize,
 }
 
 impl Default for BatchConfig {
     fn default() -> Self {
-        Self { batch_size: 32 }
+        Self {
+            batch_size: 32,
+            max_concurrency: 4,
+        }
+    }
+}
+
+/// Query embeddings kept for repeated searches
+const QUERY_CACHE_CAPACITY: usize = 256;
+
+/// How long a cached query embedding is reused
+const QUERY_CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+
+struct CachedQuery {
+    provider_id: String,
+    embedding: Vec<f32>,
+    cached_at: Instant,
+}
+
+/// Recent query embeddings, keyed by provider config, embedding model and query text,
+/// so re-running a search doesn't cost another embedding call
+fn query_cache() -> &'static Mutex<LruCache<String, CachedQuery>> {
+    static CACHE: OnceLock<Mutex<LruCache<String, CachedQuery>>> = OnceLock::new();
+    CACHE.get_or_init(|| {
+        Mutex::new(LruCache::new(
+            NonZeroUsize::new(QUERY_CACHE_CAPACITY).expect("capacity is non-zero"),
+        ))
+    })
+}
+
+/// Forget cached query embeddings of a provider, e.g. when its configuration changes
+pub fn invalidate_query_embeddings(provider_id: &str) {
+    let mut cache = query_cache().lock().unwrap_or_else(|e| e.into_inner());
+    let stale: Vec<String> = cache
+        .iter()
+        .filter(|(_, cached)| cached.provider_id == provider_id)
+        .map(|(key, _)| key.clone())
+        .collect();
+    for key in stale {
+        cache.pop(&key);
     }
 }
 
+/// Collapse runs of whitespace and trim, so trivially different queries share an embedding
+fn normalize_query(query: &str) -> String {
+    query.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
 pub struct EmbeddingService {
     provider: Arc<dyn LlmProvider>,
     batch_config: BatchConfig,
@@ -48,47 +102,146 @@ impl EmbeddingService {
         }
     }
 
+    /// Identifies the provider and embedding model, for keying cached embeddings
+    pub fn model_key(&self) -> String {
+        format!(
+            "{}/{}",
+            self.provider.id(),
+            self.provider.embedding_model().unwrap_or_default()
+        )
+    }
+
+    /// Length of the embeddings this service produces, if known before embedding anything
+    pub fn dimension(&self) -> Option<usize> {
+        self.provider.embedding_dimension()
+    }
+
     /// Generate embeddings for a list of texts with batch processing
     /// Optimized for high-memory environments (128GB+ RAM)
     /// Returns a vector of embeddings (one per input text)
     pub async fn embed_texts(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, EmbeddingError> {
+        self.embed_texts_with_progress(texts, |_, _| {}).await
+    }
+
+    /// Like `embed_texts`, calling `on_progress(done, total)` after each batch
+    pub async fn embed_texts_with_progress<F>(
+        &self,
+        texts: Vec<String>,
+        mut on_progress: F,
+    ) -> Result<Vec<Vec<f32>>, EmbeddingError>
+    where
+        F: FnMut(usize, usize) + Send,
+    {
         if texts.is_empty() {
             return Ok(Vec::new());
         }
 
         // For small batches, process directly
         if texts.len() <= self.batch_config.batch_size {
-            return Ok(self.provider.embed(texts).await?);
+            let total = texts.len();
+            let embeddings = self.provider.embed(texts).await?;
+            on_progress(total, total);
+            return Ok(embeddings);
         }
 
-        // For large batches, process in chunks to avoid overwhelming the API
-        let mut all_embeddings = Vec::with_capacity(texts.len());
+        // For large batches, process in chunks to avoid overwhelming the API.
+        // Batches may finish out of order, so each lands in the slot of its index.
+        // The futures are collected first so the stream holds no borrowing closure,
+        // which would keep callers' futures from being `Send`
+        let batches: Vec<_> = texts
+            .chunks(self.batch_config.batch_size)
+            .map(<[String]>::to_vec)
+            .enumerate()
+            .map(|(idx, batch)| {
+                let provider = self.provider.clone();
+                async move { (idx, provider.embed(batch).await) }
+            })
+            .collect();
+        let mut results =
+            stream::iter(batches).buffer_unordered(self.batch_config.max_concurrency.max(1));
+
+        let mut slots: Vec<Option<Vec<Vec<f32>>>> =
+            vec![None; texts.len().div_ceil(self.batch_config.batch_size)];
+        let mut done = 0;
 
-        for chunk in texts.chunks(self.batch_config.batch_size) {
-            let chunk_embeddings = self.provider.embed(chunk.to_vec()).await?;
-            all_embeddings.extend(chunk_embeddings);
+        while let Some((idx, result)) = results.next().await {
+            let chunk_embeddings = result?;
+            done += chunk_embeddings.len();
 
             tracing::debug!(
                 "Processed batch of {} embeddings, total: {}/{}",
-                chunk.len(),
-                all_embeddings.len(),
+                chunk_embeddings.len(),
+                done,
                 texts.len()
             );
+            slots[idx] = Some(chunk_embeddings);
+            on_progress(done, texts.len());
         }
 
-        Ok(all_embeddings)
+        Ok(slots.into_iter().flatten().flatten().collect())
     }
 
     /// Generate embedding for a single text
     pub async fn embed_text(&self, text: String) -> Result<Vec<f32>, EmbeddingError> {
         let mut embeddings = self.embed_texts(vec![text]).await?;
 
-        embeddings
-            .pop()
-            .ok_or(EmbeddingError::NoProviderConfigured)
+        embeddings.pop().ok_or(EmbeddingError::NoProviderConfigured)
+    }
+
+    /// Embed a search query, reusing the embedding of the same query made recently
+    /// through the same provider config (`provider_id`) and model
+    pub async fn embed_query(
+        &self,
+        provider_id: &str,
+        query: &str,
+    ) -> Result<Vec<f32>, EmbeddingError> {
+        let query = normalize_query(query);
+        let key = content_hash(&format!("{}/{}", provider_id, self.model_key()), &query);
+
+        {
+            let mut cache = query_cache().lock().unwrap_or_else(|e| e.into_inner());
+            match cache.get(&key) {
+                Some(cached) if cached.cached_at.elapsed() < QUERY_CACHE_TTL => {
+                    tracing::debug!("Using cached embedding for query");
+                    return Ok(cached.embedding.clone());
+                }
+                Some(_) => {
+                    cache.pop(&key);
+                }
+                None => {}
+            }
+        }
+
+        let embedding = self.embed_text(query).await?;
+
+        query_cache().lock().unwrap_or_else(|e| e.into_inner()).put(
+            key,
+            CachedQuery {
+                provider_id: provider_id.to_string(),
+                embedding: embedding.clone(),
+                cached_at: Instant::now(),
+            },
+        );
+
+        Ok(embedding)
     }
 }
 
+/// Embedding cache key for `text` embedded by `model`: hex SHA-256 of both,
+/// so the same text embedded by different models never shares an entry
+pub fn content_hash(model: &str, text: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(model.as_bytes());
+    hasher.update([0]);
+    hasher.update(text.as_bytes());
+
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
 /// Compute cosine similarity between two vectors
 /// Optimized for high-memory systems with vectorized operations
 /// For GPU acceleration, consider using libraries like:
@@ -119,6 +272,87 @@ pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
     dot_product / (magnitude_a * magnitude_b)
 }
 
+/// Dot product of two vectors (0.0 if the dimensions differ)
+pub fn dot_product(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+
+    a.iter().zip(b.iter()).map(|(x, y)| x * y).sum()
+}
+
+/// Euclidean (L2) norm of a vector
+pub fn l2_norm(v: &[f32]) -> f32 {
+    v.iter().map(|x| x * x).sum::<f32>().sqrt()
+}
+
+/// Cosine similarity using precomputed norms
+/// Fast path for search: stored chunk norms never change and the query norm is computed
+/// once per query, so each comparison is a single dot product
+pub fn cosine_similarity_with_norms(a: &[f32], b: &[f32], norm_a: f32, norm_b: f32) -> f32 {
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+
+    dot_product(a, b) / (norm_a * norm_b)
+}
+
+/// Euclidean (L2) distance between two vectors (infinite if the dimensions differ)
+pub fn euclidean_distance(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return f32::INFINITY;
+    }
+
+    a.iter()
+        .zip(b.iter())
+        .map(|(x, y)| (x - y) * (x - y))
+        .sum::<f32>()
+        .sqrt()
+}
+
+/// How vectors are compared during search
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DistanceMetric {
+    #[default]
+    Cosine,
+    DotProduct,
+    Euclidean,
+}
+
+impl DistanceMetric {
+    /// Parse a metric name ("cosine", "dot_product", "euclidean")
+    pub fn parse(metric: &str) -> Option<Self> {
+        match metric {
+            "cosine" => Some(DistanceMetric::Cosine),
+            "dot_product" => Some(DistanceMetric::DotProduct),
+            "euclidean" => Some(DistanceMetric::Euclidean),
+            _ => None,
+        }
+    }
+
+    /// Score a vector against the query; higher is always better
+    /// Euclidean distance is negated so every metric ranks in the same direction.
+    /// Norms are precomputed (see `l2_norm`), so each metric costs one dot product.
+    pub fn score(&self, query: &[f32], query_norm: f32, vector: &[f32], vector_norm: f32) -> f32 {
+        match self {
+            DistanceMetric::Cosine => {
+                cosine_similarity_with_norms(query, vector, query_norm, vector_norm)
+            }
+            DistanceMetric::DotProduct => dot_product(query, vector),
+            DistanceMetric::Euclidean => {
+                if query.len() != vector.len() {
+                    return f32::NEG_INFINITY;
+                }
+                // |q - v|^2 = |q|^2 + |v|^2 - 2 q.v
+                let squared = query_norm * query_norm + vector_norm * vector_norm
+                    - 2.0 * dot_product(query, vector);
+                -squared.max(0.0).sqrt()
+            }
+        }
+    }
+}
+
 /// Batch compute cosine similarities between a query and multiple vectors
 /// Optimized for high-memory systems - processes all similarities in parallel
 pub fn batch_cosine_similarity(query: &[f32], vectors: &[Vec<f32>]) -> Vec<f32> {
@@ -131,6 +365,8 @@ pub fn batch_cosine_similarity(query: &[f32], vectors: &[Vec<f32>]) -> Vec<f32>
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::llm_providers::mock::{MockProvider, MOCK_EMBEDDING_DIM};
+    use crate::llm_providers::offline::hash_embedding;
 
     #[test]
     fn test_cosine_similarity_identical() {
@@ -155,4 +391,122 @@ mod tests {
         let similarity = cosine_similarity(&a, &b);
         assert!((similarity + 1.0).abs() < 0.001);
     }
+
+    #[test]
+    fn test_cosine_with_norms_matches_cosine() {
+        let a = vec![1.0, 2.0, 3.0];
+        let b = vec![4.0, 5.0, 6.0];
+        let fast = cosine_similarity_with_norms(&a, &b, l2_norm(&a), l2_norm(&b));
+        assert!((fast - cosine_similarity(&a, &b)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_dot_product_and_norm() {
+        assert_eq!(dot_product(&[1.0, 2.0], &[3.0, 4.0]), 11.0);
+        assert_eq!(dot_product(&[1.0], &[1.0, 2.0]), 0.0);
+        assert!((l2_norm(&[3.0, 4.0]) - 5.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_metrics_rank_higher_is_better() {
+        let query = vec![1.0, 0.0];
+        let near = vec![2.0, 0.1];
+        let far = vec![-1.0, 0.5];
+
+        for metric in [
+            DistanceMetric::Cosine,
+            DistanceMetric::DotProduct,
+            DistanceMetric::Euclidean,
+        ] {
+            let score = |v: &[f32]| metric.score(&query, l2_norm(&query), v, l2_norm(v));
+            assert!(score(&near) > score(&far), "{:?}", metric);
+        }
+    }
+
+    #[test]
+    fn test_euclidean_score_matches_distance() {
+        let a = vec![1.0, 2.0, 3.0];
+        let b = vec![4.0, 6.0, 3.0];
+        let score = DistanceMetric::Euclidean.score(&a, l2_norm(&a), &b, l2_norm(&b));
+        assert!((score + euclidean_distance(&a, &b)).abs() < 1e-4);
+        assert!((euclidean_distance(&a, &b) - 5.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_parse_metric() {
+        assert_eq!(
+            DistanceMetric::parse("dot_product"),
+            Some(DistanceMetric::DotProduct)
+        );
+        assert_eq!(DistanceMetric::parse("Cosine"), None);
+    }
+
+    #[test]
+    fn test_content_hash_keyed_by_model() {
+        let hash = content_hash("gemini/embedding-001", "text");
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, content_hash("gemini/embedding-001", "text"));
+        assert_ne!(hash, content_hash("gemini/text-embedding-004", "text"));
+        assert_ne!(hash, content_hash("gemini/embedding-001", "other"));
+    }
+
+    #[tokio::test]
+    async fn test_query_embeddings_are_cached_per_provider() {
+        let provider = Arc::new(MockProvider::new());
+        let service = EmbeddingService::new(provider.clone());
+
+        let first = service
+            .embed_query("query-cache-a", "what is  rust?")
+            .await
+            .unwrap();
+        let repeated = service
+            .embed_query("query-cache-a", " what is rust? ")
+            .await
+            .unwrap();
+        assert_eq!(first, repeated);
+        assert_eq!(provider.calls(), 1);
+
+        // Another provider config embeds separately
+        service
+            .embed_query("query-cache-b", "what is rust?")
+            .await
+            .unwrap();
+        assert_eq!(provider.calls(), 2);
+
+        // Invalidated, the query is embedded again
+        invalidate_query_embeddings("query-cache-a");
+        service
+            .embed_query("query-cache-a", "what is rust?")
+            .await
+            .unwrap();
+        assert_eq!(provider.calls(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_batches_keep_order() {
+        let provider = Arc::new(MockProvider::new());
+        let service = EmbeddingService::with_batch_config(
+            provider.clone(),
+            BatchConfig {
+                batch_size: 3,
+                max_concurrency: 4,
+            },
+        );
+        let texts: Vec<String> = (0..20).map(|i| format!("text{}", i)).collect();
+
+        let mut progress = Vec::new();
+        let embeddings = service
+            .embed_texts_with_progress(texts.clone(), |done, total| progress.push((done, total)))
+            .await
+            .unwrap();
+
+        let expected: Vec<Vec<f32>> = texts
+            .iter()
+            .map(|text| hash_embedding(text, MOCK_EMBEDDING_DIM))
+            .collect();
+        assert_eq!(embeddings, expected);
+        assert_eq!(provider.calls(), 7);
+        assert_eq!(progress.len(), 7);
+        assert_eq!(progress.last(), Some(&(20, 20)));
+    }
 }
@@ -0,0 +1,196 @@
+//! Portable project archives: a project with its documents and embedded chunks,
+//! so a knowledge base can move between machines without re-embedding
+
+use super::database::DatabaseError;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+/// Version of the archive layout written by `RagDatabase::export_project`
+pub const EXPORT_FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectExport {
+    pub format_version: u32,
+    pub name: String,
+    #[serde(default)]
+    pub canvas_state: Option<String>,
+    /// Dimension every chunk embedding has; None for a project without chunks
+    #[serde(default)]
+    pub embedding_dim: Option<usize>,
+    /// Provider and embedding model ("provider/model") the chunks were embedded with
+    #[serde(default)]
+    pub embedding_model: Option<String>,
+    pub documents: Vec<DocumentExport>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentExport {
+    pub name: String,
+    #[serde(default)]
+    pub source_path: Option<String>,
+    #[serde(default)]
+    pub content: Option<String>,
+    pub chunks: Vec<ChunkExport>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkExport {
+    pub chunk_index: i32,
+    pub content: String,
+    /// The stored embedding blob, base64-encoded so it round-trips byte for byte
+    pub embedding: String,
+    /// Source page, for chunks of paginated documents
+    #[serde(default)]
+    pub page_number: Option<i32>,
+    /// Character range in the document text, when recorded
+    #[serde(default)]
+    pub start_offset: Option<i64>,
+    #[serde(default)]
+    pub end_offset: Option<i64>,
+}
+
+impl ChunkExport {
+    pub fn new(chunk_index: i32, content: String, embedding_blob: &[u8]) -> Self {
+        Self {
+            chunk_index,
+            content,
+            embedding: BASE64.encode(embedding_blob),
+            page_number: None,
+            start_offset: None,
+            end_offset: None,
+        }
+    }
+
+    pub fn with_page_number(mut self, page_number: Option<i32>) -> Self {
+        self.page_number = page_number;
+        self
+    }
+
+    pub fn with_offsets(mut self, start_offset: Option<i64>, end_offset: Option<i64>) -> Self {
+        self.start_offset = start_offset;
+        self.end_offset = end_offset;
+        self
+    }
+
+    /// The embedding blob and the vector it holds
+    pub fn decode_embedding(&self) -> Result<(Vec<u8>, Vec<f32>), DatabaseError> {
+        let blob = BASE64
+            .decode(&self.embedding)
+            .map_err(|e| DatabaseError::InvalidExport(format!("bad embedding encoding: {}", e)))?;
+        let embedding: Vec<f32> = bincode::deserialize(&blob)
+            .map_err(|e| DatabaseError::InvalidExport(format!("bad embedding: {}", e)))?;
+        Ok((blob, embedding))
+    }
+}
+
+impl ProjectExport {
+    /// Check the archive can be imported: a known format and every embedding of `embedding_dim`
+    pub fn validate(&self) -> Result<(), DatabaseError> {
+        if self.format_version != EXPORT_FORMAT_VERSION {
+            return Err(DatabaseError::InvalidExport(format!(
+                "unsupported format version {}",
+                self.format_version
+            )));
+        }
+        if self.name.trim().is_empty() {
+            return Err(DatabaseError::InvalidExport(
+                "project name is empty".to_string(),
+            ));
+        }
+
+        let has_chunks = self.documents.iter().any(|d| !d.chunks.is_empty());
+        let expected = match (self.embedding_dim, has_chunks) {
+            (Some(dim), true) => dim,
+            (None, true) => {
+                return Err(DatabaseError::InvalidExport(
+                    "embedding_dim is missing".to_string(),
+                ))
+            }
+            (_, false) => return Ok(()),
+        };
+
+        for chunk in self.documents.iter().flat_map(|d| &d.chunks) {
+            let (_, embedding) = chunk.decode_embedding()?;
+            if embedding.len() != expected {
+                return Err(DatabaseError::DimensionMismatch {
+                    expected,
+                    got: embedding.len(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn export_with(embeddings: &[Vec<f32>], embedding_dim: Option<usize>) -> ProjectExport {
+        let chunks = embeddings
+            .iter()
+            .enumerate()
+            .map(|(i, e)| {
+                ChunkExport::new(
+                    i as i32,
+                    format!("chunk {}", i),
+                    &bincode::serialize(e).unwrap(),
+                )
+            })
+            .collect();
+        ProjectExport {
+            format_version: EXPORT_FORMAT_VERSION,
+            name: "Project".to_string(),
+            canvas_state: None,
+            embedding_dim,
+            embedding_model: None,
+            documents: vec![DocumentExport {
+                name: "Doc".to_string(),
+                source_path: None,
+                content: None,
+                chunks,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_embedding_roundtrips_exactly() {
+        let embedding = vec![0.1f32, -2.5e-7, f32::MAX];
+        let blob = bincode::serialize(&embedding).unwrap();
+        let chunk = ChunkExport::new(0, "text".to_string(), &blob);
+
+        let (decoded_blob, decoded) = chunk.decode_embedding().unwrap();
+        assert_eq!(decoded_blob, blob);
+        assert_eq!(decoded, embedding);
+    }
+
+    #[test]
+    fn test_validate_rejects_mixed_dimensions() {
+        assert!(export_with(&[vec![1.0, 0.0], vec![0.0, 1.0]], Some(2))
+            .validate()
+            .is_ok());
+        assert!(matches!(
+            export_with(&[vec![1.0, 0.0], vec![1.0]], Some(2)).validate(),
+            Err(DatabaseError::DimensionMismatch {
+                expected: 2,
+                got: 1
+            })
+        ));
+        assert!(matches!(
+            export_with(&[vec![1.0]], None).validate(),
+            Err(DatabaseError::InvalidExport(_))
+        ));
+    }
+
+    #[test]
+    fn test_validate_rejects_unknown_version() {
+        let mut export = export_with(&[], None);
+        export.format_version = EXPORT_FORMAT_VERSION + 1;
+        assert!(matches!(
+            export.validate(),
+            Err(DatabaseError::InvalidExport(_))
+        ));
+    }
+}
@@ -0,0 +1,187 @@
+//! Text extraction from document files, so the frontend can hand over a path
+//! instead of the text itself
+
+use super::chunking::PAGE_SEPARATOR;
+use crate::validation::strip_control_chars;
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ExtractError {
+    #[error("Failed to read file: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Failed to extract text from PDF: {0}")]
+    Pdf(String),
+
+    #[error("File is neither a PDF nor UTF-8 text")]
+    NotText,
+
+    #[error("File is larger than {max_bytes} bytes")]
+    TooLarge { max_bytes: u64 },
+}
+
+/// How much bigger than the text limit a PDF file may be, since most of a PDF is
+/// layout, fonts and images rather than text
+const PDF_SIZE_FACTOR: u64 = 10;
+
+/// Whether the file is a PDF, going by its extension
+pub fn is_pdf(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("pdf"))
+}
+
+/// The text of a document file, with control characters stripped
+/// A PDF's pages are joined with `PAGE_SEPARATOR` so chunks can keep their page number;
+/// any other file is read as UTF-8 text. Files over `max_bytes` (`PDF_SIZE_FACTOR` times
+/// that for PDFs) are refused before being read. Blocks on file I/O and PDF parsing.
+/// pdf-extract panics on some malformed PDFs, so a panic while parsing is caught and
+/// reported as `ExtractError::Pdf`.
+pub fn extract_file_text(path: &Path, max_bytes: usize) -> Result<String, ExtractError> {
+    let max_bytes = if is_pdf(path) {
+        max_bytes as u64 * PDF_SIZE_FACTOR
+    } else {
+        max_bytes as u64
+    };
+    if std::fs::metadata(path)?.len() > max_bytes {
+        return Err(ExtractError::TooLarge { max_bytes });
+    }
+    let bytes = std::fs::read(path)?;
+
+    if is_pdf(path) {
+        let pages =
+            std::panic::catch_unwind(|| pdf_extract::extract_text_from_mem_by_pages(&bytes))
+                .map_err(|_| ExtractError::Pdf("the PDF is malformed".to_string()))?
+                .map_err(|e| ExtractError::Pdf(e.to_string()))?;
+        // A separator inside a page would shift the page numbers of the rest
+        return Ok(pages
+            .iter()
+            .map(|page| strip_control_chars(page).replace(PAGE_SEPARATOR, "\n"))
+            .collect::<Vec<_>>()
+            .join(&PAGE_SEPARATOR.to_string()));
+    }
+
+    let text = String::from_utf8(bytes).map_err(|_| ExtractError::NotText)?;
+    Ok(strip_control_chars(&text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_is_pdf() {
+        assert!(is_pdf(Path::new("paper.pdf")));
+        assert!(is_pdf(Path::new("/docs/Paper.PDF")));
+        assert!(!is_pdf(Path::new("notes.txt")));
+        assert!(!is_pdf(Path::new("pdf")));
+    }
+
+    #[test]
+    fn test_extract_text_file() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("notes.md");
+        std::fs::write(&path, "# Notes\n\u{0}body").unwrap();
+        assert_eq!(extract_file_text(&path, 100).unwrap(), "# Notes\nbody");
+        assert!(matches!(
+            extract_file_text(&path, 5),
+            Err(ExtractError::TooLarge { max_bytes: 5 })
+        ));
+
+        let binary = dir.path().join("image.png");
+        std::fs::write(&binary, [0x89, 0x50, 0xff, 0xfe]).unwrap();
+        assert!(matches!(
+            extract_file_text(&binary, 100),
+            Err(ExtractError::NotText)
+        ));
+    }
+
+    /// A well-formed PDF with one page of text set in `font`
+    fn pdf_with_font(font: &str) -> Vec<u8> {
+        let content = "BT /F1 12 Tf 72 720 Td (Hello) Tj ET";
+        let objects = [
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 612 792] \
+             /Resources << /Font << /F1 4 0 R >> >> /Contents 5 0 R >>"
+                .to_string(),
+            font.to_string(),
+            format!(
+                "<< /Length {} >>\nstream\n{}\nendstream",
+                content.len(),
+                content
+            ),
+        ];
+
+        let mut pdf = b"%PDF-1.4\n".to_vec();
+        let mut offsets = Vec::new();
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.extend(format!("{} 0 obj\n{}\nendobj\n", i + 1, object).bytes());
+        }
+        let xref = pdf.len();
+        pdf.extend(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).bytes());
+        for offset in offsets {
+            pdf.extend(format!("{:010} 00000 n \n", offset).bytes());
+        }
+        pdf.extend(
+            format!(
+                "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+                objects.len() + 1,
+                xref
+            )
+            .bytes(),
+        );
+        pdf
+    }
+
+    #[test]
+    fn test_extract_pdf() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("hello.pdf");
+        std::fs::write(
+            &path,
+            pdf_with_font("<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>"),
+        )
+        .unwrap();
+        assert!(extract_file_text(&path, 1000).unwrap().contains("Hello"));
+    }
+
+    #[test]
+    fn test_extract_corrupt_pdf() {
+        let dir = TempDir::new().unwrap();
+        let truncated = dir.path().join("truncated.pdf");
+        std::fs::write(
+            &truncated,
+            b"%PDF-1.4\n1 0 obj\n<< /Type /Catalog /Pages 2 0 R",
+        )
+        .unwrap();
+        assert!(matches!(
+            extract_file_text(&truncated, 1000),
+            Err(ExtractError::Pdf(_))
+        ));
+
+        // pdf-extract panics on an encoding it doesn't know
+        let bad_font = dir.path().join("bad_font.pdf");
+        std::fs::write(
+            &bad_font,
+            pdf_with_font(
+                "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /NoSuchEncoding >>",
+            ),
+        )
+        .unwrap();
+        assert!(matches!(
+            extract_file_text(&bad_font, 1000),
+            Err(ExtractError::Pdf(_))
+        ));
+
+        let garbage = dir.path().join("garbage.pdf");
+        std::fs::write(&garbage, [0x25, 0x50, 0x44, 0x46, 0x00, 0xff, 0x13, 0x37]).unwrap();
+        assert!(matches!(
+            extract_file_text(&garbage, 1000),
+            Err(ExtractError::Pdf(_))
+        ));
+    }
+}
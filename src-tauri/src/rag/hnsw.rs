@@ -0,0 +1,975 @@
+//! Approximate nearest-neighbor search over a project's chunks with an HNSW graph
+//! (hierarchical navigable small world, Malkov & Yashunin 2018)
+//!
+//! Exact search scores every chunk, which stops being interactive somewhere past
+//! ~100k chunks. Large projects get a per-project index instead, kept in memory,
+//! persisted next to the database and updated as chunks are inserted or removed.
+//! The index only ranks by cosine similarity; other metrics always scan.
+//!
+//! The persisted index records the highest chunk id it contains. Chunk ids only grow
+//! (AUTOINCREMENT), so chunks added while the index wasn't loaded are caught up from
+//! the database, and removed ones are reconciled when the index is loaded.
+
+use super::database::{ChunkEmbedding, DatabaseError, RagDatabase};
+use super::embeddings::{dot_product, l2_norm};
+use futures::StreamExt;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+
+/// Projects with fewer chunks are searched exactly; below this a scan is fast enough
+pub const INDEX_MIN_CHUNKS: i64 = 20_000;
+
+/// Neighbors kept per node on the upper layers (twice this on the bottom layer)
+const DEFAULT_M: usize = 16;
+
+/// Candidate list size while inserting; higher builds a better graph, more slowly
+const DEFAULT_EF_CONSTRUCTION: usize = 200;
+
+/// Candidate list size while searching, unless the request sets one.
+/// Higher improves recall at the cost of latency; it is never below `top_k`.
+pub const DEFAULT_EF_SEARCH: usize = 64;
+
+/// Chunks inserted per blocking task while building an index
+const BUILD_BATCH_SIZE: usize = 4096;
+
+/// Changes after which `save_indexes` rewrites an index file; smaller change sets are
+/// cheaper to catch up from the database on the next load than to persist
+const SAVE_AFTER_CHANGES: usize = 10_000;
+
+/// Share of removed nodes above which an index is rebuilt without them. Removed nodes
+/// still cost memory and search time, since searches traverse them.
+const MAX_REMOVED_FRACTION: f64 = 0.25;
+
+/// A similarity paired with a node, ordered by similarity
+#[derive(Debug, Clone, Copy)]
+struct Scored(f32, u32);
+
+impl PartialEq for Scored {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Scored {}
+
+impl PartialOrd for Scored {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scored {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0).then(self.1.cmp(&other.1))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Node {
+    chunk_id: i64,
+    /// Unit length, so the dot product is the cosine similarity
+    vector: Vec<f32>,
+    /// Neighbor lists, one per layer the node is on (layer 0 first)
+    layers: Vec<Vec<u32>>,
+    /// Removed chunks stay in the graph for navigation but are never returned
+    deleted: bool,
+}
+
+/// An HNSW graph over unit-length embeddings, keyed by chunk id
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HnswIndex {
+    m: usize,
+    ef_construction: usize,
+    dimension: usize,
+    nodes: Vec<Node>,
+    positions: HashMap<i64, u32>,
+    entry_point: Option<u32>,
+    /// Highest chunk id ever inserted
+    max_chunk_id: i64,
+    /// Inserts and removals since the index was last saved or loaded
+    #[serde(skip)]
+    changes: usize,
+}
+
+impl HnswIndex {
+    pub fn new(dimension: usize) -> Self {
+        Self {
+            m: DEFAULT_M,
+            ef_construction: DEFAULT_EF_CONSTRUCTION,
+            dimension,
+            nodes: Vec::new(),
+            positions: HashMap::new(),
+            entry_point: None,
+            max_chunk_id: 0,
+            changes: 0,
+        }
+    }
+
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    /// Number of chunks that can be returned (removed ones excluded)
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    pub fn max_chunk_id(&self) -> i64 {
+        self.max_chunk_id
+    }
+
+    /// Chunk ids currently in the index
+    pub fn chunk_ids(&self) -> impl Iterator<Item = i64> + '_ {
+        self.positions.keys().copied()
+    }
+
+    fn similarity(&self, query: &[f32], node: u32) -> f32 {
+        dot_product(query, &self.nodes[node as usize].vector)
+    }
+
+    /// Add a chunk, replacing it if already present
+    pub fn insert(&mut self, chunk_id: i64, embedding: &[f32]) -> Result<(), DatabaseError> {
+        if embedding.len() != self.dimension {
+            return Err(DatabaseError::DimensionMismatch {
+                expected: self.dimension,
+                got: embedding.len(),
+            });
+        }
+        self.remove(chunk_id);
+
+        let vector = normalize(embedding);
+        let level = self.random_level();
+        let id = self.nodes.len() as u32;
+        self.nodes.push(Node {
+            chunk_id,
+            vector,
+            layers: vec![Vec::new(); level + 1],
+            deleted: false,
+        });
+        self.positions.insert(chunk_id, id);
+        self.max_chunk_id = self.max_chunk_id.max(chunk_id);
+        self.changes += 1;
+
+        let Some(entry_point) = self.entry_point else {
+            self.entry_point = Some(id);
+            return Ok(());
+        };
+
+        let query = self.nodes[id as usize].vector.clone();
+        let top_level = self.nodes[entry_point as usize].layers.len() - 1;
+
+        // Greedy descent through the layers above the new node's
+        let mut entry_points = vec![entry_point];
+        for layer in (level + 1..=top_level).rev() {
+            entry_points = self.closest(&query, &entry_points, layer);
+        }
+
+        for layer in (0..=level.min(top_level)).rev() {
+            let candidates =
+                self.search_layer(&query, &entry_points, self.ef_construction, layer, false);
+            let neighbors = self.select_neighbors(&candidates, self.m);
+            self.nodes[id as usize].layers[layer] = neighbors.clone();
+
+            let max_connections = if layer == 0 { self.m * 2 } else { self.m };
+            for neighbor in neighbors {
+                self.nodes[neighbor as usize].layers[layer].push(id);
+                if self.nodes[neighbor as usize].layers[layer].len() > max_connections {
+                    self.prune(neighbor, layer, max_connections);
+                }
+            }
+
+            entry_points = candidates.iter().map(|scored| scored.1).collect();
+        }
+
+        if level > top_level {
+            self.entry_point = Some(id);
+        }
+        Ok(())
+    }
+
+    /// Stop returning a chunk; returns whether it was present
+    pub fn remove(&mut self, chunk_id: i64) -> bool {
+        match self.positions.remove(&chunk_id) {
+            Some(id) => {
+                self.nodes[id as usize].deleted = true;
+                self.changes += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Whether enough nodes were removed that the graph should be rebuilt without them
+    fn needs_compaction(&self) -> bool {
+        let removed = self.nodes.len() - self.positions.len();
+        removed > 0 && removed as f64 > self.nodes.len() as f64 * MAX_REMOVED_FRACTION
+    }
+
+    /// Rebuild the graph from the chunks still in it, if enough were removed
+    fn compact_if_needed(&mut self) {
+        if !self.needs_compaction() {
+            return;
+        }
+
+        let mut compacted = HnswIndex {
+            m: self.m,
+            ef_construction: self.ef_construction,
+            ..HnswIndex::new(self.dimension)
+        };
+        for node in self.nodes.iter().filter(|node| !node.deleted) {
+            compacted
+                .insert(node.chunk_id, &node.vector)
+                .expect("nodes share the index dimension");
+        }
+        // Chunks above the highest live id may have been inserted and removed since;
+        // lowering the mark would have them caught up from the database again
+        compacted.max_chunk_id = self.max_chunk_id;
+        // A rewrite is worth persisting, or the next load would compact again
+        compacted.changes = self.changes.max(SAVE_AFTER_CHANGES);
+        *self = compacted;
+    }
+
+    /// Approximately the `k` chunks most similar to `query`, as (cosine similarity, chunk id)
+    /// sorted by similarity. `ef` is the candidate list size (raised to at least `k`).
+    pub fn search(&self, query: &[f32], k: usize, ef: usize) -> Vec<(f32, i64)> {
+        let Some(entry_point) = self.entry_point else {
+            return Vec::new();
+        };
+        if k == 0 || query.len() != self.dimension {
+            return Vec::new();
+        }
+
+        let query = normalize(query);
+        let top_level = self.nodes[entry_point as usize].layers.len() - 1;
+
+        let mut entry_points = vec![entry_point];
+        for layer in (1..=top_level).rev() {
+            entry_points = self.closest(&query, &entry_points, layer);
+        }
+
+        let mut results = self.search_layer(&query, &entry_points, ef.max(k), 0, true);
+        results.truncate(k);
+        results
+            .into_iter()
+            .map(|Scored(similarity, id)| (similarity, self.nodes[id as usize].chunk_id))
+            .collect()
+    }
+
+    /// The single closest node on `layer`, starting from `entry_points`
+    fn closest(&self, query: &[f32], entry_points: &[u32], layer: usize) -> Vec<u32> {
+        self.search_layer(query, entry_points, 1, layer, false)
+            .first()
+            .map(|scored| vec![scored.1])
+            .unwrap_or_else(|| entry_points.to_vec())
+    }
+
+    /// Best-first search of one layer, returning up to `ef` nodes sorted by similarity.
+    /// With `live_only`, removed nodes are traversed but not returned.
+    fn search_layer(
+        &self,
+        query: &[f32],
+        entry_points: &[u32],
+        ef: usize,
+        layer: usize,
+        live_only: bool,
+    ) -> Vec<Scored> {
+        let mut visited: HashSet<u32> = HashSet::new();
+        let mut candidates: BinaryHeap<Scored> = BinaryHeap::new();
+        let mut results: BinaryHeap<Reverse<Scored>> = BinaryHeap::new();
+
+        for &entry_point in entry_points {
+            if visited.insert(entry_point) {
+                let scored = Scored(self.similarity(query, entry_point), entry_point);
+                candidates.push(scored);
+                if !(live_only && self.nodes[entry_point as usize].deleted) {
+                    results.push(Reverse(scored));
+                }
+            }
+        }
+
+        while let Some(candidate) = candidates.pop() {
+            let worst = results.peek().map_or(f32::NEG_INFINITY, |r| r.0 .0);
+            if results.len() >= ef && candidate.0 < worst {
+                break;
+            }
+
+            let Some(neighbors) = self.nodes[candidate.1 as usize].layers.get(layer) else {
+                continue;
+            };
+            for &neighbor in neighbors {
+                if !visited.insert(neighbor) {
+                    continue;
+                }
+                let similarity = self.similarity(query, neighbor);
+                let worst = results.peek().map_or(f32::NEG_INFINITY, |r| r.0 .0);
+                if results.len() < ef || similarity > worst {
+                    let scored = Scored(similarity, neighbor);
+                    candidates.push(scored);
+                    if !(live_only && self.nodes[neighbor as usize].deleted) {
+                        results.push(Reverse(scored));
+                        if results.len() > ef {
+                            results.pop();
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut results: Vec<Scored> = results.into_iter().map(|r| r.0).collect();
+        results.sort_unstable_by(|a, b| b.cmp(a));
+        results
+    }
+
+    /// Pick up to `m` of the (similarity-sorted) candidates, preferring ones that are
+    /// closer to the query than to an already picked neighbor, so the graph keeps
+    /// links in every direction instead of clustering them
+    fn select_neighbors(&self, candidates: &[Scored], m: usize) -> Vec<u32> {
+        let mut selected: Vec<u32> = Vec::with_capacity(m);
+        let mut skipped: Vec<u32> = Vec::new();
+
+        for &Scored(similarity, candidate) in candidates {
+            if selected.len() >= m {
+                break;
+            }
+            let vector = &self.nodes[candidate as usize].vector;
+            let diverse = selected
+                .iter()
+                .all(|&picked| self.similarity(vector, picked) < similarity);
+            if diverse {
+                selected.push(candidate);
+            } else {
+                skipped.push(candidate);
+            }
+        }
+
+        // Fill remaining slots with the best of the skipped candidates
+        let missing = m.saturating_sub(selected.len());
+        selected.extend(skipped.into_iter().take(missing));
+        selected
+    }
+
+    /// Cut a node's neighbor list on `layer` back to `max_connections`
+    fn prune(&mut self, node: u32, layer: usize, max_connections: usize) {
+        let vector = self.nodes[node as usize].vector.clone();
+        let mut scored: Vec<Scored> = self.nodes[node as usize].layers[layer]
+            .iter()
+            .map(|&neighbor| Scored(self.similarity(&vector, neighbor), neighbor))
+            .collect();
+        scored.sort_unstable_by(|a, b| b.cmp(a));
+
+        let kept = self.select_neighbors(&scored, max_connections);
+        self.nodes[node as usize].layers[layer] = kept;
+    }
+
+    /// Layer for a new node: geometrically distributed, so each layer up has ~1/m the nodes
+    fn random_level(&self) -> usize {
+        let uniform: f64 = rand::thread_rng().gen_range(f64::EPSILON..1.0);
+        let level_multiplier = 1.0 / (self.m as f64).ln();
+        (-uniform.ln() * level_multiplier).floor() as usize
+    }
+
+    /// Write the index to `path`, replacing any previous file atomically
+    fn save(&self, path: &Path) -> Result<(), DatabaseError> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let temp_path = path.with_extension("tmp");
+        let file = std::io::BufWriter::new(std::fs::File::create(&temp_path)?);
+        bincode::serialize_into(file, self)
+            .map_err(|e| DatabaseError::SerializationError(e.to_string()))?;
+        std::fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+
+    /// Read an index written by `save`, or None if there is none
+    fn load(path: &Path) -> Result<Option<Self>, DatabaseError> {
+        let file = match std::fs::File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        bincode::deserialize_from(std::io::BufReader::new(file))
+            .map(Some)
+            .map_err(|e| DatabaseError::SerializationError(e.to_string()))
+    }
+}
+
+/// Scale to unit length; the zero vector is left as is
+fn normalize(vector: &[f32]) -> Vec<f32> {
+    let norm = l2_norm(vector);
+    if norm == 0.0 {
+        return vector.to_vec();
+    }
+    vector.iter().map(|x| x / norm).collect()
+}
+
+/// The loaded indexes of a database's projects, and where they are persisted
+pub struct ProjectIndexes {
+    dir: PathBuf,
+    loaded: Mutex<HashMap<i64, Arc<RwLock<HnswIndex>>>>,
+    /// Projects whose index is being built in the background
+    building: Mutex<HashSet<i64>>,
+}
+
+impl ProjectIndexes {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            loaded: Mutex::new(HashMap::new()),
+            building: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Directory the index files are kept in
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, project_id: i64) -> PathBuf {
+        self.dir.join(format!("project_{}.hnsw", project_id))
+    }
+
+    fn get(&self, project_id: i64) -> Option<Arc<RwLock<HnswIndex>>> {
+        self.loaded
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&project_id)
+            .cloned()
+    }
+
+    fn install(&self, project_id: i64, index: HnswIndex) -> Arc<RwLock<HnswIndex>> {
+        let index = Arc::new(RwLock::new(index));
+        self.loaded
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(project_id, index.clone());
+        index
+    }
+
+    /// Mark a project's index as being built; false if a build is already running
+    fn start_building(&self, project_id: i64) -> bool {
+        self.building
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(project_id)
+    }
+
+    fn finish_building(&self, project_id: i64) {
+        self.building
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&project_id);
+    }
+
+    fn is_building(&self, project_id: i64) -> bool {
+        self.building
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .contains(&project_id)
+    }
+
+    /// Add newly stored chunks to the project's index, if it is loaded.
+    /// Chunks of another dimension mean the project was re-indexed with a different
+    /// model, so the index is discarded and rebuilt when next needed.
+    /// Graph updates are CPU-bound and hold the index's lock, so they run on a
+    /// blocking thread.
+    pub async fn insert(&self, project_id: i64, chunks: Vec<(i64, Vec<f32>)>) {
+        let Some(index) = self.get(project_id) else {
+            return;
+        };
+        let updated = tokio::task::spawn_blocking(move || {
+            let mut index = index.write().unwrap_or_else(|e| e.into_inner());
+            chunks
+                .iter()
+                .try_for_each(|(chunk_id, embedding)| index.insert(*chunk_id, embedding))
+        })
+        .await;
+
+        match updated {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => {
+                tracing::info!(
+                    "Discarding index of project {} after re-indexing: {}",
+                    project_id,
+                    e
+                );
+                self.discard(project_id);
+            }
+            Err(e) => {
+                tracing::error!("Failed to update index of project {}: {}", project_id, e);
+                self.discard(project_id);
+            }
+        }
+    }
+
+    /// Remove deleted chunks from the project's index, if it is loaded, rebuilding
+    /// the graph once removed chunks make up too much of it
+    pub async fn remove(&self, project_id: i64, chunk_ids: Vec<i64>) {
+        let Some(index) = self.get(project_id) else {
+            return;
+        };
+        let updated = tokio::task::spawn_blocking(move || {
+            let mut index = index.write().unwrap_or_else(|e| e.into_inner());
+            for chunk_id in chunk_ids {
+                index.remove(chunk_id);
+            }
+            index.compact_if_needed();
+        })
+        .await;
+
+        if let Err(e) = updated {
+            tracing::error!("Failed to update index of project {}: {}", project_id, e);
+            self.discard(project_id);
+        }
+    }
+
+    /// Forget the project's index, in memory and on disk
+    pub fn discard(&self, project_id: i64) {
+        self.loaded
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&project_id);
+        if let Err(e) = std::fs::remove_file(self.path(project_id)) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                tracing::warn!("Failed to remove index of project {}: {}", project_id, e);
+            }
+        }
+    }
+}
+
+/// Approximate top-k as (cosine similarity, chunk id), or None when the project should
+/// be scanned exactly: it is small, or its index is still being built. The first search
+/// of a large project without an index starts building one in the background.
+pub async fn search_index(
+    db: &RagDatabase,
+    project_id: i64,
+    query: &[f32],
+    top_k: usize,
+    ef_search: Option<usize>,
+) -> Result<Option<Vec<(f32, i64)>>, DatabaseError> {
+    let indexes = db.indexes();
+    if indexes.is_building(project_id) {
+        return Ok(None);
+    }
+
+    let index = match indexes.get(project_id) {
+        Some(index) => index,
+        None => {
+            if db.count_chunks(project_id).await? < INDEX_MIN_CHUNKS {
+                return Ok(None);
+            }
+            match load_index(db, project_id).await? {
+                Some(index) => index,
+                None => {
+                    spawn_build(db, project_id);
+                    return Ok(None);
+                }
+            }
+        }
+    };
+
+    catch_up(db, project_id, &index).await?;
+
+    let query = query.to_vec();
+    let ef = ef_search.unwrap_or(DEFAULT_EF_SEARCH);
+    let results = tokio::task::spawn_blocking(move || {
+        let index = index.read().unwrap_or_else(|e| e.into_inner());
+        if index.dimension() != query.len() {
+            return None;
+        }
+        Some(index.search(&query, top_k, ef))
+    })
+    .await
+    .map_err(|e| DatabaseError::Index(e.to_string()))?;
+
+    Ok(results)
+}
+
+/// Build the project's index from scratch, persist it and start using it.
+/// Returns the number of chunks indexed.
+pub async fn rebuild_index(db: &RagDatabase, project_id: i64) -> Result<usize, DatabaseError> {
+    let indexes = db.indexes();
+    if !indexes.start_building(project_id) {
+        return Err(DatabaseError::IndexBuildInProgress(project_id));
+    }
+    let result = build_index(db, project_id).await;
+    indexes.finish_building(project_id);
+    result
+}
+
+/// Build in the background (used when a search first finds the project large enough)
+fn spawn_build(db: &RagDatabase, project_id: i64) {
+    let db = db.clone();
+    tokio::spawn(async move {
+        tracing::info!("Building search index for project {}", project_id);
+        match rebuild_index(&db, project_id).await {
+            Ok(count) => tracing::info!(
+                "Built search index for project {} over {} chunks",
+                project_id,
+                count
+            ),
+            Err(DatabaseError::IndexBuildInProgress(_)) => {}
+            Err(e) => tracing::error!(
+                "Failed to build search index for project {}: {}",
+                project_id,
+                e
+            ),
+        }
+    });
+}
+
+async fn build_index(db: &RagDatabase, project_id: i64) -> Result<usize, DatabaseError> {
+    let mut index: Option<HnswIndex> = None;
+    let mut batch: Vec<ChunkEmbedding> = Vec::with_capacity(BUILD_BATCH_SIZE);
+    let mut embeddings = db.stream_chunk_embeddings_after(project_id, 0);
+
+    loop {
+        let next = embeddings.next().await.transpose()?;
+        let done = next.is_none();
+        batch.extend(next);
+
+        if (batch.len() >= BUILD_BATCH_SIZE || done) && !batch.is_empty() {
+            let mut building = index
+                .take()
+                .unwrap_or_else(|| HnswIndex::new(batch[0].embedding.len()));
+            let chunks = std::mem::take(&mut batch);
+            index = Some(
+                tokio::task::spawn_blocking(move || {
+                    for chunk in chunks {
+                        // The project's pinned dimension keeps these uniform
+                        if let Err(e) = building.insert(chunk.id, &chunk.embedding) {
+                            tracing::warn!("Not indexing chunk {}: {}", chunk.id, e);
+                        }
+                    }
+                    building
+                })
+                .await
+                .map_err(|e| DatabaseError::Index(e.to_string()))?,
+            );
+        }
+
+        if done {
+            break;
+        }
+    }
+    drop(embeddings);
+
+    let indexes = db.indexes();
+    let Some(mut index) = index else {
+        indexes.discard(project_id);
+        return Ok(0);
+    };
+
+    let count = index.len();
+    let path = indexes.path(project_id);
+    index = tokio::task::spawn_blocking(move || {
+        index.save(&path)?;
+        index.changes = 0;
+        Ok::<_, DatabaseError>(index)
+    })
+    .await
+    .map_err(|e| DatabaseError::Index(e.to_string()))??;
+
+    indexes.install(project_id, index);
+    Ok(count)
+}
+
+/// Load the project's persisted index and drop chunks deleted since it was saved,
+/// or None if it has none
+async fn load_index(
+    db: &RagDatabase,
+    project_id: i64,
+) -> Result<Option<Arc<RwLock<HnswIndex>>>, DatabaseError> {
+    let path = db.indexes().path(project_id);
+    let loaded = tokio::task::spawn_blocking(move || HnswIndex::load(&path))
+        .await
+        .map_err(|e| DatabaseError::Index(e.to_string()))?;
+
+    let mut index = match loaded {
+        Ok(Some(index)) => index,
+        Ok(None) => return Ok(None),
+        Err(e) => {
+            tracing::warn!(
+                "Discarding unreadable index of project {}: {}",
+                project_id,
+                e
+            );
+            return Ok(None);
+        }
+    };
+
+    let live: HashSet<i64> = db.list_chunk_ids(project_id).await?.into_iter().collect();
+    let index = tokio::task::spawn_blocking(move || {
+        let removed: Vec<i64> = index.chunk_ids().filter(|id| !live.contains(id)).collect();
+        for chunk_id in removed {
+            index.remove(chunk_id);
+        }
+        index.compact_if_needed();
+        index
+    })
+    .await
+    .map_err(|e| DatabaseError::Index(e.to_string()))?;
+
+    Ok(Some(db.indexes().install(project_id, index)))
+}
+
+/// Insert chunks stored after the index was last updated
+async fn catch_up(
+    db: &RagDatabase,
+    project_id: i64,
+    index: &Arc<RwLock<HnswIndex>>,
+) -> Result<(), DatabaseError> {
+    let after = index
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .max_chunk_id();
+    let missing: Vec<ChunkEmbedding> = db
+        .stream_chunk_embeddings_after(project_id, after)
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect::<Result<_, _>>()?;
+
+    if !missing.is_empty() {
+        let chunks = missing
+            .into_iter()
+            .map(|chunk| (chunk.id, chunk.embedding))
+            .collect();
+        db.indexes().insert(project_id, chunks).await;
+    }
+    Ok(())
+}
+
+/// Persist loaded indexes that changed substantially since they were last saved
+pub async fn save_indexes(db: &RagDatabase) -> Result<(), DatabaseError> {
+    let indexes = db.indexes();
+    let loaded: Vec<(i64, Arc<RwLock<HnswIndex>>)> = indexes
+        .loaded
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .map(|(project_id, index)| (*project_id, index.clone()))
+        .collect();
+
+    for (project_id, index) in loaded {
+        let path = indexes.path(project_id);
+        tokio::task::spawn_blocking(move || {
+            let mut index = index.write().unwrap_or_else(|e| e.into_inner());
+            if index.changes < SAVE_AFTER_CHANGES {
+                return Ok(());
+            }
+            index.save(&path)?;
+            index.changes = 0;
+            Ok::<_, DatabaseError>(())
+        })
+        .await
+        .map_err(|e| DatabaseError::Index(e.to_string()))??;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn random_vectors(count: usize, dimension: usize, seed: u64) -> Vec<Vec<f32>> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..count)
+            .map(|_| (0..dimension).map(|_| rng.gen_range(-1.0..1.0)).collect())
+            .collect()
+    }
+
+    fn exact_top_k(vectors: &[Vec<f32>], query: &[f32], k: usize) -> Vec<i64> {
+        let query = normalize(query);
+        let mut scored: Vec<(f32, i64)> = vectors
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (dot_product(&query, &normalize(v)), i as i64 + 1))
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        scored.into_iter().take(k).map(|(_, id)| id).collect()
+    }
+
+    #[test]
+    fn test_search_recall_against_exact() {
+        let vectors = random_vectors(2000, 16, 7);
+        let mut index = HnswIndex::new(16);
+        for (i, vector) in vectors.iter().enumerate() {
+            index.insert(i as i64 + 1, vector).unwrap();
+        }
+        assert_eq!(index.len(), 2000);
+        assert_eq!(index.max_chunk_id(), 2000);
+
+        let queries = random_vectors(50, 16, 11);
+        let mut found = 0;
+        for query in &queries {
+            let expected = exact_top_k(&vectors, query, 10);
+            let results = index.search(query, 10, DEFAULT_EF_SEARCH);
+            assert_eq!(results.len(), 10);
+            assert!(results.windows(2).all(|w| w[0].0 >= w[1].0));
+            found += results
+                .iter()
+                .filter(|(_, id)| expected.contains(id))
+                .count();
+        }
+
+        let recall = found as f32 / (queries.len() * 10) as f32;
+        assert!(recall > 0.9, "recall@10 was {}", recall);
+    }
+
+    #[test]
+    fn test_removed_chunks_are_not_returned() {
+        let vectors = random_vectors(300, 8, 3);
+        let mut index = HnswIndex::new(8);
+        for (i, vector) in vectors.iter().enumerate() {
+            index.insert(i as i64 + 1, vector).unwrap();
+        }
+
+        let nearest = index.search(&vectors[41], 1, DEFAULT_EF_SEARCH)[0];
+        assert_eq!(nearest.1, 42);
+        assert!((nearest.0 - 1.0).abs() < 1e-5);
+
+        assert!(index.remove(42));
+        assert!(!index.remove(42));
+        assert_eq!(index.len(), 299);
+        let results = index.search(&vectors[41], 20, DEFAULT_EF_SEARCH);
+        assert_eq!(results.len(), 20);
+        assert!(results.iter().all(|(_, id)| *id != 42));
+    }
+
+    #[test]
+    fn test_insert_rejects_other_dimensions() {
+        let mut index = HnswIndex::new(4);
+        assert!(matches!(
+            index.insert(1, &[1.0, 0.0]),
+            Err(DatabaseError::DimensionMismatch {
+                expected: 4,
+                got: 2
+            })
+        ));
+        assert!(index.is_empty());
+    }
+
+    #[test]
+    fn test_compaction_drops_removed_nodes() {
+        let vectors = random_vectors(200, 8, 9);
+        let mut index = HnswIndex::new(8);
+        for (i, vector) in vectors.iter().enumerate() {
+            index.insert(i as i64 + 1, vector).unwrap();
+        }
+
+        for chunk_id in 1..=40 {
+            index.remove(chunk_id);
+        }
+        index.compact_if_needed();
+        // 20% removed: below the threshold, so the tombstones stay
+        assert_eq!(index.nodes.len(), 200);
+
+        for chunk_id in 41..=100 {
+            index.remove(chunk_id);
+        }
+        index.compact_if_needed();
+        assert_eq!(index.nodes.len(), 100);
+        assert_eq!(index.len(), 100);
+        assert_eq!(index.max_chunk_id(), 200);
+        assert!(index.changes >= SAVE_AFTER_CHANGES);
+
+        let nearest = index.search(&vectors[149], 1, DEFAULT_EF_SEARCH)[0];
+        assert_eq!(nearest.1, 150);
+        let results = index.search(&vectors[0], 20, DEFAULT_EF_SEARCH);
+        assert!(results.iter().all(|(_, id)| *id > 100));
+    }
+
+    #[test]
+    fn test_save_and_load_roundtrip() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("indexes").join("project_1.hnsw");
+        assert!(HnswIndex::load(&path).unwrap().is_none());
+
+        let vectors = random_vectors(100, 4, 5);
+        let mut index = HnswIndex::new(4);
+        for (i, vector) in vectors.iter().enumerate() {
+            index.insert(i as i64 + 1, vector).unwrap();
+        }
+        index.save(&path).unwrap();
+
+        let loaded = HnswIndex::load(&path).unwrap().unwrap();
+        assert_eq!(loaded.len(), 100);
+        assert_eq!(loaded.max_chunk_id(), 100);
+        assert_eq!(
+            loaded.search(&vectors[9], 5, 32),
+            index.search(&vectors[9], 5, 32)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_index_follows_chunk_changes() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("rag.db");
+        std::fs::File::create(&path).unwrap();
+        let db = RagDatabase::new(path.clone(), 1).await.unwrap();
+
+        let project = db.create_project("Project".to_string()).await.unwrap();
+        let first = db
+            .create_document(project.id, "First".to_string(), None, None)
+            .await
+            .unwrap();
+        for (i, vector) in random_vectors(50, 8, 1).into_iter().enumerate() {
+            db.insert_chunk(
+                first.id,
+                project.id,
+                format!("chunk {}", i),
+                vector,
+                i as i32,
+            )
+            .await
+            .unwrap();
+        }
+        assert_eq!(rebuild_index(&db, project.id).await.unwrap(), 50);
+
+        // Inserted after the build: picked up through the insert hook
+        let second = db
+            .create_document(project.id, "Second".to_string(), None, None)
+            .await
+            .unwrap();
+        let target = vec![1.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0];
+        let target_id = db
+            .insert_chunk(
+                second.id,
+                project.id,
+                "target".to_string(),
+                target.clone(),
+                0,
+            )
+            .await
+            .unwrap();
+        let results = search_index(&db, project.id, &target, 3, None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(results[0].1, target_id);
+
+        // A fresh database handle loads the saved index, drops deleted chunks
+        // and catches up on ones stored since it was saved
+        db.delete_document(first.id).await.unwrap();
+        let reopened = RagDatabase::new(path, 1).await.unwrap();
+        // Too small to be loaded by a search on its own
+        assert!(load_index(&reopened, project.id).await.unwrap().is_some());
+        let results = search_index(&reopened, project.id, &target, 10, None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].1, target_id);
+    }
+}
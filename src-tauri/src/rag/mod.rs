@@ -1,9 +1,19 @@
+pub mod chunking;
+pub mod citations;
+pub mod context;
 pub mod database;
 pub mod embeddings;
-pub mod chunking;
+pub mod export;
+pub mod extract;
+pub mod hnsw;
 pub mod search;
+pub mod snippet;
 
-pub use database::{RagDatabase, Project, Document, Chunk, Conversation, Message, ChunkMatch};
-pub use embeddings::EmbeddingService;
 pub use chunking::chunk_text;
+pub use database::{
+    CanvasEdge, CanvasNode, CanvasState, CanvasVersion, Chunk, ChunkMatch, Conversation,
+    ConversationSettings, Document, DocumentStats, MatchExplanation, Message, NewChunk, Page,
+    Position, Project, ProjectEmbeddingInfo, ProjectStats, RagDatabase, UsageGroupBy, UsageTotal,
+};
+pub use embeddings::EmbeddingService;
 pub use search::search_similar;
@@ -1,26 +1,40 @@
-use super::database::{Chunk, ChunkMatch, RagDatabase};
-use super::embeddings::cosine_similarity;
+use super::chunking::merge_chunks;
+use super::database::{Chunk, ChunkEmbedding, ChunkMatch, MatchExplanation, RagDatabase};
+use super::embeddings::{cosine_similarity, l2_norm, DistanceMetric};
+use super::hnsw::search_index;
+use crate::llm_providers::{LlmProvider, ProviderError};
+use futures::StreamExt;
 use rayon::prelude::*;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
 use thiserror::Error;
 
+/// Number of embeddings scored together while streaming a project's chunks
+const SCORING_BATCH_SIZE: usize = 4096;
+
+/// Rank offset in Reciprocal Rank Fusion scores, 1 / (RRF_K + rank); 60 is the usual value
+const RRF_K: f32 = 60.0;
+
 #[derive(Error, Debug)]
 pub enum SearchError {
     #[error("Database error: {0}")]
     DatabaseError(#[from] super::database::DatabaseError),
+
+    #[error("Rerank error: {0}")]
+    RerankError(#[from] ProviderError),
 }
 
 /// Search for chunks similar to the query embedding
 /// Returns top-k most similar chunks with their similarity scores
+/// With `document_ids`, only chunks of those documents are loaded and scored.
 ///
-/// OPTIMIZED FOR HIGH-MEMORY SYSTEMS (128GB+ RAM):
+/// Cosine searches of projects past `hnsw::INDEX_MIN_CHUNKS` use the project's HNSW
+/// index (approximate; `ef_search` trades latency for recall). Everything else,
+/// including searches restricted to some documents, is an exact scan:
 /// - Uses parallel processing via rayon for similarity computation
 /// - In-memory cosine similarity is very fast with modern CPUs
-/// - For datasets > 100k chunks, consider:
-///   1. HNSW indexing (via hnswlib or faiss)
-///   2. GPU acceleration (via faiss with CUDA)
-///   3. Approximate nearest neighbor search for sub-millisecond queries
 ///
-/// Current performance (estimated on modern CPU):
+/// Exact scan performance (estimated on modern CPU):
 /// - 10k chunks: ~10-50ms
 /// - 100k chunks: ~100-500ms
 /// - 1M chunks: ~1-5 seconds
@@ -29,58 +43,122 @@ pub async fn search_similar(
     project_id: i64,
     query_embedding: Vec<f32>,
     top_k: usize,
+    metric: DistanceMetric,
+    ef_search: Option<usize>,
+    document_ids: Option<&[i64]>,
 ) -> Result<Vec<ChunkMatch>, SearchError> {
-    // Get all chunks for the project
-    let chunks = db.get_chunks_for_project(project_id).await?;
+    // The index covers the whole project, so it can't answer a filtered search
+    if metric == DistanceMetric::Cosine && document_ids.is_none() {
+        if let Some(top) = search_index(db, project_id, &query_embedding, top_k, ef_search).await? {
+            tracing::debug!("Searched project {} with its HNSW index", project_id);
+            return load_matches(db, top).await;
+        }
+    }
+
+    let query_norm = l2_norm(&query_embedding);
+
+    // Stream embeddings (without content) and keep only a running top-k,
+    // so memory stays bounded by one batch regardless of project size
+    let mut embeddings = match document_ids {
+        Some(document_ids) => db
+            .stream_document_chunk_embeddings(project_id, document_ids)
+            .boxed(),
+        None => db.stream_chunk_embeddings(project_id).boxed(),
+    };
+    let mut batch: Vec<ChunkEmbedding> = Vec::with_capacity(SCORING_BATCH_SIZE);
+    let mut top: Vec<(f32, i64)> = Vec::new();
+    let mut backfill: Vec<(i64, f32)> = Vec::new();
+    let mut chunk_count = 0;
+
+    loop {
+        let next = embeddings.next().await.transpose()?;
+        let done = next.is_none();
+        batch.extend(next);
+
+        if batch.len() < SCORING_BATCH_SIZE && !done {
+            continue;
+        }
+
+        chunk_count += batch.len();
+
+        // Compute similarity for each chunk in the batch IN PARALLEL
+        // Rayon automatically uses all available CPU cores
+        let scored: Vec<(f32, i64, Option<f32>)> = batch
+            .par_drain(..)
+            .map(|chunk| {
+                // Chunks stored before norms were recorded get theirs computed here
+                let (chunk_norm, missing) = match chunk.embedding_norm {
+                    Some(norm) => (norm, None),
+                    None => {
+                        let norm = l2_norm(&chunk.embedding);
+                        (norm, Some(norm))
+                    }
+                };
+                let similarity =
+                    metric.score(&query_embedding, query_norm, &chunk.embedding, chunk_norm);
+                (similarity, chunk.id, missing)
+            })
+            .collect();
+
+        backfill.extend(
+            scored
+                .iter()
+                .filter_map(|(_, id, missing)| missing.map(|norm| (*id, norm))),
+        );
+        top.extend(
+            scored
+                .into_iter()
+                .map(|(similarity, id, _)| (similarity, id)),
+        );
+        top = top_k_by_score(top, top_k);
 
-    if chunks.is_empty() {
-        return Ok(Vec::new());
+        if done {
+            break;
+        }
     }
+    drop(embeddings);
 
-    let chunk_count = chunks.len();
     tracing::debug!(
-        "Searching {} chunks in project {} with parallel processing",
+        "Searched {} chunks in project {} with parallel processing",
         chunk_count,
         project_id
     );
 
-    // Compute similarity for each chunk IN PARALLEL
-    // With 128GB RAM, we can easily handle millions of chunks in memory
-    // Rayon automatically uses all available CPU cores
-    let mut scored_chunks: Vec<(f32, Chunk)> = chunks
-        .into_par_iter() // Parallel iterator for multi-core processing
-        .map(|chunk| {
-            let similarity = cosine_similarity(&query_embedding, &chunk.embedding);
-            (similarity, chunk)
-        })
-        .collect();
-
-    // Sort by similarity (descending)
-    // For very large datasets (>1M chunks), consider using partial_sort or select_nth
-    scored_chunks.par_sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+    // Persist norms computed above, so the migration happens lazily on first search
+    if !backfill.is_empty() {
+        tracing::debug!("Storing embedding norms for {} chunks", backfill.len());
+        db.set_embedding_norms(&backfill).await?;
+    }
 
-    // Take top-k
-    let top_chunks: Vec<_> = scored_chunks.into_iter().take(top_k).collect();
+    load_matches(db, top).await
+}
 
-    // Build ChunkMatch results (fetch all document names in one optimized query)
-    let chunk_ids: Vec<i64> = top_chunks.iter().map(|(_, chunk)| chunk.id).collect();
+/// Turn ranked (similarity, chunk id) pairs into matches with content and document names
+/// Chunks deleted since they were ranked are skipped.
+async fn load_matches(
+    db: &RagDatabase,
+    top: Vec<(f32, i64)>,
+) -> Result<Vec<ChunkMatch>, SearchError> {
+    // Fetch content and document names only for the winners, in one query
+    let chunk_ids: Vec<i64> = top.iter().map(|(_, id)| *id).collect();
     let chunks_with_docs = db.get_chunks_with_documents(&chunk_ids).await?;
 
-    // Create a map of chunk_id -> document_name for quick lookup
-    let mut doc_name_map: std::collections::HashMap<i64, String> =
-        chunks_with_docs
-            .into_iter()
-            .map(|(chunk, doc_name)| (chunk.id, doc_name))
-            .collect();
+    let mut chunk_map: HashMap<i64, (Chunk, String)> = chunks_with_docs
+        .into_iter()
+        .map(|(chunk, doc_name)| (chunk.id, (chunk, doc_name)))
+        .collect();
 
-    // Build results maintaining the original order and similarity scores
-    let results: Vec<ChunkMatch> = top_chunks
+    // Build results maintaining the ranked order and similarity scores
+    let results: Vec<ChunkMatch> = top
         .into_iter()
-        .filter_map(|(similarity, chunk)| {
-            doc_name_map.remove(&chunk.id).map(|doc_name| ChunkMatch {
+        .filter_map(|(similarity, id)| {
+            chunk_map.remove(&id).map(|(chunk, doc_name)| ChunkMatch {
                 chunk,
                 similarity,
                 document_name: doc_name,
+                expanded_content: None,
+                explanation: None,
+                snippet: None,
             })
         })
         .collect();
@@ -90,6 +168,65 @@ pub async fn search_similar(
     Ok(results)
 }
 
+/// Keep the `k` highest-scoring items, sorted by score (descending)
+/// Partitions around the k-th score in O(n) and only sorts the kept slice,
+/// instead of sorting all n items to discard most of them
+fn top_k_by_score<T>(mut scored: Vec<(f32, T)>, k: usize) -> Vec<(f32, T)> {
+    let descending = |a: &(f32, T), b: &(f32, T)| b.0.total_cmp(&a.0);
+
+    if k == 0 {
+        return Vec::new();
+    }
+    if k < scored.len() {
+        scored.select_nth_unstable_by(k - 1, descending);
+        scored.truncate(k);
+    }
+
+    scored.sort_unstable_by(descending);
+    scored
+}
+
+/// Fill in `expanded_content` for each match with its chunk merged with up to
+/// `context_window` neighboring chunks on either side from the same document
+pub async fn expand_matches(
+    db: &RagDatabase,
+    matches: &mut [ChunkMatch],
+    context_window: usize,
+) -> Result<(), SearchError> {
+    if context_window == 0 {
+        return Ok(());
+    }
+    let window = i32::try_from(context_window).unwrap_or(i32::MAX);
+
+    for chunk_match in matches.iter_mut() {
+        let index = chunk_match.chunk.chunk_index;
+        let neighbors = db
+            .get_chunk_contents_in_range(
+                chunk_match.chunk.document_id,
+                index.saturating_sub(window),
+                index.saturating_add(window),
+            )
+            .await?;
+
+        let contents: Vec<String> = neighbors.into_iter().map(|(_, content)| content).collect();
+        chunk_match.expanded_content = Some(merge_chunks(&contents));
+    }
+
+    Ok(())
+}
+
+/// Attach a `MatchExplanation` to each match, ranked in the order given
+pub fn explain_matches(matches: &mut [ChunkMatch], metric: DistanceMetric) {
+    for (idx, chunk_match) in matches.iter_mut().enumerate() {
+        chunk_match.explanation = Some(MatchExplanation {
+            rank: idx + 1,
+            similarity: chunk_match.similarity,
+            metric,
+            chunk_chars: chunk_match.chunk.content.chars().count(),
+        });
+    }
+}
+
 /// Advanced search with filtering and re-ranking
 /// For high-memory systems, this performs multi-stage retrieval:
 /// 1. Fast cosine similarity to get top-N candidates (N > k)
@@ -101,10 +238,20 @@ pub async fn search_with_rerank(
     query_embedding: Vec<f32>,
     top_k: usize,
     candidate_multiplier: usize, // Get this many candidates before re-ranking
+    metric: DistanceMetric,
 ) -> Result<Vec<ChunkMatch>, SearchError> {
     // First stage: Get more candidates than needed
-    let candidate_count = top_k * candidate_multiplier;
-    let mut candidates = search_similar(db, project_id, query_embedding, candidate_count).await?;
+    let candidate_count = top_k.saturating_mul(candidate_multiplier);
+    let mut candidates = search_similar(
+        db,
+        project_id,
+        query_embedding,
+        candidate_count,
+        metric,
+        None,
+        None,
+    )
+    .await?;
 
     if candidates.len() <= top_k {
         return Ok(candidates);
@@ -131,8 +278,7 @@ pub async fn search_with_rerank(
                 .fold(0.0f32, f32::max);
 
             // Penalize similar results
-            let diversity_score =
-                candidate.similarity - (diversity_penalty * max_sim_to_selected);
+            let diversity_score = candidate.similarity - (diversity_penalty * max_sim_to_selected);
 
             if diversity_score > best_score {
                 best_score = diversity_score;
@@ -152,8 +298,99 @@ pub async fn search_with_rerank(
     Ok(selected)
 }
 
+/// Multi-query retrieval: search with each query embedding (e.g. of paraphrases of one
+/// question) and fuse the rankings with Reciprocal Rank Fusion
+/// A chunk's fused score is the sum of 1 / (60 + rank) over the searches that found it, so
+/// chunks found by several queries rise. Each chunk is returned once, with its best similarity.
+pub async fn search_multi_query(
+    db: &RagDatabase,
+    project_id: i64,
+    query_embeddings: Vec<Vec<f32>>,
+    top_k: usize,
+    metric: DistanceMetric,
+) -> Result<Vec<ChunkMatch>, SearchError> {
+    let mut rankings = Vec::with_capacity(query_embeddings.len());
+    for query_embedding in query_embeddings {
+        rankings.push(
+            search_similar(db, project_id, query_embedding, top_k, metric, None, None).await?,
+        );
+    }
+
+    tracing::debug!("Fusing the rankings of {} queries", rankings.len());
+
+    Ok(fuse_rankings(rankings, top_k))
+}
+
+/// Merge rankings with Reciprocal Rank Fusion, deduplicating by chunk id, and keep the top-k
+fn fuse_rankings(rankings: Vec<Vec<ChunkMatch>>, top_k: usize) -> Vec<ChunkMatch> {
+    let mut fused: HashMap<i64, (f32, ChunkMatch)> = HashMap::new();
+
+    for ranking in rankings {
+        for (idx, chunk_match) in ranking.into_iter().enumerate() {
+            let score = 1.0 / (RRF_K + (idx + 1) as f32);
+            match fused.entry(chunk_match.chunk.id) {
+                Entry::Occupied(mut entry) => {
+                    let (fused_score, best) = entry.get_mut();
+                    *fused_score += score;
+                    best.similarity = best.similarity.max(chunk_match.similarity);
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert((score, chunk_match));
+                }
+            }
+        }
+    }
+
+    top_k_by_score(fused.into_values().collect(), top_k)
+        .into_iter()
+        .map(|(_, chunk_match)| chunk_match)
+        .collect()
+}
+
+/// Second stage of retrieval with a cross-encoder: the reranker scores each candidate
+/// of an embedding search against the query text, and the top-k by rerank score are
+/// returned, with it replacing `similarity`
+/// Takes the candidates rather than the database, so no lock is held during the request.
+pub async fn rerank_candidates(
+    reranker: &dyn LlmProvider,
+    query: &str,
+    candidates: Vec<ChunkMatch>,
+    top_k: usize,
+) -> Result<Vec<ChunkMatch>, SearchError> {
+    if candidates.is_empty() {
+        return Ok(candidates);
+    }
+
+    let documents: Vec<String> = candidates.iter().map(|m| m.chunk.content.clone()).collect();
+    let ranked = reranker.rerank(query, &documents).await?;
+
+    tracing::debug!("Cross-encoder re-ranked {} candidates", candidates.len());
+
+    Ok(apply_rerank(candidates, ranked, top_k))
+}
+
+/// Reorder candidates by (index, score) pairs from a reranker, keeping the top-k
+/// Candidates the reranker left out are dropped; out-of-range or repeated indices are ignored.
+fn apply_rerank(
+    candidates: Vec<ChunkMatch>,
+    mut ranked: Vec<(usize, f32)>,
+    top_k: usize,
+) -> Vec<ChunkMatch> {
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let mut slots: Vec<Option<ChunkMatch>> = candidates.into_iter().map(Some).collect();
+    ranked
+        .into_iter()
+        .filter_map(|(idx, score)| {
+            let mut chunk_match = slots.get_mut(idx)?.take()?;
+            chunk_match.similarity = score;
+            Some(chunk_match)
+        })
+        .take(top_k)
+        .collect()
+}
+
 // TODO: Future enhancements for re-ranking:
-// - Cross-encoder models (Hugging Face transformers for accurate relevance scoring)
 // - Hybrid search (combine semantic embeddings with BM25 keyword matching)
 // - MMR (Maximal Marginal Relevance) algorithm with configurable lambda
 // - Query expansion for better recall
@@ -167,7 +404,10 @@ mod tests {
         let v1 = vec![1.0, 0.0, 0.0];
         let v2 = vec![1.0, 0.0, 0.0];
         let similarity = cosine_similarity(&v1, &v2);
-        assert!((similarity - 1.0).abs() < 1e-6, "Identical vectors should have similarity of 1.0");
+        assert!(
+            (similarity - 1.0).abs() < 1e-6,
+            "Identical vectors should have similarity of 1.0"
+        );
     }
 
     #[test]
@@ -175,7 +415,10 @@ mod tests {
         let v1 = vec![1.0, 0.0, 0.0];
         let v2 = vec![0.0, 1.0, 0.0];
         let similarity = cosine_similarity(&v1, &v2);
-        assert!(similarity.abs() < 1e-6, "Orthogonal vectors should have similarity of 0.0");
+        assert!(
+            similarity.abs() < 1e-6,
+            "Orthogonal vectors should have similarity of 0.0"
+        );
     }
 
     #[test]
@@ -183,7 +426,10 @@ mod tests {
         let v1 = vec![1.0, 0.0, 0.0];
         let v2 = vec![-1.0, 0.0, 0.0];
         let similarity = cosine_similarity(&v1, &v2);
-        assert!((similarity + 1.0).abs() < 1e-6, "Opposite vectors should have similarity of -1.0");
+        assert!(
+            (similarity + 1.0).abs() < 1e-6,
+            "Opposite vectors should have similarity of -1.0"
+        );
     }
 
     #[test]
@@ -191,7 +437,10 @@ mod tests {
         let v1 = vec![2.0, 0.0, 0.0];
         let v2 = vec![3.0, 0.0, 0.0];
         let similarity = cosine_similarity(&v1, &v2);
-        assert!((similarity - 1.0).abs() < 1e-6, "Parallel vectors should have similarity of 1.0");
+        assert!(
+            (similarity - 1.0).abs() < 1e-6,
+            "Parallel vectors should have similarity of 1.0"
+        );
     }
 
     #[test]
@@ -201,6 +450,286 @@ mod tests {
         let similarity = cosine_similarity(&v1, &v2);
         // Expected: (1*4 + 2*5 + 3*6) / (sqrt(14) * sqrt(77))
         // = 32 / sqrt(1078) ≈ 0.9746
-        assert!(similarity > 0.97 && similarity < 0.98, "Expected similarity around 0.9746");
+        assert!(
+            similarity > 0.97 && similarity < 0.98,
+            "Expected similarity around 0.9746"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_backfills_missing_norms() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("rag.db");
+        std::fs::File::create(&path).unwrap();
+        let db = RagDatabase::new(path, 1).await.unwrap();
+
+        let project = db.create_project("Project".to_string()).await.unwrap();
+        let document = db
+            .create_document(project.id, "Doc".to_string(), None, None)
+            .await
+            .unwrap();
+        let near = db
+            .insert_chunk(
+                document.id,
+                project.id,
+                "near".to_string(),
+                vec![1.0, 0.1],
+                0,
+            )
+            .await
+            .unwrap();
+        db.insert_chunk(
+            document.id,
+            project.id,
+            "far".to_string(),
+            vec![0.0, 2.0],
+            1,
+        )
+        .await
+        .unwrap();
+
+        // Simulate chunks stored before norms were recorded
+        sqlx::query("UPDATE chunks SET embedding_norm = NULL")
+            .execute(db.pool())
+            .await
+            .unwrap();
+
+        let results = search_similar(
+            &db,
+            project.id,
+            vec![1.0, 0.0],
+            2,
+            DistanceMetric::Cosine,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].chunk.id, near);
+        assert!(results[0].similarity > results[1].similarity);
+
+        let chunks = db.get_chunks_for_project(project.id).await.unwrap();
+        assert!(chunks.iter().all(|c| c.embedding_norm.is_some()));
+    }
+
+    #[tokio::test]
+    async fn test_expand_matches_merges_neighbors() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("rag.db");
+        std::fs::File::create(&path).unwrap();
+        let db = RagDatabase::new(path, 1).await.unwrap();
+
+        let project = db.create_project("Project".to_string()).await.unwrap();
+        let document = db
+            .create_document(project.id, "Doc".to_string(), None, None)
+            .await
+            .unwrap();
+        // Consecutive chunks overlapping by one word, as chunk_text produces
+        let contents = ["one two ", "two three ", "three four ", "four five"];
+        for (i, content) in contents.iter().enumerate() {
+            let embedding = if i == 2 {
+                vec![1.0, 0.0]
+            } else {
+                vec![0.0, 1.0]
+            };
+            db.insert_chunk(
+                document.id,
+                project.id,
+                content.to_string(),
+                embedding,
+                i as i32,
+            )
+            .await
+            .unwrap();
+        }
+
+        let mut results = search_similar(
+            &db,
+            project.id,
+            vec![1.0, 0.0],
+            1,
+            DistanceMetric::Cosine,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        expand_matches(&db, &mut results, 1).await.unwrap();
+
+        assert_eq!(results[0].chunk.content, "three four ");
+        assert_eq!(
+            results[0].expanded_content.as_deref(),
+            Some("two three four five")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_restricted_to_documents() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("rag.db");
+        std::fs::File::create(&path).unwrap();
+        let db = RagDatabase::new(path, 1).await.unwrap();
+
+        let project = db.create_project("Project".to_string()).await.unwrap();
+        let mut chunk_ids = Vec::new();
+        for (name, embedding) in [("v1 notes", vec![1.0, 0.0]), ("v2 notes", vec![0.0, 1.0])] {
+            let document = db
+                .create_document(project.id, name.to_string(), None, None)
+                .await
+                .unwrap();
+            let chunk_id = db
+                .insert_chunk(document.id, project.id, name.to_string(), embedding, 0)
+                .await
+                .unwrap();
+            chunk_ids.push((document.id, chunk_id));
+        }
+
+        let v2 = db.find_document_ids(project.id, "V2%").await.unwrap();
+        assert_eq!(v2, vec![chunk_ids[1].0]);
+
+        // The v1 chunk is the better match but isn't in the searched documents
+        let results = search_similar(
+            &db,
+            project.id,
+            vec![1.0, 0.0],
+            2,
+            DistanceMetric::Cosine,
+            None,
+            Some(&v2),
+        )
+        .await
+        .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].chunk.id, chunk_ids[1].1);
+
+        let results = search_similar(
+            &db,
+            project.id,
+            vec![1.0, 0.0],
+            2,
+            DistanceMetric::Cosine,
+            None,
+            Some(&[]),
+        )
+        .await
+        .unwrap();
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn test_fuse_rankings_favors_chunks_found_by_several_queries() {
+        let with_id = |id: i64, similarity: f32| {
+            let mut chunk_match = chunk_match(&format!("chunk {}", id), similarity);
+            chunk_match.chunk.id = id;
+            chunk_match
+        };
+        let rankings = vec![
+            vec![with_id(1, 0.9), with_id(2, 0.8), with_id(3, 0.7)],
+            vec![with_id(4, 0.95), with_id(2, 0.85)],
+        ];
+
+        let fused = fuse_rankings(rankings, 3);
+        let ids: Vec<i64> = fused.iter().map(|m| m.chunk.id).collect();
+        // Chunk 2 is second in both rankings, beating the single first places
+        assert_eq!(ids[0], 2);
+        assert_eq!(fused[0].similarity, 0.85);
+        assert_eq!(fused.len(), 3);
+        assert!(!ids.contains(&3));
+    }
+
+    fn chunk_match(content: &str, similarity: f32) -> ChunkMatch {
+        ChunkMatch {
+            chunk: Chunk {
+                id: 1,
+                document_id: 1,
+                project_id: 1,
+                content: content.to_string(),
+                embedding: Vec::new(),
+                chunk_index: 0,
+                embedding_norm: None,
+                page_number: None,
+                start_offset: None,
+                end_offset: None,
+            },
+            similarity,
+            document_name: "doc".to_string(),
+            expanded_content: None,
+            explanation: None,
+            snippet: None,
+        }
+    }
+
+    #[test]
+    fn test_explain_matches_ranks_in_order() {
+        let mut matches = vec![chunk_match("première", 0.9), chunk_match("second", 0.4)];
+
+        explain_matches(&mut matches, DistanceMetric::DotProduct);
+
+        let first = matches[0].explanation.as_ref().unwrap();
+        assert_eq!((first.rank, first.chunk_chars), (1, 8));
+        assert_eq!(first.metric, DistanceMetric::DotProduct);
+        let second = matches[1].explanation.as_ref().unwrap();
+        assert_eq!((second.rank, second.similarity), (2, 0.4));
+    }
+
+    #[test]
+    fn test_apply_rerank_orders_by_rerank_score() {
+        let candidates = vec![
+            chunk_match("a", 0.9),
+            chunk_match("b", 0.8),
+            chunk_match("c", 0.7),
+        ];
+        // Unsorted, with a repeated and an out-of-range index
+        let ranked = vec![(0, 0.1), (2, 0.95), (1, 0.5), (2, 0.2), (7, 0.99)];
+
+        let reranked = apply_rerank(candidates, ranked, 2);
+        let order: Vec<_> = reranked
+            .iter()
+            .map(|m| (m.chunk.content.as_str(), m.similarity))
+            .collect();
+        assert_eq!(order, vec![("c", 0.95), ("b", 0.5)]);
+    }
+
+    #[test]
+    fn test_top_k_by_score_matches_full_sort() {
+        let scores: Vec<f32> = (0..1000)
+            .map(|i| ((i * 7919) % 1000) as f32 / 1000.0)
+            .collect();
+        let scored: Vec<(f32, usize)> = scores.iter().copied().zip(0..).collect();
+
+        let mut full = scored.clone();
+        full.sort_by(|a, b| b.0.total_cmp(&a.0));
+        full.truncate(10);
+
+        assert_eq!(top_k_by_score(scored.clone(), 10), full);
+        assert_eq!(top_k_by_score(scored.clone(), 0), Vec::new());
+        assert_eq!(top_k_by_score(scored.clone(), 5000).len(), 1000);
+    }
+
+    /// Times partial selection against a full sort; run with
+    /// `cargo test --release -- --ignored --nocapture` to see the timings
+    #[test]
+    #[ignore]
+    fn bench_top_k_vs_full_sort() {
+        let scored: Vec<(f32, usize)> = (0..1_000_000)
+            .map(|i| (((i as u64 * 2_654_435_761) % 1_000_003) as f32, i))
+            .collect();
+
+        let start = std::time::Instant::now();
+        let mut full = scored.clone();
+        full.par_sort_by(|a, b| b.0.total_cmp(&a.0));
+        full.truncate(10);
+        let full_time = start.elapsed();
+
+        let start = std::time::Instant::now();
+        let partial = top_k_by_score(scored, 10);
+        let partial_time = start.elapsed();
+
+        println!(
+            "partial selection took {:?}, full sort {:?}",
+            partial_time, full_time
+        );
+        assert_eq!(partial, full);
     }
 }
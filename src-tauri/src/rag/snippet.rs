@@ -0,0 +1,219 @@
+//! Short excerpts of search results around the words of the query, for result lists
+
+use super::database::ChunkMatch;
+
+/// Length of the snippets attached to search results, in characters
+pub const SNIPPET_CHARS: usize = 200;
+
+/// Words too common to be worth highlighting
+const STOP_WORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "by", "do", "does", "for", "from", "how", "in",
+    "is", "it", "of", "on", "or", "that", "the", "this", "to", "was", "what", "when", "where",
+    "which", "who", "why", "with",
+];
+
+/// The lowercased words of a query worth highlighting, without stop words or repeats
+pub fn query_terms(query: &str) -> Vec<String> {
+    let mut terms: Vec<String> = Vec::new();
+    for word in query.split(|c: char| !c.is_alphanumeric()) {
+        let word = word.to_lowercase();
+        if word.is_empty() || STOP_WORDS.contains(&word.as_str()) || terms.contains(&word) {
+            continue;
+        }
+        terms.push(word);
+    }
+    terms
+}
+
+/// Words of `chars` as `(start, end)` char ranges
+fn words(chars: &[char]) -> Vec<(usize, usize)> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (i, c) in chars.iter().enumerate() {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                words.push((s, i));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        words.push((s, chars.len()));
+    }
+    words
+}
+
+/// An excerpt of about `window` characters of `content` around its densest cluster of
+/// query terms, each term marked as `**term**` and with "..." where the text was cut.
+/// A word matches a term it starts with, so "borrow" also marks "borrowing".
+/// When no term occurs, as is common for purely semantic matches, the excerpt is the
+/// start of the content.
+pub fn snippet(content: &str, query_terms: &[String], window: usize) -> String {
+    let chars: Vec<char> = content.chars().collect();
+    let words = words(&chars);
+    let hits: Vec<(usize, usize)> = words
+        .iter()
+        .copied()
+        .filter(|&(start, end)| {
+            let word = chars[start..end].iter().collect::<String>().to_lowercase();
+            query_terms
+                .iter()
+                .any(|term| word.starts_with(term.as_str()))
+        })
+        .collect();
+
+    // The densest cluster starts at the hit followed by the most others within one window;
+    // a hit longer than the window is in no cluster
+    let mut densest: Option<(usize, usize)> = None;
+    for (i, &(start, _)) in hits.iter().enumerate() {
+        let count = hits[i..]
+            .iter()
+            .take_while(|&&(_, end)| end - start <= window)
+            .count();
+        if count > 0 && densest.is_none_or(|(_, best)| count > best) {
+            densest = Some((i, count));
+        }
+    }
+
+    let (mut from, mut to) = match densest {
+        Some((i, count)) => {
+            // Center the cluster in the window
+            let cluster_start = hits[i].0;
+            let cluster_end = hits[i + count - 1].1;
+            let slack = window.saturating_sub(cluster_end - cluster_start);
+            let from = cluster_start.saturating_sub(slack / 2);
+            let to = (from + window).min(chars.len());
+            // Near the end, use the text before the cluster instead
+            (to.saturating_sub(window).min(from), to)
+        }
+        None => (0, window.min(chars.len())),
+    };
+
+    // Don't cut words in half, unless a single word is longer than the window
+    let snapped_from = if from == 0 {
+        0
+    } else {
+        words
+            .iter()
+            .map(|&(start, _)| start)
+            .find(|&start| start >= from)
+            .unwrap_or(from)
+    };
+    let snapped_to = if to == chars.len() {
+        to
+    } else {
+        words
+            .iter()
+            .rev()
+            .map(|&(_, end)| end)
+            .find(|&end| end <= to)
+            .unwrap_or(to)
+    };
+    if snapped_from < snapped_to {
+        from = snapped_from;
+        to = snapped_to;
+    }
+
+    let mut excerpt = String::with_capacity(window + 16);
+    if from > 0 {
+        excerpt.push_str("...");
+    }
+    let mut hits = hits
+        .into_iter()
+        .filter(|&(start, end)| start >= from && end <= to)
+        .peekable();
+    let mut last_was_space = false;
+    for (i, &c) in chars.iter().enumerate().take(to).skip(from) {
+        if hits.peek().is_some_and(|&(_, end)| end == i) {
+            excerpt.push_str("**");
+            hits.next();
+        }
+        if hits.peek().is_some_and(|&(start, _)| start == i) {
+            excerpt.push_str("**");
+        }
+        // Line breaks and runs of whitespace read as a single space in a result list
+        if c.is_whitespace() {
+            if !last_was_space {
+                excerpt.push(' ');
+            }
+            last_was_space = true;
+        } else {
+            excerpt.push(c);
+            last_was_space = false;
+        }
+    }
+    if hits.peek().is_some_and(|&(_, end)| end == to) {
+        excerpt.push_str("**");
+    }
+    if to < chars.len() {
+        excerpt.push_str("...");
+    }
+
+    excerpt.trim().to_string()
+}
+
+/// Fill in `snippet` for each match, highlighting the words of `query`
+pub fn add_snippets(matches: &mut [ChunkMatch], query: &str) {
+    let terms = query_terms(query);
+    for chunk_match in matches.iter_mut() {
+        chunk_match.snippet = Some(snippet(&chunk_match.chunk.content, &terms, SNIPPET_CHARS));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_terms_skip_stop_words_and_repeats() {
+        assert_eq!(
+            query_terms("How does the Borrow checker borrow?"),
+            vec!["borrow".to_string(), "checker".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_snippet_centers_densest_cluster() {
+        let content = format!(
+            "{} The borrow checker rejects code where a reference outlives its value. {}",
+            "Unrelated filler text. ".repeat(20),
+            "More filler afterwards. ".repeat(20)
+        );
+        let terms = query_terms("borrow checker reference");
+        let excerpt = snippet(&content, &terms, 120);
+
+        assert!(excerpt.starts_with("..."));
+        assert!(excerpt.ends_with("..."));
+        assert!(excerpt.contains("The **borrow** **checker** rejects"));
+        assert!(excerpt.contains("a **reference** outlives"));
+        // "..." on both sides plus three pairs of markers
+        assert!(excerpt.chars().count() <= 120 + 6 + 12);
+    }
+
+    #[test]
+    fn test_snippet_falls_back_to_start() {
+        let content = "Ownership\nrules in   Rust: each value has one owner.".repeat(10);
+        let excerpt = snippet(&content, &query_terms("lifetimes"), 40);
+        assert!(excerpt.starts_with("Ownership rules in Rust: each value"));
+        assert!(excerpt.ends_with("..."));
+        assert!(!excerpt.contains("**"));
+
+        let short = snippet("Naïve café", &query_terms("cafe"), 200);
+        assert_eq!(short, "Naïve café");
+    }
+
+    #[test]
+    fn test_snippet_skips_hits_longer_than_the_window() {
+        let content = format!("see c{}", "a".repeat(250));
+        let excerpt = snippet(&content, &query_terms("c"), SNIPPET_CHARS);
+        assert_eq!(excerpt, "see...");
+    }
+
+    #[test]
+    fn test_snippet_marks_prefix_matches_at_the_end() {
+        let excerpt = snippet("Rules about borrowing", &query_terms("borrow"), 200);
+        assert_eq!(excerpt, "Rules about **borrowing**");
+    }
+}
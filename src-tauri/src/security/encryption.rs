@@ -1,6 +1,9 @@
+use aes_gcm::Aes256Gcm;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
 use chacha20poly1305::{
-    aead::{Aead, KeyInit, OsRng},
-    ChaCha20Poly1305, Nonce,
+    aead::{self, Aead, KeyInit, OsRng},
+    ChaCha20Poly1305,
 };
 use rand::RngCore;
 use thiserror::Error;
@@ -19,69 +22,179 @@ pub enum EncryptionError {
     #[error("Invalid ciphertext format")]
     InvalidFormat,
 
+    #[error("Unsupported encrypted format version {0}")]
+    UnsupportedVersion(u8),
+
     #[error("Base64 decode error: {0}")]
     Base64Error(#[from] base64::DecodeError),
 }
 
-const NONCE_SIZE: usize = 12; // 96 bits for ChaCha20Poly1305
+const NONCE_SIZE: usize = 12; // 96 bits for both ciphers
+
+/// Layout of the decoded ciphertext written by `encrypt_with`
+/// Version 0 (no header): [nonce || ciphertext], always ChaCha20Poly1305.
+/// Version 1: [`FORMAT_MAGIC` || version || cipher tag || nonce || ciphertext].
+pub const FORMAT_VERSION: u8 = 1;
+
+/// Start of every versioned ciphertext; a version 0 nonce begins with it only by a
+/// 1 in 2^32 chance
+const FORMAT_MAGIC: &[u8] = b"LLMW";
+
+/// Environment variable selecting the cipher new config files are written with
+pub const CIPHER_ENV_VAR: &str = "LLM_WORKBENCH_CIPHER";
+
+/// AEAD cipher used to encrypt the config, both with a 256-bit key and 96-bit nonce
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Cipher {
+    #[default]
+    ChaCha20Poly1305,
+    Aes256Gcm,
+}
+
+impl Cipher {
+    /// Byte prefixed to the ciphertext to record which cipher wrote it
+    fn tag(self) -> u8 {
+        match self {
+            Cipher::ChaCha20Poly1305 => 1,
+            Cipher::Aes256Gcm => 2,
+        }
+    }
+
+    fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            1 => Some(Cipher::ChaCha20Poly1305),
+            2 => Some(Cipher::Aes256Gcm),
+            _ => None,
+        }
+    }
+
+    /// Cipher named by `LLM_WORKBENCH_CIPHER` ("aes-256-gcm" or "chacha20-poly1305"),
+    /// or the default when unset or unrecognised
+    pub fn from_env() -> Self {
+        match std::env::var(CIPHER_ENV_VAR).as_deref() {
+            Ok("aes-256-gcm") => Cipher::Aes256Gcm,
+            Ok("chacha20-poly1305") | Err(_) => Cipher::ChaCha20Poly1305,
+            Ok(other) => {
+                tracing::warn!(
+                    "Unknown cipher {:?} in {}, using default",
+                    other,
+                    CIPHER_ENV_VAR
+                );
+                Cipher::default()
+            }
+        }
+    }
+
+    fn seal(self, key: &[u8], nonce: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        match self {
+            Cipher::ChaCha20Poly1305 => seal_with::<ChaCha20Poly1305>(key, nonce, plaintext),
+            Cipher::Aes256Gcm => seal_with::<Aes256Gcm>(key, nonce, plaintext),
+        }
+    }
+
+    fn open(self, key: &[u8], nonce: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        match self {
+            Cipher::ChaCha20Poly1305 => open_with::<ChaCha20Poly1305>(key, nonce, ciphertext),
+            Cipher::Aes256Gcm => open_with::<Aes256Gcm>(key, nonce, ciphertext),
+        }
+    }
+}
+
+fn seal_with<C: KeyInit + Aead>(
+    key: &[u8],
+    nonce: &[u8],
+    plaintext: &[u8],
+) -> Result<Vec<u8>, EncryptionError> {
+    let cipher =
+        C::new_from_slice(key).map_err(|e| EncryptionError::EncryptionFailed(e.to_string()))?;
+    cipher
+        .encrypt(aead::Nonce::<C>::from_slice(nonce), plaintext)
+        .map_err(|e| EncryptionError::EncryptionFailed(e.to_string()))
+}
+
+fn open_with<C: KeyInit + Aead>(
+    key: &[u8],
+    nonce: &[u8],
+    ciphertext: &[u8],
+) -> Result<Vec<u8>, EncryptionError> {
+    let cipher =
+        C::new_from_slice(key).map_err(|e| EncryptionError::DecryptionFailed(e.to_string()))?;
+    cipher
+        .decrypt(aead::Nonce::<C>::from_slice(nonce), ciphertext)
+        .map_err(|e| EncryptionError::DecryptionFailed(e.to_string()))
+}
 
 /// Encrypt plaintext using ChaCha20Poly1305 with a 256-bit key
-/// Returns base64-encoded: [nonce || ciphertext || tag]
+/// Returns base64-encoded: [magic || version || cipher tag || nonce || ciphertext || tag]
 pub fn encrypt(plaintext: &[u8], key: &[u8]) -> Result<String, EncryptionError> {
+    encrypt_with(Cipher::default(), plaintext, key)
+}
+
+/// Encrypt plaintext using `cipher` with a 256-bit key
+/// Returns base64-encoded: [magic || version || cipher tag || nonce || ciphertext || tag]
+pub fn encrypt_with(
+    cipher: Cipher,
+    plaintext: &[u8],
+    key: &[u8],
+) -> Result<String, EncryptionError> {
     if key.len() != 32 {
         return Err(EncryptionError::InvalidKeyLength(key.len()));
     }
 
-    // Create cipher instance
-    let cipher = ChaCha20Poly1305::new_from_slice(key)
-        .map_err(|e| EncryptionError::EncryptionFailed(e.to_string()))?;
-
     // Generate random nonce
     let mut nonce_bytes = [0u8; NONCE_SIZE];
     OsRng.fill_bytes(&mut nonce_bytes);
-    let nonce = Nonce::from_slice(&nonce_bytes);
 
     // Encrypt
-    let ciphertext = cipher
-        .encrypt(nonce, plaintext)
-        .map_err(|e| EncryptionError::EncryptionFailed(e.to_string()))?;
+    let ciphertext = cipher.seal(key, &nonce_bytes, plaintext)?;
 
-    // Combine: nonce || ciphertext (ciphertext already includes the auth tag)
-    let mut combined = Vec::with_capacity(NONCE_SIZE + ciphertext.len());
+    // Combine: header || nonce || ciphertext (ciphertext already includes the auth tag)
+    let mut combined = Vec::with_capacity(FORMAT_MAGIC.len() + 2 + NONCE_SIZE + ciphertext.len());
+    combined.extend_from_slice(FORMAT_MAGIC);
+    combined.push(FORMAT_VERSION);
+    combined.push(cipher.tag());
     combined.extend_from_slice(&nonce_bytes);
     combined.extend_from_slice(&ciphertext);
 
     // Encode to base64
-    Ok(base64::encode(&combined))
+    Ok(BASE64.encode(&combined))
+}
+
+/// The cipher and [nonce || ciphertext] of decoded ciphertext, going by its format version
+fn parse(combined: &[u8]) -> Result<(Cipher, &[u8]), EncryptionError> {
+    let Some(versioned) = combined.strip_prefix(FORMAT_MAGIC) else {
+        return Ok((Cipher::ChaCha20Poly1305, combined));
+    };
+    match versioned {
+        [FORMAT_VERSION, tag, sealed @ ..] => {
+            let cipher = Cipher::from_tag(*tag).ok_or(EncryptionError::InvalidFormat)?;
+            Ok((cipher, sealed))
+        }
+        [version, ..] if *version != FORMAT_VERSION => {
+            Err(EncryptionError::UnsupportedVersion(*version))
+        }
+        _ => Err(EncryptionError::InvalidFormat),
+    }
 }
 
-/// Decrypt base64-encoded ciphertext
-/// Expected format: base64([nonce || ciphertext || tag])
+/// Decrypt base64-encoded ciphertext in any known format version
+/// Expected format: base64([magic || version || cipher tag || nonce || ciphertext || tag]),
+/// or the headerless base64([nonce || ciphertext || tag]) of version 0, which is always
+/// ChaCha. Versions newer than `FORMAT_VERSION` are refused.
 pub fn decrypt(ciphertext_b64: &str, key: &[u8]) -> Result<Vec<u8>, EncryptionError> {
     if key.len() != 32 {
         return Err(EncryptionError::InvalidKeyLength(key.len()));
     }
 
     // Decode base64
-    let combined = base64::decode(ciphertext_b64)?;
+    let combined = BASE64.decode(ciphertext_b64)?;
 
-    // Extract nonce and ciphertext
-    if combined.len() < NONCE_SIZE {
+    let (cipher, sealed) = parse(&combined)?;
+    if sealed.len() < NONCE_SIZE {
         return Err(EncryptionError::InvalidFormat);
     }
-
-    let (nonce_bytes, ciphertext) = combined.split_at(NONCE_SIZE);
-    let nonce = Nonce::from_slice(nonce_bytes);
-
-    // Create cipher and decrypt
-    let cipher = ChaCha20Poly1305::new_from_slice(key)
-        .map_err(|e| EncryptionError::DecryptionFailed(e.to_string()))?;
-
-    let plaintext = cipher
-        .decrypt(nonce, ciphertext)
-        .map_err(|e| EncryptionError::DecryptionFailed(e.to_string()))?;
-
-    Ok(plaintext)
+    let (nonce, ciphertext) = sealed.split_at(NONCE_SIZE);
+    cipher.open(key, nonce, ciphertext)
 }
 
 #[cfg(test)]
@@ -111,6 +224,51 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_decrypt_dispatches_on_cipher_tag() {
+        let key = [7u8; 32];
+        let plaintext = b"Secret";
+
+        let aes = encrypt_with(Cipher::Aes256Gcm, plaintext, &key).unwrap();
+        let chacha = encrypt_with(Cipher::ChaCha20Poly1305, plaintext, &key).unwrap();
+        assert_eq!(
+            BASE64.decode(&aes).unwrap()[..6],
+            [FORMAT_MAGIC, &[FORMAT_VERSION, Cipher::Aes256Gcm.tag()]].concat()
+        );
+        assert_eq!(decrypt(&aes, &key).unwrap(), plaintext);
+        assert_eq!(decrypt(&chacha, &key).unwrap(), plaintext);
+    }
+
+    #[test]
+    fn test_newer_version_is_refused() {
+        let key = [5u8; 32];
+        let encrypted = encrypt(b"Secret", &key).unwrap();
+        let mut combined = BASE64.decode(&encrypted).unwrap();
+        combined[FORMAT_MAGIC.len()] = FORMAT_VERSION + 1;
+
+        assert!(matches!(
+            decrypt(&BASE64.encode(&combined), &key),
+            Err(EncryptionError::UnsupportedVersion(2))
+        ));
+    }
+
+    #[test]
+    fn test_untagged_ciphertext_decrypts_as_chacha() {
+        let key = [3u8; 32];
+        let plaintext = b"Written before the cipher tag";
+
+        // Legacy layout, including nonces that happen to start like a version and tag
+        for first in [0u8, 1, 2, 0xff] {
+            let mut nonce = [Cipher::ChaCha20Poly1305.tag(); NONCE_SIZE];
+            nonce[0] = first;
+            let sealed = Cipher::ChaCha20Poly1305
+                .seal(&key, &nonce, plaintext)
+                .unwrap();
+            let legacy = BASE64.encode([nonce.as_slice(), &sealed].concat());
+            assert_eq!(decrypt(&legacy, &key).unwrap(), plaintext);
+        }
+    }
+
     #[test]
     fn test_invalid_key_length() {
         let short_key = [0u8; 16];
@@ -1,3 +1,4 @@
+use base64::Engine;
 use keyring::Entry;
 use rand::RngCore;
 use thiserror::Error;
@@ -12,6 +13,9 @@ pub enum KeychainError {
 
     #[error("Invalid key format: expected 32 bytes, got {0}")]
     InvalidKeyFormat(usize),
+
+    #[error("Master key not found in OS keychain")]
+    KeyMissing,
 }
 
 const SERVICE_NAME: &str = "llm_workbench_master_key";
@@ -25,7 +29,7 @@ pub fn get_master_key() -> Result<Vec<u8>, KeychainError> {
     match entry.get_password() {
         Ok(password) => {
             // Decode existing key from base64
-            let key = base64::decode(password)?;
+            let key = base64::engine::general_purpose::STANDARD.decode(password)?;
             if key.len() != 32 {
                 return Err(KeychainError::InvalidKeyFormat(key.len()));
             }
@@ -43,6 +47,23 @@ pub fn get_master_key() -> Result<Vec<u8>, KeychainError> {
     }
 }
 
+/// Read the master key from the OS keychain without creating one
+pub fn read_master_key() -> Result<Vec<u8>, KeychainError> {
+    let entry = Entry::new(SERVICE_NAME, ACCOUNT_NAME)?;
+
+    match entry.get_password() {
+        Ok(password) => {
+            let key = base64::engine::general_purpose::STANDARD.decode(password)?;
+            if key.len() != 32 {
+                return Err(KeychainError::InvalidKeyFormat(key.len()));
+            }
+            Ok(key)
+        }
+        Err(keyring::Error::NoEntry) => Err(KeychainError::KeyMissing),
+        Err(e) => Err(KeychainError::KeyringError(e)),
+    }
+}
+
 /// Store the master key in OS keychain
 pub fn store_master_key(key: &[u8]) -> Result<(), KeychainError> {
     if key.len() != 32 {
@@ -50,7 +71,7 @@ pub fn store_master_key(key: &[u8]) -> Result<(), KeychainError> {
     }
 
     let entry = Entry::new(SERVICE_NAME, ACCOUNT_NAME)?;
-    let key_b64 = base64::encode(key);
+    let key_b64 = base64::engine::general_purpose::STANDARD.encode(key);
     entry.set_password(&key_b64)?;
 
     tracing::info!("Stored master key in OS keychain");
@@ -65,7 +86,6 @@ fn generate_master_key() -> Result<Vec<u8>, KeychainError> {
 }
 
 /// Delete the master key from OS keychain (for testing or reset)
-#[allow(dead_code)]
 pub fn delete_master_key() -> Result<(), KeychainError> {
     let entry = Entry::new(SERVICE_NAME, ACCOUNT_NAME)?;
     entry.delete_password()?;
@@ -1,5 +1,9 @@
 pub mod encryption;
 pub mod keychain;
+pub mod passphrase;
+pub mod redact;
 
-pub use encryption::{encrypt, decrypt};
-pub use keychain::{get_master_key, store_master_key};
+pub use encryption::{decrypt, encrypt, encrypt_with, Cipher};
+pub use keychain::{delete_master_key, get_master_key, read_master_key, store_master_key};
+pub use passphrase::{derive_master_key, prompt_passphrase};
+pub use redact::{redact_secret, redact_url};
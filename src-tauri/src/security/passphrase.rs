@@ -0,0 +1,142 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use rand::RngCore;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum PassphraseError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("Base64 decode error: {0}")]
+    Base64Error(#[from] base64::DecodeError),
+
+    #[error("Invalid salt format: expected 16 bytes, got {0}")]
+    InvalidSaltFormat(usize),
+
+    #[error("Passphrase cannot be empty")]
+    EmptyPassphrase,
+
+    #[error("Key derivation failed: {0}")]
+    DerivationFailed(String),
+
+    #[error(
+        "No terminal to prompt for the passphrase: set {} or make an OS keychain available",
+        PASSPHRASE_ENV_VAR
+    )]
+    NoTerminal,
+}
+
+const SALT_SIZE: usize = 16; // 128-bit salt, as recommended for Argon2
+const KEY_SIZE: usize = 32; // 256-bit key for ChaCha20Poly1305
+
+/// Environment variable checked before prompting, for non-interactive environments
+pub const PASSPHRASE_ENV_VAR: &str = "LLM_WORKBENCH_PASSPHRASE";
+
+/// Derive the master encryption key from a passphrase using Argon2id
+/// The salt is read from `salt_path`, or generated and stored there on first use.
+/// Only the salt is persisted; the derived key never touches disk.
+pub fn derive_master_key(passphrase: &str, salt_path: &Path) -> Result<Vec<u8>, PassphraseError> {
+    if passphrase.is_empty() {
+        return Err(PassphraseError::EmptyPassphrase);
+    }
+
+    let salt = load_or_create_salt(salt_path)?;
+
+    let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, Params::default());
+    let mut key = vec![0u8; KEY_SIZE];
+    argon2
+        .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+        .map_err(|e| PassphraseError::DerivationFailed(e.to_string()))?;
+
+    tracing::info!("Derived master key from passphrase");
+    Ok(key)
+}
+
+/// Read the salt file, or generate a new random salt on first run
+fn load_or_create_salt(salt_path: &Path) -> Result<Vec<u8>, PassphraseError> {
+    if salt_path.exists() {
+        let salt = BASE64.decode(fs::read_to_string(salt_path)?.trim())?;
+        if salt.len() != SALT_SIZE {
+            return Err(PassphraseError::InvalidSaltFormat(salt.len()));
+        }
+        return Ok(salt);
+    }
+
+    tracing::info!("Generating new key salt (first run)");
+    let mut salt = vec![0u8; SALT_SIZE];
+    rand::rngs::OsRng.fill_bytes(&mut salt);
+    fs::write(salt_path, BASE64.encode(&salt))?;
+    Ok(salt)
+}
+
+/// Obtain a passphrase from the environment, or prompt for one on the terminal
+/// Fails with `NoTerminal` when stdin isn't a terminal (e.g. a release GUI build).
+pub fn prompt_passphrase() -> Result<String, PassphraseError> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV_VAR) {
+        if !passphrase.is_empty() {
+            return Ok(passphrase);
+        }
+    }
+
+    // GUI builds have no console, so reading stdin would block forever
+    if !io::stdin().is_terminal() {
+        return Err(PassphraseError::NoTerminal);
+    }
+
+    eprint!("Enter passphrase to unlock configuration: ");
+    io::stderr().flush()?;
+
+    let mut line = String::new();
+    io::stdin().lock().read_line(&mut line)?;
+    let passphrase = line.trim_end_matches(['\r', '\n']).to_string();
+
+    if passphrase.is_empty() {
+        return Err(PassphraseError::EmptyPassphrase);
+    }
+
+    Ok(passphrase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_same_passphrase_same_key() {
+        let temp_dir = TempDir::new().unwrap();
+        let salt_path = temp_dir.path().join("key.salt");
+
+        let key1 = derive_master_key("correct horse", &salt_path).unwrap();
+        let key2 = derive_master_key("correct horse", &salt_path).unwrap();
+
+        assert_eq!(key1.len(), 32);
+        assert_eq!(key1, key2);
+        assert!(salt_path.exists());
+    }
+
+    #[test]
+    fn test_different_passphrase_different_key() {
+        let temp_dir = TempDir::new().unwrap();
+        let salt_path = temp_dir.path().join("key.salt");
+
+        let key1 = derive_master_key("correct horse", &salt_path).unwrap();
+        let key2 = derive_master_key("battery staple", &salt_path).unwrap();
+
+        assert_ne!(key1, key2);
+    }
+
+    #[test]
+    fn test_empty_passphrase_rejected() {
+        let temp_dir = TempDir::new().unwrap();
+        let salt_path = temp_dir.path().join("key.salt");
+
+        assert!(derive_master_key("", &salt_path).is_err());
+        assert!(!salt_path.exists());
+    }
+}
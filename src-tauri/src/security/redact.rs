@@ -0,0 +1,71 @@
+use reqwest::Url;
+
+/// Placeholder substituted for secrets in logged or displayed text
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Query parameters that carry API keys
+const KEY_PARAMS: &[&str] = &["key", "api_key", "apikey"];
+
+/// Replace every occurrence of `secret` in `text` with `REDACTED`
+pub fn redact_secret(text: &str, secret: &str) -> String {
+    if secret.is_empty() {
+        return text.to_string();
+    }
+    text.replace(secret, REDACTED)
+}
+
+/// Replace the values of API key query parameters in `url` with `REDACTED`
+pub fn redact_url(url: &mut Url) {
+    if !url
+        .query_pairs()
+        .any(|(name, _)| KEY_PARAMS.contains(&name.as_ref()))
+    {
+        return;
+    }
+
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(name, value)| {
+            if KEY_PARAMS.contains(&name.as_ref()) {
+                (name.into_owned(), REDACTED.to_string())
+            } else {
+                (name.into_owned(), value.into_owned())
+            }
+        })
+        .collect();
+
+    url.query_pairs_mut().clear().extend_pairs(pairs);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_secret() {
+        assert_eq!(
+            redact_secret("invalid key sk-123 given", "sk-123"),
+            "invalid key [REDACTED] given"
+        );
+        assert_eq!(redact_secret("no secret here", ""), "no secret here");
+    }
+
+    #[test]
+    fn test_redact_url_strips_key_param() {
+        let mut url =
+            Url::parse("https://example.com/models/m:streamGenerateContent?alt=sse&key=secret")
+                .unwrap();
+        redact_url(&mut url);
+
+        assert!(!url.as_str().contains("secret"));
+        assert_eq!(url.query_pairs().count(), 2);
+        assert!(url.query_pairs().any(|(k, v)| k == "alt" && v == "sse"));
+    }
+
+    #[test]
+    fn test_redact_url_leaves_other_urls_alone() {
+        let mut url = Url::parse("https://example.com/v1/models?limit=10").unwrap();
+        redact_url(&mut url);
+        assert_eq!(url.as_str(), "https://example.com/v1/models?limit=10");
+    }
+}
@@ -0,0 +1,84 @@
+use crate::llm_providers::ChatMessage;
+use std::sync::OnceLock;
+use tiktoken_rs::CoreBPE;
+
+/// Tokens added per message for role and delimiters in OpenAI-style chat formats
+const TOKENS_PER_MESSAGE: usize = 3;
+
+/// Tokens priming the assistant's reply
+const REPLY_PRIMING_TOKENS: usize = 3;
+
+/// Providers whose models use cl100k-style BPE tokenization (OpenAI API family)
+const BPE_PROVIDERS: &[&str] = &["deepseek", "openai-compatible"];
+
+/// Shared cl100k_base tokenizer, built once on first use
+/// Exact for OpenAI-style models (incl. DeepSeek); a close estimate for Claude and Gemini
+fn bpe() -> Option<&'static CoreBPE> {
+    static BPE: OnceLock<Option<CoreBPE>> = OnceLock::new();
+    BPE.get_or_init(|| match tiktoken_rs::cl100k_base() {
+        Ok(bpe) => Some(bpe),
+        Err(e) => {
+            tracing::warn!("Failed to load tokenizer, falling back to estimate: {}", e);
+            None
+        }
+    })
+    .as_ref()
+}
+
+/// Count the tokens in a piece of text
+/// Falls back to ~4 characters per token if the tokenizer is unavailable
+pub fn count_tokens(text: &str) -> usize {
+    match bpe() {
+        Some(bpe) => bpe.encode_with_special_tokens(text).len(),
+        None => estimate_tokens(text),
+    }
+}
+
+/// Estimate tokens as ~4 characters per token
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// Count the tokens in text sent to or received from `provider_id`
+/// Exact for OpenAI-family providers; other providers use their own tokenizers,
+/// so their count is a character-based estimate.
+pub fn count_provider_tokens(provider_id: &str, text: &str) -> usize {
+    if BPE_PROVIDERS.contains(&provider_id) {
+        count_tokens(text)
+    } else {
+        estimate_tokens(text)
+    }
+}
+
+/// Count the prompt tokens of a chat request to `provider_id`, including message framing
+pub fn count_prompt_tokens(provider_id: &str, messages: &[ChatMessage]) -> usize {
+    messages
+        .iter()
+        .map(|m| count_provider_tokens(provider_id, &m.content) + TOKENS_PER_MESSAGE)
+        .sum::<usize>()
+        + REPLY_PRIMING_TOKENS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_tokens() {
+        assert_eq!(count_tokens(""), 0);
+        assert_eq!(count_tokens("hello world"), 2);
+        assert!(count_tokens(&"word ".repeat(100)) >= 100);
+    }
+
+    #[test]
+    fn test_count_prompt_tokens() {
+        use crate::llm_providers::ChatRole;
+
+        let messages = vec![ChatMessage {
+            role: ChatRole::User,
+            content: "hello world".to_string(),
+        }];
+        assert_eq!(count_prompt_tokens("deepseek", &messages), 2 + 3 + 3);
+        assert_eq!(count_prompt_tokens("claude", &messages), 3 + 3 + 3);
+    }
+}
@@ -1,4 +1,9 @@
+use crate::rag::chunking::PAGE_SEPARATOR;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::RwLock;
 use thiserror::Error;
+use url::Url;
 
 #[derive(Error, Debug)]
 pub enum ValidationError {
@@ -11,6 +16,9 @@ pub enum ValidationError {
     #[error("Field '{field}' is below minimum length of {min_len} characters")]
     TooShort { field: String, min_len: usize },
 
+    #[error("Field '{field}' exceeds maximum size of {max_bytes} bytes")]
+    TooLarge { field: String, max_bytes: usize },
+
     #[error("Field '{field}' value {value} is out of range [{min}, {max}]")]
     OutOfRange {
         field: String,
@@ -21,8 +29,99 @@ pub enum ValidationError {
 
     #[error("Field '{field}' contains invalid characters")]
     InvalidCharacters { field: String },
+
+    #[error("Field '{field}' value '{value}' is not one of: {allowed}")]
+    NotAllowed {
+        field: String,
+        value: String,
+        allowed: String,
+    },
+
+    #[error("Field '{field}' is not a valid URL: {reason}")]
+    InvalidUrl { field: String, reason: String },
+
+    #[error("Field '{field}' is not a valid JSON Schema: {reason}")]
+    InvalidSchema { field: String, reason: String },
+
+    #[error("Invalid canvas: {0}")]
+    InvalidCanvas(String),
+}
+
+/// Largest document, in bytes, accepted by default
+pub const DEFAULT_MAX_DOCUMENT_BYTES: usize = 10_485_760; // 10MB
+
+/// Longest search query, in characters, accepted by default
+pub const DEFAULT_MAX_QUERY_CHARS: usize = 10_000;
+
+/// Largest chat message or system prompt, in bytes, accepted by default
+pub const DEFAULT_MAX_MESSAGE_BYTES: usize = 1_048_576; // 1MB
+
+/// Size limits on user input, adjustable with `set_validation_limits`
+/// Defaults are the historical fixed limits; a machine with more memory can raise them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ValidationLimits {
+    pub max_document_bytes: usize,
+    pub max_query_chars: usize,
+    pub max_message_bytes: usize,
+    pub max_top_k: usize,
+}
+
+impl Default for ValidationLimits {
+    fn default() -> Self {
+        Self {
+            max_document_bytes: DEFAULT_MAX_DOCUMENT_BYTES,
+            max_query_chars: DEFAULT_MAX_QUERY_CHARS,
+            max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+            max_top_k: MAX_TOP_K,
+        }
+    }
 }
 
+/// The limits in effect, held in app state and shared with the MCP server
+#[derive(Debug, Default)]
+pub struct LimitsState(RwLock<ValidationLimits>);
+
+impl LimitsState {
+    pub fn new(limits: ValidationLimits) -> Self {
+        Self(RwLock::new(limits))
+    }
+
+    pub fn get(&self) -> ValidationLimits {
+        *self.0.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn set(&self, limits: ValidationLimits) {
+        *self.0.write().unwrap_or_else(|e| e.into_inner()) = limits;
+    }
+}
+
+/// Validate limits before they replace the current ones
+/// Each has a hard ceiling, so a typo cannot let a single request exhaust memory.
+pub fn validate_limits(limits: &ValidationLimits) -> Result<(), ValidationError> {
+    validate_range(
+        "max_document_bytes",
+        limits.max_document_bytes,
+        1024,
+        1 << 30, // 1GB
+    )?;
+    validate_range("max_query_chars", limits.max_query_chars, 1, 1_000_000)?;
+    validate_range(
+        "max_message_bytes",
+        limits.max_message_bytes,
+        1024,
+        100 << 20, // 100MB
+    )?;
+    validate_range("max_top_k", limits.max_top_k, 1, 100_000)?;
+    Ok(())
+}
+
+/// Message roles understood by every provider
+pub const ALLOWED_ROLES: &[&str] = &["system", "user", "assistant"];
+
+/// Distance metrics supported by RAG search
+pub const ALLOWED_METRICS: &[&str] = &["cosine", "dot_product", "euclidean"];
+
 /// Validate that a string is not empty or only whitespace
 pub fn validate_not_empty(field: &str, value: &str) -> Result<(), ValidationError> {
     if value.trim().is_empty() {
@@ -33,14 +132,14 @@ pub fn validate_not_empty(field: &str, value: &str) -> Result<(), ValidationErro
     Ok(())
 }
 
-/// Validate string length constraints
+/// Validate string length constraints, in characters (Unicode scalar values)
 pub fn validate_length(
     field: &str,
     value: &str,
     min_len: Option<usize>,
     max_len: Option<usize>,
 ) -> Result<(), ValidationError> {
-    let len = value.len();
+    let len = value.chars().count();
 
     if let Some(min) = min_len {
         if len < min {
@@ -63,6 +162,21 @@ pub fn validate_length(
     Ok(())
 }
 
+/// Validate a string's size in bytes, for limits on raw content size
+pub fn validate_byte_length(
+    field: &str,
+    value: &str,
+    max_bytes: usize,
+) -> Result<(), ValidationError> {
+    if value.len() > max_bytes {
+        return Err(ValidationError::TooLarge {
+            field: field.to_string(),
+            max_bytes,
+        });
+    }
+    Ok(())
+}
+
 /// Validate a number is within a range
 pub fn validate_range<T: PartialOrd + ToString>(
     field: &str,
@@ -86,9 +200,16 @@ pub fn validate_temperature(temp: f32) -> Result<(), ValidationError> {
     validate_range("temperature", temp, 0.0, 2.0)
 }
 
-/// Validate top_k parameter (1 to 100)
-pub fn validate_top_k(top_k: usize) -> Result<(), ValidationError> {
-    validate_range("top_k", top_k, 1, 100)
+/// Most results a search may return by default
+pub const MAX_TOP_K: usize = 1000;
+
+/// Most candidates re-ranking may consider; diversity re-ranking compares every selected
+/// result with every remaining candidate, so its cost grows with `top_k` times this
+pub const MAX_CANDIDATE_COUNT: usize = 2000;
+
+/// Validate top_k parameter (1 to `max_top_k`)
+pub fn validate_top_k(top_k: usize, limits: &ValidationLimits) -> Result<(), ValidationError> {
+    validate_range("top_k", top_k, 1, limits.max_top_k)
 }
 
 /// Validate max_tokens parameter (1 to 100000)
@@ -96,6 +217,69 @@ pub fn validate_max_tokens(max_tokens: u32) -> Result<(), ValidationError> {
     validate_range("max_tokens", max_tokens, 1, 100_000)
 }
 
+/// Validate streaming channel capacity (1 to 10000 chunks)
+pub fn validate_stream_capacity(capacity: usize) -> Result<(), ValidationError> {
+    validate_range("stream_capacity", capacity, 1, 10_000)
+}
+
+/// Validate the number of automatic continuations of a truncated response (1 to 10)
+pub fn validate_max_continuations(max_continuations: u32) -> Result<(), ValidationError> {
+    validate_range("max_continuations", max_continuations, 1, 10)
+}
+
+/// Validate a provider's request budget (0 for unlimited, up to 10000 per minute)
+pub fn validate_requests_per_minute(rate: u32) -> Result<(), ValidationError> {
+    validate_range("requests_per_minute", rate, 0, 10_000)
+}
+
+/// Validate a local server port (1024 to 65535, so no privileges are needed)
+pub fn validate_port(port: u16) -> Result<(), ValidationError> {
+    validate_range("port", port, 1024, u16::MAX)
+}
+
+/// Validate frequency/presence penalty parameters (-2.0 to 2.0)
+pub fn validate_penalty(field: &str, penalty: f32) -> Result<(), ValidationError> {
+    validate_range(field, penalty, -2.0, 2.0)
+}
+
+/// Validate search context window (0 to 10 neighboring chunks per side)
+pub fn validate_context_window(context_window: usize) -> Result<(), ValidationError> {
+    validate_range("context_window", context_window, 0, 10)
+}
+
+/// Validate HNSW search candidate list size (10 to 4096)
+pub fn validate_ef_search(ef_search: usize) -> Result<(), ValidationError> {
+    validate_range("ef_search", ef_search, 10, 4096)
+}
+
+/// Validate re-ranking candidate multiplier (2 to 10)
+pub fn validate_candidate_multiplier(multiplier: usize) -> Result<(), ValidationError> {
+    validate_range("candidate_multiplier", multiplier, 2, 10)
+}
+
+/// Validate the candidates retrieved for re-ranking, `top_k * candidate_multiplier`
+/// (1 to `MAX_CANDIDATE_COUNT`)
+pub fn validate_candidate_count(top_k: usize, multiplier: usize) -> Result<(), ValidationError> {
+    validate_range(
+        "top_k * candidate_multiplier",
+        top_k.saturating_mul(multiplier),
+        1,
+        MAX_CANDIDATE_COUNT,
+    )
+}
+
+/// Validate pagination parameters (limit 1 to 500, offset >= 0)
+pub fn validate_pagination(limit: i64, offset: i64) -> Result<(), ValidationError> {
+    validate_range("limit", limit, 1, 500)?;
+    validate_range("offset", offset, 0, i64::MAX)
+}
+
+/// Validate chunking parameters (chunk_size 100 to 100000 chars, overlap smaller than chunk_size)
+pub fn validate_chunk_config(chunk_size: usize, overlap: usize) -> Result<(), ValidationError> {
+    validate_range("chunk_size", chunk_size, 100, 100_000)?;
+    validate_range("overlap", overlap, 0, chunk_size - 1)
+}
+
 /// Validate project/conversation name (1-200 chars, no special chars)
 pub fn validate_name(field: &str, name: &str) -> Result<(), ValidationError> {
     validate_not_empty(field, name)?;
@@ -111,20 +295,122 @@ pub fn validate_name(field: &str, name: &str) -> Result<(), ValidationError> {
     Ok(())
 }
 
-/// Validate document content (not empty, max 10MB)
-pub fn validate_document_content(content: &str) -> Result<(), ValidationError> {
+/// Validate message role (one of ALLOWED_ROLES)
+pub fn validate_role(role: &str) -> Result<(), ValidationError> {
+    if !ALLOWED_ROLES.contains(&role) {
+        return Err(ValidationError::NotAllowed {
+            field: "role".to_string(),
+            value: role.to_string(),
+            allowed: ALLOWED_ROLES.join(", "),
+        });
+    }
+    Ok(())
+}
+
+/// Validate search distance metric (one of ALLOWED_METRICS)
+pub fn validate_metric(metric: &str) -> Result<(), ValidationError> {
+    if !ALLOWED_METRICS.contains(&metric) {
+        return Err(ValidationError::NotAllowed {
+            field: "metric".to_string(),
+            value: metric.to_string(),
+            allowed: ALLOWED_METRICS.join(", "),
+        });
+    }
+    Ok(())
+}
+
+/// Hosts that may be reached over plain HTTP (local servers such as Ollama)
+const LOCAL_HOSTS: &[&str] = &["localhost", "127.0.0.1", "[::1]"];
+
+/// Validate a provider base URL (https, or http for localhost; no surrounding whitespace)
+pub fn validate_url(field: &str, value: &str) -> Result<(), ValidationError> {
+    let invalid = |reason: &str| ValidationError::InvalidUrl {
+        field: field.to_string(),
+        reason: reason.to_string(),
+    };
+
+    validate_not_empty(field, value)?;
+    if value.trim() != value {
+        return Err(invalid("must not have leading or trailing whitespace"));
+    }
+
+    let url = Url::parse(value).map_err(|e| invalid(&e.to_string()))?;
+    let is_local = url
+        .host_str()
+        .is_some_and(|host| LOCAL_HOSTS.contains(&host));
+
+    match url.scheme() {
+        "https" => Ok(()),
+        "http" if is_local => Ok(()),
+        "http" => Err(invalid("plain http is only allowed for localhost")),
+        scheme => Err(invalid(&format!("unsupported scheme '{}'", scheme))),
+    }
+}
+
+/// Validate a JSON Schema (an object that compiles against its meta-schema)
+pub fn validate_json_schema(field: &str, schema: &Value) -> Result<(), ValidationError> {
+    let invalid = |reason: String| ValidationError::InvalidSchema {
+        field: field.to_string(),
+        reason,
+    };
+
+    if !schema.is_object() {
+        return Err(invalid("schema must be a JSON object".to_string()));
+    }
+    jsonschema::validator_for(schema).map_err(|e| invalid(e.to_string()))?;
+    Ok(())
+}
+
+/// C0 control characters other than tab, newline and the form feed separating pages
+fn is_disallowed_control(c: char) -> bool {
+    c.is_ascii_control() && c != '\t' && c != '\n' && c != PAGE_SEPARATOR && c != '\x7f'
+}
+
+/// Remove C0 control characters (null bytes, carriage returns, etc.) except tab, newline
+/// and form feed
+pub fn strip_control_chars(value: &str) -> String {
+    value
+        .chars()
+        .filter(|&c| !is_disallowed_control(c))
+        .collect()
+}
+
+/// Validate text has no C0 control characters except tab, newline and form feed
+pub fn validate_no_control_chars(field: &str, value: &str) -> Result<(), ValidationError> {
+    if value.chars().any(is_disallowed_control) {
+        return Err(ValidationError::InvalidCharacters {
+            field: field.to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Validate document content (not empty, at most `max_document_bytes`)
+pub fn validate_document_content(
+    content: &str,
+    limits: &ValidationLimits,
+) -> Result<(), ValidationError> {
     validate_not_empty("content", content)?;
-    validate_length("content", content, Some(1), Some(10_485_760))?; // 10MB limit
+    validate_byte_length("content", content, limits.max_document_bytes)?;
     Ok(())
 }
 
-/// Validate query string (not empty, max 10000 chars)
-pub fn validate_query(query: &str) -> Result<(), ValidationError> {
+/// Validate query string (not empty, at most `max_query_chars`)
+pub fn validate_query(query: &str, limits: &ValidationLimits) -> Result<(), ValidationError> {
     validate_not_empty("query", query)?;
-    validate_length("query", query, Some(1), Some(10_000))?;
+    validate_length("query", query, Some(1), Some(limits.max_query_chars))?;
     Ok(())
 }
 
+/// Validate a chat message or prompt (at most `max_message_bytes`)
+pub fn validate_message_size(
+    field: &str,
+    value: &str,
+    limits: &ValidationLimits,
+) -> Result<(), ValidationError> {
+    validate_byte_length(field, value, limits.max_message_bytes)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -143,6 +429,37 @@ mod tests {
         assert!(validate_length("test", "hello", None, Some(3)).is_err());
     }
 
+    #[test]
+    fn test_validate_length_counts_characters() {
+        // 200 kanji are 600 bytes but within a 200-character limit
+        let kanji = "漢".repeat(200);
+        assert!(validate_length("name", &kanji, Some(1), Some(200)).is_ok());
+        assert!(validate_name("name", &kanji).is_ok());
+        assert!(validate_name("name", &"漢".repeat(201)).is_err());
+
+        let emoji = "🦀".repeat(3);
+        assert!(validate_length("name", &emoji, Some(3), Some(3)).is_ok());
+    }
+
+    #[test]
+    fn test_validate_byte_length() {
+        assert!(validate_byte_length("content", "abc", 3).is_ok());
+        assert!(validate_byte_length("content", "漢", 2).is_err());
+    }
+
+    #[test]
+    fn test_control_chars() {
+        let content = "line one\r\nline\0 two\tend\x1b";
+        assert_eq!(strip_control_chars(content), "line one\nline two\tend");
+        assert!(validate_no_control_chars("content", content).is_err());
+        assert!(validate_no_control_chars("content", "tab\tand\nnewline").is_ok());
+
+        // Page separators survive, so paginated text can be edited and re-indexed
+        let pages = "page one\u{c}page two";
+        assert_eq!(strip_control_chars(pages), pages);
+        assert!(validate_no_control_chars("content", pages).is_ok());
+    }
+
     #[test]
     fn test_validate_range() {
         assert!(validate_range("test", 5, 1, 10).is_ok());
@@ -156,4 +473,125 @@ mod tests {
         assert!(validate_name("name", "").is_err());
         assert!(validate_name("name", "test\0name").is_err());
     }
+
+    #[test]
+    fn test_validate_role() {
+        assert!(validate_role("system").is_ok());
+        assert!(validate_role("user").is_ok());
+        assert!(validate_role("assistant").is_ok());
+        assert!(validate_role("assitant").is_err());
+        assert!(validate_role("User").is_err());
+        assert!(validate_role("").is_err());
+    }
+
+    #[test]
+    fn test_validate_chunk_config() {
+        assert!(validate_chunk_config(2048, 200).is_ok());
+        assert!(validate_chunk_config(2048, 2048).is_err());
+        assert!(validate_chunk_config(10, 0).is_err());
+    }
+
+    #[test]
+    fn test_validate_penalty() {
+        assert!(validate_penalty("frequency_penalty", -2.0).is_ok());
+        assert!(validate_penalty("frequency_penalty", 0.5).is_ok());
+        assert!(validate_penalty("presence_penalty", 2.1).is_err());
+    }
+
+    #[test]
+    fn test_validate_context_window() {
+        assert!(validate_context_window(0).is_ok());
+        assert!(validate_context_window(10).is_ok());
+        assert!(validate_context_window(11).is_err());
+    }
+
+    #[test]
+    fn test_validate_max_continuations() {
+        assert!(validate_max_continuations(1).is_ok());
+        assert!(validate_max_continuations(10).is_ok());
+        assert!(validate_max_continuations(0).is_err());
+        assert!(validate_max_continuations(11).is_err());
+    }
+
+    #[test]
+    fn test_validation_limits_are_adjustable() {
+        let content = "x".repeat(DEFAULT_MAX_DOCUMENT_BYTES + 1);
+        let defaults = ValidationLimits::default();
+        assert!(validate_document_content(&content, &defaults).is_err());
+
+        let state = LimitsState::default();
+        let raised = ValidationLimits {
+            max_document_bytes: 40 << 20,
+            ..defaults
+        };
+        assert!(validate_limits(&raised).is_ok());
+        state.set(raised);
+        assert!(validate_document_content(&content, &state.get()).is_ok());
+
+        assert!(validate_limits(&ValidationLimits {
+            max_top_k: 0,
+            ..defaults
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn test_validate_ef_search() {
+        assert!(validate_ef_search(10).is_ok());
+        assert!(validate_ef_search(4096).is_ok());
+        assert!(validate_ef_search(9).is_err());
+        assert!(validate_ef_search(4097).is_err());
+    }
+
+    #[test]
+    fn test_validate_candidate_multiplier() {
+        assert!(validate_candidate_multiplier(2).is_ok());
+        assert!(validate_candidate_multiplier(1).is_err());
+        assert!(validate_candidate_multiplier(11).is_err());
+    }
+
+    #[test]
+    fn test_validate_top_k_and_candidate_count() {
+        let limits = ValidationLimits::default();
+        assert!(validate_top_k(1, &limits).is_ok());
+        assert!(validate_top_k(MAX_TOP_K, &limits).is_ok());
+        assert!(validate_top_k(0, &limits).is_err());
+        assert!(validate_top_k(MAX_TOP_K + 1, &limits).is_err());
+
+        assert!(validate_candidate_count(100, 10).is_ok());
+        assert!(validate_candidate_count(1000, 2).is_ok());
+        assert!(validate_candidate_count(1000, 3).is_err());
+        assert!(validate_candidate_count(usize::MAX, 2).is_err());
+    }
+
+    #[test]
+    fn test_validate_url() {
+        assert!(validate_url("base_url", "https://api.deepseek.com/v1").is_ok());
+        assert!(validate_url("base_url", "http://localhost:11434/v1").is_ok());
+        assert!(validate_url("base_url", "http://127.0.0.1:11434").is_ok());
+        assert!(validate_url("base_url", "http://api.example.com/v1").is_err());
+        assert!(validate_url("base_url", "htpp://api.example.com").is_err());
+        assert!(validate_url("base_url", "https://api.example.com ").is_err());
+        assert!(validate_url("base_url", "api.example.com").is_err());
+        assert!(validate_url("base_url", "").is_err());
+    }
+
+    #[test]
+    fn test_validate_json_schema() {
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": {"name": {"type": "string"}},
+            "required": ["name"]
+        });
+        assert!(validate_json_schema("schema", &schema).is_ok());
+        assert!(validate_json_schema("schema", &serde_json::json!({"type": "strng"})).is_err());
+        assert!(validate_json_schema("schema", &serde_json::json!("object")).is_err());
+    }
+
+    #[test]
+    fn test_validate_metric() {
+        assert!(validate_metric("cosine").is_ok());
+        assert!(validate_metric("euclidean").is_ok());
+        assert!(validate_metric("manhattan").is_err());
+    }
 }